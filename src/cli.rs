@@ -0,0 +1,364 @@
+#![allow(dead_code)]
+
+//! The `curves` command line
+//!
+//! ```text
+//! curves render script -o face.png --size 800x800 --quality adaptive
+//! curves animate scene.mdl -o spin.gif --fps 24
+//! curves convert face.ppm face.png --size 250x250
+//! curves repl --size 250x250
+//! ```
+//!
+//! The REPL takes script commands one line at a time, with their arguments on the same line,
+//! and shows the edges after each one in a preview window with the `preview` feature, or
+//! in the terminal otherwise. `undo` takes back the last line or curve.
+//!
+//! Files ending in `.mdl` are read as MDL, anything else as a drawing script. Either way the
+//! drawing space is 500 by 500 with y going up. It is centered on the image and keeps its
+//! proportions, so an image wider than it is tall shows more beyond it to the left and
+//! right, and a taller one more above and below.
+
+use std::io::{self, prelude::*};
+use std::path::Path;
+
+use clap::{Parser, ValueEnum};
+
+use crate::graphics::animation::{FrameSequence, FrameSink};
+use crate::graphics::expr::Variables;
+use crate::graphics::gif::{GifWriter, Repeat};
+use crate::graphics::mdl::{self, Mdl, Op};
+use crate::graphics::png::ApngWriter;
+#[cfg(feature = "preview")]
+use crate::graphics::preview::Preview;
+use crate::graphics::resize::Filter;
+use crate::graphics::script::{self, Command, Script};
+use crate::graphics::terminal::{TerminalMode, TerminalWriter};
+use crate::graphics::tessellation::Tessellation;
+use crate::graphics::utils::create_file;
+use crate::graphics::video::Mp4Writer;
+use crate::graphics::PPMImg;
+
+/// Draw curves and 3D scenes from scripts and MDL files
+#[derive(Clone, Debug, PartialEq, Parser)]
+#[command(name = "curves")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Subcommand,
+}
+
+/// How finely curves are drawn
+#[derive(Copy, Clone, Debug, PartialEq, ValueEnum)]
+pub enum Quality {
+    /// Segments of up to 8 pixels
+    Draft,
+    /// Segments of up to 2 pixels
+    Normal,
+    /// As many segments as it takes to stay within a quarter pixel of every curve
+    Adaptive,
+}
+
+impl Quality {
+    pub fn tessellation(self) -> Tessellation {
+        match self {
+            Quality::Draft => Tessellation::MaxSegmentLength(8.0),
+            Quality::Normal => Tessellation::MaxSegmentLength(2.0),
+            Quality::Adaptive => Tessellation::Adaptive(0.25),
+        }
+    }
+}
+
+/// What the command line asks for
+#[derive(Clone, Debug, PartialEq, clap::Subcommand)]
+pub enum Subcommand {
+    /// Run a script or MDL file, and save its first frame
+    Render {
+        input: String,
+        /// Where to save the image; without it, files that save nothing themselves are saved
+        /// next to the input as PNG
+        #[arg(short, long)]
+        output: Option<String>,
+        /// Width and height of the image, like 800x600
+        #[arg(long, value_parser = parse_size, default_value = "500x500")]
+        size: (u32, u32),
+        /// How finely script curves are drawn
+        #[arg(long, value_enum, default_value_t = Quality::Normal)]
+        quality: Quality,
+    },
+    /// Draw every frame of an MDL file
+    Animate {
+        input: String,
+        /// A gif, png or mp4 file; without it, frames are saved one by one in the anim
+        /// directory
+        #[arg(short, long)]
+        output: Option<String>,
+        /// Width and height of the frames, like 800x600
+        #[arg(long, value_parser = parse_size, default_value = "500x500")]
+        size: (u32, u32),
+        #[arg(long, default_value_t = 24, value_parser = clap::value_parser!(u32).range(1..))]
+        fps: u32,
+    },
+    /// Read an image and write it in another format
+    Convert {
+        input: String,
+        output: String,
+        /// Resize to this width and height on the way
+        #[arg(long, value_parser = parse_size)]
+        size: Option<(u32, u32)>,
+    },
+    /// Run script commands as they are typed
+    Repl {
+        /// Width and height of the image, like 800x600
+        #[arg(long, value_parser = parse_size, default_value = "500x500")]
+        size: (u32, u32),
+        /// How finely curves are drawn
+        #[arg(long, value_enum, default_value_t = Quality::Normal)]
+        quality: Quality,
+        /// Most frames shown per second in the preview window
+        #[arg(long, default_value_t = 24, value_parser = clap::value_parser!(u32).range(1..))]
+        fps: u32,
+    },
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+/// Read a size like `800x600`
+fn parse_size(size: &str) -> Result<(u32, u32), String> {
+    let bad = || format!("Bad size `{}`, expected something like 800x600", size);
+    let (w, h) = size.split_once('x').ok_or_else(bad)?;
+    match (w.parse(), h.parse()) {
+        (Ok(w), Ok(h)) if w > 0 && h > 0 => Ok((w, h)),
+        _ => Err(bad()),
+    }
+}
+
+/// A clear image of `size`, over the drawing space of the course
+///
+/// The 500 by 500 space is scaled the same both ways, to fit the shorter side of the image.
+fn canvas((width, height): (u32, u32)) -> PPMImg {
+    let mut img = PPMImg::new(height, width, 255);
+    let (w, h) = ((width.max(2) - 1) as f64, (height.max(2) - 1) as f64);
+    let scale = (w / 499.0).min(h / 499.0);
+    let (dx, dy) = ((w / scale - 499.0) / 2.0, (h / scale - 499.0) / 2.0);
+    img.set_world_bounds(-dx, -dy, 499.0 + dx, 499.0 + dy);
+    img
+}
+
+fn is_mdl(filepath: &str) -> bool {
+    Path::new(filepath).extension().is_some_and(|e| e.eq_ignore_ascii_case("mdl"))
+}
+
+/// Do what the command line asks for
+pub fn run(subcommand: &Subcommand) -> io::Result<()> {
+    match subcommand {
+        Subcommand::Render { input, output, size, quality } => {
+            render(input, output.as_deref(), *size, *quality)
+        }
+        Subcommand::Animate { input, output, size, fps } => {
+            animate(input, output.as_deref(), *size, *fps)
+        }
+        Subcommand::Convert { input, output, size } => {
+            let img = PPMImg::open(input)?;
+            match size {
+                Some((w, h)) => img.resize(*w, *h, Filter::Box).save_as(output),
+                None => img.save_as(output),
+            }
+        }
+        Subcommand::Repl { size, quality, fps } => repl(*size, *quality, *fps),
+    }
+}
+
+fn render(input: &str, output: Option<&str>, size: (u32, u32), quality: Quality) -> io::Result<()> {
+    let mut img = canvas(size);
+    let saves = if is_mdl(input) {
+        let mdl = mdl::open_mdl(input)?;
+        img.depth_test(true);
+        mdl.draw_frame(&mut img, 0)?;
+        mdl.ops.iter().any(|op| matches!(op, Op::Save(_)))
+    } else {
+        let commands = script::open_script(input)?;
+        let mut script = Script::with_image(img);
+        script.edges.set_tessellation(quality.tessellation());
+        script.run(&commands)?;
+        script.draw();
+        img = script.img;
+        commands.iter().any(|c| matches!(c, Command::Save(_)))
+    };
+    match output {
+        Some(output) => img.save_as(output),
+        None if saves => Ok(()),
+        // the image would be lost otherwise
+        None => img.save_as(&default_output(input)),
+    }
+}
+
+/// `input` with a `.png` extension in place of its own
+fn default_output(input: &str) -> String {
+    Path::new(input).with_extension("png").to_string_lossy().into_owned()
+}
+
+fn animate(input: &str, output: Option<&str>, size: (u32, u32), fps: u32) -> io::Result<()> {
+    if !is_mdl(input) {
+        return Err(invalid(format!("Only MDL files can be animated, not `{}`", input)));
+    }
+    let mdl: Mdl = mdl::open_mdl(input)?;
+    let mut img = canvas(size);
+    img.depth_test(true);
+    let (width, height) = (img.width(), img.height());
+    let delay = (100 / fps).max(1) as u16;
+    let ext = output
+        .and_then(|o| Path::new(o).extension())
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    let sink: Box<dyn FrameSink> = match (output, ext.as_deref()) {
+        (None, _) => {
+            let basename = mdl.basename.as_deref().unwrap_or("frame");
+            Box::new(FrameSequence::new("anim", basename, "png", true)?)
+        }
+        (Some(o), Some("gif")) => {
+            Box::new(GifWriter::create(o, width, height, delay, Repeat::Forever)?)
+        }
+        (Some(o), Some("png")) => {
+            Box::new(ApngWriter::new(create_file(o)?, width, height, delay, Repeat::Forever))
+        }
+        (Some(o), Some("mp4")) => Box::new(Mp4Writer::new(o, width, height, fps)?),
+        (Some(o), _) => return Err(invalid(format!("Animations can't be saved as `{}`", o))),
+    };
+    mdl.animate(&mut img, sink)
+}
+
+/// Where the REPL shows the edges
+enum View {
+    #[cfg(feature = "preview")]
+    Window(Box<Preview>),
+    Terminal(TerminalWriter<io::Stdout>),
+}
+
+impl View {
+    /// A preview window if there can be one, or else the terminal
+    #[cfg_attr(not(feature = "preview"), allow(unused_variables))]
+    fn open(width: u32, height: u32, fps: u32) -> Self {
+        #[cfg(feature = "preview")]
+        match Preview::open(width, height, fps) {
+            Ok(preview) => return View::Window(Box::new(preview)),
+            Err(e) => eprintln!("{}; showing the edges here instead", e),
+        }
+        View::Terminal(TerminalWriter::new(io::stdout(), TerminalMode::Ansi))
+    }
+
+    fn show(&mut self, img: &PPMImg) -> io::Result<()> {
+        match self {
+            // once the window is closed, go on in the terminal
+            #[cfg(feature = "preview")]
+            View::Window(preview) => {
+                if !preview.show(img)? {
+                    *self = View::Terminal(TerminalWriter::new(io::stdout(), TerminalMode::Ansi));
+                    return self.show(img);
+                }
+            }
+            View::Terminal(terminal) => {
+                terminal.show(img)?;
+                // clear whatever was typed below the last image
+                print!("\x1b[J");
+            }
+        }
+        Ok(())
+    }
+}
+
+fn repl(size: (u32, u32), quality: Quality, fps: u32) -> io::Result<()> {
+    let mut script = Script::with_image(canvas(size));
+    script.edges.set_tessellation(quality.tessellation());
+    let mut view = View::open(script.img.width(), script.img.height(), fps);
+    script.draw();
+    view.show(&script.img)?;
+    let mut vars = Variables::new();
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("> ");
+        io::stdout().flush()?;
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => break,
+        };
+        let command = match script::read_command(&line, &mut vars) {
+            Ok(Some(command)) => command,
+            Ok(None) => continue,
+            Err(e) => {
+                println!("{}", e);
+                continue;
+            }
+        };
+        if command == Command::Undo {
+            if !script.undo() {
+                println!("There is nothing to undo");
+                continue;
+            }
+        } else {
+            match script.execute(&command) {
+                Ok(true) => {}
+                Ok(false) => break,
+                // a failed display or save leaves everything else as it was
+                Err(e) => println!("{}", e),
+            }
+        }
+        script.draw();
+        view.show(&script.img)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{error::ErrorKind, CommandFactory};
+
+    fn parse(line: &str) -> Result<Subcommand, clap::Error> {
+        let args = std::iter::once("curves").chain(line.split_whitespace());
+        Cli::try_parse_from(args).map(|cli| cli.command)
+    }
+
+    #[test]
+    fn arguments_parse() {
+        Cli::command().debug_assert();
+        let render = parse("render script.txt -o out.png --size 800x600 --quality adaptive");
+        let expected = Subcommand::Render {
+            input: "script.txt".to_string(),
+            output: Some("out.png".to_string()),
+            size: (800, 600),
+            quality: Quality::Adaptive,
+        };
+        assert_eq!(expected, render.unwrap());
+        match parse("repl").unwrap() {
+            Subcommand::Repl { size, quality, fps } => {
+                assert_eq!(((500, 500), Quality::Normal, 24), (size, quality, fps))
+            }
+            other => panic!("Not a REPL: {:?}", other),
+        }
+        match parse("convert a.ppm b.png").unwrap() {
+            Subcommand::Convert { output, size, .. } => assert_eq!(("b.png", None), (&*output, size)),
+            other => panic!("Not a conversion: {:?}", other),
+        }
+
+        let error = |line| parse(line).unwrap_err().kind();
+        assert_eq!(ErrorKind::ValueValidation, error("render a --size 800"));
+        assert_eq!(ErrorKind::ValueValidation, error("animate a.mdl --fps 0"));
+        assert_eq!(ErrorKind::MissingRequiredArgument, error("convert a.ppm"));
+        assert_eq!(ErrorKind::UnknownArgument, error("render a --colour red"));
+        assert_eq!(ErrorKind::InvalidValue, error("animate a.mdl -o"));
+        assert_eq!(ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand, error(""));
+        assert_eq!("dir/face.png", default_output("dir/face.txt"));
+    }
+
+    #[test]
+    fn the_drawing_space_keeps_its_shape() {
+        let img = canvas((1000, 500));
+        // the middle of the space is the middle of the image, and a square stays square
+        assert_eq!((499.5, 249.5), img.world_to_screen(249.5, 249.5));
+        let (x0, y0) = img.world_to_screen(0.0, 0.0);
+        let (x1, y1) = img.world_to_screen(499.0, 499.0);
+        assert!(((x1 - x0) - (y0 - y1)).abs() < 1e-9 && (y0 - 499.0).abs() < 1e-9);
+    }
+}
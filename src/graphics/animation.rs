@@ -0,0 +1,282 @@
+#![allow(dead_code)]
+
+//! Drawing animations frame by frame, without managing files by hand
+
+use std::fs;
+use std::io::{self, prelude::Write};
+use std::path::{Path, PathBuf};
+
+use super::gif::GifWriter;
+use super::png::ApngWriter;
+#[cfg(feature = "preview")]
+use super::preview::Preview;
+use super::terminal::TerminalWriter;
+use super::video::Mp4Writer;
+use super::PPMImg;
+
+/// Somewhere finished frames go, in order
+pub trait FrameSink {
+    fn add_frame(&mut self, img: &PPMImg) -> io::Result<()>;
+
+    /// Finish the output after the last frame
+    fn finish(self: Box<Self>) -> io::Result<()>;
+}
+
+impl<W: Write> FrameSink for GifWriter<W> {
+    fn add_frame(&mut self, img: &PPMImg) -> io::Result<()> {
+        GifWriter::add_frame(self, img)
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        GifWriter::finish(*self).map(|_| ())
+    }
+}
+
+impl<W: Write> FrameSink for ApngWriter<W> {
+    fn add_frame(&mut self, img: &PPMImg) -> io::Result<()> {
+        ApngWriter::add_frame(self, img)
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        ApngWriter::finish(*self).map(|_| ())
+    }
+}
+
+impl FrameSink for Mp4Writer {
+    fn add_frame(&mut self, img: &PPMImg) -> io::Result<()> {
+        Mp4Writer::add_frame(self, img)
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        Mp4Writer::finish(*self)
+    }
+}
+
+impl<W: Write> FrameSink for TerminalWriter<W> {
+    fn add_frame(&mut self, img: &PPMImg) -> io::Result<()> {
+        self.show(img)
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Frames go to the window until it is closed, and are dropped after that
+#[cfg(feature = "preview")]
+impl FrameSink for Preview {
+    fn add_frame(&mut self, img: &PPMImg) -> io::Result<()> {
+        self.show(img).map(|_| ())
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        self.wait()
+    }
+}
+
+/// Numbered image files in a directory, like `frame_0000.png`, `frame_0001.png` and so on
+///
+/// The names match `frame_%04d.png` as ffmpeg and ImageMagick's `convert` take them, see
+/// `pattern`. The format follows from the extension, as for `PPMImg::save_as`.
+pub struct FrameSequence {
+    dir: PathBuf,
+    basename: String,
+    extension: String,
+    next: usize,
+}
+
+impl FrameSequence {
+    /// Start a sequence in `dir`, creating it if needed
+    ///
+    /// With `clean` set, frames of the same name left from an earlier run are deleted first, so
+    /// that a shorter run does not end with stale frames.
+    pub fn new(dir: &str, basename: &str, extension: &str, clean: bool) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let seq = FrameSequence {
+            dir: PathBuf::from(dir),
+            basename: basename.to_string(),
+            extension: extension.trim_start_matches('.').to_string(),
+            next: 0,
+        };
+        if clean {
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+                if seq.is_frame(&path) {
+                    fs::remove_file(path)?;
+                }
+            }
+        }
+        Ok(seq)
+    }
+
+    /// The file for frame `i`
+    pub fn path(&self, i: usize) -> PathBuf {
+        self.dir.join(format!("{}_{:04}.{}", self.basename, i, self.extension))
+    }
+
+    /// The file names as a printf pattern, for `ffmpeg -i`
+    pub fn pattern(&self) -> PathBuf {
+        self.dir.join(format!("{}_%04d.{}", self.basename, self.extension))
+    }
+
+    /// How many frames have been written
+    pub fn len(&self) -> usize {
+        self.next
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.next == 0
+    }
+
+    fn is_frame(&self, path: &Path) -> bool {
+        let name = match path.file_name().and_then(|n| n.to_str()) {
+            Some(name) => name,
+            None => return false,
+        };
+        let number = name
+            .strip_prefix(&self.basename)
+            .and_then(|n| n.strip_prefix('_'))
+            .and_then(|n| n.strip_suffix(&self.extension))
+            .and_then(|n| n.strip_suffix('.'));
+        number.is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+    }
+}
+
+impl FrameSink for FrameSequence {
+    fn add_frame(&mut self, img: &PPMImg) -> io::Result<()> {
+        let path = self.path(self.next);
+        img.save_as(&path.to_string_lossy())?;
+        self.next += 1;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// What is left on the image when a new frame starts
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BetweenFrames {
+    /// Start every frame from the background
+    Clear,
+    /// Draw over the last frame, for trails and things that build up
+    Keep,
+}
+
+/// An image to draw each frame on, handing every finished frame to a sink
+pub struct FrameBuffer {
+    pub img: PPMImg,
+    pub between: BetweenFrames,
+    frame: usize,
+    sink: Box<dyn FrameSink>,
+}
+
+impl FrameBuffer {
+    pub fn new(img: PPMImg, between: BetweenFrames, sink: impl FrameSink + 'static) -> Self {
+        FrameBuffer {
+            img,
+            between,
+            frame: 0,
+            sink: Box::new(sink),
+        }
+    }
+
+    /// Index of the frame being drawn, from 0
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// Hand the frame that was drawn to the sink, and start the next one
+    pub fn next_frame(&mut self) -> io::Result<()> {
+        if self.img.samples > 1 {
+            self.sink.add_frame(&self.img.downsample())?;
+        } else {
+            self.sink.add_frame(&self.img)?;
+        }
+        self.frame += 1;
+        if self.between == BetweenFrames::Clear {
+            self.img.clear();
+        }
+        Ok(())
+    }
+
+    /// Draw `frames` frames, calling `draw` with the image and the index of each
+    pub fn render(
+        &mut self,
+        frames: usize,
+        mut draw: impl FnMut(&mut PPMImg, usize),
+    ) -> io::Result<()> {
+        for _ in 0..frames {
+            draw(&mut self.img, self.frame);
+            self.next_frame()?;
+        }
+        Ok(())
+    }
+
+    /// Finish the output, after the last call to `next_frame`
+    pub fn finish(self) -> io::Result<()> {
+        self.sink.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::RGB;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Keeps the top left pixel of every frame
+    struct Corners(Rc<RefCell<Vec<RGB>>>);
+
+    impl FrameSink for Corners {
+        fn add_frame(&mut self, img: &PPMImg) -> io::Result<()> {
+            self.0.borrow_mut().push(img.pixel(0, 0).unwrap());
+            Ok(())
+        }
+
+        fn finish(self: Box<Self>) -> io::Result<()> {
+            self.0.borrow_mut().push(RGB::WHITE);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn frames_are_cleared_or_kept() {
+        let cases = [(BetweenFrames::Clear, RGB::BLACK), (BetweenFrames::Keep, RGB::RED)];
+        for &(between, second) in cases.iter() {
+            let seen = Rc::new(RefCell::new(vec![]));
+            let mut fb = FrameBuffer::new(PPMImg::new(2, 2, 255), between, Corners(seen.clone()));
+            fb.render(2, |img, i| {
+                if i == 0 {
+                    img.plot_color(0, 0, RGB::RED);
+                }
+            })
+            .unwrap();
+            assert_eq!(2, fb.frame());
+            fb.finish().unwrap();
+            assert_eq!(vec![RGB::RED, second, RGB::WHITE], *seen.borrow());
+        }
+    }
+
+    #[test]
+    fn sequences_are_numbered_and_cleaned() {
+        let dir = std::env::temp_dir().join(format!("frames-{}", std::process::id()));
+        let dir = dir.to_str().unwrap();
+        let stale = Path::new(dir).join("f_0099.ppm");
+        fs::create_dir_all(dir).unwrap();
+        fs::write(&stale, b"old").unwrap();
+        fs::write(Path::new(dir).join("keep.ppm"), b"mine").unwrap();
+
+        let mut seq = FrameSequence::new(dir, "f", "ppm", true).unwrap();
+        assert!(!stale.exists() && Path::new(dir).join("keep.ppm").exists());
+        for _ in 0..2 {
+            seq.add_frame(&PPMImg::new(1, 1, 255)).unwrap();
+        }
+        assert_eq!(2, seq.len());
+        assert!(seq.path(1).ends_with("f_0001.ppm") && seq.path(1).exists());
+        assert!(seq.pattern().ends_with("f_%04d.ppm"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
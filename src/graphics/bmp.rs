@@ -0,0 +1,74 @@
+#![allow(dead_code)]
+
+use std::io::{self, prelude::Write};
+
+use super::utils::create_file;
+use super::PPMImg;
+
+/// Save an image as an uncompressed 24 bit BMP
+pub fn save_bmp(filepath: &str, img: &PPMImg) -> io::Result<()> {
+    let mut file = create_file(filepath)?;
+    write_bmp(&mut file, img)?;
+    file.flush()
+}
+
+/// Write an image as an uncompressed 24 bit BMP, see `save_bmp`
+pub fn write_bmp(w: &mut impl Write, img: &PPMImg) -> io::Result<()> {
+    let (width, height) = (img.width(), img.height());
+    // every row is padded to a multiple of 4 bytes
+    let row_len = (width * 3).div_ceil(4) * 4;
+    let data_len = row_len * height;
+    let header_len = 14 + 40;
+
+    // file header
+    w.write_all(b"BM")?;
+    w.write_all(&(header_len + data_len).to_le_bytes())?;
+    w.write_all(&[0; 4])?;
+    w.write_all(&header_len.to_le_bytes())?;
+    // BITMAPINFOHEADER
+    w.write_all(&40u32.to_le_bytes())?;
+    w.write_all(&(width as i32).to_le_bytes())?;
+    w.write_all(&(height as i32).to_le_bytes())?;
+    w.write_all(&1u16.to_le_bytes())?;
+    w.write_all(&24u16.to_le_bytes())?;
+    // no compression, then the data size, 72 dpi both ways, and no palette
+    w.write_all(&0u32.to_le_bytes())?;
+    w.write_all(&data_len.to_le_bytes())?;
+    w.write_all(&2835i32.to_le_bytes())?;
+    w.write_all(&2835i32.to_le_bytes())?;
+    w.write_all(&[0; 8])?;
+
+    // rows go from the bottom up, and each pixel is blue, green, red
+    let rows: Vec<_> = img.rows().collect();
+    let mut bytes = Vec::with_capacity(row_len as usize);
+    for row in rows.iter().rev() {
+        bytes.clear();
+        for p in row.iter() {
+            let [r, g, b] = p.to_bytes(img.depth());
+            bytes.extend_from_slice(&[b, g, r]);
+        }
+        bytes.resize(row_len as usize, 0);
+        w.write_all(&bytes)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::RGB;
+
+    #[test]
+    fn rows_are_padded_and_flipped() {
+        let mut img = PPMImg::new(2, 1, 255);
+        img.fg_color = RGB::new(1, 2, 3);
+        img.plot(0, 0);
+
+        let mut bmp = vec![];
+        write_bmp(&mut bmp, &img).unwrap();
+        assert_eq!(54 + 2 * 4, bmp.len());
+        assert_eq!(bmp.len() as u32, u32::from_le_bytes([bmp[2], bmp[3], bmp[4], bmp[5]]));
+        // the bottom row comes first
+        assert_eq!(&[0, 0, 0, 0, 3, 2, 1, 0], &bmp[54..]);
+    }
+}
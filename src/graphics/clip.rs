@@ -0,0 +1,111 @@
+#![allow(dead_code)]
+
+type Point = (f64, f64);
+
+/// Clip the line from `p0` to `p1` to the rectangle from `min` to `max`, by Liang-Barsky
+///
+/// Returns the range of t, from 0 at `p0` to 1 at `p1`, that is inside, or None if none is.
+/// Either bound may be infinite, to clip on one axis only.
+pub fn clip_line(p0: Point, p1: Point, min: Point, max: Point) -> Option<(f64, f64)> {
+    let (dx, dy) = (p1.0 - p0.0, p1.1 - p0.1);
+    let (mut t0, mut t1) = (0.0f64, 1.0f64);
+    // each side as (-delta, distance from the start to it), inside where delta * t <= distance
+    let sides = [
+        (-dx, p0.0 - min.0),
+        (dx, max.0 - p0.0),
+        (-dy, p0.1 - min.1),
+        (dy, max.1 - p0.1),
+    ];
+    for &(p, q) in sides.iter() {
+        if p == 0.0 {
+            // parallel to this side, so entirely in or out of it
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let t = q / p;
+            if p < 0.0 {
+                t0 = t0.max(t);
+            } else {
+                t1 = t1.min(t);
+            }
+        }
+    }
+    if t0 <= t1 {
+        Some((t0, t1))
+    } else {
+        None
+    }
+}
+
+/// Clip a polygon to the rectangle from `min` to `max`, by Sutherland-Hodgman
+///
+/// Concave polygons stay in one piece, with edges along the border where parts were cut off.
+/// The result does not repeat its first point.
+pub fn clip_polygon(polygon: &[Point], min: Point, max: Point) -> Vec<Point> {
+    let mut poly = polygon.to_vec();
+    if poly.len() > 1 && poly.first() == poly.last() {
+        poly.pop();
+    }
+    // signed distance inside each side
+    let sides: [&dyn Fn(Point) -> f64; 4] = [
+        &|p| p.0 - min.0,
+        &|p| max.0 - p.0,
+        &|p| p.1 - min.1,
+        &|p| max.1 - p.1,
+    ];
+    for inside in sides.iter() {
+        poly = clip_to_half_plane(&poly, inside);
+    }
+    poly
+}
+
+/// Keep the part of a polygon where `inside` is not negative
+fn clip_to_half_plane(polygon: &[Point], inside: impl Fn(Point) -> f64) -> Vec<Point> {
+    let mut out = vec![];
+    for (i, &q) in polygon.iter().enumerate() {
+        let p = polygon[(i + polygon.len() - 1) % polygon.len()];
+        let (dp, dq) = (inside(p), inside(q));
+        if (dp >= 0.0) != (dq >= 0.0) {
+            let t = dp / (dp - dq);
+            out.push((p.0 + (q.0 - p.0) * t, p.1 + (q.1 - p.1) * t));
+        }
+        if dq >= 0.0 {
+            out.push(q);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_against_a_box() {
+        let (min, max) = ((0.0, 0.0), (10.0, 10.0));
+        assert_eq!(Some((0.0, 1.0)), clip_line((1.0, 1.0), (9.0, 2.0), min, max));
+        assert_eq!(Some((0.25, 0.75)), clip_line((-5.0, 5.0), (15.0, 5.0), min, max));
+        assert_eq!(None, clip_line((-5.0, -1.0), (15.0, -1.0), min, max));
+        // crosses the corner region without entering the box
+        assert_eq!(None, clip_line((-3.0, 1.0), (1.0, -3.0), min, max));
+        let inf = f64::INFINITY;
+        assert_eq!(
+            Some((0.25, 0.75)),
+            clip_line((-5.0, 50.0), (15.0, 50.0), (0.0, -inf), (10.0, inf))
+        );
+    }
+
+    #[test]
+    fn polygon_against_a_box() {
+        use crate::graphics::polygon::signed_area;
+        let (min, max) = ((0.0, 0.0), (10.0, 10.0));
+        // a diamond poking out of every side
+        let diamond = [(5.0, -3.0), (13.0, 5.0), (5.0, 13.0), (-3.0, 5.0), (5.0, -3.0)];
+        let clipped = clip_polygon(&diamond, min, max);
+        assert_eq!(8, clipped.len());
+        // the box loses a triangle of area 2 at each corner
+        assert!((signed_area(&clipped) / 2.0 - 92.0).abs() < 1e-9);
+        assert!(clip_polygon(&[(20.0, 20.0), (30.0, 20.0), (30.0, 30.0)], min, max).is_empty());
+    }
+}
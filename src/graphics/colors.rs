@@ -1,7 +1,7 @@
-use std::cmp;
 use std::convert;
+use std::ops::{Add, Mul, Sub};
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct RGB {
     pub red: u16,
     pub blue: u16,
@@ -11,6 +11,18 @@ pub struct RGB {
 // Constructor and some useful "constants"
 impl RGB
 {
+    pub const BLACK: RGB = RGB::new(0, 0, 0);
+    pub const WHITE: RGB = RGB::new(255, 255, 255);
+    pub const RED: RGB = RGB::new(255, 0, 0);
+    pub const GREEN: RGB = RGB::new(0, 255, 0);
+    pub const BLUE: RGB = RGB::new(0, 0, 255);
+    pub const YELLOW: RGB = RGB::new(255, 255, 0);
+    pub const CYAN: RGB = RGB::new(0, 255, 255);
+    pub const MAGENTA: RGB = RGB::new(255, 0, 255);
+    pub const ORANGE: RGB = RGB::new(255, 165, 0);
+    pub const PURPLE: RGB = RGB::new(128, 0, 128);
+    pub const GRAY: RGB = RGB::new(128, 128, 128);
+
     pub fn gray(depth: u16) -> Self
     {
         RGB {
@@ -20,62 +32,481 @@ impl RGB
         }
     }
 
-    pub fn new(red: u16, green: u16, blue: u16) -> Self
+    pub const fn new(red: u16, green: u16, blue: u16) -> Self
     {
         RGB {
             red, green, blue
         }
     }
+
+    /// Parse a color like `"#1e90ff"` or `"#f80"`, with or without the `#`
+    pub fn from_hex(hex: &str) -> Option<Self>
+    {
+        let hex = hex.strip_prefix('#').unwrap_or(hex);
+        if !hex.is_ascii() {
+            return None;
+        }
+        let channel = |s: &str| u16::from_str_radix(s, 16).ok();
+        match hex.len() {
+            3 => {
+                // each digit is doubled, so f is ff
+                let d: Vec<u16> = hex
+                    .chars()
+                    .map(|c| channel(&c.to_string()))
+                    .collect::<Option<_>>()?;
+                Some(RGB::new(d[0] * 17, d[1] * 17, d[2] * 17))
+            }
+            6 => Some(RGB::new(channel(&hex[0..2])?, channel(&hex[2..4])?, channel(&hex[4..6])?)),
+            _ => None,
+        }
+    }
+
+    /// Hex form of a color with depth 255, like `"#1e90ff"`
+    pub fn to_hex(self) -> String
+    {
+        let [r, g, b] = self.to_bytes(255);
+        format!("#{:02x}{:02x}{:02x}", r, g, b)
+    }
+
+    /// The color `t` of the way from `self` to `other`
+    pub fn lerp(self, other: RGB, t: f64) -> RGB
+    {
+        let mix = |a: u16, b: u16| (a as f64 + (b as f64 - a as f64) * t).round().max(0.0) as u16;
+        RGB::new(mix(self.red, other.red), mix(self.green, other.green), mix(self.blue, other.blue))
+    }
+
+    /// The colors of the corners of a triangle, mixed by how much each counts somewhere in it
+    pub fn blend3(colors: [RGB; 3], weights: [f64; 3]) -> RGB
+    {
+        let mix = |c: fn(RGB) -> u16| {
+            (0..3).map(|k| c(colors[k]) as f64 * weights[k]).sum::<f64>().round().max(0.0) as u16
+        };
+        RGB::new(mix(|c| c.red), mix(|c| c.green), mix(|c| c.blue))
+    }
+}
+
+// Arithmetic, channel by channel, stopping at 0 and u16::MAX
+impl Add for RGB
+{
+    type Output = RGB;
+
+    fn add(self, other: RGB) -> RGB
+    {
+        RGB::new(
+            self.red.saturating_add(other.red),
+            self.green.saturating_add(other.green),
+            self.blue.saturating_add(other.blue),
+        )
+    }
+}
+
+impl Sub for RGB
+{
+    type Output = RGB;
+
+    fn sub(self, other: RGB) -> RGB
+    {
+        RGB::new(
+            self.red.saturating_sub(other.red),
+            self.green.saturating_sub(other.green),
+            self.blue.saturating_sub(other.blue),
+        )
+    }
+}
+
+impl Mul<f64> for RGB
+{
+    type Output = RGB;
+
+    fn mul(self, k: f64) -> RGB
+    {
+        // the cast saturates
+        let scale = |c: u16| (c as f64 * k).round() as u16;
+        RGB::new(scale(self.red), scale(self.green), scale(self.blue))
+    }
+}
+
+// Conversion to bytes for image formats
+impl RGB
+{
+    /// Scale every channel from `0..=depth` to `0..=255`
+    pub fn to_bytes(self, depth: u16) -> [u8; 3]
+    {
+        let scale = |c: u16| {
+            if depth == 255 {
+                c as u8
+            } else {
+                ((c.min(depth) as u32 * 255 + depth as u32 / 2) / depth as u32) as u8
+            }
+        };
+        [scale(self.red), scale(self.green), scale(self.blue)]
+    }
+}
+
+// Linear light, for blending the way light adds up
+/// Undo the sRGB transfer curve, from 0..=1 to light intensity in 0..=1
+pub fn srgb_to_linear(v: f64) -> f64 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Apply the sRGB transfer curve, from light intensity in 0..=1 to 0..=1
+pub fn linear_to_srgb(v: f64) -> f64 {
+    if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+impl RGB
+{
+    /// Light intensity of every channel, from 0 to 1, treating the color as sRGB
+    pub fn to_linear(self, depth: u16) -> [f64; 3]
+    {
+        let d = depth as f64;
+        [self.red, self.green, self.blue].map(|c| srgb_to_linear(c as f64 / d))
+    }
+
+    /// The sRGB color for light intensities from 0 to 1, see `to_linear`
+    pub fn from_linear(light: [f64; 3], depth: u16) -> RGB
+    {
+        let d = depth as f64;
+        let [r, g, b] = light.map(|v| (linear_to_srgb(v.clamp(0.0, 1.0)) * d).round() as u16);
+        RGB::new(r, g, b)
+    }
+}
+
+/// A color with an opacity, from 0 for invisible to 1 for solid
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RGBA {
+    pub red: u16,
+    pub green: u16,
+    pub blue: u16,
+    pub alpha: f64,
+}
+
+impl RGBA
+{
+    pub const fn new(red: u16, green: u16, blue: u16, alpha: f64) -> Self
+    {
+        RGBA { red, green, blue, alpha }
+    }
+
+    pub fn rgb(self) -> RGB
+    {
+        RGB::new(self.red, self.green, self.blue)
+    }
+
+    /// Composite this color over an opaque one, the Porter-Duff source over operator
+    pub fn over(self, dst: RGB) -> RGB
+    {
+        dst.lerp(self.rgb(), self.alpha.clamp(0.0, 1.0))
+    }
+
+    /// Source over in linear light, for colors with channels up to `depth`
+    ///
+    /// Half covered pixels come out as bright as they should, instead of too dark
+    pub fn over_linear(self, dst: RGB, depth: u16) -> RGB
+    {
+        let a = self.alpha.clamp(0.0, 1.0);
+        let (src, dst) = (self.rgb().to_linear(depth), dst.to_linear(depth));
+        RGB::from_linear([0, 1, 2].map(|i| dst[i] + (src[i] - dst[i]) * a), depth)
+    }
+}
+
+impl RGB
+{
+    pub fn with_alpha(self, alpha: f64) -> RGBA
+    {
+        RGBA::new(self.red, self.green, self.blue, alpha)
+    }
+}
+
+impl convert::From<RGB> for RGBA {
+    fn from(rgb: RGB) -> RGBA {
+        rgb.with_alpha(1.0)
+    }
+}
+
+/// How a color drawn onto a pixel combines with what is there, before opacity is applied
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BlendMode {
+    /// The new color replaces the old one
+    Normal,
+    /// Channels multiply, only ever darkening, for shading overlays
+    Multiply,
+    /// The inverse of multiplying the inverses, only ever lightening
+    Screen,
+    /// Channels add up to full, like light painting
+    Add,
+}
+
+impl BlendMode
+{
+    /// What `src` drawn onto `dst` comes to, for colors with channels up to `depth`, working in
+    /// linear light if `linear` is set
+    pub fn mix(self, src: RGB, dst: RGB, depth: u16, linear: bool) -> RGB
+    {
+        if self == BlendMode::Normal {
+            return src;
+        }
+        let d = depth as f64;
+        let channels = |c: RGB| {
+            if linear {
+                c.to_linear(depth)
+            } else {
+                [c.red, c.green, c.blue].map(|v| v as f64 / d)
+            }
+        };
+        let (s, t) = (channels(src), channels(dst));
+        let mixed = [0, 1, 2].map(|i| match self {
+            BlendMode::Normal => s[i],
+            BlendMode::Multiply => s[i] * t[i],
+            BlendMode::Screen => 1.0 - (1.0 - s[i]) * (1.0 - t[i]),
+            BlendMode::Add => (s[i] + t[i]).min(1.0),
+        });
+        if linear {
+            RGB::from_linear(mixed, depth)
+        } else {
+            let [r, g, b] = mixed.map(|v| (v.clamp(0.0, 1.0) * d).round() as u16);
+            RGB::new(r, g, b)
+        }
+    }
+}
+
+/// Colors at positions from 0 to 1, blended in between
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gradient {
+    stops: Vec<(f64, RGB)>,
+}
+
+/// What a gradient along a curve follows
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Along {
+    /// The curve's parameter t
+    T,
+    /// Distance along the curve, so the colors move at an even pace
+    ArcLength,
+}
+
+impl Gradient {
+    /// A gradient through `stops` of (position, color), which need not be in order
+    pub fn new(mut stops: Vec<(f64, RGB)>) -> Self {
+        assert!(!stops.is_empty(), "Gradient needs at least one stop");
+        stops.sort_by(|a, b| a.0.partial_cmp(&b.0).expect("Stop positions must not be NaN"));
+        Gradient { stops }
+    }
+
+    /// A gradient with `colors` spread out evenly from 0 to 1
+    pub fn even(colors: &[RGB]) -> Self {
+        let last = (colors.len().max(2) - 1) as f64;
+        Gradient::new(colors.iter().enumerate().map(|(i, &c)| (i as f64 / last, c)).collect())
+    }
+
+    /// Color at `t`, keeping the end colors past either end
+    pub fn at(&self, t: f64) -> RGB {
+        let i = self.stops.partition_point(|s| s.0 <= t);
+        if i == 0 {
+            return self.stops[0].1;
+        }
+        if i == self.stops.len() {
+            return self.stops[i - 1].1;
+        }
+        let ((t0, c0), (t1, c1)) = (self.stops[i - 1], self.stops[i]);
+        c0.lerp(c1, (t - t0) / (t1 - t0))
+    }
 }
 
 /// Hue, Saturation, Luminosity
 /// 
 /// range: [0, 1]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct HSL {
     pub h: f64,
     pub s: f64,
     pub l: f64,
 }
 
+/// Hue, Saturation, Value
+///
+/// range: [0, 1]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct HSV {
+    pub h: f64,
+    pub s: f64,
+    pub v: f64,
+}
+
+impl HSL {
+    /// Turn the hue by `turns` of the color wheel, wrapping around
+    pub fn rotate_hue(self, turns: f64) -> Self {
+        HSL {
+            h: (self.h + turns).rem_euclid(1.0),
+            ..self
+        }
+    }
+}
+
+impl HSV {
+    /// Turn the hue by `turns` of the color wheel, wrapping around
+    pub fn rotate_hue(self, turns: f64) -> Self {
+        HSV {
+            h: (self.h + turns).rem_euclid(1.0),
+            ..self
+        }
+    }
+}
+
+impl RGB {
+    /// Turn the hue of a color of depth 255 by `turns` of the color wheel
+    pub fn rotate_hue(self, turns: f64) -> Self {
+        RGB::from(HSL::from(self).rotate_hue(turns))
+    }
+}
+
 impl convert::From<HSL> for RGB {
     // https://en.wikipedia.org/wiki/HSL_and_HSV#HSL_to_RGB_alternative
     fn from(hsl: HSL) -> RGB {
-        let hue = (hsl.h * 360.0).round() as i32;
-        let a = hsl.s * fmin2(hsl.l, 1.0 - hsl.l, 1000);
-        let f = |n| {
-            hsl.l
-                - a * fmax2(
-                    -1.0,
-                    cmp::min(
-                        cmp::min((n + hue / 30) % 12 - 3, 9 - (n + hue / 30) % 12),
-                        1,
-                    ) as f64,
-                    1000,
-                )
+        let a = hsl.s * hsl.l.min(1.0 - hsl.l);
+        let f = |n: f64| {
+            let k = (n + hsl.h * 12.0).rem_euclid(12.0);
+            hsl.l - a * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0)
         };
-        let (r, g, b) = (f(0).round() as i32, f(8).round() as i32, f(4).round() as i32);
-        assert!(r == 1 || r == 0);
-        assert!(g == 1 || g == 0);
-        assert!(b == 1 || b == 0);
-        RGB {
-            red: (f(0) * 255.0) as u16,
-            green: (f(8) * 255.0) as u16,
-            blue: (f(4) * 255.0) as u16,
-        }
+        from_unit(f(0.0), f(8.0), f(4.0))
     }
 }
 
-fn fmax2(a: f64, b: f64, prec: i32) -> f64 {
-    let fprec = prec as f64;
-    cmp::max((a * fprec).round() as i32, (b * fprec).round() as i32) as f64 / fprec
+impl convert::From<HSV> for RGB {
+    // https://en.wikipedia.org/wiki/HSL_and_HSV#HSV_to_RGB_alternative
+    fn from(hsv: HSV) -> RGB {
+        let f = |n: f64| {
+            let k = (n + hsv.h * 6.0).rem_euclid(6.0);
+            hsv.v - hsv.v * hsv.s * k.min(4.0 - k).clamp(0.0, 1.0)
+        };
+        from_unit(f(5.0), f(3.0), f(1.0))
+    }
 }
 
-fn fmin2(a: f64, b: f64, prec: i32) -> f64 {
-    let fprec = prec as f64;
-    cmp::min((a * fprec).round() as i32, (b * fprec).round() as i32) as f64 / fprec
+/// For colors of depth 255
+impl convert::From<RGB> for HSL {
+    fn from(rgb: RGB) -> HSL {
+        let (h, max, min) = hue_max_min(rgb);
+        let l = (max + min) / 2.0;
+        let s = if max == min {
+            0.0
+        } else {
+            (max - min) / (1.0 - (2.0 * l - 1.0).abs())
+        };
+        HSL { h, s, l }
+    }
 }
 
-fn fmin3(a: f64, b: f64, c: f64, prec: i32) -> f64 {
-    fmin2(fmin2(a, b, prec), c, prec)
-}
\ No newline at end of file
+/// For colors of depth 255
+impl convert::From<RGB> for HSV {
+    fn from(rgb: RGB) -> HSV {
+        let (h, max, min) = hue_max_min(rgb);
+        let s = if max == 0.0 { 0.0 } else { (max - min) / max };
+        HSV { h, s, v: max }
+    }
+}
+
+/// Hue of a color of depth 255, with its largest and smallest channels scaled to [0, 1]
+fn hue_max_min(rgb: RGB) -> (f64, f64, f64) {
+    let [r, g, b] = rgb.to_bytes(255).map(|c| c as f64 / 255.0);
+    let (max, min) = (r.max(g).max(b), r.min(g).min(b));
+    let c = max - min;
+    let sixths = if c == 0.0 {
+        0.0
+    } else if max == r {
+        ((g - b) / c).rem_euclid(6.0)
+    } else if max == g {
+        (b - r) / c + 2.0
+    } else {
+        (r - g) / c + 4.0
+    };
+    (sixths / 6.0, max, min)
+}
+
+fn from_unit(r: f64, g: f64, b: f64) -> RGB {
+    let scale = |c: f64| (c.clamp(0.0, 1.0) * 255.0).round() as u16;
+    RGB::new(scale(r), scale(g), scale(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_and_arithmetic() {
+        assert_eq!(Some(RGB::new(0x1e, 0x90, 0xff)), RGB::from_hex("#1e90ff"));
+        assert_eq!(Some(RGB::new(0xff, 0x88, 0x00)), RGB::from_hex("f80"));
+        assert_eq!(None, RGB::from_hex("#12345"));
+        assert_eq!(None, RGB::from_hex("#gg0000"));
+        assert_eq!("#1e90ff", RGB::new(0x1e, 0x90, 0xff).to_hex());
+
+        assert_eq!(RGB::new(128, 0, 128), RGB::RED.lerp(RGB::BLUE, 0.5));
+        assert_eq!(RGB::WHITE, RGB::YELLOW + RGB::BLUE);
+        assert_eq!(RGB::RED, RGB::MAGENTA - RGB::BLUE - RGB::BLUE);
+        assert_eq!(RGB::new(128, 128, 0), RGB::YELLOW * 0.5);
+    }
+
+    #[test]
+    fn hsl_and_hsv_round_trip() {
+        let dodger = RGB::new(0x1e, 0x90, 0xff);
+        assert_eq!(dodger, RGB::from(HSL::from(dodger)));
+        assert_eq!(dodger, RGB::from(HSV::from(dodger)));
+        assert_eq!(RGB::GREEN, RGB::from(HSV { h: 1.0 / 3.0, s: 1.0, v: 1.0 }));
+        assert_eq!(RGB::YELLOW, RGB::from(HSL { h: 1.0 / 6.0 + 1.0, s: 1.0, l: 0.5 }));
+        // a third of a turn takes red to green and green to blue
+        assert_eq!(RGB::GREEN, RGB::RED.rotate_hue(1.0 / 3.0));
+        assert_eq!(RGB::BLUE, RGB::GREEN.rotate_hue(-2.0 / 3.0));
+        let h = HSV::from(RGB::ORANGE).rotate_hue(0.5).h;
+        assert!((h - (165.0 / 255.0 / 6.0 + 0.5)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn gradient_stops() {
+        let g = Gradient::new(vec![(1.0, RGB::BLUE), (0.0, RGB::RED), (0.5, RGB::GREEN)]);
+        assert_eq!(RGB::RED, g.at(-1.0));
+        assert_eq!(RGB::GREEN, g.at(0.5));
+        assert_eq!(RGB::new(0, 128, 128), g.at(0.75));
+        assert_eq!(RGB::BLUE, g.at(2.0));
+        assert_eq!(g, Gradient::even(&[RGB::RED, RGB::GREEN, RGB::BLUE]));
+    }
+
+    #[test]
+    fn source_over() {
+        let half_red = RGB::RED.with_alpha(0.5);
+        assert_eq!(RGB::new(128, 0, 0), half_red.over(RGB::BLACK));
+        assert_eq!(RGB::new(255, 128, 128), half_red.over(RGB::WHITE));
+        assert_eq!(RGB::BLUE, RGB::RED.with_alpha(0.0).over(RGB::BLUE));
+        assert_eq!(RGB::RED, RGBA::from(RGB::RED).over(RGB::BLUE));
+    }
+
+    #[test]
+    fn linear_light_blending() {
+        for v in [0.0, 0.02, 0.5, 1.0].iter() {
+            assert!((linear_to_srgb(srgb_to_linear(*v)) - v).abs() < 1e-12);
+        }
+        // half white over black is half the light, which is brighter than half the value
+        assert_eq!(RGB::gray(128), RGB::WHITE.with_alpha(0.5).over(RGB::BLACK));
+        assert_eq!(RGB::gray(188), RGB::WHITE.with_alpha(0.5).over_linear(RGB::BLACK, 255));
+        assert_eq!(RGB::RED, RGB::RED.with_alpha(1.0).over_linear(RGB::BLUE, 255));
+    }
+
+    #[test]
+    fn blend_modes() {
+        let (half, orange) = (RGB::gray(128), RGB::new(255, 128, 0));
+        assert_eq!(RGB::new(128, 64, 0), BlendMode::Multiply.mix(half, orange, 255, false));
+        assert_eq!(RGB::new(255, 192, 128), BlendMode::Screen.mix(half, orange, 255, false));
+        assert_eq!(RGB::new(255, 255, 128), BlendMode::Add.mix(half, orange, 255, false));
+        assert_eq!(half, BlendMode::Normal.mix(half, orange, 255, true));
+        // half the light twice over is all of it
+        let half_light = RGB::from_linear([0.5; 3], 255);
+        assert_eq!(RGB::WHITE, BlendMode::Add.mix(half_light, half_light, 255, true));
+    }
+}
@@ -0,0 +1,326 @@
+#![allow(dead_code)]
+
+//! Boolean operations on closed triangle meshes with BSP trees, after Evan Wallace's csg.js
+
+use std::collections::HashMap;
+use std::mem;
+
+use super::mesh::{vertex_key, Mesh};
+use super::vector::{self, Vec3};
+
+/// How far from a plane a point can be and still count as on it
+const EPSILON: f64 = 1e-5;
+
+#[derive(Copy, Clone, Debug)]
+struct Plane {
+    normal: Vec3,
+    w: f64,
+}
+
+impl Plane {
+    /// The plane through three points, or None if they are on a line
+    fn from_points(a: Vec3, b: Vec3, c: Vec3) -> Option<Plane> {
+        let n = vector::triangle_normal(a, b, c);
+        if vector::length(n) < EPSILON * EPSILON {
+            return None;
+        }
+        let normal = vector::normalize(n);
+        Some(Plane {
+            normal,
+            w: vector::dot(normal, a),
+        })
+    }
+
+    fn flip(&mut self) {
+        self.normal = vector::scale(self.normal, -1.0);
+        self.w = -self.w;
+    }
+
+    /// Sort `poly` by which side of this plane it is on, cutting it in two if it spans the plane
+    fn split(
+        &self,
+        poly: &Polygon,
+        coplanar_front: &mut Vec<Polygon>,
+        coplanar_back: &mut Vec<Polygon>,
+        front: &mut Vec<Polygon>,
+        back: &mut Vec<Polygon>,
+    ) {
+        const COPLANAR: u8 = 0;
+        const FRONT: u8 = 1;
+        const BACK: u8 = 2;
+        const SPANNING: u8 = 3;
+
+        let sides: Vec<u8> = poly
+            .vertices
+            .iter()
+            .map(|&v| {
+                let t = vector::dot(self.normal, v) - self.w;
+                if t < -EPSILON {
+                    BACK
+                } else if t > EPSILON {
+                    FRONT
+                } else {
+                    COPLANAR
+                }
+            })
+            .collect();
+        match sides.iter().fold(COPLANAR, |acc, s| acc | s) {
+            COPLANAR => {
+                if vector::dot(self.normal, poly.plane.normal) > 0.0 {
+                    coplanar_front.push(poly.clone());
+                } else {
+                    coplanar_back.push(poly.clone());
+                }
+            }
+            FRONT => front.push(poly.clone()),
+            BACK => back.push(poly.clone()),
+            _ => {
+                let (mut f, mut b) = (vec![], vec![]);
+                let n = poly.vertices.len();
+                for i in 0..n {
+                    let j = (i + 1) % n;
+                    let (si, sj) = (sides[i], sides[j]);
+                    let (vi, vj) = (poly.vertices[i], poly.vertices[j]);
+                    if si != BACK {
+                        f.push(vi);
+                    }
+                    if si != FRONT {
+                        b.push(vi);
+                    }
+                    if si | sj == SPANNING {
+                        let d = vector::sub(vj, vi);
+                        let t =
+                            (self.w - vector::dot(self.normal, vi)) / vector::dot(self.normal, d);
+                        let v = vector::add(vi, vector::scale(d, t));
+                        f.push(v);
+                        b.push(v);
+                    }
+                }
+                if f.len() >= 3 {
+                    front.push(Polygon {
+                        vertices: f,
+                        plane: poly.plane,
+                    });
+                }
+                if b.len() >= 3 {
+                    back.push(Polygon {
+                        vertices: b,
+                        plane: poly.plane,
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// A convex polygon, wound counter clockwise around its plane's normal
+#[derive(Clone, Debug)]
+struct Polygon {
+    vertices: Vec<Vec3>,
+    plane: Plane,
+}
+
+impl Polygon {
+    fn flip(&mut self) {
+        self.vertices.reverse();
+        self.plane.flip();
+    }
+}
+
+/// A node of a BSP tree: everything in front of `plane` goes to `front`, and the rest to `back`
+#[derive(Debug, Default)]
+struct Node {
+    plane: Option<Plane>,
+    front: Option<Box<Node>>,
+    back: Option<Box<Node>>,
+    polygons: Vec<Polygon>,
+}
+
+impl Node {
+    fn new(polygons: Vec<Polygon>) -> Node {
+        let mut node = Node::default();
+        node.build(polygons);
+        node
+    }
+
+    /// Turn solid space into empty space and the other way around
+    fn invert(&mut self) {
+        for p in self.polygons.iter_mut() {
+            p.flip();
+        }
+        if let Some(plane) = self.plane.as_mut() {
+            plane.flip();
+        }
+        for child in [&mut self.front, &mut self.back].iter_mut() {
+            if let Some(node) = child.as_mut() {
+                node.invert();
+            }
+        }
+        mem::swap(&mut self.front, &mut self.back);
+    }
+
+    /// Remove the parts of `polygons` that are inside this tree
+    fn clip_polygons(&self, polygons: Vec<Polygon>) -> Vec<Polygon> {
+        let plane = match self.plane {
+            Some(plane) => plane,
+            None => return polygons,
+        };
+        let (mut front, mut back) = (vec![], vec![]);
+        for p in polygons.iter() {
+            let (mut cf, mut cb) = (vec![], vec![]);
+            plane.split(p, &mut cf, &mut cb, &mut front, &mut back);
+            front.append(&mut cf);
+            back.append(&mut cb);
+        }
+        let mut front = match &self.front {
+            Some(node) => node.clip_polygons(front),
+            None => front,
+        };
+        // behind a leaf is inside the solid
+        if let Some(node) = &self.back {
+            front.extend(node.clip_polygons(back));
+        }
+        front
+    }
+
+    /// Remove the parts of this tree's polygons that are inside `bsp`
+    fn clip_to(&mut self, bsp: &Node) {
+        self.polygons = bsp.clip_polygons(mem::take(&mut self.polygons));
+        for child in [&mut self.front, &mut self.back].iter_mut() {
+            if let Some(node) = child.as_mut() {
+                node.clip_to(bsp);
+            }
+        }
+    }
+
+    fn all_polygons(&self) -> Vec<Polygon> {
+        let mut all = self.polygons.clone();
+        for node in [&self.front, &self.back].iter().copied().flatten() {
+            all.extend(node.all_polygons());
+        }
+        all
+    }
+
+    fn build(&mut self, polygons: Vec<Polygon>) {
+        if polygons.is_empty() {
+            return;
+        }
+        let plane = *self.plane.get_or_insert(polygons[0].plane);
+        let (mut front, mut back) = (vec![], vec![]);
+        for p in polygons.iter() {
+            let (mut cf, mut cb) = (vec![], vec![]);
+            plane.split(p, &mut cf, &mut cb, &mut front, &mut back);
+            self.polygons.append(&mut cf);
+            self.polygons.append(&mut cb);
+        }
+        if !front.is_empty() {
+            self.front.get_or_insert_with(Default::default).build(front);
+        }
+        if !back.is_empty() {
+            self.back.get_or_insert_with(Default::default).build(back);
+        }
+    }
+}
+
+fn to_polygons(mesh: &Mesh) -> Vec<Polygon> {
+    mesh.faces_iter()
+        .filter_map(|[a, b, c]| {
+            Plane::from_points(a, b, c).map(|plane| Polygon {
+                vertices: vec![a, b, c],
+                plane,
+            })
+        })
+        .collect()
+}
+
+fn to_mesh(polygons: Vec<Polygon>) -> Mesh {
+    let mut mesh = Mesh::new();
+    let mut seen = HashMap::new();
+    for p in polygons.iter() {
+        let ids: Vec<usize> = p
+            .vertices
+            .iter()
+            .map(|&v| *seen.entry(vertex_key(v)).or_insert_with(|| mesh.add_vertex(v)))
+            .collect();
+        // the polygons are convex, so fans work
+        for i in 1..ids.len() - 1 {
+            mesh.add_face(ids[0], ids[i], ids[i + 1]);
+        }
+    }
+    mesh
+}
+
+/// Everything inside `a` or `b`
+///
+/// Both meshes must be closed, with faces pointing out
+pub fn union(a: &Mesh, b: &Mesh) -> Mesh {
+    let (mut a, mut b) = (Node::new(to_polygons(a)), Node::new(to_polygons(b)));
+    a.clip_to(&b);
+    b.clip_to(&a);
+    // drop faces of b that touch a face of a
+    b.invert();
+    b.clip_to(&a);
+    b.invert();
+    a.build(b.all_polygons());
+    to_mesh(a.all_polygons())
+}
+
+/// Everything inside `a` but not inside `b`
+///
+/// Both meshes must be closed, with faces pointing out
+pub fn difference(a: &Mesh, b: &Mesh) -> Mesh {
+    let (mut a, mut b) = (Node::new(to_polygons(a)), Node::new(to_polygons(b)));
+    a.invert();
+    a.clip_to(&b);
+    b.clip_to(&a);
+    b.invert();
+    b.clip_to(&a);
+    b.invert();
+    a.build(b.all_polygons());
+    a.invert();
+    to_mesh(a.all_polygons())
+}
+
+/// Everything inside both `a` and `b`
+///
+/// Both meshes must be closed, with faces pointing out
+pub fn intersection(a: &Mesh, b: &Mesh) -> Mesh {
+    let (mut a, mut b) = (Node::new(to_polygons(a)), Node::new(to_polygons(b)));
+    a.invert();
+    b.clip_to(&a);
+    b.invert();
+    a.clip_to(&b);
+    b.clip_to(&a);
+    a.build(b.all_polygons());
+    a.invert();
+    to_mesh(a.all_polygons())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::matrix::Matrix;
+
+    fn cube(corner: Vec3, size: f64) -> Mesh {
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.add_box_polygons(corner, size, size, size);
+        Mesh::from_polygons(&m)
+    }
+
+    /// Volume of a closed mesh by the divergence theorem
+    fn volume(mesh: &Mesh) -> f64 {
+        mesh.faces_iter()
+            .map(|[a, b, c]| vector::dot(a, vector::cross(b, c)) / 6.0)
+            .sum()
+    }
+
+    #[test]
+    fn overlapping_cubes() {
+        // [0, 2] and [1, 3] on every axis
+        let (a, b) = (cube((0.0, 2.0, 2.0), 2.0), cube((1.0, 3.0, 3.0), 2.0));
+        assert!((volume(&a) - 8.0).abs() < 1e-9);
+        assert!((volume(&union(&a, &b)) - 15.0).abs() < 1e-9);
+        assert!((volume(&intersection(&a, &b)) - 1.0).abs() < 1e-9);
+        assert!((volume(&difference(&a, &b)) - 7.0).abs() < 1e-9);
+    }
+}
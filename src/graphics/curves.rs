@@ -0,0 +1,412 @@
+#![allow(dead_code)]
+
+use super::parametrics::{gauss_arc_length, Curve};
+use super::utils;
+use std::f64::consts;
+
+/// Evaluate at^3 + bt^2 + ct + d
+fn cubic(coef: (f64, f64, f64, f64), t: f64) -> f64 {
+    let (a, b, c, d) = coef;
+    ((a * t + b) * t + c) * t + d
+}
+
+/// Evaluate the derivative of at^3 + bt^2 + ct + d
+fn cubic_derivative(coef: (f64, f64, f64, f64), t: f64) -> f64 {
+    let (a, b, c, _d) = coef;
+    (3.0 * a * t + 2.0 * b) * t + c
+}
+
+/// A straight line segment from `p0` to `p1`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Line {
+    pub p0: (f64, f64),
+    pub p1: (f64, f64),
+}
+
+impl Line {
+    pub fn new(p0: (f64, f64), p1: (f64, f64)) -> Self {
+        Line { p0, p1 }
+    }
+}
+
+impl Curve for Line {
+    fn point_at(&self, t: f64) -> (f64, f64) {
+        (
+            self.p0.0 + (self.p1.0 - self.p0.0) * t,
+            self.p0.1 + (self.p1.1 - self.p0.1) * t,
+        )
+    }
+
+    fn derivative_at(&self, _t: f64) -> (f64, f64) {
+        (self.p1.0 - self.p0.0, self.p1.1 - self.p0.1)
+    }
+
+    fn arc_length(&self, t0: f64, t1: f64, _tolerance: f64) -> f64 {
+        let (dx, dy) = self.derivative_at(0.0);
+        dx.hypot(dy) * (t1 - t0).abs()
+    }
+}
+
+/// A circular arc around `center`
+///
+/// Angles go counter clockwise from the x axis, in degrees
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Arc {
+    pub center: (f64, f64),
+    pub radius: f64,
+    pub start_deg: f64,
+    pub sweep_deg: f64,
+}
+
+impl Arc {
+    pub fn new(center: (f64, f64), radius: f64, start_deg: f64, sweep_deg: f64) -> Self {
+        Arc {
+            center,
+            radius,
+            start_deg,
+            sweep_deg,
+        }
+    }
+
+    fn angle_at(&self, t: f64) -> f64 {
+        (self.start_deg + self.sweep_deg * t).to_radians()
+    }
+}
+
+impl Curve for Arc {
+    fn point_at(&self, t: f64) -> (f64, f64) {
+        let (dx, dy) = utils::polar_to_xy(self.radius, self.start_deg + self.sweep_deg * t);
+        (self.center.0 + dx, self.center.1 + dy)
+    }
+
+    fn derivative_at(&self, t: f64) -> (f64, f64) {
+        let (sin, cos) = self.angle_at(t).sin_cos();
+        let w = self.sweep_deg.to_radians() * self.radius;
+        (-sin * w, cos * w)
+    }
+
+    fn arc_length(&self, t0: f64, t1: f64, _tolerance: f64) -> f64 {
+        (self.sweep_deg.to_radians() * self.radius * (t1 - t0)).abs()
+    }
+}
+
+/// A cubic Bezier curve
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Bezier3 {
+    pub p0: (f64, f64),
+    pub p1: (f64, f64),
+    pub p2: (f64, f64),
+    pub p3: (f64, f64),
+    xcoef: (f64, f64, f64, f64),
+    ycoef: (f64, f64, f64, f64),
+}
+
+impl Bezier3 {
+    /// Make a new cubic Bezier curve
+    /// # Arguments
+    /// `p[0-3]` - control points
+    pub fn new(p0: (f64, f64), p1: (f64, f64), p2: (f64, f64), p3: (f64, f64)) -> Self {
+        Bezier3 {
+            p0,
+            p1,
+            p2,
+            p3,
+            xcoef: utils::compute_bezier3_coef(p0.0, p1.0, p2.0, p3.0),
+            ycoef: utils::compute_bezier3_coef(p0.1, p1.1, p2.1, p3.1),
+        }
+    }
+}
+
+impl Curve for Bezier3 {
+    fn point_at(&self, t: f64) -> (f64, f64) {
+        (cubic(self.xcoef, t), cubic(self.ycoef, t))
+    }
+
+    fn derivative_at(&self, t: f64) -> (f64, f64) {
+        (
+            cubic_derivative(self.xcoef, t),
+            cubic_derivative(self.ycoef, t),
+        )
+    }
+
+    fn arc_length(&self, t0: f64, t1: f64, tolerance: f64) -> f64 {
+        gauss_arc_length(self, t0, t1, tolerance)
+    }
+}
+
+/// A cubic Hermite curve
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Hermite3 {
+    pub p0: (f64, f64),
+    pub p1: (f64, f64),
+    pub r0: (f64, f64),
+    pub r1: (f64, f64),
+    xcoef: (f64, f64, f64, f64),
+    ycoef: (f64, f64, f64, f64),
+}
+
+impl Hermite3 {
+    /// Make a new cubic Hermite curve
+    /// # Arguments
+    /// `p0`, `p1` - endpoints
+    ///
+    /// `r0`, `r1` - rate of change at each endpoint
+    pub fn new(p0: (f64, f64), p1: (f64, f64), r0: (f64, f64), r1: (f64, f64)) -> Self {
+        Hermite3 {
+            p0,
+            p1,
+            r0,
+            r1,
+            xcoef: utils::compute_hermite3_coef(p0.0, p1.0, r0.0, r1.0),
+            ycoef: utils::compute_hermite3_coef(p0.1, p1.1, r0.1, r1.1),
+        }
+    }
+}
+
+impl Curve for Hermite3 {
+    fn point_at(&self, t: f64) -> (f64, f64) {
+        (cubic(self.xcoef, t), cubic(self.ycoef, t))
+    }
+
+    fn derivative_at(&self, t: f64) -> (f64, f64) {
+        (
+            cubic_derivative(self.xcoef, t),
+            cubic_derivative(self.ycoef, t),
+        )
+    }
+
+    fn arc_length(&self, t0: f64, t1: f64, tolerance: f64) -> f64 {
+        gauss_arc_length(self, t0, t1, tolerance)
+    }
+}
+
+/// Fresnel integrals (C(x), S(x)), with the integrand cos(pi t^2 / 2) and sin(pi t^2 / 2)
+pub fn fresnel(x: f64) -> (f64, f64) {
+    let ax = x.abs();
+    let (c, s) = if ax < 1.5 {
+        // power series, sum of (-1)^n (pi/2)^(2n) x^(4n+1) / ((2n)! (4n+1)), likewise for S
+        let z = consts::FRAC_PI_2 * ax * ax;
+        let (mut c, mut s) = (0.0, 0.0);
+        // term = z^k / k! * ax, where even k go to C and odd k go to S
+        let mut term = ax;
+        for k in 0..80 {
+            let contrib = term / (2 * k + 1) as f64;
+            match k % 4 {
+                0 => c += contrib,
+                1 => s += contrib,
+                2 => c -= contrib,
+                _ => s -= contrib,
+            }
+            term *= z / (k + 1) as f64;
+            if term < 1e-17 {
+                break;
+            }
+        }
+        (c, s)
+    } else {
+        // continued fraction of the complex error function, evaluated by Lentz's method as in
+        // Numerical Recipes; unlike the series, no terms grow large, and unlike an asymptotic
+        // expansion it converges all the way down to 1.5
+        let mul = |a: (f64, f64), b: (f64, f64)| (a.0 * b.0 - a.1 * b.1, a.0 * b.1 + a.1 * b.0);
+        let inv = |a: (f64, f64)| {
+            let d = a.0 * a.0 + a.1 * a.1;
+            (a.0 / d, -a.1 / d)
+        };
+        let px2 = consts::PI * ax * ax;
+        let mut b = (1.0, -px2);
+        let mut cf = (1e300, 0.0);
+        let mut d = inv(b);
+        let mut h = d;
+        for k in 1..100 {
+            let n = (2 * k - 1) as f64;
+            let a = -n * (n + 1.0);
+            b.0 += 4.0;
+            d = inv((a * d.0 + b.0, a * d.1 + b.1));
+            let r = inv(cf);
+            cf = (b.0 + a * r.0, b.1 + a * r.1);
+            let del = mul(cf, d);
+            h = mul(h, del);
+            if (del.0 - 1.0).abs() + del.1.abs() < 1e-16 {
+                break;
+            }
+        }
+        let h = mul((ax, -ax), h);
+        let (sin, cos) = (px2 / 2.0).sin_cos();
+        let e = mul((cos, sin), h);
+        mul((0.5, 0.5), (1.0 - e.0, -e.1))
+    };
+    if x < 0.0 {
+        (-c, -s)
+    } else {
+        (c, s)
+    }
+}
+
+/// A clothoid (Euler spiral) segment, whose curvature changes linearly along its length
+///
+/// Curvature is positive for turning counter clockwise
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Clothoid {
+    pub start: (f64, f64),
+    /// Heading at the start, counter clockwise from the x axis
+    pub start_deg: f64,
+    pub length: f64,
+    /// Curvature (1 / radius) at the start
+    pub k0: f64,
+    /// Curvature (1 / radius) at the end
+    pub k1: f64,
+}
+
+impl Clothoid {
+    pub fn new(start: (f64, f64), start_deg: f64, length: f64, k0: f64, k1: f64) -> Self {
+        // curvature changes by (k1 - k0) / length, so a segment needs some length
+        assert!(length > 0.0, "Clothoid length must be positive");
+        Clothoid {
+            start,
+            start_deg,
+            length,
+            k0,
+            k1,
+        }
+    }
+
+    /// Heading in radians at arc length `s`
+    fn heading(&self, s: f64) -> f64 {
+        let a = (self.k1 - self.k0) / self.length;
+        self.start_deg.to_radians() + self.k0 * s + a * s * s / 2.0
+    }
+
+    /// Heading at the end, in degrees, for chaining segments
+    pub fn end_deg(&self) -> f64 {
+        self.heading(self.length).to_degrees()
+    }
+}
+
+impl Curve for Clothoid {
+    fn point_at(&self, t: f64) -> (f64, f64) {
+        let s = t * self.length;
+        let (x0, y0) = self.start;
+        let theta0 = self.start_deg.to_radians();
+        let a = (self.k1 - self.k0) / self.length;
+        if a.abs() < 1e-12 {
+            // constant curvature, so an arc or a line
+            if self.k0.abs() < 1e-12 {
+                let (sin, cos) = theta0.sin_cos();
+                return (x0 + s * cos, y0 + s * sin);
+            }
+            let r = 1.0 / self.k0;
+            let theta = self.heading(s);
+            return (
+                x0 + r * (theta.sin() - theta0.sin()),
+                y0 - r * (theta.cos() - theta0.cos()),
+            );
+        }
+
+        // complete the square: heading = phi + sign * pi/2 * u^2
+        // with u = sqrt(|a| / pi) * (s + k0 / a)
+        let sign = a.signum();
+        let scale = (a.abs() / consts::PI).sqrt();
+        let phi = theta0 - self.k0 * self.k0 / (2.0 * a);
+        let (c0, s0) = fresnel(scale * (self.k0 / a));
+        let (c1, s1) = fresnel(scale * (s + self.k0 / a));
+        let (dc, ds) = (c1 - c0, s1 - s0);
+        let (sin, cos) = phi.sin_cos();
+        (
+            x0 + (cos * dc - sign * sin * ds) / scale,
+            y0 + (sin * dc + sign * cos * ds) / scale,
+        )
+    }
+
+    fn derivative_at(&self, t: f64) -> (f64, f64) {
+        let (sin, cos) = self.heading(t * self.length).sin_cos();
+        (cos * self.length, sin * self.length)
+    }
+
+    fn arc_length(&self, t0: f64, t1: f64, _tolerance: f64) -> f64 {
+        (t1 - t0).abs() * self.length
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::parametrics::Parametric;
+    use super::*;
+
+    #[test]
+    fn straight_bezier_length() {
+        let b = Bezier3::new((0.0, 0.0), (0.6, 0.8), (1.5, 2.0), (3.0, 4.0));
+        assert!((b.length(1e-9) - 5.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn hermite_matches_bezier() {
+        // a hermite with r = 3 * (control point offset) is the same curve as the bezier
+        let b = Bezier3::new((0.0, 0.0), (10.0, 40.0), (60.0, 40.0), (80.0, 0.0));
+        let h = Hermite3::new((0.0, 0.0), (80.0, 0.0), (30.0, 120.0), (60.0, -120.0));
+        assert!((b.length(1e-9) - h.length(1e-9)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn t_at_length_on_arc() {
+        let a = Arc::new((0.0, 0.0), 10.0, 0.0, 180.0);
+        let t = a.t_at_length(consts::PI * 10.0 / 4.0, 1e-9);
+        assert!((t - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn closest_point_on_arch() {
+        let b = Bezier3::new((0.0, 0.0), (0.0, 40.0), (60.0, 40.0), (60.0, 0.0));
+        let (t, (x, y)) = b.closest_point((30.0, 100.0));
+        assert!((t - 0.5).abs() < 1e-6);
+        assert!((x - 30.0).abs() < 1e-6 && (y - 30.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn fresnel_values() {
+        // reference values from Abramowitz and Stegun, table 7.7
+        let (c, s) = fresnel(1.0);
+        assert!((c - 0.779_893_400_4).abs() < 1e-9 && (s - 0.438_259_147_4).abs() < 1e-9);
+        let (c, s) = fresnel(5.0);
+        assert!((c - 0.563_631_188_2).abs() < 1e-9 && (s - 0.499_191_381_7).abs() < 1e-9);
+        // on both sides of where the series hands over to the continued fraction, and where
+        // the series would have lost most of its digits
+        for &(x, c0, s0) in [
+            (1.499_999, 0.445_262_099_920_256, 0.697_505_342_763_349),
+            (1.5, 0.445_261_176_039_822, 0.697_504_960_082_093),
+            (3.5, 0.532_572_435_028_001, 0.415_248_011_972_438),
+            (-3.6, -0.587_953_259_673_416, -0.492_309_489_111_002),
+        ]
+        .iter()
+        {
+            let (c, s) = fresnel(x);
+            assert!((c - c0).abs() < 1e-12 && (s - s0).abs() < 1e-12, "{}: {} {}", x, c, s);
+        }
+        assert_eq!(fresnel(0.0), (0.0, 0.0));
+    }
+
+    #[test]
+    fn clothoid_matches_integration() {
+        let c = Clothoid::new((10.0, 20.0), 30.0, 200.0, -0.01, 0.03);
+        // integrate the heading numerically with small steps
+        let (mut x, mut y) = c.start;
+        let n = 200_000;
+        let ds = c.length / n as f64;
+        for i in 0..n {
+            let theta = c.heading((i as f64 + 0.5) * ds);
+            x += theta.cos() * ds;
+            y += theta.sin() * ds;
+        }
+        let (cx, cy) = c.point_at(1.0);
+        assert!((cx - x).abs() < 1e-6 && (cy - y).abs() < 1e-6);
+    }
+
+    #[test]
+    fn circle_length() {
+        let r = 100.0;
+        let c = Parametric::new(
+            |t: f64| r * (t * 2.0 * consts::PI).cos(),
+            |t: f64| r * (t * 2.0 * consts::PI).sin(),
+        );
+        assert!((c.length(1e-6) - 2.0 * consts::PI * r).abs() < 1e-3);
+    }
+}
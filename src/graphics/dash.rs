@@ -0,0 +1,93 @@
+#![allow(dead_code)]
+
+/// A dash pattern of alternating on and off lengths, in pixels
+///
+/// `vec![10.0, 5.0]` draws 10 pixels, skips 5, and repeats.
+/// A zero on length makes a dot. Odd length patterns are repeated, as in SVG.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dash {
+    pub pattern: Vec<f64>,
+    /// Distance into the pattern at which drawing starts
+    pub offset: f64,
+}
+
+impl Dash {
+    pub fn new(mut pattern: Vec<f64>, offset: f64) -> Self {
+        assert!(!pattern.is_empty(), "Dash pattern must not be empty");
+        assert!(
+            pattern.iter().all(|d| *d >= 0.0) && pattern.iter().sum::<f64>() > 0.0,
+            "Dash lengths must be non-negative and not all zero"
+        );
+        if pattern.len() % 2 == 1 {
+            pattern.extend(pattern.clone());
+        }
+        Dash { pattern, offset }
+    }
+
+    /// Split a polyline into the pieces that are "on", measured by arc length
+    pub fn apply(&self, pts: &[(f64, f64)]) -> Vec<Vec<(f64, f64)>> {
+        let mut dashes = vec![];
+        if pts.is_empty() {
+            return dashes;
+        }
+
+        // find where in the pattern the offset lands
+        let total: f64 = self.pattern.iter().sum();
+        let mut into = self.offset.rem_euclid(total);
+        let mut i = 0;
+        while into > 0.0 && into >= self.pattern[i] {
+            into -= self.pattern[i];
+            i = (i + 1) % self.pattern.len();
+        }
+        let mut left = self.pattern[i] - into;
+
+        let mut current = if i % 2 == 0 { vec![pts[0]] } else { vec![] };
+        for w in pts.windows(2) {
+            let (mut p, q) = (w[0], w[1]);
+            let mut seg = (q.0 - p.0).hypot(q.1 - p.1);
+            while seg > left {
+                // the pattern changes within this segment
+                let f = left / seg;
+                p = (p.0 + (q.0 - p.0) * f, p.1 + (q.1 - p.1) * f);
+                seg -= left;
+                if i % 2 == 0 {
+                    current.push(p);
+                    dashes.push(std::mem::take(&mut current));
+                } else {
+                    current.push(p);
+                }
+                i = (i + 1) % self.pattern.len();
+                left = self.pattern[i];
+            }
+            left -= seg;
+            if i % 2 == 0 {
+                current.push(q);
+            }
+        }
+        if i % 2 == 0 && !current.is_empty() {
+            dashes.push(current);
+        }
+        dashes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dashes_along_polyline() {
+        let dash = Dash::new(vec![4.0, 2.0], 1.0);
+        let pieces = dash.apply(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)]);
+        let spans: Vec<_> = pieces.iter().map(|p| (p[0], p[p.len() - 1])).collect();
+        assert_eq!(
+            vec![
+                ((0.0, 0.0), (3.0, 0.0)),
+                ((5.0, 0.0), (9.0, 0.0)),
+                ((10.0, 1.0), (10.0, 5.0)),
+                ((10.0, 7.0), (10.0, 10.0)),
+            ],
+            spans
+        );
+    }
+}
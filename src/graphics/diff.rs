@@ -0,0 +1,91 @@
+#![allow(dead_code)]
+
+//! Comparing renders against known good ones
+
+use super::{PPMImg, RGB};
+
+/// How two images of the same size differ
+pub struct DiffReport {
+    /// Pixels where some channel differs by more than the tolerance
+    pub differing: usize,
+    pub total: usize,
+    /// Largest difference of any channel of any pixel
+    pub max_difference: u16,
+    /// The first image dimmed, with the differing pixels in red
+    pub image: PPMImg,
+}
+
+impl DiffReport {
+    /// Whether every pixel is within the tolerance
+    pub fn matches(&self) -> bool {
+        self.differing == 0
+    }
+
+    /// Share of the pixels that differ, from 0 to 1
+    pub fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.differing as f64 / self.total as f64
+        }
+    }
+}
+
+/// Compare two images pixel by pixel, allowing every channel to be off by `tolerance`
+pub fn diff(a: &PPMImg, b: &PPMImg, tolerance: u16) -> DiffReport {
+    assert!(
+        a.width() == b.width() && a.height() == b.height(),
+        "Images of {}x{} and {}x{} cannot be compared",
+        a.width(),
+        a.height(),
+        b.width(),
+        b.height()
+    );
+    let mut image = PPMImg::new(a.height(), a.width(), a.depth());
+    let (mut differing, mut max_difference) = (0, 0);
+    for (y, (ra, rb)) in a.rows().zip(b.rows()).enumerate() {
+        for (x, (pa, pb)) in ra.iter().zip(rb.iter()).enumerate() {
+            let d = pa
+                .red
+                .abs_diff(pb.red)
+                .max(pa.green.abs_diff(pb.green))
+                .max(pa.blue.abs_diff(pb.blue));
+            max_difference = max_difference.max(d);
+            let color = if d > tolerance {
+                differing += 1;
+                RGB::new(a.depth(), 0, 0)
+            } else {
+                *pa * 0.25
+            };
+            image.plot_color(x as i32, y as i32, color);
+        }
+    }
+    DiffReport {
+        differing,
+        total: (a.width() * a.height()) as usize,
+        max_difference,
+        image,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn differences_past_the_tolerance() {
+        let mut a = PPMImg::new(2, 2, 255);
+        a.plot_color(0, 0, RGB::gray(100));
+        let mut b = PPMImg::new(2, 2, 255);
+        b.plot_color(0, 0, RGB::gray(103));
+        b.plot_color(1, 1, RGB::WHITE);
+
+        let report = diff(&a, &b, 3);
+        assert_eq!(1, report.differing);
+        assert_eq!(255, report.max_difference);
+        assert_eq!(0.25, report.fraction());
+        assert_eq!(Some(RGB::gray(25)), report.image.pixel(0, 0));
+        assert_eq!(Some(RGB::RED), report.image.pixel(1, 1));
+        assert!(diff(&a, &a, 0).matches());
+    }
+}
@@ -0,0 +1,124 @@
+#![allow(dead_code)]
+
+//! Dithering, for reducing images to a few colors without banding
+
+use super::{PPMImg, RGB};
+
+/// How to spread out the error of rounding each pixel to the palette
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Dither {
+    /// Round every pixel to the closest color
+    None,
+    /// Nudge pixels by a 4 by 4 Bayer matrix before rounding, for a regular cross hatch
+    Bayer,
+    /// Push the error of every pixel onto the ones right and below it, for a fine grain
+    FloydSteinberg,
+}
+
+/// Thresholds from 0 to 15, in the order they turn on
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Round every pixel of a `width` wide image to a palette, with dithering
+///
+/// `nearest` gives the palette index and value closest to a color, whose channels may fall
+/// outside `0..=255`. `spread` is about how far apart palette colors are, and scales the Bayer
+/// matrix. Returns the palette index of every pixel.
+pub fn dither_with(
+    pixels: &[[u8; 3]],
+    width: usize,
+    dither: Dither,
+    spread: f64,
+    nearest: impl Fn([f64; 3]) -> (usize, [u8; 3]),
+) -> Vec<usize> {
+    let to_f64 = |p: [u8; 3]| p.map(|c| c as f64);
+    match dither {
+        Dither::None => pixels.iter().map(|&p| nearest(to_f64(p)).0).collect(),
+        Dither::Bayer => pixels
+            .iter()
+            .enumerate()
+            .map(|(i, &p)| {
+                let t = BAYER[i / width % 4][i % width % 4] as f64;
+                let offset = ((t + 0.5) / 16.0 - 0.5) * spread;
+                nearest(to_f64(p).map(|c| c + offset)).0
+            })
+            .collect(),
+        Dither::FloydSteinberg => {
+            let mut error = vec![[0.0; 3]; pixels.len()];
+            let mut out = Vec::with_capacity(pixels.len());
+            for (i, &p) in pixels.iter().enumerate() {
+                let mut wanted = to_f64(p);
+                for (w, e) in wanted.iter_mut().zip(error[i].iter()) {
+                    *w += e;
+                }
+                let (index, got) = nearest(wanted);
+                out.push(index);
+                let (x, below) = (i % width, i + width);
+                let mut spill = |j: usize, k: f64| {
+                    if j < error.len() {
+                        for c in 0..3 {
+                            error[j][c] += (wanted[c] - got[c] as f64) * k;
+                        }
+                    }
+                };
+                if x + 1 < width {
+                    spill(i + 1, 7.0 / 16.0);
+                    spill(below + 1, 1.0 / 16.0);
+                }
+                if x > 0 {
+                    spill(below - 1, 3.0 / 16.0);
+                }
+                spill(below, 5.0 / 16.0);
+            }
+            out
+        }
+    }
+}
+
+/// Round every pixel of a `width` wide image to the closest color of `palette`, with dithering
+pub fn dither(pixels: &[[u8; 3]], width: usize, palette: &[[u8; 3]], dither: Dither) -> Vec<usize> {
+    assert!(!palette.is_empty(), "Palette needs at least one color");
+    let nearest = |c: [f64; 3]| {
+        let distance = |p: &[u8; 3]| (0..3).map(|i| (c[i] - p[i] as f64).powi(2)).sum::<f64>();
+        palette
+            .iter()
+            .enumerate()
+            .min_by(|a, b| distance(a.1).partial_cmp(&distance(b.1)).unwrap())
+            .map(|(i, &p)| (i, p))
+            .unwrap()
+    };
+    // about the distance between neighbors, if the palette were a cube of evenly spaced colors
+    let spread = 255.0 / ((palette.len() as f64).cbrt() - 1.0).max(1.0);
+    dither_with(pixels, width, dither, spread, nearest)
+}
+
+/// A copy of `img` that only uses the colors of `palette`, like black and white for a plotter
+pub fn reduce(img: &PPMImg, palette: &[RGB], dither: Dither) -> PPMImg {
+    let bytes: Vec<[u8; 3]> = palette.iter().map(|c| c.to_bytes(img.depth())).collect();
+    let pixels: Vec<[u8; 3]> = img
+        .rows()
+        .flat_map(|row| row.iter().map(|p| p.to_bytes(img.depth())))
+        .collect();
+    let mut out = PPMImg::new_with_bg(img.height(), img.width(), img.depth(), img.bg_color);
+    out.fg_color = img.fg_color;
+    let indices = self::dither(&pixels, img.width() as usize, &bytes, dither);
+    for (d, i) in out.data.iter_mut().zip(indices) {
+        *d = palette[i];
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gray_dithers_to_half_white() {
+        let gray = vec![[128, 128, 128]; 64];
+        let bw = [[0, 0, 0], [255, 255, 255]];
+        assert!(dither(&gray, 8, &bw, Dither::None).iter().all(|&i| i == 1));
+        for &d in [Dither::Bayer, Dither::FloydSteinberg].iter() {
+            let white = dither(&gray, 8, &bw, d).iter().filter(|&&i| i == 1).count();
+            assert!((30..=34).contains(&white), "{:?} gave {} white pixels", d, white);
+        }
+    }
+}
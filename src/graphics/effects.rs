@@ -0,0 +1,289 @@
+#![allow(dead_code)]
+
+//! Post-processing passes that run over a finished image
+
+use super::{OutOfBounds, PPMImg, RGB};
+
+/// One pass over an image, run after drawing and before saving
+///
+/// Any `Fn(&mut PPMImg)` is a pass, so effects can be written on the spot with `map_pixels`.
+pub trait Effect {
+    fn apply(&self, img: &mut PPMImg);
+}
+
+impl<F: Fn(&mut PPMImg)> Effect for F {
+    fn apply(&self, img: &mut PPMImg) {
+        self(img)
+    }
+}
+
+/// Darkens the image towards its corners
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Vignette {
+    /// How much of the light is taken from the corners, from 0 to 1
+    pub strength: f64,
+    /// Share of the way to the corners that is left alone, from 0 to 1
+    pub radius: f64,
+}
+
+impl Effect for Vignette {
+    fn apply(&self, img: &mut PPMImg) {
+        let (cx, cy) = ((img.width() as f64 - 1.0) / 2.0, (img.height() as f64 - 1.0) / 2.0);
+        let corner = (cx * cx + cy * cy).sqrt().max(1.0);
+        img.map_pixels(|x, y, color| {
+            let (dx, dy) = (x as f64 - cx, y as f64 - cy);
+            let r = (dx * dx + dy * dy).sqrt() / corner;
+            let t = ((r - self.radius) / (1.0 - self.radius).max(1e-9)).clamp(0.0, 1.0);
+            // smoothstep, so the edge of the untouched middle does not show
+            color * (1.0 - self.strength * t * t * (3.0 - 2.0 * t))
+        });
+    }
+}
+
+/// Simple color grading: a gain per channel, then contrast and saturation
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ColorGrade {
+    /// Multiplies red, green and blue
+    pub gain: [f64; 3],
+    /// Spreads the channels away from middle gray, 1 leaves them
+    pub contrast: f64,
+    /// Spreads the channels away from the pixel's gray, 0 makes it gray, 1 leaves it
+    pub saturation: f64,
+}
+
+impl Default for ColorGrade {
+    fn default() -> Self {
+        ColorGrade {
+            gain: [1.0; 3],
+            contrast: 1.0,
+            saturation: 1.0,
+        }
+    }
+}
+
+impl Effect for ColorGrade {
+    fn apply(&self, img: &mut PPMImg) {
+        let depth = img.depth() as f64;
+        img.map_pixels(|_, _, color| {
+            let c = [color.red, color.green, color.blue];
+            let mut v = [0.0; 3];
+            for i in 0..3 {
+                v[i] = c[i] as f64 / depth * self.gain[i];
+                v[i] = (v[i] - 0.5) * self.contrast + 0.5;
+            }
+            let luma = 0.2126 * v[0] + 0.7152 * v[1] + 0.0722 * v[2];
+            let channel = |v: f64| {
+                ((luma + (v - luma) * self.saturation).clamp(0.0, 1.0) * depth).round() as u16
+            };
+            RGB::new(channel(v[0]), channel(v[1]), channel(v[2]))
+        });
+    }
+}
+
+/// Gaussian blur, with a standard deviation of `sigma` pixels
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Blur {
+    pub sigma: f64,
+}
+
+impl Effect for Blur {
+    fn apply(&self, img: &mut PPMImg) {
+        let mut light = img.light();
+        let (w, h) = (img.width() as usize, img.height() as usize);
+        gaussian(&mut light, w, h, self.sigma, img.wraps());
+        img.set_light(&light);
+    }
+}
+
+/// Bloom: whatever is brighter than `threshold` bleeds light around it, for neon looking lines
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Glow {
+    /// Brightness from 0 to 1 that starts to glow
+    pub threshold: f64,
+    /// How far the glow reaches, as for `Blur`
+    pub sigma: f64,
+    /// How much of the glow is added back
+    pub strength: f64,
+}
+
+impl Effect for Glow {
+    fn apply(&self, img: &mut PPMImg) {
+        let light = img.light();
+        let mut bright: Vec<[f64; 4]> = light
+            .iter()
+            .map(|p| {
+                let luma = (0.2126 * p[0] + 0.7152 * p[1] + 0.0722 * p[2]) / p[3].max(1e-9);
+                if luma > self.threshold {
+                    *p
+                } else {
+                    [0.0; 4]
+                }
+            })
+            .collect();
+        let (w, h) = (img.width() as usize, img.height() as usize);
+        gaussian(&mut bright, w, h, self.sigma, img.wraps());
+        let glowing: Vec<[f64; 4]> = light
+            .iter()
+            .zip(bright.iter())
+            .map(|(p, g)| {
+                let [r, gr, b, a] = [0, 1, 2, 3].map(|c| p[c] + g[c] * self.strength);
+                // light added to a see through pixel makes it show
+                [r, gr, b, a.min(1.0)]
+            })
+            .collect();
+        img.set_light(&glowing);
+    }
+}
+
+impl PPMImg {
+    /// Blurs the image, see `Blur`
+    pub fn blur(&mut self, sigma: f64) {
+        Blur { sigma }.apply(self);
+    }
+
+    /// Makes bright parts glow, see `Glow`
+    pub fn glow(&mut self, threshold: f64, sigma: f64, strength: f64) {
+        Glow {
+            threshold,
+            sigma,
+            strength,
+        }
+        .apply(self);
+    }
+
+    fn wraps(&self) -> (bool, bool) {
+        (self.x_bounds == OutOfBounds::Wrap, self.y_bounds == OutOfBounds::Wrap)
+    }
+
+    /// Every pixel as red, green, blue from 0 to 1 times opacity, then opacity
+    ///
+    /// Premultiplied, so that see through pixels do not bleed their color into their neighbors.
+    /// In linear light when `linear_light` is set.
+    fn light(&self) -> Vec<[f64; 4]> {
+        let d = self.depth() as f64;
+        self.data
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                let a = self.alpha.as_ref().map_or(1.0, |alpha| alpha[i]);
+                let [r, g, b] = if self.linear_light {
+                    c.to_linear(self.depth())
+                } else {
+                    [c.red, c.green, c.blue].map(|v| v as f64 / d)
+                };
+                [r * a, g * a, b * a, a]
+            })
+            .collect()
+    }
+
+    fn set_light(&mut self, light: &[[f64; 4]]) {
+        let (d, depth, linear) = (self.depth() as f64, self.depth(), self.linear_light);
+        for (i, p) in light.iter().enumerate() {
+            let a = p[3].clamp(0.0, 1.0);
+            if let Some(alpha) = self.alpha.as_mut() {
+                alpha[i] = a;
+            }
+            let rgb = match self.alpha {
+                Some(_) if a > 0.0 => [0, 1, 2].map(|c| p[c] / a),
+                Some(_) => [0.0; 3],
+                None => [p[0], p[1], p[2]],
+            };
+            self.data[i] = if linear {
+                RGB::from_linear(rgb, depth)
+            } else {
+                let [r, g, b] = rgb.map(|v| (v.clamp(0.0, 1.0) * d).round() as u16);
+                RGB::new(r, g, b)
+            };
+        }
+    }
+}
+
+/// Gaussian blur of a `w` by `h` image, one axis at a time
+///
+/// Past the edges the edge pixels go on, or the image repeats along axes that wrap.
+fn gaussian(pixels: &mut [[f64; 4]], w: usize, h: usize, sigma: f64, wrap: (bool, bool)) {
+    if sigma <= 0.0 || pixels.is_empty() {
+        return;
+    }
+    let radius = (3.0 * sigma).ceil() as i64;
+    let kernel: Vec<f64> = (-radius..=radius)
+        .map(|i| (-(i * i) as f64 / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let total: f64 = kernel.iter().sum();
+    let kernel: Vec<f64> = kernel.iter().map(|k| k / total).collect();
+    blur_lines(pixels, w, h, &kernel, true, wrap.0);
+    blur_lines(pixels, w, h, &kernel, false, wrap.1);
+}
+
+/// One pass of a blur, along rows if `rows` is set or else along columns
+fn blur_lines(pixels: &mut [[f64; 4]], w: usize, h: usize, kernel: &[f64], rows: bool, wrap: bool) {
+    let (n, lines) = if rows { (w, h) } else { (h, w) };
+    let at = |l: usize, i: usize| if rows { l * w + i } else { i * w + l };
+    let radius = (kernel.len() / 2) as i64;
+    let mut line = vec![[0.0; 4]; n];
+    for l in 0..lines {
+        for (i, out) in line.iter_mut().enumerate() {
+            let mut sum = [0.0; 4];
+            for (k, weight) in kernel.iter().enumerate() {
+                let j = i as i64 + k as i64 - radius;
+                let j = if wrap { j.rem_euclid(n as i64) } else { j.clamp(0, n as i64 - 1) };
+                let p = pixels[at(l, j as usize)];
+                for (s, c) in sum.iter_mut().zip(p.iter()) {
+                    *s += c * weight;
+                }
+            }
+            *out = sum;
+        }
+        for (i, p) in line.iter().enumerate() {
+            pixels[at(l, i)] = *p;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passes_run_in_order() {
+        let mut img = PPMImg::new(5, 5, 255);
+        img.fill_background(RGB::WHITE);
+        let invert = |img: &mut PPMImg| img.map_pixels(|_, _, c| RGB::WHITE - c);
+        let vignette = Vignette {
+            strength: 1.0,
+            radius: 0.5,
+        };
+        img.post_process(&[&vignette, &invert]);
+        assert_eq!(Some(RGB::BLACK), img.pixel(2, 2));
+        assert_eq!(Some(RGB::WHITE), img.pixel(0, 0));
+
+        let gray = ColorGrade {
+            saturation: 0.0,
+            ..ColorGrade::default()
+        };
+        img.fill_background(RGB::RED);
+        img.post_process(&[&gray]);
+        assert_eq!(Some(RGB::gray(54)), img.pixel(1, 3));
+    }
+
+    #[test]
+    fn blur_spreads_and_glow_adds_light() {
+        let mut img = PPMImg::new(1, 9, 255);
+        img.plot_color(4, 0, RGB::WHITE);
+        img.blur(1.0);
+        let row: Vec<u16> = (0..9).map(|x| img.pixel(x, 0).unwrap().red).collect();
+        assert!(row[4] > row[3] && row[3] > row[2] && row[3] == row[5] && row[0] == 0);
+        // the light is all still there
+        let total: u16 = row.iter().sum();
+        assert!((250..=260).contains(&total), "{}", total);
+
+        let mut img = PPMImg::new(1, 9, 255);
+        img.plot_color(4, 0, RGB::WHITE);
+        img.plot_color(0, 0, RGB::gray(100));
+        img.glow(0.5, 1.0, 1.0);
+        assert_eq!(Some(RGB::WHITE), img.pixel(4, 0));
+        assert!(img.pixel(3, 0).unwrap().red > 0);
+        // too dim to glow
+        assert_eq!(Some(RGB::gray(100)), img.pixel(0, 0));
+    }
+}
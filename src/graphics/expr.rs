@@ -0,0 +1,258 @@
+#![allow(dead_code)]
+
+//! Arithmetic on numbers and named variables, for scripts
+//!
+//! Expressions have `+`, `-`, `*`, `/`, `%` and `^` with the usual precedence, parentheses,
+//! `pi`, and the functions `sqrt`, `abs`, `sin`, `cos` and `tan`, which take degrees like
+//! `rotate` does.
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// Values of variables, by name
+pub type Variables = HashMap<String, f64>;
+
+/// What is wrong with an expression
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExprError {
+    UnknownVariable(String),
+    UnknownFunction(String),
+    /// Something out of place, like a second operator or a stray `)`
+    Unexpected(String),
+    /// An operator or `(` with nothing after it
+    EndsTooSoon,
+}
+
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExprError::UnknownVariable(name) => write!(f, "an unknown variable `{}`", name),
+            ExprError::UnknownFunction(name) => write!(f, "an unknown function `{}`", name),
+            ExprError::Unexpected(what) => write!(f, "an unexpected `{}`", what),
+            ExprError::EndsTooSoon => write!(f, "an expression that ends too soon"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Op(char),
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, ExprError> {
+    let mut tokens = vec![];
+    let mut chars = text.char_indices().peekable();
+    while let Some(&(i, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut end = i;
+            while let Some(&(j, d)) = chars.peek() {
+                // an exponent may have a sign, as in 1e-3
+                let signed = (d == '-' || d == '+') && text[..j].ends_with(['e', 'E']);
+                if !(d.is_ascii_alphanumeric() || d == '.' || signed) {
+                    break;
+                }
+                end = j + d.len_utf8();
+                chars.next();
+            }
+            let number = &text[i..end];
+            let value = number.parse().map_err(|_| ExprError::Unexpected(number.to_string()))?;
+            tokens.push(Token::Number(value));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut end = i;
+            while let Some(&(j, d)) = chars.peek() {
+                if !(d.is_alphanumeric() || d == '_') {
+                    break;
+                }
+                end = j + d.len_utf8();
+                chars.next();
+            }
+            tokens.push(Token::Name(text[i..end].to_string()));
+        } else if "+-*/%^()".contains(c) {
+            tokens.push(Token::Op(c));
+            chars.next();
+        } else {
+            return Err(ExprError::Unexpected(c.to_string()));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Whether a name can be given to a variable
+pub fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Work out the value of an expression
+pub fn eval(text: &str, vars: &Variables) -> Result<f64, ExprError> {
+    let tokens = tokenize(text)?;
+    let mut parser = Parser {
+        tokens: &tokens,
+        at: 0,
+        vars,
+    };
+    let value = parser.sum()?;
+    match parser.tokens.get(parser.at) {
+        None => Ok(value),
+        Some(token) => Err(unexpected(token)),
+    }
+}
+
+/// Split arguments at whitespace outside of parentheses, so `(r * 2)` stays one argument
+pub fn split_arguments(text: &str) -> Vec<&str> {
+    let mut args = vec![];
+    let (mut depth, mut start) = (0, None);
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ => {}
+        }
+        if c.is_whitespace() && depth <= 0 {
+            if let Some(s) = start.take() {
+                args.push(&text[s..i]);
+            }
+        } else if start.is_none() {
+            start = Some(i);
+        }
+    }
+    if let Some(s) = start {
+        args.push(&text[s..]);
+    }
+    args
+}
+
+fn unexpected(token: &Token) -> ExprError {
+    ExprError::Unexpected(match token {
+        Token::Number(n) => n.to_string(),
+        Token::Name(name) => name.clone(),
+        Token::Op(c) => c.to_string(),
+    })
+}
+
+/// Recursive descent, one method for each level of precedence
+struct Parser<'a> {
+    tokens: &'a [Token],
+    at: usize,
+    vars: &'a Variables,
+}
+
+impl Parser<'_> {
+    fn next(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.at);
+        self.at += 1;
+        token
+    }
+
+    fn skip_op(&mut self, ops: &str) -> Option<char> {
+        match self.tokens.get(self.at) {
+            Some(&Token::Op(c)) if ops.contains(c) => {
+                self.at += 1;
+                Some(c)
+            }
+            _ => None,
+        }
+    }
+
+    fn sum(&mut self) -> Result<f64, ExprError> {
+        let mut value = self.product()?;
+        while let Some(op) = self.skip_op("+-") {
+            let rhs = self.product()?;
+            value = if op == '+' { value + rhs } else { value - rhs };
+        }
+        Ok(value)
+    }
+
+    fn product(&mut self) -> Result<f64, ExprError> {
+        let mut value = self.unary()?;
+        while let Some(op) = self.skip_op("*/%") {
+            let rhs = self.unary()?;
+            value = match op {
+                '*' => value * rhs,
+                '/' => value / rhs,
+                _ => value % rhs,
+            };
+        }
+        Ok(value)
+    }
+
+    /// Negation, which binds looser than `^`, so -2^2 is -4
+    fn unary(&mut self) -> Result<f64, ExprError> {
+        if self.skip_op("-").is_some() {
+            return Ok(-self.unary()?);
+        }
+        let base = self.atom()?;
+        if self.skip_op("^").is_some() {
+            return Ok(base.powf(self.unary()?));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<f64, ExprError> {
+        match self.next().cloned() {
+            Some(Token::Number(n)) => Ok(n),
+            Some(Token::Op('(')) => self.parenthesized(),
+            Some(Token::Name(name)) => {
+                if self.skip_op("(").is_some() {
+                    let x = self.parenthesized()?;
+                    return match name.as_str() {
+                        "sqrt" => Ok(x.sqrt()),
+                        "abs" => Ok(x.abs()),
+                        "sin" => Ok(x.to_radians().sin()),
+                        "cos" => Ok(x.to_radians().cos()),
+                        "tan" => Ok(x.to_radians().tan()),
+                        _ => Err(ExprError::UnknownFunction(name)),
+                    };
+                }
+                match self.vars.get(&name) {
+                    Some(&value) => Ok(value),
+                    None if name == "pi" => Ok(std::f64::consts::PI),
+                    None => Err(ExprError::UnknownVariable(name)),
+                }
+            }
+            Some(token) => Err(unexpected(&token)),
+            None => Err(ExprError::EndsTooSoon),
+        }
+    }
+
+    /// The rest of an expression in parentheses, after the `(`
+    fn parenthesized(&mut self) -> Result<f64, ExprError> {
+        let value = self.sum()?;
+        match self.next() {
+            Some(Token::Op(')')) => Ok(value),
+            Some(token) => Err(unexpected(&token.clone())),
+            None => Err(ExprError::EndsTooSoon),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expressions_follow_precedence() {
+        let mut vars = Variables::new();
+        vars.insert("r".to_string(), 75.0);
+        let eval = |text| eval(text, &vars);
+        assert_eq!(Ok(75.0), eval("50 * 1.5"));
+        assert_eq!(Ok(14.0), eval("2 + 3 * 4"));
+        assert_eq!(Ok(20.0), eval("(2 + 3) * 4"));
+        assert_eq!(Ok(-4.0), eval("-2^2"));
+        assert_eq!(Ok(512.0), eval("2^3^2"));
+        assert_eq!(Ok(1.0), eval("7 % 3"));
+        assert_eq!(Ok(150.0), eval("r*2"));
+        assert_eq!(Ok(0.001), eval("1e-3"));
+        assert!((eval("sin(30) * r").unwrap() - 37.5).abs() < 1e-9);
+
+        assert_eq!(Err(ExprError::UnknownVariable("d".to_string())), eval("d / 2"));
+        assert_eq!(Err(ExprError::EndsTooSoon), eval("(1 +"));
+        assert_eq!("an unexpected `)`", eval("1 + 2)").unwrap_err().to_string());
+        assert_eq!(vec!["250", "0", "(r * 2)", "-r"], split_arguments(" 250 0 (r * 2)  -r"));
+    }
+}
@@ -0,0 +1,224 @@
+#![allow(dead_code)]
+
+use super::curves::Bezier3;
+use super::parametrics::Curve;
+
+type Point = (f64, f64);
+
+/// Max number of Newton reparametrizations before a span is split
+const MAX_ITERATIONS: usize = 4;
+
+/// Fit a chain of cubic bezier curves through a sequence of points
+///
+/// Based on Philip J. Schneider's algorithm from Graphics Gems (1990)
+/// # Arguments
+/// `points` - The polyline to fit, e.g. mouse samples
+/// `tolerance` - Max distance allowed between any point and the fitted curves
+pub fn fit_beziers(points: &[Point], tolerance: f64) -> Vec<Bezier3> {
+    let mut pts: Vec<Point> = Vec::with_capacity(points.len());
+    for &p in points {
+        if pts.last() != Some(&p) {
+            pts.push(p);
+        }
+    }
+    let mut curves = vec![];
+    if pts.len() < 2 {
+        return curves;
+    }
+    let last = pts.len() - 1;
+    let t0 = normalize(sub(pts[1], pts[0]));
+    let t1 = normalize(sub(pts[last - 1], pts[last]));
+    fit_cubic(&pts, t0, t1, tolerance, &mut curves);
+    curves
+}
+
+fn fit_cubic(pts: &[Point], that0: Point, that1: Point, tolerance: f64, out: &mut Vec<Bezier3>) {
+    let last = pts.len() - 1;
+    if pts.len() == 2 {
+        // use the heuristic from the paper for a straight span
+        let d = distance(pts[0], pts[1]) / 3.0;
+        out.push(Bezier3::new(
+            pts[0],
+            add(pts[0], scale(that0, d)),
+            add(pts[1], scale(that1, d)),
+            pts[1],
+        ));
+        return;
+    }
+
+    let mut u = chord_length_parametrize(pts);
+    let mut bezier = generate_bezier(pts, &u, that0, that1);
+    let (mut err, mut split) = max_error(pts, &bezier, &u);
+    if err < tolerance {
+        out.push(bezier);
+        return;
+    }
+
+    // if the error is not too large, try reparametrizing and fitting again
+    if err < tolerance * 4.0 {
+        for _ in 0..MAX_ITERATIONS {
+            u = reparametrize(pts, &u, &bezier);
+            bezier = generate_bezier(pts, &u, that0, that1);
+            let (e, s) = max_error(pts, &bezier, &u);
+            err = e;
+            split = s;
+            if err < tolerance {
+                out.push(bezier);
+                return;
+            }
+        }
+    }
+
+    // fitting failed, so split at the point of max error and fit each half
+    let split = split.clamp(1, last - 1);
+    let center = normalize(sub(pts[split - 1], pts[split + 1]));
+    fit_cubic(&pts[..=split], that0, center, tolerance, out);
+    fit_cubic(&pts[split..], scale(center, -1.0), that1, tolerance, out);
+}
+
+/// Least squares fit of the inner control points along fixed end tangents
+fn generate_bezier(pts: &[Point], u: &[f64], that0: Point, that1: Point) -> Bezier3 {
+    let (first, last) = (pts[0], pts[pts.len() - 1]);
+    let (mut c00, mut c01, mut c11, mut x0, mut x1) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    for (&p, &t) in pts.iter().zip(u.iter()) {
+        let (b0, b1, b2, b3) = bernstein(t);
+        let a0 = scale(that0, b1);
+        let a1 = scale(that1, b2);
+        c00 += dot(a0, a0);
+        c01 += dot(a0, a1);
+        c11 += dot(a1, a1);
+        let tmp = sub(p, add(scale(first, b0 + b1), scale(last, b2 + b3)));
+        x0 += dot(a0, tmp);
+        x1 += dot(a1, tmp);
+    }
+
+    let det = c00 * c11 - c01 * c01;
+    let (alpha0, alpha1) = if det.abs() > 1e-12 {
+        ((x0 * c11 - x1 * c01) / det, (c00 * x1 - c01 * x0) / det)
+    } else {
+        (0.0, 0.0)
+    };
+
+    // fall back to the straight span heuristic if alpha is degenerate
+    let seg = distance(first, last);
+    let eps = 1e-6 * seg;
+    let (alpha0, alpha1) = if alpha0 < eps || alpha1 < eps {
+        (seg / 3.0, seg / 3.0)
+    } else {
+        (alpha0, alpha1)
+    };
+    Bezier3::new(
+        first,
+        add(first, scale(that0, alpha0)),
+        add(last, scale(that1, alpha1)),
+        last,
+    )
+}
+
+/// One Newton step per point to find a better t for each point
+fn reparametrize(pts: &[Point], u: &[f64], bezier: &Bezier3) -> Vec<f64> {
+    pts.iter()
+        .zip(u.iter())
+        .map(|(&p, &t)| {
+            let d = sub(bezier.point_at(t), p);
+            let d1 = bezier.derivative_at(t);
+            let d2 = second_derivative(bezier, t);
+            let denom = dot(d1, d1) + dot(d, d2);
+            if denom.abs() < 1e-12 {
+                t
+            } else {
+                (t - dot(d, d1) / denom).clamp(0.0, 1.0)
+            }
+        })
+        .collect()
+}
+
+fn second_derivative(b: &Bezier3, t: f64) -> Point {
+    // 6(1 - t)(p2 - 2p1 + p0) + 6t(p3 - 2p2 + p1)
+    let q0 = add(sub(b.p2, scale(b.p1, 2.0)), b.p0);
+    let q1 = add(sub(b.p3, scale(b.p2, 2.0)), b.p1);
+    add(scale(q0, 6.0 * (1.0 - t)), scale(q1, 6.0 * t))
+}
+
+/// Returns the max distance between points and curve, and the index of that point
+fn max_error(pts: &[Point], bezier: &Bezier3, u: &[f64]) -> (f64, usize) {
+    let mut split = pts.len() / 2;
+    let mut max = 0.0;
+    for (i, (&p, &t)) in pts.iter().zip(u.iter()).enumerate().skip(1) {
+        let d = distance(bezier.point_at(t), p);
+        if d > max {
+            max = d;
+            split = i;
+        }
+    }
+    (max, split)
+}
+
+fn chord_length_parametrize(pts: &[Point]) -> Vec<f64> {
+    let mut u = vec![0.0];
+    for w in pts.windows(2) {
+        let prev = u[u.len() - 1];
+        u.push(prev + distance(w[0], w[1]));
+    }
+    let total = u[u.len() - 1];
+    u.iter().map(|d| d / total).collect()
+}
+
+fn bernstein(t: f64) -> (f64, f64, f64, f64) {
+    let mt = 1.0 - t;
+    (mt * mt * mt, 3.0 * t * mt * mt, 3.0 * t * t * mt, t * t * t)
+}
+
+fn add(a: Point, b: Point) -> Point {
+    (a.0 + b.0, a.1 + b.1)
+}
+
+fn sub(a: Point, b: Point) -> Point {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+fn scale(a: Point, s: f64) -> Point {
+    (a.0 * s, a.1 * s)
+}
+
+fn dot(a: Point, b: Point) -> f64 {
+    a.0 * b.0 + a.1 * b.1
+}
+
+fn distance(a: Point, b: Point) -> f64 {
+    (b.0 - a.0).hypot(b.1 - a.1)
+}
+
+fn normalize(a: Point) -> Point {
+    let len = a.0.hypot(a.1);
+    if len == 0.0 {
+        a
+    } else {
+        scale(a, 1.0 / len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts;
+
+    #[test]
+    fn fit_semicircle() {
+        let pts: Vec<Point> = (0..=50)
+            .map(|i| {
+                let a = consts::PI * i as f64 / 50.0;
+                (100.0 * a.cos(), 100.0 * a.sin())
+            })
+            .collect();
+        let curves = fit_beziers(&pts, 0.5);
+        assert!(!curves.is_empty() && curves.len() <= 4);
+        for p in pts {
+            let d = curves
+                .iter()
+                .map(|c| distance(c.closest_point(p).1, p))
+                .fold(f64::INFINITY, f64::min);
+            assert!(d < 0.5);
+        }
+    }
+}
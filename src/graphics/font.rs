@@ -0,0 +1,663 @@
+#![allow(dead_code)]
+//! A minimal TrueType reader that turns glyph outlines into paths
+//!
+//! Only what is needed for outlines is read: `cmap` (formats 4 and 12), `head`, `maxp`,
+//! `hhea`, `hmtx`, `loca` and `glyf`, including composite glyphs.
+//! Hinting and kerning are ignored.
+//!
+//! Like the module, the tests need the feature: `cargo test --features truetype`
+
+use std::fs;
+use std::io;
+
+use super::parametrics::Curve;
+use super::path::Path;
+
+/// A point of a glyph contour in font units, and whether it is on the curve
+type OutlinePoint = (f64, f64, bool);
+
+/// Composite glyphs may nest, but not forever
+const MAX_COMPOSITE_DEPTH: u32 = 8;
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+pub struct Font {
+    data: Vec<u8>,
+    units_per_em: u16,
+    num_glyphs: u16,
+    long_loca: bool,
+    loca: usize,
+    glyf: usize,
+    hmtx: usize,
+    num_hmetrics: u16,
+    ascender: i16,
+    descender: i16,
+    line_gap: i16,
+    cmap: usize,
+    cmap_format: u16,
+}
+
+// constructor and raw reads
+impl Font {
+    /// Read a .ttf file
+    pub fn open(filepath: &str) -> io::Result<Font> {
+        Font::from_bytes(fs::read(filepath)?)
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> io::Result<Font> {
+        let mut font = Font {
+            data,
+            units_per_em: 0,
+            num_glyphs: 0,
+            long_loca: false,
+            loca: 0,
+            glyf: 0,
+            hmtx: 0,
+            num_hmetrics: 0,
+            ascender: 0,
+            descender: 0,
+            line_gap: 0,
+            cmap: 0,
+            cmap_format: 0,
+        };
+
+        let head = font.table(b"head")?;
+        font.units_per_em = font.u16_at(head + 18)?;
+        font.long_loca = font.u16_at(head + 50)? == 1;
+        let maxp = font.table(b"maxp")?;
+        font.num_glyphs = font.u16_at(maxp + 4)?;
+        let hhea = font.table(b"hhea")?;
+        font.ascender = font.u16_at(hhea + 4)? as i16;
+        font.descender = font.u16_at(hhea + 6)? as i16;
+        font.line_gap = font.u16_at(hhea + 8)? as i16;
+        font.num_hmetrics = font.u16_at(hhea + 34)?;
+        font.hmtx = font.table(b"hmtx")?;
+        font.loca = font.table(b"loca")?;
+        font.glyf = font.table(b"glyf")?;
+
+        // prefer a full unicode map, then the basic multilingual plane
+        let cmap = font.table(b"cmap")?;
+        let (mut best, mut best_format) = (0, 0);
+        for i in 0..font.u16_at(cmap + 2)? as usize {
+            let rec = cmap + 4 + i * 8;
+            let (platform, encoding) = (font.u16_at(rec)?, font.u16_at(rec + 2)?);
+            let sub = cmap + font.u32_at(rec + 4)? as usize;
+            let format = font.u16_at(sub)?;
+            let unicode = platform == 0 || (platform == 3 && (encoding == 1 || encoding == 10));
+            if unicode && (format == 12 || (format == 4 && best_format != 12)) {
+                best = sub;
+                best_format = format;
+            }
+        }
+        if best_format == 0 {
+            return Err(invalid("No unicode cmap of format 4 or 12"));
+        }
+        font.cmap = best;
+        font.cmap_format = best_format;
+        Ok(font)
+    }
+
+    fn table(&self, tag: &[u8; 4]) -> io::Result<usize> {
+        let num_tables = self.u16_at(4)? as usize;
+        for i in 0..num_tables {
+            let rec = 12 + i * 16;
+            if self.data.get(rec..rec + 4) == Some(&tag[..]) {
+                return Ok(self.u32_at(rec + 8)? as usize);
+            }
+        }
+        Err(invalid(&format!(
+            "Missing table {}",
+            String::from_utf8_lossy(tag)
+        )))
+    }
+
+    fn u8_at(&self, i: usize) -> io::Result<u8> {
+        self.data
+            .get(i)
+            .copied()
+            .ok_or_else(|| invalid("Unexpected end of font"))
+    }
+
+    fn u16_at(&self, i: usize) -> io::Result<u16> {
+        Ok(u16::from(self.u8_at(i)?) << 8 | u16::from(self.u8_at(i + 1)?))
+    }
+
+    fn u32_at(&self, i: usize) -> io::Result<u32> {
+        Ok(u32::from(self.u16_at(i)?) << 16 | u32::from(self.u16_at(i + 2)?))
+    }
+
+    fn i16_at(&self, i: usize) -> io::Result<i16> {
+        Ok(self.u16_at(i)? as i16)
+    }
+
+    /// F2Dot14 fixed point
+    fn f2dot14_at(&self, i: usize) -> io::Result<f64> {
+        Ok(f64::from(self.i16_at(i)?) / 16384.0)
+    }
+}
+
+// metrics and char map
+impl Font {
+    pub fn units_per_em(&self) -> u16 {
+        self.units_per_em
+    }
+
+    /// Distance between baselines, in font units
+    pub fn line_height(&self) -> f64 {
+        f64::from(self.ascender) - f64::from(self.descender) + f64::from(self.line_gap)
+    }
+
+    /// Returns the glyph for a char, or glyph 0 (the missing glyph box)
+    pub fn glyph_index(&self, ch: char) -> u16 {
+        self.lookup(ch as u32).unwrap_or(0)
+    }
+
+    fn lookup(&self, code: u32) -> io::Result<u16> {
+        let sub = self.cmap;
+        if self.cmap_format == 12 {
+            let groups = self.u32_at(sub + 12)? as usize;
+            for g in 0..groups {
+                let rec = sub + 16 + g * 12;
+                let (start, end) = (self.u32_at(rec)?, self.u32_at(rec + 4)?);
+                if (start..=end).contains(&code) {
+                    return Ok((self.u32_at(rec + 8)? + code - start) as u16);
+                }
+            }
+            return Ok(0);
+        }
+
+        if code > 0xFFFF {
+            return Ok(0);
+        }
+        let code = code as u16;
+        let segs = self.u16_at(sub + 6)? as usize / 2;
+        let ends = sub + 14;
+        let starts = ends + segs * 2 + 2;
+        let deltas = starts + segs * 2;
+        let offsets = deltas + segs * 2;
+        for s in 0..segs {
+            if code > self.u16_at(ends + s * 2)? {
+                continue;
+            }
+            let start = self.u16_at(starts + s * 2)?;
+            if code < start {
+                return Ok(0);
+            }
+            let delta = self.u16_at(deltas + s * 2)?;
+            let range = self.u16_at(offsets + s * 2)? as usize;
+            if range == 0 {
+                return Ok(code.wrapping_add(delta));
+            }
+            let at = offsets + s * 2 + range + (code - start) as usize * 2;
+            let glyph = self.u16_at(at)?;
+            return Ok(if glyph == 0 { 0 } else { glyph.wrapping_add(delta) });
+        }
+        Ok(0)
+    }
+
+    /// Horizontal advance of a glyph, in font units
+    pub fn advance(&self, glyph: u16) -> f64 {
+        let i = glyph.min(self.num_hmetrics.saturating_sub(1)) as usize;
+        f64::from(self.u16_at(self.hmtx + i * 4).unwrap_or(0))
+    }
+}
+
+// outlines
+impl Font {
+    /// The contours of a glyph in font units, y up
+    pub fn glyph_contours(&self, glyph: u16) -> io::Result<Vec<Vec<OutlinePoint>>> {
+        self.contours(glyph, 0)
+    }
+
+    fn glyph_range(&self, glyph: u16) -> io::Result<(usize, usize)> {
+        if glyph >= self.num_glyphs {
+            return Err(invalid("Glyph index out of range"));
+        }
+        let g = glyph as usize;
+        let (start, end) = if self.long_loca {
+            (
+                self.u32_at(self.loca + g * 4)? as usize,
+                self.u32_at(self.loca + g * 4 + 4)? as usize,
+            )
+        } else {
+            (
+                self.u16_at(self.loca + g * 2)? as usize * 2,
+                self.u16_at(self.loca + g * 2 + 2)? as usize * 2,
+            )
+        };
+        Ok((self.glyf + start, self.glyf + end))
+    }
+
+    fn contours(&self, glyph: u16, depth: u32) -> io::Result<Vec<Vec<OutlinePoint>>> {
+        let (start, end) = self.glyph_range(glyph)?;
+        if start == end {
+            // no outline, e.g. space
+            return Ok(vec![]);
+        }
+        let num_contours = self.i16_at(start)?;
+        if num_contours >= 0 {
+            self.simple_contours(start, num_contours as usize)
+        } else if depth < MAX_COMPOSITE_DEPTH {
+            self.composite_contours(start, depth)
+        } else {
+            Err(invalid("Composite glyphs nest too deep"))
+        }
+    }
+
+    fn simple_contours(&self, start: usize, n: usize) -> io::Result<Vec<Vec<OutlinePoint>>> {
+        let mut ends = Vec::with_capacity(n);
+        for c in 0..n {
+            ends.push(self.u16_at(start + 10 + c * 2)? as usize);
+        }
+        let num_points = ends.last().map_or(0, |e| e + 1);
+        let instructions = self.u16_at(start + 10 + n * 2)? as usize;
+        let mut i = start + 12 + n * 2 + instructions;
+
+        let mut flags = Vec::with_capacity(num_points);
+        while flags.len() < num_points {
+            let flag = self.u8_at(i)?;
+            i += 1;
+            flags.push(flag);
+            if flag & 0x08 != 0 {
+                let repeat = self.u8_at(i)?;
+                i += 1;
+                for _ in 0..repeat {
+                    flags.push(flag);
+                }
+            }
+        }
+
+        // x then y coordinates, each stored as deltas
+        let mut coords = [vec![0.0; num_points], vec![0.0; num_points]];
+        for (axis, (short, same)) in [(0x02, 0x10), (0x04, 0x20)].iter().enumerate() {
+            let mut value = 0i32;
+            for (p, flag) in flags.iter().take(num_points).enumerate() {
+                if flag & short != 0 {
+                    let d = i32::from(self.u8_at(i)?);
+                    i += 1;
+                    value += if flag & same != 0 { d } else { -d };
+                } else if flag & same == 0 {
+                    value += i32::from(self.i16_at(i)?);
+                    i += 2;
+                }
+                coords[axis][p] = f64::from(value);
+            }
+        }
+
+        let mut contours = vec![];
+        let mut first = 0;
+        for end in ends {
+            contours.push(
+                (first..=end)
+                    .map(|p| (coords[0][p], coords[1][p], flags[p] & 0x01 != 0))
+                    .collect(),
+            );
+            first = end + 1;
+        }
+        Ok(contours)
+    }
+
+    fn composite_contours(&self, start: usize, depth: u32) -> io::Result<Vec<Vec<OutlinePoint>>> {
+        let mut contours = vec![];
+        let mut i = start + 10;
+        loop {
+            let flags = self.u16_at(i)?;
+            let glyph = self.u16_at(i + 2)?;
+            i += 4;
+            let (dx, dy) = if flags & 0x01 != 0 {
+                let d = (self.i16_at(i)?, self.i16_at(i + 2)?);
+                i += 4;
+                (f64::from(d.0), f64::from(d.1))
+            } else {
+                let d = (self.u8_at(i)? as i8, self.u8_at(i + 1)? as i8);
+                i += 2;
+                (f64::from(d.0), f64::from(d.1))
+            };
+            // matching points instead of offsets is rare, so treat it as no offset
+            let (dx, dy) = if flags & 0x02 != 0 { (dx, dy) } else { (0.0, 0.0) };
+
+            let (mut a, mut b, mut c, mut d) = (1.0, 0.0, 0.0, 1.0);
+            if flags & 0x08 != 0 {
+                a = self.f2dot14_at(i)?;
+                d = a;
+                i += 2;
+            } else if flags & 0x40 != 0 {
+                a = self.f2dot14_at(i)?;
+                d = self.f2dot14_at(i + 2)?;
+                i += 4;
+            } else if flags & 0x80 != 0 {
+                a = self.f2dot14_at(i)?;
+                b = self.f2dot14_at(i + 2)?;
+                c = self.f2dot14_at(i + 4)?;
+                d = self.f2dot14_at(i + 6)?;
+                i += 8;
+            }
+
+            for contour in self.contours(glyph, depth + 1)? {
+                contours.push(
+                    contour
+                        .iter()
+                        .map(|&(x, y, on)| (a * x + c * y + dx, b * x + d * y + dy, on))
+                        .collect(),
+                );
+            }
+            if flags & 0x20 == 0 {
+                break;
+            }
+        }
+        Ok(contours)
+    }
+
+    /// Append the outline of a glyph to `path`
+    /// # Arguments
+    /// `origin` - Where the glyph's origin on the baseline goes
+    /// `scale` - Pixels per font unit
+    pub fn append_glyph(
+        &self,
+        path: &mut Path,
+        glyph: u16,
+        origin: (f64, f64),
+        scale: f64,
+    ) -> io::Result<()> {
+        self.append_glyph_mapped(path, glyph, &|x, y| {
+            (origin.0 + x * scale, origin.1 + y * scale)
+        })
+    }
+
+    /// Append the outline of a glyph to `path`, with font units mapped to pixels by `to_px`
+    ///
+    /// `to_px` should be affine, which keeps the curves exact
+    fn append_glyph_mapped(
+        &self,
+        path: &mut Path,
+        glyph: u16,
+        to_px: &impl Fn(f64, f64) -> (f64, f64),
+    ) -> io::Result<()> {
+        for contour in self.glyph_contours(glyph)? {
+            if contour.is_empty() {
+                continue;
+            }
+            let n = contour.len();
+            // start on the curve, making up a midpoint if every neighbor is off it
+            let start_i = contour.iter().position(|p| p.2);
+            let (start, rotate) = match start_i {
+                Some(s) => ((contour[s].0, contour[s].1), s),
+                None => {
+                    let (a, b) = (contour[0], contour[n - 1]);
+                    (((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0), 0)
+                }
+            };
+            let p = to_px(start.0, start.1);
+            path.move_to(p.0, p.1);
+
+            let mut current = start;
+            let mut control: Option<(f64, f64)> = None;
+            let skip = if start_i.is_some() { 1 } else { 0 };
+            for k in skip..=n {
+                let (x, y, on) = if k == n {
+                    // come back to the start to close
+                    (start.0, start.1, true)
+                } else {
+                    contour[(rotate + k) % n]
+                };
+                match (control, on) {
+                    (None, true) => {
+                        let p = to_px(x, y);
+                        path.line_to(p.0, p.1);
+                        current = (x, y);
+                    }
+                    (None, false) => control = Some((x, y)),
+                    (Some(q), true) => {
+                        quad_to(path, to_px, current, q, (x, y));
+                        current = (x, y);
+                        control = None;
+                    }
+                    (Some(q), false) => {
+                        // two controls in a row imply an on-curve point between them
+                        let mid = ((q.0 + x) / 2.0, (q.1 + y) / 2.0);
+                        quad_to(path, to_px, current, q, mid);
+                        current = mid;
+                        control = Some((x, y));
+                    }
+                }
+            }
+            path.close_path();
+        }
+        Ok(())
+    }
+
+    /// Lay out a line of text as a path
+    ///
+    /// Lines are broken on '\n', and later lines go down (toward -y)
+    /// # Arguments
+    /// `origin` - Start of the baseline of the first line
+    /// `size` - Height of the em square in pixels
+    pub fn text_path(&self, text: &str, origin: (f64, f64), size: f64) -> io::Result<Path> {
+        let scale = size / f64::from(self.units_per_em);
+        let mut path = Path::new();
+        let (mut x, mut y) = origin;
+        for ch in text.chars() {
+            if ch == '\n' {
+                x = origin.0;
+                y -= self.line_height() * scale;
+                continue;
+            }
+            let glyph = self.glyph_index(ch);
+            self.append_glyph(&mut path, glyph, (x, y), scale)?;
+            x += self.advance(glyph) * scale;
+        }
+        Ok(path)
+    }
+}
+
+// text on a curve
+impl Font {
+    /// Lay out text along a curve, with each glyph turned to the curve's tangent
+    ///
+    /// Glyphs that would run past the end of the curve are left out
+    /// # Arguments
+    /// `start` - Distance along the curve, in pixels, at which the text starts
+    /// `size` - Height of the em square in pixels
+    pub fn text_on_curve(
+        &self,
+        text: &str,
+        curve: &impl Curve,
+        start: f64,
+        size: f64,
+    ) -> io::Result<Path> {
+        let tolerance = 0.01;
+        let scale = size / f64::from(self.units_per_em);
+        let length = curve.length(tolerance);
+        let mut path = Path::new();
+        let mut pen = start;
+        for ch in text.chars() {
+            let glyph = self.glyph_index(ch);
+            let width = self.advance(glyph) * scale;
+            // place the glyph by the middle of its advance so it sits evenly on the curve
+            let mid = pen + width / 2.0;
+            if mid > length {
+                break;
+            }
+            let t = curve.t_at_length(mid, tolerance);
+            let (px, py) = curve.point_at(t);
+            let (dx, dy) = curve.derivative_at(t);
+            let (sin, cos) = dy.atan2(dx).sin_cos();
+            let half = width / 2.0;
+            self.append_glyph_mapped(&mut path, glyph, &|x, y| {
+                let (gx, gy) = (x * scale - half, y * scale);
+                (px + gx * cos - gy * sin, py + gx * sin + gy * cos)
+            })?;
+            pen += width;
+        }
+        Ok(path)
+    }
+}
+
+/// Quadratic segments are written as the equivalent cubic
+fn quad_to(
+    path: &mut Path,
+    to_px: &impl Fn(f64, f64) -> (f64, f64),
+    p0: (f64, f64),
+    q: (f64, f64),
+    p1: (f64, f64),
+) {
+    let c1 = (p0.0 + 2.0 / 3.0 * (q.0 - p0.0), p0.1 + 2.0 / 3.0 * (q.1 - p0.1));
+    let c2 = (p1.0 + 2.0 / 3.0 * (q.0 - p1.0), p1.1 + 2.0 / 3.0 * (q.1 - p1.1));
+    path.curve_to(to_px(c1.0, c1.1), to_px(c2.0, c2.1), to_px(p1.0, p1.1));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::matrix::Matrix;
+    use crate::graphics::path::Segment;
+
+    // A made up font with three glyphs: 0 is empty, 1 is a square with one quadratic corner
+    // and 2 is glyph 1 at half size, moved right by 200. Every table is big endian 16 bit words.
+
+    fn cmap4() -> Vec<u16> {
+        // 'A'..='B' by delta, 'C' through the glyph id array, then the required 0xFFFF
+        let mut sub = vec![4, 0, 0, 6, 0, 0, 0];
+        sub.extend(&[0x42, 0x43, 0xFFFF, 0, 0x41, 0x43, 0xFFFF]);
+        sub.extend(&[1u16.wrapping_sub(0x41), 0, 1, 0, 4, 0, 2]);
+        sub
+    }
+
+    fn cmap12() -> Vec<u16> {
+        // 'A'..='B' and one char past the basic plane
+        let mut sub = vec![12, 0, 0, 0, 0, 0, 0, 2];
+        sub.extend(&[0, 0x41, 0, 0x42, 0, 1, 0x1, 0xF600, 0x1, 0xF600, 0, 2]);
+        sub
+    }
+
+    fn font_bytes(long_loca: bool, subtables: &[(u16, Vec<u16>)]) -> Vec<u8> {
+        let mut head = vec![0; 27];
+        head[9] = 1000;
+        head[25] = long_loca as u16;
+        let maxp = vec![0, 0x5000, 3];
+        let mut hhea = vec![0; 18];
+        hhea[2] = 800;
+        hhea[3] = (-200i16) as u16;
+        hhea[4] = 100;
+        hhea[17] = 3;
+        let hmtx = vec![500, 0, 600, 0, 700, 0];
+        let mut glyf = vec![1, 0, 0, 100, 100, 3, 0];
+        // flags are bytes: on, on, off, on
+        glyf.extend(&[0x0101, 0x0001]);
+        glyf.extend(&[0, 100, 0, (-100i16) as u16]);
+        glyf.extend(&[0, 0, 100, 0]);
+        // composite: offsets as words, one uniform scale of 0.5
+        glyf.extend(&[(-1i16) as u16, 200, 0, 250, 50, 0x000B, 1, 200, 0, 0x2000]);
+        let loca = if long_loca {
+            vec![0, 0, 0, 0, 0, 34, 0, 54]
+        } else {
+            vec![0, 0, 17, 27]
+        };
+        let mut cmap = vec![0, subtables.len() as u16];
+        let mut offset = 4 + 8 * subtables.len();
+        for (encoding, sub) in subtables {
+            cmap.extend(&[3, *encoding, 0, offset as u16]);
+            offset += sub.len() * 2;
+        }
+        for (_, sub) in subtables {
+            cmap.extend(sub);
+        }
+
+        let tables = [
+            (b"cmap", cmap),
+            (b"glyf", glyf),
+            (b"head", head),
+            (b"hhea", hhea),
+            (b"hmtx", hmtx),
+            (b"loca", loca),
+            (b"maxp", maxp),
+        ];
+        let mut words = vec![1, 0, tables.len() as u16, 0, 0, 0];
+        let mut offset = 12 + 16 * tables.len();
+        for (tag, table) in tables.iter() {
+            words.push(u16::from_be_bytes([tag[0], tag[1]]));
+            words.push(u16::from_be_bytes([tag[2], tag[3]]));
+            words.extend(&[0, 0, 0, offset as u16, 0, table.len() as u16 * 2]);
+            offset += table.len() * 2;
+        }
+        for table in tables.iter().map(|t| &t.1) {
+            words.extend(table);
+        }
+        words.iter().flat_map(|w| w.to_be_bytes().to_vec()).collect()
+    }
+
+    fn close(a: (f64, f64), b: (f64, f64)) -> bool {
+        (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9
+    }
+
+    #[test]
+    fn chars_map_through_format_4_or_12() {
+        let font = Font::from_bytes(font_bytes(false, &[(1, cmap4())])).unwrap();
+        let glyphs: Vec<u16> = "ABCD\u{1F600}".chars().map(|c| font.glyph_index(c)).collect();
+        assert_eq!(vec![1, 2, 2, 0, 0], glyphs);
+        // with both, the full unicode map wins
+        let font = Font::from_bytes(font_bytes(false, &[(1, cmap4()), (10, cmap12())])).unwrap();
+        let glyphs: Vec<u16> = "ABCD\u{1F600}".chars().map(|c| font.glyph_index(c)).collect();
+        assert_eq!(vec![1, 2, 0, 0, 2], glyphs);
+        assert_eq!((1000, 1100.0), (font.units_per_em(), font.line_height()));
+    }
+
+    #[test]
+    fn simple_and_composite_glyphs_read_with_either_loca() {
+        let square = vec![
+            (0.0, 0.0, true),
+            (100.0, 0.0, true),
+            (100.0, 100.0, false),
+            (0.0, 100.0, true),
+        ];
+        let half: Vec<_> = square
+            .iter()
+            .map(|&(x, y, on)| (x / 2.0 + 200.0, y / 2.0, on))
+            .collect();
+        for &long_loca in &[false, true] {
+            let font = Font::from_bytes(font_bytes(long_loca, &[(1, cmap4())])).unwrap();
+            assert_eq!(Vec::<Vec<OutlinePoint>>::new(), font.glyph_contours(0).unwrap());
+            assert_eq!(vec![square.clone()], font.glyph_contours(1).unwrap());
+            assert_eq!(vec![half.clone()], font.glyph_contours(2).unwrap());
+            assert!(font.glyph_contours(3).is_err());
+        }
+    }
+
+    #[test]
+    fn quadratic_outlines_become_cubics() {
+        let font = Font::from_bytes(font_bytes(false, &[(1, cmap4())])).unwrap();
+        let path = font.text_path("A", (0.0, 0.0), 1000.0).unwrap();
+        let sp = &path.subpaths()[0];
+        assert!(sp.closed);
+        assert_eq!(3, sp.segments.len());
+        match sp.segments[1] {
+            Segment::Cubic(c) => {
+                let two_thirds = 200.0 / 3.0;
+                assert!(close(c.p0, (100.0, 0.0)) && close(c.p3, (0.0, 100.0)));
+                assert!(close(c.p1, (100.0, two_thirds)) && close(c.p2, (two_thirds, 100.0)));
+            }
+            seg => panic!("{:?} should be a cubic", seg),
+        }
+    }
+
+    #[test]
+    fn text_advances_between_glyphs() {
+        let font = Font::from_bytes(font_bytes(true, &[(1, cmap4())])).unwrap();
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.add_text(&font, "AA", (10.0, 20.0, 0.0), 100.0).unwrap();
+        let rows: Vec<&[f64]> = m.iter_by_row().collect();
+        assert!(!rows.is_empty());
+        // both glyphs give as many rows, and 600 units at 0.1 pixels puts the second 60 pixels on
+        let half = rows.len() / 2;
+        for (i, row) in rows.iter().enumerate() {
+            let (x, y) = (row[0], row[1]);
+            let left = if i < half { 10.0 } else { 70.0 };
+            let column = left - 1e-9..=left + 10.0 + 1e-9;
+            assert!(column.contains(&x), "x {} in row {}", x, i);
+            assert!((20.0 - 1e-9..=30.0 + 1e-9).contains(&y), "y {} in row {}", y, i);
+        }
+        assert!(close((rows[half][0], rows[half][1]), (70.0, 20.0)));
+    }
+}
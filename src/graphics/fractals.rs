@@ -0,0 +1,106 @@
+#![allow(dead_code)]
+
+use super::lsystem::LSystem;
+use super::matrix::Matrix;
+
+type Point = (f64, f64);
+
+/// The lines of a fractal curve along with their bounding box
+#[derive(Clone, Debug)]
+pub struct Fractal {
+    pub lines: Vec<(Point, Point)>,
+    /// Lower left corner of the bounding box
+    pub min: Point,
+    /// Upper right corner of the bounding box
+    pub max: Point,
+}
+
+impl Fractal {
+    pub fn new(lines: Vec<(Point, Point)>) -> Self {
+        let mut min = (f64::INFINITY, f64::INFINITY);
+        let mut max = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for &(p, q) in lines.iter() {
+            for &(x, y) in [p, q].iter() {
+                min = (min.0.min(x), min.1.min(y));
+                max = (max.0.max(x), max.1.max(y));
+            }
+        }
+        Fractal { lines, min, max }
+    }
+
+    pub fn width(&self) -> f64 {
+        self.max.0 - self.min.0
+    }
+
+    pub fn height(&self) -> f64 {
+        self.max.1 - self.min.1
+    }
+
+    /// Scale uniformly and move so that the curve is centered in the box from `min` to `max`
+    pub fn fit_to(&mut self, min: Point, max: Point) {
+        let mut edges = Matrix::new(0, 4, vec![]);
+        edges.add_fractal(self, 0.0);
+        // fit onto a canvas as big as the box, then move that canvas onto the box
+        let fit = edges
+            .fit_to(max.0 - min.0, max.1 - min.1, 0.0)
+            .mul(&Matrix::mv(min.0, min.1, 0.0));
+        let map = |(x, y): Point| {
+            let (x, y, _) = fit.transform_point((x, y, 0.0));
+            (x, y)
+        };
+        let lines = self.lines.iter().map(|&(p, q)| (map(p), map(q))).collect();
+        *self = Fractal::new(lines);
+    }
+}
+
+fn from_lsystem(lsys: &LSystem, depth: usize) -> Fractal {
+    Fractal::new(lsys.generate(depth, (0.0, 0.0), 0.0))
+}
+
+/// Koch curve: every segment grows a triangular bump, 4^depth lines
+pub fn koch(depth: usize) -> Fractal {
+    from_lsystem(&LSystem::new("F", 60.0, 1.0).rule('F', "F+F--F+F"), depth)
+}
+
+/// Koch snowflake: three Koch curves around a triangle, 3 * 4^depth lines
+pub fn koch_snowflake(depth: usize) -> Fractal {
+    from_lsystem(&LSystem::new("F--F--F", 60.0, 1.0).rule('F', "F+F--F+F"), depth)
+}
+
+/// Heighway dragon, 2^depth lines
+pub fn dragon(depth: usize) -> Fractal {
+    let lsys = LSystem::new("FX", 90.0, 1.0)
+        .rule('X', "X+YF+")
+        .rule('Y', "-FX-Y");
+    from_lsystem(&lsys, depth)
+}
+
+/// Hilbert curve filling a square, 4^depth - 1 lines
+pub fn hilbert(depth: usize) -> Fractal {
+    let lsys = LSystem::new("A", 90.0, 1.0)
+        .rule('A', "+BF-AFA-FB+")
+        .rule('B', "-AF+BFB+FA-");
+    from_lsystem(&lsys, depth)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_counts() {
+        assert_eq!(64, koch(3).lines.len());
+        assert_eq!(48, koch_snowflake(2).lines.len());
+        assert_eq!(256, dragon(8).lines.len());
+        assert_eq!(63, hilbert(3).lines.len());
+    }
+
+    #[test]
+    fn hilbert_fits_canvas() {
+        let mut h = hilbert(4);
+        assert!((h.width() - 15.0).abs() < 1e-9 && (h.height() - 15.0).abs() < 1e-9);
+        h.fit_to((10.0, 10.0), (490.0, 290.0));
+        assert!((h.min.0 - 110.0).abs() < 1e-9 && (h.max.0 - 390.0).abs() < 1e-9);
+        assert!((h.min.1 - 10.0).abs() < 1e-9 && (h.max.1 - 290.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,358 @@
+#![allow(dead_code)]
+
+//! Animated GIF output, with a palette per frame
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, prelude::Write, BufWriter};
+
+use super::dither::{self, Dither};
+use super::palette::{self, Quantizer};
+use super::utils::create_file;
+use super::PPMImg;
+
+/// How many times an animation plays
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Repeat {
+    Once,
+    Forever,
+    /// Replays after the first time through
+    Times(u16),
+}
+
+/// Writes frames of the same size one at a time into an animated GIF
+///
+/// Call `finish` after the last frame to end the file.
+pub struct GifWriter<W: Write> {
+    w: W,
+    width: u16,
+    height: u16,
+    /// Time each frame is shown for, in hundredths of a second
+    pub delay: u16,
+    /// How frames with too many colors are dithered down to their palette
+    pub dither: Dither,
+    /// How frames with too many colors get their palette
+    pub quantizer: Quantizer,
+}
+
+impl GifWriter<BufWriter<File>> {
+    /// Start an animated GIF at `filepath`, see `new`
+    pub fn create(
+        filepath: &str,
+        width: u32,
+        height: u32,
+        delay: u16,
+        repeat: Repeat,
+    ) -> io::Result<Self> {
+        GifWriter::new(create_file(filepath)?, width, height, delay, repeat)
+    }
+}
+
+impl<W: Write> GifWriter<W> {
+    /// Write the header of an animation
+    ///
+    /// # Arguments
+    /// `delay` - Time each frame is shown for, in hundredths of a second
+    /// `repeat` - How many times the animation plays
+    pub fn new(mut w: W, width: u32, height: u32, delay: u16, repeat: Repeat) -> io::Result<Self> {
+        if width > u16::MAX as u32 || height > u16::MAX as u32 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "GIF images are at most 65535 pixels each way",
+            ));
+        }
+        let (width, height) = (width as u16, height as u16);
+        w.write_all(b"GIF89a")?;
+        w.write_all(&width.to_le_bytes())?;
+        w.write_all(&height.to_le_bytes())?;
+        // no global color table, since every frame brings its own
+        w.write_all(&[0, 0, 0])?;
+
+        let loops = match repeat {
+            Repeat::Once => None,
+            Repeat::Forever => Some(0),
+            Repeat::Times(n) => Some(n.max(1)),
+        };
+        if let Some(n) = loops {
+            w.write_all(&[0x21, 0xff, 11])?;
+            w.write_all(b"NETSCAPE2.0")?;
+            w.write_all(&[3, 1])?;
+            w.write_all(&u16::to_le_bytes(n))?;
+            w.write_all(&[0])?;
+        }
+        Ok(GifWriter {
+            w,
+            width,
+            height,
+            delay,
+            dither: Dither::None,
+            quantizer: Quantizer::Uniform,
+        })
+    }
+
+    /// Add a frame, which must be as big as the animation
+    ///
+    /// Frames with more than 256 colors are reduced to 256 at most, see `quantizer`
+    pub fn add_frame(&mut self, img: &PPMImg) -> io::Result<()> {
+        if img.width() != self.width as u32 || img.height() != self.height as u32 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Every frame must be as big as the animation",
+            ));
+        }
+        let pixels: Vec<[u8; 3]> = img
+            .rows()
+            .flat_map(|row| row.iter().map(|p| p.to_bytes(img.depth())))
+            .collect();
+        let width = self.width as usize;
+        let (mut palette, indices) = quantize(&pixels, width, self.dither, &self.quantizer);
+
+        // graphic control extension: keep the frame when the next one comes, and wait
+        self.w.write_all(&[0x21, 0xf9, 4, 0x04])?;
+        self.w.write_all(&self.delay.to_le_bytes())?;
+        self.w.write_all(&[0, 0])?;
+
+        // image descriptor with a local color table of 2 ^ bits colors
+        let bits = (1..=8).find(|b| palette.len() <= 1 << b).unwrap();
+        palette.resize(1 << bits, [0, 0, 0]);
+        self.w.write_all(&[0x2c, 0, 0, 0, 0])?;
+        self.w.write_all(&self.width.to_le_bytes())?;
+        self.w.write_all(&self.height.to_le_bytes())?;
+        self.w.write_all(&[0x80 | (bits - 1)])?;
+        for c in palette.iter() {
+            self.w.write_all(c)?;
+        }
+
+        let min_code_size = bits.max(2);
+        self.w.write_all(&[min_code_size])?;
+        for block in lzw_encode(&indices, min_code_size).chunks(255) {
+            self.w.write_all(&[block.len() as u8])?;
+            self.w.write_all(block)?;
+        }
+        self.w.write_all(&[0])
+    }
+
+    /// End the file, giving back the writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.w.write_all(&[0x3b])?;
+        self.w.flush()?;
+        Ok(self.w)
+    }
+}
+
+/// A palette of at most 256 colors, and the index into it of every pixel
+///
+/// A fixed palette is always used. Otherwise colors are kept exactly when there are few enough
+/// of them, or reduced by `quantizer`, with `dither` spreading out the rounding.
+pub(crate) fn quantize(
+    pixels: &[[u8; 3]],
+    width: usize,
+    dither: Dither,
+    quantizer: &Quantizer,
+) -> (Vec<[u8; 3]>, Vec<u8>) {
+    if let Quantizer::Fixed(colors) = quantizer {
+        assert!(colors.len() <= 256, "GIF palettes have at most 256 colors");
+        return quantize_to(pixels, width, dither, colors.clone());
+    }
+    let mut palette = vec![];
+    let mut seen = HashMap::new();
+    for p in pixels.iter() {
+        if !seen.contains_key(p) {
+            if palette.len() == 256 {
+                return match quantizer {
+                    Quantizer::VarianceCut => {
+                        quantize_to(pixels, width, dither, palette::variance_cut(pixels, 256))
+                    }
+                    _ => quantize_uniform(pixels, width, dither),
+                };
+            }
+            seen.insert(*p, palette.len() as u8);
+            palette.push(*p);
+        }
+    }
+    (palette, pixels.iter().map(|p| seen[p]).collect())
+}
+
+fn quantize_to(
+    pixels: &[[u8; 3]],
+    width: usize,
+    dither: Dither,
+    palette: Vec<[u8; 3]>,
+) -> (Vec<[u8; 3]>, Vec<u8>) {
+    let indices = dither::dither(pixels, width, &palette, dither);
+    (palette, indices.into_iter().map(|i| i as u8).collect())
+}
+
+fn quantize_uniform(pixels: &[[u8; 3]], width: usize, dither: Dither) -> (Vec<[u8; 3]>, Vec<u8>) {
+    const LEVELS: [u32; 3] = [6, 7, 6];
+    let level = |c: f64, n: u32| {
+        let top = (n - 1) as f64;
+        (c * top / 255.0).round().clamp(0.0, top) as u32
+    };
+    let value = |l: u32, n: u32| (l * 255 / (n - 1)) as u8;
+
+    let mut palette = vec![];
+    for r in 0..LEVELS[0] {
+        for g in 0..LEVELS[1] {
+            for b in 0..LEVELS[2] {
+                palette.push([value(r, LEVELS[0]), value(g, LEVELS[1]), value(b, LEVELS[2])]);
+            }
+        }
+    }
+    let nearest = |p: [f64; 3]| {
+        let [r, g, b] = [0, 1, 2].map(|c| level(p[c], LEVELS[c]));
+        let index = ((r * LEVELS[1] + g) * LEVELS[2] + b) as usize;
+        (index, palette[index])
+    };
+    let indices = dither::dither_with(pixels, width, dither, 255.0 / 5.0, nearest);
+    (palette, indices.into_iter().map(|i| i as u8).collect())
+}
+
+/// Variable length LZW as GIF uses it, with codes packed from the lowest bit up
+fn lzw_encode(indices: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+
+    let mut out = vec![];
+    let (mut acc, mut acc_bits) = (0u32, 0);
+    let mut emit = |code: u16, size: u8, out: &mut Vec<u8>| {
+        acc |= (code as u32) << acc_bits;
+        acc_bits += size;
+        while acc_bits >= 8 {
+            out.push(acc as u8);
+            acc >>= 8;
+            acc_bits -= 8;
+        }
+    };
+
+    let mut size = min_code_size + 1;
+    let mut next = end + 1;
+    let mut dict: HashMap<(u16, u8), u16> = HashMap::new();
+    emit(clear, size, &mut out);
+
+    let mut iter = indices.iter();
+    let mut prefix = match iter.next() {
+        Some(&i) => i as u16,
+        None => {
+            emit(end, size, &mut out);
+            emit(0, 7, &mut out);
+            return out;
+        }
+    };
+    for &i in iter {
+        if let Some(&code) = dict.get(&(prefix, i)) {
+            prefix = code;
+            continue;
+        }
+        emit(prefix, size, &mut out);
+        // the decoder is one code behind, so the size grows before the new code is used
+        if next == 1 << size && size < 12 {
+            size += 1;
+        }
+        if next < 4096 {
+            dict.insert((prefix, i), next);
+            next += 1;
+        } else {
+            emit(clear, size, &mut out);
+            dict.clear();
+            size = min_code_size + 1;
+            next = end + 1;
+        }
+        prefix = i as u16;
+    }
+    emit(prefix, size, &mut out);
+    if next == 1 << size && size < 12 {
+        size += 1;
+    }
+    emit(end, size, &mut out);
+    // flush the last partial byte
+    emit(0, 7, &mut out);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::RGB;
+
+    /// Plain LZW decoder, to check the encoder against
+    fn lzw_decode(data: &[u8], min_code_size: u8) -> Vec<u8> {
+        let clear = 1usize << min_code_size;
+        let mut table: Vec<Vec<u8>> = vec![];
+        let mut size = min_code_size + 1;
+        let (mut pos, mut out, mut last): (usize, Vec<u8>, Option<Vec<u8>>) = (0, vec![], None);
+        loop {
+            let code = (0..size as usize)
+                .map(|k| ((data[(pos + k) / 8] >> ((pos + k) % 8)) & 1) as usize)
+                .enumerate()
+                .fold(0, |acc, (k, bit)| acc | bit << k);
+            pos += size as usize;
+            if code == clear {
+                table = (0..clear).map(|i| vec![i as u8]).collect();
+                table.push(vec![]);
+                table.push(vec![]);
+                size = min_code_size + 1;
+                last = None;
+                continue;
+            }
+            if code == clear + 1 {
+                return out;
+            }
+            let entry = match (table.get(code), &last) {
+                (Some(e), _) if code != table.len() => e.clone(),
+                (_, Some(l)) => [l.clone(), vec![l[0]]].concat(),
+                _ => panic!("Bad code"),
+            };
+            if let Some(l) = last {
+                if table.len() < 4096 {
+                    table.push([l, vec![entry[0]]].concat());
+                }
+            }
+            if table.len() == 1 << size && size < 12 {
+                size += 1;
+            }
+            out.extend_from_slice(&entry);
+            last = Some(entry);
+        }
+    }
+
+    #[test]
+    fn lzw_round_trip() {
+        // long enough to fill the table and clear it
+        let indices: Vec<u8> = (0..40000u32).map(|i| ((i * i) / 7 % 13) as u8).collect();
+        for &min in [4, 8].iter() {
+            assert_eq!(indices, lzw_decode(&lzw_encode(&indices, min), min));
+        }
+        assert_eq!(Vec::<u8>::new(), lzw_decode(&lzw_encode(&[], 2), 2));
+    }
+
+    #[test]
+    fn animation_structure() {
+        let mut gif = GifWriter::new(vec![], 4, 3, 5, Repeat::Forever).unwrap();
+        let mut frame = PPMImg::new(3, 4, 255);
+        gif.add_frame(&frame).unwrap();
+        frame.fg_color = RGB::new(255, 0, 0);
+        frame.plot(1, 1);
+        gif.add_frame(&frame).unwrap();
+        assert!(gif.add_frame(&PPMImg::new(4, 4, 255)).is_err());
+        let bytes = gif.finish().unwrap();
+
+        assert_eq!(b"GIF89a", &bytes[..6]);
+        assert_eq!(b"NETSCAPE2.0", &bytes[16..27]);
+        assert_eq!(Some(&0x3b), bytes.last());
+        // two frames, each with its own delay
+        let frames = bytes.windows(4).filter(|w| w == &[0x21, 0xf9, 4, 0x04]).count();
+        assert_eq!(2, frames);
+    }
+
+    #[test]
+    fn many_colors_are_reduced() {
+        let pixels: Vec<[u8; 3]> = (0..1000u32).map(|i| [i as u8, (i / 4) as u8, 77]).collect();
+        let (palette, indices) = quantize(&pixels, 40, Dither::None, &Quantizer::Uniform);
+        assert_eq!(252, palette.len());
+        for (p, &i) in pixels.iter().zip(indices.iter()) {
+            let q = palette[i as usize];
+            assert!((0..3).all(|c| (p[c] as i32 - q[c] as i32).abs() <= 26));
+        }
+    }
+}
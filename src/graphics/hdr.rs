@@ -0,0 +1,179 @@
+#![allow(dead_code)]
+
+//! A canvas of light that does not stop at white, tone mapped into an image when done
+//!
+//! Thousands of faint additive curves on a `PPMImg` reach the top of the depth after a few
+//! dozen, and everything past that is lost. Here every channel is an f32 of linear light with
+//! no upper bound, and only the tone mapping on export squeezes it back into range.
+
+use std::io;
+
+use super::colors::BlendMode;
+use super::target::RenderTarget;
+use super::{PPMImg, RGB};
+
+/// How light above 1 is brought into the range of an image
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ToneMap {
+    /// Everything above 1 is white
+    Clamp,
+    /// x / (1 + x), which never quite reaches white
+    Reinhard,
+    /// Krzysztof Narkowicz's fit of the ACES filmic curve, with more contrast than Reinhard
+    Aces,
+}
+
+impl ToneMap {
+    pub fn map(self, x: f64) -> f64 {
+        let x = x.max(0.0);
+        match self {
+            ToneMap::Clamp => x.min(1.0),
+            ToneMap::Reinhard => x / (1.0 + x),
+            ToneMap::Aces => {
+                ((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)).clamp(0.0, 1.0)
+            }
+        }
+    }
+}
+
+/// A canvas of f32 red, green and blue linear light
+pub struct HdrCanvas {
+    height: u32,
+    width: u32,
+    data: Vec<[f32; 3]>,
+    pub bg_color: [f32; 3],
+    /// Light plotted by `plot`, as for `RenderTarget`
+    pub fg_color: [f32; 3],
+    /// Opacity of everything drawn; with `BlendMode::Add`, how much of the light is added
+    pub fg_alpha: f32,
+    pub blend_mode: BlendMode,
+    /// Light is multiplied by 2 to this power before tone mapping
+    pub exposure: f64,
+    pub tone_map: ToneMap,
+}
+
+impl HdrCanvas {
+    pub fn new(height: u32, width: u32) -> Self {
+        HdrCanvas {
+            height,
+            width,
+            data: vec![[0.0; 3]; (width * height) as usize],
+            bg_color: [0.0; 3],
+            fg_color: [1.0; 3],
+            fg_alpha: 1.0,
+            blend_mode: BlendMode::Normal,
+            exposure: 0.0,
+            tone_map: ToneMap::Reinhard,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The light at (x, y), or None if that is off the canvas
+    pub fn light(&self, x: i32, y: i32) -> Option<[f32; 3]> {
+        self.index(x, y).map(|i| self.data[i])
+    }
+
+    /// Draws light at (x, y), blended as set by `blend_mode` and `fg_alpha`
+    pub fn plot_light(&mut self, x: i32, y: i32, light: [f32; 3]) {
+        let i = match self.index(x, y) {
+            Some(i) => i,
+            None => return,
+        };
+        let (dst, a) = (self.data[i], self.fg_alpha);
+        self.data[i] = [0, 1, 2].map(|c| {
+            let (s, d) = (light[c], dst[c]);
+            let mixed = match self.blend_mode {
+                BlendMode::Normal => s,
+                BlendMode::Multiply => s * d,
+                // screening only keeps to 1 for light up to 1, so leave brighter light alone
+                BlendMode::Screen => s + d - s.min(1.0) * d.min(1.0),
+                BlendMode::Add => s + d,
+            };
+            d + (mixed - d) * a
+        });
+    }
+
+    /// The tone mapped image, with channels up to `depth`
+    pub fn to_image(&self, depth: u16) -> PPMImg {
+        let gain = 2f64.powf(self.exposure);
+        let mut img = PPMImg::new(self.height, self.width, depth);
+        for (pixel, light) in img.data.iter_mut().zip(self.data.iter()) {
+            *pixel = RGB::from_linear(light.map(|c| self.tone_map.map(c as f64 * gain)), depth);
+        }
+        img
+    }
+
+    /// Tone map and save, as for `PPMImg::save_as`
+    pub fn save_as(&self, filepath: &str) -> io::Result<()> {
+        self.to_image(255).save_as(filepath)
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return None;
+        }
+        Some(y as usize * self.width as usize + x as usize)
+    }
+}
+
+/// Colors plotted through the trait are taken as 8 bit sRGB, scaled by `fg_color`
+impl RenderTarget for HdrCanvas {
+    fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn plot(&mut self, x: i32, y: i32, color: RGB) {
+        let light = color.to_linear(255);
+        let fg = self.fg_color;
+        self.plot_light(x, y, [0, 1, 2].map(|c| light[c] as f32 * fg[c]));
+    }
+
+    fn clear(&mut self) {
+        let bg = self.bg_color;
+        self.data.iter_mut().for_each(|p| *p = bg);
+    }
+
+    /// The light stays in memory, to be tone mapped when done
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn faint_strokes_add_up_past_white() {
+        let mut hdr = HdrCanvas::new(1, 3);
+        hdr.blend_mode = BlendMode::Add;
+        hdr.fg_alpha = 0.01;
+        for i in 0..1000 {
+            hdr.draw_line(0.0, 0.0, if i < 100 { 2.0 } else { 1.0 }, 0.0, RGB::WHITE);
+        }
+        assert!((hdr.light(0, 0).unwrap()[0] - 10.0).abs() < 1e-3);
+        assert!((hdr.light(2, 0).unwrap()[0] - 1.0).abs() < 1e-3);
+
+        // both end up white when clamped, but stay apart when tone mapped
+        hdr.tone_map = ToneMap::Clamp;
+        let clamped = hdr.to_image(255);
+        assert_eq!(clamped.pixel(0, 0), clamped.pixel(2, 0));
+        for &op in [ToneMap::Reinhard, ToneMap::Aces].iter() {
+            hdr.tone_map = op;
+            let img = hdr.to_image(255);
+            let (bright, dim) = (img.pixel(0, 0).unwrap(), img.pixel(2, 0).unwrap());
+            assert!(dim.red < bright.red, "{:?}", op);
+        }
+        hdr.tone_map = ToneMap::Reinhard;
+        assert!(hdr.to_image(255).pixel(0, 0).unwrap().red < 255);
+        hdr.exposure = -4.0;
+        assert!(hdr.to_image(255).pixel(0, 0).unwrap().red < 200);
+    }
+}
@@ -0,0 +1,136 @@
+#![allow(dead_code)]
+
+use super::surfaces::Grid;
+
+type Point = (f64, f64);
+
+/// Heights sampled on a regular grid over the xy plane
+///
+/// `heights[i][j]` is the height at `x = origin.0 + j * cell.0`, `y = origin.1 + i * cell.1`
+#[derive(Clone, Debug)]
+pub struct Heightmap {
+    pub heights: Vec<Vec<f64>>,
+    pub origin: Point,
+    pub cell: Point,
+}
+
+impl Heightmap {
+    pub fn new(heights: Vec<Vec<f64>>, origin: Point, cell: Point) -> Self {
+        assert!(
+            heights.windows(2).all(|w| w[0].len() == w[1].len()),
+            "Every row of heights must be as long"
+        );
+        Heightmap {
+            heights,
+            origin,
+            cell,
+        }
+    }
+
+    /// Sample `f(x, y)` with `nx` by `ny` steps over the rectangle from `min` to `max`
+    pub fn from_fn(
+        f: impl Fn(f64, f64) -> f64,
+        min: Point,
+        max: Point,
+        nx: usize,
+        ny: usize,
+    ) -> Self {
+        assert!(nx > 0 && ny > 0, "Heightmap needs at least one step each way");
+        let cell = ((max.0 - min.0) / nx as f64, (max.1 - min.1) / ny as f64);
+        let heights = (0..=ny)
+            .map(|i| {
+                let y = min.1 + i as f64 * cell.1;
+                (0..=nx).map(|j| f(min.0 + j as f64 * cell.0, y)).collect()
+            })
+            .collect();
+        Heightmap::new(heights, min, cell)
+    }
+
+    fn xy(&self, i: usize, j: usize) -> Point {
+        (
+            self.origin.0 + j as f64 * self.cell.0,
+            self.origin.1 + i as f64 * self.cell.1,
+        )
+    }
+
+    /// The terrain as a grid of points, with triangles facing up toward +z
+    pub fn grid(&self) -> Grid {
+        let cols = self.heights.first().map_or(0, |r| r.len());
+        // rows of the grid run along x, so that u x v points up
+        (0..cols)
+            .map(|j| {
+                (0..self.heights.len())
+                    .map(|i| {
+                        let (x, y) = self.xy(i, j);
+                        (x, y, self.heights[i][j])
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Lines where the terrain crosses height `level`, by marching squares
+    pub fn contours(&self, level: f64) -> Vec<(Point, Point)> {
+        let mut lines = vec![];
+        let h = &self.heights;
+        for i in 1..h.len() {
+            for j in 1..h[i].len() {
+                // corners counter clockwise from the bottom left
+                let corners = [(i - 1, j - 1), (i - 1, j), (i, j), (i, j - 1)];
+                let values: Vec<f64> = corners.iter().map(|&(a, b)| h[a][b]).collect();
+                // where each side, bottom, right, top, left, crosses the level
+                let crossings: Vec<Point> = (0..4)
+                    .filter_map(|k| {
+                        let (vp, vq) = (values[k], values[(k + 1) % 4]);
+                        if (vp >= level) == (vq >= level) {
+                            return None;
+                        }
+                        let (c0, c1) = (corners[k], corners[(k + 1) % 4]);
+                        let (p, q) = (self.xy(c0.0, c0.1), self.xy(c1.0, c1.1));
+                        let t = (level - vp) / (vq - vp);
+                        Some((p.0 + (q.0 - p.0) * t, p.1 + (q.1 - p.1) * t))
+                    })
+                    .collect();
+                match crossings.len() {
+                    2 => lines.push((crossings[0], crossings[1])),
+                    4 => {
+                        // a saddle; the average at the center decides which corners connect
+                        let center = values.iter().sum::<f64>() / 4.0;
+                        if (center >= level) == (values[0] >= level) {
+                            // bottom left joins top right, so cut off the other two corners
+                            lines.push((crossings[0], crossings[1]));
+                            lines.push((crossings[2], crossings[3]));
+                        } else {
+                            lines.push((crossings[3], crossings[0]));
+                            lines.push((crossings[1], crossings[2]));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bowl_contours_are_circles() {
+        let bowl = Heightmap::from_fn(|x, y| x * x + y * y, (-2.0, -2.0), (2.0, 2.0), 40, 40);
+        let grid = bowl.grid();
+        assert_eq!((41, 41), (grid.len(), grid[0].len()));
+        // rows go along x
+        let p = grid[0][1];
+        assert!((p.0 + 2.0).abs() < 1e-9 && (p.1 + 1.9).abs() < 1e-9 && (p.2 - 7.61).abs() < 1e-9);
+
+        let lines = bowl.contours(1.0);
+        assert!(lines.len() > 30);
+        for (p, q) in lines.iter() {
+            assert!((p.0.hypot(p.1) - 1.0).abs() < 0.01);
+            assert!((q.0.hypot(q.1) - 1.0).abs() < 0.01);
+        }
+    }
+}
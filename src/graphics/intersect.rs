@@ -0,0 +1,158 @@
+#![allow(dead_code)]
+
+use super::curves::Line;
+use super::parametrics::{flatten, Curve};
+
+/// A point where two curves meet
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Intersection {
+    /// Parameter on the first curve
+    pub t0: f64,
+    /// Parameter on the second curve
+    pub t1: f64,
+    pub point: (f64, f64),
+}
+
+/// Find the intersections of two curves
+///
+/// Both curves are flattened to within `tolerance`, crossing segments are found,
+/// and each crossing is refined with Newton's method on the curves themselves.
+pub fn intersect_curves(a: &impl Curve, b: &impl Curve, tolerance: f64) -> Vec<Intersection> {
+    let (pa, pb) = (flatten(a, tolerance), flatten(b, tolerance));
+    let mut found = vec![];
+    for sa in pa.windows(2) {
+        for sb in pb.windows(2) {
+            if let Some((u, v)) = segment_intersection(sa[0].1, sa[1].1, sb[0].1, sb[1].1) {
+                let s = lerp(sa[0].0, sa[1].0, u);
+                let t = lerp(sb[0].0, sb[1].0, v);
+                push_unique(&mut found, refine(a, b, s, t), tolerance);
+            }
+        }
+    }
+    found
+}
+
+/// Find the intersections of a curve with the line segment from `p0` to `p1`
+///
+/// `t1` of each result runs from 0 at `p0` to 1 at `p1`
+pub fn intersect_line(
+    c: &impl Curve,
+    p0: (f64, f64),
+    p1: (f64, f64),
+    tolerance: f64,
+) -> Vec<Intersection> {
+    intersect_curves(c, &Line::new(p0, p1), tolerance)
+}
+
+/// Find the points where a curve crosses itself, with `t0 < t1`
+pub fn self_intersections(c: &impl Curve, tolerance: f64) -> Vec<Intersection> {
+    let pts = flatten(c, tolerance);
+    let segments: Vec<_> = pts.windows(2).collect();
+    let mut found = vec![];
+    for (i, sa) in segments.iter().enumerate() {
+        // neighbouring segments always share an endpoint, so skip them
+        for sb in segments.iter().skip(i + 2) {
+            if let Some((u, v)) = segment_intersection(sa[0].1, sa[1].1, sb[0].1, sb[1].1) {
+                let s = lerp(sa[0].0, sa[1].0, u);
+                let t = lerp(sb[0].0, sb[1].0, v);
+                let hit = refine(c, c, s, t);
+                // newton may slide both parameters onto the same point
+                if (hit.t1 - hit.t0).abs() > 1e-6 {
+                    push_unique(&mut found, hit, tolerance);
+                }
+            }
+        }
+    }
+    found
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+/// Returns (u, v) such that a0 + u(a1 - a0) == b0 + v(b1 - b0), if both are within [0, 1]
+fn segment_intersection(
+    a0: (f64, f64),
+    a1: (f64, f64),
+    b0: (f64, f64),
+    b1: (f64, f64),
+) -> Option<(f64, f64)> {
+    let (dax, day) = (a1.0 - a0.0, a1.1 - a0.1);
+    let (dbx, dby) = (b1.0 - b0.0, b1.1 - b0.1);
+    let denom = dax * dby - day * dbx;
+    if denom == 0.0 {
+        return None;
+    }
+    let (ox, oy) = (b0.0 - a0.0, b0.1 - a0.1);
+    let u = (ox * dby - oy * dbx) / denom;
+    let v = (ox * day - oy * dax) / denom;
+    if (0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v) {
+        Some((u, v))
+    } else {
+        None
+    }
+}
+
+/// Newton iteration on a(s) - b(t) = 0 starting from the polyline estimate
+fn refine(a: &impl Curve, b: &impl Curve, s: f64, t: f64) -> Intersection {
+    let (mut s, mut t) = (s, t);
+    for _ in 0..8 {
+        let (pa, pb) = (a.point_at(s), b.point_at(t));
+        let (fx, fy) = (pa.0 - pb.0, pa.1 - pb.1);
+        let (da, db) = (a.derivative_at(s), b.derivative_at(t));
+        // jacobian is [da, -db]
+        let det = -da.0 * db.1 + da.1 * db.0;
+        if det.abs() < 1e-12 {
+            break;
+        }
+        let ds = (-fx * -db.1 + fy * -db.0) / det;
+        let dt = (-da.0 * fy + da.1 * fx) / det;
+        let (ns, nt) = ((s + ds).clamp(0.0, 1.0), (t + dt).clamp(0.0, 1.0));
+        let done = (ns - s).abs() < 1e-12 && (nt - t).abs() < 1e-12;
+        s = ns;
+        t = nt;
+        if done {
+            break;
+        }
+    }
+    Intersection {
+        t0: s,
+        t1: t,
+        point: a.point_at(s),
+    }
+}
+
+fn push_unique(found: &mut Vec<Intersection>, hit: Intersection, tolerance: f64) {
+    let near = |other: &Intersection| {
+        (other.point.0 - hit.point.0).hypot(other.point.1 - hit.point.1) <= tolerance
+            && (other.t0 - hit.t0).abs() < 1e-3
+    };
+    if !found.iter().any(near) {
+        found.push(hit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::curves::Bezier3;
+    use super::*;
+
+    #[test]
+    fn bezier_crosses_line() {
+        // symmetric arch crossing the line y = 10 twice
+        let b = Bezier3::new((0.0, 0.0), (0.0, 40.0), (60.0, 40.0), (60.0, 0.0));
+        let hits = intersect_line(&b, (-10.0, 10.0), (70.0, 10.0), 0.01);
+        assert_eq!(2, hits.len());
+        for h in hits {
+            assert!((h.point.1 - 10.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn loop_self_intersects() {
+        let b = Bezier3::new((0.0, 0.0), (150.0, 100.0), (-50.0, 100.0), (100.0, 0.0));
+        let hits = self_intersections(&b, 0.01);
+        assert_eq!(1, hits.len());
+        assert!((hits[0].point.0 - 50.0).abs() < 1e-6);
+    }
+}
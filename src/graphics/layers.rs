@@ -0,0 +1,126 @@
+#![allow(dead_code)]
+
+use super::{PPMImg, RGB};
+
+/// One image in a stack of layers
+pub struct Layer {
+    pub name: String,
+    /// Starts out transparent, see `PPMImg::new_transparent`
+    pub img: PPMImg,
+    pub visible: bool,
+    /// Opacity of the whole layer, from 0 to 1
+    pub opacity: f64,
+}
+
+/// Named layers of the same size, composited from the bottom up over a background color
+///
+/// Each layer can be cleared and redrawn on its own, and hidden or faded without redrawing.
+pub struct Layers {
+    height: u32,
+    width: u32,
+    depth: u16,
+    pub bg_color: RGB,
+    /// Bottom first
+    layers: Vec<Layer>,
+}
+
+impl Layers {
+    pub fn new(height: u32, width: u32, depth: u16, bg_color: RGB) -> Self {
+        Layers {
+            height,
+            width,
+            depth,
+            bg_color,
+            layers: vec![],
+        }
+    }
+
+    /// Add a transparent layer on top, returning its image to draw on
+    pub fn add(&mut self, name: &str) -> &mut PPMImg {
+        assert!(self.position(name).is_none(), "There is already a layer named {}", name);
+        self.layers.push(Layer {
+            name: name.to_string(),
+            img: PPMImg::new_transparent(self.height, self.width, self.depth),
+            visible: true,
+            opacity: 1.0,
+        });
+        &mut self.layers.last_mut().unwrap().img
+    }
+
+    /// Index of the layer named `name`, counting from the bottom
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.layers.iter().position(|l| l.name == name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Layer> {
+        self.layers.iter().find(|l| l.name == name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Layer> {
+        self.layers.iter_mut().find(|l| l.name == name)
+    }
+
+    /// Move a layer to `index` from the bottom, shifting the ones in between
+    pub fn move_to(&mut self, name: &str, index: usize) {
+        let from = self.position(name).expect("No layer with that name");
+        let layer = self.layers.remove(from);
+        self.layers.insert(index.min(self.layers.len()), layer);
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Layer> {
+        self.position(name).map(|i| self.layers.remove(i))
+    }
+
+    /// Layer names from the bottom up
+    pub fn names(&self) -> Vec<&str> {
+        self.layers.iter().map(|l| l.name.as_str()).collect()
+    }
+
+    /// Flatten the visible layers onto the background
+    pub fn composite(&self) -> PPMImg {
+        let mut out = PPMImg::new_with_bg(self.height, self.width, self.depth, self.bg_color);
+        for layer in self.layers.iter().filter(|l| l.visible && l.opacity > 0.0) {
+            for y in 0..self.height as i32 {
+                for x in 0..self.width as i32 {
+                    let mut c = layer.img.pixel_rgba(x, y).unwrap();
+                    c.alpha *= layer.opacity;
+                    if c.alpha > 0.0 {
+                        out.plot_rgba(x, y, c);
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layers_stack_in_order() {
+        let mut layers = Layers::new(4, 4, 255, RGB::WHITE);
+        let grid = layers.add("grid");
+        grid.fg_color = RGB::BLUE;
+        grid.draw_line(0.0, 1.0, 3.0, 1.0);
+        let art = layers.add("art");
+        art.fg_color = RGB::RED;
+        art.draw_line(1.0, 0.0, 1.0, 3.0);
+
+        let out = layers.composite();
+        assert_eq!(Some(RGB::WHITE), out.pixel(0, 0));
+        assert_eq!(Some(RGB::BLUE), out.pixel(0, 1));
+        assert_eq!(Some(RGB::RED), out.pixel(1, 1));
+
+        layers.move_to("art", 0);
+        assert_eq!(vec!["art", "grid"], layers.names());
+        layers.get_mut("grid").unwrap().opacity = 0.5;
+        let out = layers.composite();
+        assert_eq!(Some(RGB::new(128, 0, 128)), out.pixel(1, 1));
+        assert_eq!(Some(RGB::new(128, 128, 255)), out.pixel(0, 1));
+
+        layers.get_mut("art").unwrap().visible = false;
+        assert_eq!(Some(RGB::new(128, 128, 255)), layers.composite().pixel(1, 1));
+    }
+}
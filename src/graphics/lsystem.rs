@@ -0,0 +1,136 @@
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+
+use super::utils::polar_to_xy;
+
+type Point = (f64, f64);
+
+/// A Lindenmayer system with a turtle interpretation
+///
+/// Symbols understood by the turtle:
+/// ```text
+/// F, G    move forward `step` while drawing (see `draw_symbols`)
+/// f       move forward `step` without drawing
+/// +       turn counter clockwise by `angle_deg`
+/// -       turn clockwise by `angle_deg`
+/// |       turn around
+/// [       save position and heading
+/// ]       restore the last saved position and heading, if anything is saved
+/// ```
+///
+/// Every other symbol only takes part in rewriting.
+#[derive(Clone, Debug)]
+pub struct LSystem {
+    pub axiom: String,
+    rules: HashMap<char, String>,
+    pub angle_deg: f64,
+    pub step: f64,
+    draws: Vec<char>,
+}
+
+impl LSystem {
+    pub fn new(axiom: &str, angle_deg: f64, step: f64) -> Self {
+        LSystem {
+            axiom: axiom.to_string(),
+            rules: HashMap::new(),
+            angle_deg,
+            step,
+            draws: vec!['F', 'G'],
+        }
+    }
+
+    /// Add the production rule `from -> to`
+    pub fn rule(mut self, from: char, to: &str) -> Self {
+        self.rules.insert(from, to.to_string());
+        self
+    }
+
+    /// Set which symbols move forward while drawing, replacing the default "FG"
+    pub fn draw_symbols(mut self, symbols: &str) -> Self {
+        self.draws = symbols.chars().collect();
+        self
+    }
+
+    /// Apply the rules to the axiom `iterations` times
+    pub fn expand(&self, iterations: usize) -> String {
+        let mut s = self.axiom.clone();
+        for _ in 0..iterations {
+            s = s
+                .chars()
+                .map(|c| match self.rules.get(&c) {
+                    Some(to) => to.clone(),
+                    None => c.to_string(),
+                })
+                .collect();
+        }
+        s
+    }
+
+    /// Walk the turtle over `commands`, returning every drawn line
+    /// # Arguments
+    /// `start` - Where the turtle starts
+    /// `heading_deg` - Where the turtle faces, counter clockwise from the x axis
+    pub fn interpret(&self, commands: &str, start: Point, heading_deg: f64) -> Vec<(Point, Point)> {
+        let mut lines = vec![];
+        let (mut pos, mut heading) = (start, heading_deg);
+        let mut stack = vec![];
+        for c in commands.chars() {
+            match c {
+                '+' => heading += self.angle_deg,
+                '-' => heading -= self.angle_deg,
+                '|' => heading += 180.0,
+                '[' => stack.push((pos, heading)),
+                // a stray ] from a rule that does not balance its brackets does nothing
+                ']' => {
+                    if let Some((p, h)) = stack.pop() {
+                        pos = p;
+                        heading = h;
+                    }
+                }
+                'f' => {
+                    let (dx, dy) = polar_to_xy(self.step, heading);
+                    pos = (pos.0 + dx, pos.1 + dy);
+                }
+                _ if self.draws.contains(&c) => {
+                    let (dx, dy) = polar_to_xy(self.step, heading);
+                    let next = (pos.0 + dx, pos.1 + dy);
+                    lines.push((pos, next));
+                    pos = next;
+                }
+                _ => {}
+            }
+        }
+        lines
+    }
+
+    /// Expand the system and walk the result
+    pub fn generate(&self, iterations: usize, start: Point, heading_deg: f64) -> Vec<(Point, Point)> {
+        self.interpret(&self.expand(iterations), start, heading_deg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn algae_expansion() {
+        let algae = LSystem::new("A", 0.0, 1.0).rule('A', "AB").rule('B', "A");
+        assert_eq!("ABAABABA", algae.expand(4));
+    }
+
+    #[test]
+    fn branches_return_to_saved_state() {
+        let sys = LSystem::new("F[+F]F", 90.0, 10.0);
+        let lines = sys.generate(0, (0.0, 0.0), 90.0);
+        assert_eq!(3, lines.len());
+        // the last line starts where the branch began
+        let (p, q) = lines[2];
+        assert!((p.0).abs() < 1e-9 && (p.1 - 10.0).abs() < 1e-9);
+        assert!((q.1 - 20.0).abs() < 1e-9);
+
+        let unbalanced = LSystem::new("F]]F", 90.0, 10.0).generate(0, (0.0, 0.0), 0.0);
+        assert_eq!(((10.0, 0.0), (20.0, 0.0)), unbalanced[1]);
+    }
+}
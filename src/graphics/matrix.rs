@@ -1,6 +1,21 @@
 #![allow(dead_code)]
 
-use super::parametrics::Parametric;
+use super::colors::{Along, Gradient, RGB};
+use super::curves::{Bezier3, Hermite3};
+use super::dash::Dash;
+#[cfg(feature = "truetype")]
+use super::font::Font;
+use super::fractals::Fractal;
+use super::heightmap::Heightmap;
+use super::lsystem::LSystem;
+use super::mesh::Mesh;
+use super::parametrics::{Curve, Parametric, ParametricSurface, Surface, SurfaceMode};
+use super::path::Path;
+use super::polygon;
+use super::polyline::{self, Smoothing};
+use super::surfaces::{self, Axis};
+use super::vector::{self, Vec3};
+use super::tessellation::Tessellation;
 use super::utils;
 use std::f64::consts;
 use std::fmt;
@@ -13,6 +28,12 @@ pub struct Matrix {
     nrows: usize,
     ncols: usize,
     data: Vec<f64>,
+    /// How curves are sampled, or `None` for the fixed step each kind of curve always used
+    tessellation: Option<Tessellation>,
+    /// Color of every row, or empty when no row has one
+    colors: Vec<Option<RGB>>,
+    /// Color given to rows appended from now on
+    pen: Option<RGB>,
 }
 
 // constructor, get, set
@@ -23,7 +44,7 @@ impl Matrix {
         // col * self.nrows + row
     }
 
-    pub fn new_clone_vec(nrows: usize, ncols: usize, data: &Vec<f64>) -> Matrix {
+    pub fn new_clone_vec(nrows: usize, ncols: usize, data: &[f64]) -> Matrix {
         assert_eq!(
             nrows * ncols,
             data.len(),
@@ -33,7 +54,10 @@ impl Matrix {
         Matrix {
             nrows,
             ncols,
-            data: data.clone(),
+            data: data.to_vec(),
+            tessellation: None,
+            colors: vec![],
+            pen: None,
         }
     }
 
@@ -43,7 +67,14 @@ impl Matrix {
             data.len(),
             "nrows * ncols must == data.len()"
         );
-        Matrix { nrows, ncols, data }
+        Matrix {
+            nrows,
+            ncols,
+            data,
+            tessellation: None,
+            colors: vec![],
+            pen: None,
+        }
     }
 
     pub fn get(&self, row: usize, col: usize) -> Option<f64> {
@@ -59,6 +90,64 @@ impl Matrix {
         let i = self.index(row, col);
         self.data[i] = data;
     }
+
+    /// How curves added to this matrix are sampled, if it was set
+    pub fn tessellation(&self) -> Option<Tessellation> {
+        self.tessellation
+    }
+
+    pub fn set_tessellation(&mut self, tessellation: Tessellation) {
+        self.tessellation = Some(tessellation);
+    }
+
+    /// The tessellation to sample curves with, falling back to a fixed `step` in t
+    fn sampling(&self, step: f64) -> Tessellation {
+        self.tessellation.unwrap_or(Tessellation::Step(step))
+    }
+}
+
+// colors
+impl Matrix {
+    /// Color rows appended from now on with `color`, or leave them uncolored with None
+    ///
+    /// Uncolored rows are drawn with the image's `fg_color`
+    pub fn set_pen(&mut self, color: Option<RGB>) {
+        self.pen = color;
+    }
+
+    pub fn pen(&self) -> Option<RGB> {
+        self.pen
+    }
+
+    /// Color of a row, if it has one
+    pub fn color(&self, row: usize) -> Option<RGB> {
+        self.colors.get(row).copied().flatten()
+    }
+
+    pub fn set_color(&mut self, row: usize, color: Option<RGB>) {
+        assert!(row < self.nrows, "Index out of bound");
+        if self.colors.is_empty() {
+            if color.is_none() {
+                return;
+            }
+            self.colors = vec![None; self.nrows];
+        }
+        self.colors[row] = color;
+    }
+
+    /// Whether any row has a color
+    pub fn has_colors(&self) -> bool {
+        !self.colors.is_empty()
+    }
+
+    /// Give the last `n` rows, which were just appended, the pen color
+    fn color_new_rows(&mut self, n: usize) {
+        if self.pen.is_none() && self.colors.is_empty() {
+            return;
+        }
+        self.colors.resize(self.nrows - n, None);
+        self.colors.resize(self.nrows, self.pen);
+    }
 }
 
 // add edge (row)
@@ -71,29 +160,77 @@ impl Matrix {
         );
         self.data.append(row);
         self.nrows += 1;
+        self.color_new_rows(1);
     }
 
     /// Append an edge in the format [x0, y0, z0, x1, y1, z1]
-    pub fn append_edge(&mut self, edge: &Vec<f64>) {
+    pub fn append_edge(&mut self, edge: &[f64]) {
         assert_eq!(6, edge.len(), "Len of edge vec should be 6");
         self.data.extend_from_slice(&edge[0..3]);
         self.data.push(1.0);
         self.data.extend_from_slice(&edge[3..6]);
         self.data.push(1.0);
         self.nrows += 2;
+        self.color_new_rows(2);
+    }
+
+    /// Append all the rows of another matrix
+    ///
+    /// Rows keep their colors, and uncolored ones get the pen color
+    pub fn append_matrix(&mut self, other: &Matrix) {
+        assert_eq!(self.ncols, other.ncols, "Column sizes of matrices don't match");
+        self.data.extend_from_slice(&other.data);
+        self.nrows += other.nrows;
+        self.color_new_rows(other.nrows);
+        if other.has_colors() {
+            let start = self.nrows - other.nrows;
+            self.colors.resize(self.nrows, self.pen);
+            for (i, c) in other.colors.iter().enumerate() {
+                if c.is_some() {
+                    self.colors[start + i] = *c;
+                }
+            }
+        }
+    }
+
+    /// Append a triangle in the format [x0, y0, z0, x1, y1, z1, x2, y2, z2]
+    ///
+    /// A polygon matrix stores each triangle as three consecutive points
+    pub fn append_polygon(&mut self, polygon: &[f64]) {
+        assert_eq!(9, polygon.len(), "Len of polygon vec should be 9");
+        for p in polygon.chunks(3) {
+            self.data.extend_from_slice(p);
+            self.data.push(1.0);
+        }
+        self.nrows += 3;
+        self.color_new_rows(3);
+    }
+
+    pub fn nrows(&self) -> usize {
+        self.nrows
+    }
+
+    /// Drop every row after the first `nrows`, with their colors
+    pub fn truncate(&mut self, nrows: usize) {
+        if nrows >= self.nrows {
+            return;
+        }
+        self.nrows = nrows;
+        self.data.truncate(nrows * self.ncols);
+        self.colors.truncate(nrows);
     }
 }
 
 // row and col iter
 impl Matrix {
     /// Iterate over a certain row
-    pub fn row_iter<'a>(&'a self, r: usize) -> impl Iterator<Item = &f64> {
+    pub fn row_iter(&self, r: usize) -> impl Iterator<Item = &f64> {
         let start = r * self.ncols;
         self.data[start..start + self.ncols].iter()
     }
 
     /// Iterate over a certain column
-    pub fn col_iter<'a>(&'a self, c: usize) -> impl Iterator<Item = &f64> {
+    pub fn col_iter(&self, c: usize) -> impl Iterator<Item = &f64> {
         self.data.iter().skip(c).step_by(self.ncols)
     }
 
@@ -105,6 +242,58 @@ impl Matrix {
     }
 }
 
+// edge chains
+impl Matrix {
+    /// Join the edges of an edge matrix into chains of (x, y) points
+    ///
+    /// An edge continues the current chain when it starts where the last one ended
+    pub fn polylines(&self) -> Vec<Vec<(f64, f64)>> {
+        let mut chains: Vec<Vec<(f64, f64)>> = vec![];
+        let mut rows = self.iter_by_row();
+        while let (Some(p0), Some(p1)) = (rows.next(), rows.next()) {
+            let (a, b) = ((p0[0], p0[1]), (p1[0], p1[1]));
+            match chains.last_mut() {
+                Some(chain) if chain.last() == Some(&a) => chain.push(b),
+                _ => chains.push(vec![a, b]),
+            }
+        }
+        chains
+    }
+}
+
+// polygon normals
+impl Matrix {
+    /// Unit normal of every triangle in a polygon matrix
+    pub fn polygon_normals(&self) -> Vec<Vec3> {
+        let rows: Vec<&[f64]> = self.iter_by_row().collect();
+        rows.chunks_exact(3)
+            .map(|t| {
+                let p = |r: &[f64]| (r[0], r[1], r[2]);
+                vector::normalize(vector::triangle_normal(p(t[0]), p(t[1]), p(t[2])))
+            })
+            .collect()
+    }
+
+    /// Keep only the triangles of a polygon matrix that face the viewer
+    /// # Arguments
+    /// `view` - Direction from the scene toward the viewer; `(0, 0, 1)` looks down the z axis
+    pub fn cull_backfaces(&self, view: Vec3) -> Matrix {
+        let mut front = Matrix::new(0, self.ncols, vec![]);
+        front.tessellation = self.tessellation;
+        let triangles = self.data.chunks_exact(3 * self.ncols).zip(self.polygon_normals());
+        for (i, (tri, n)) in triangles.enumerate() {
+            if vector::dot(n, view) > 0.0 {
+                front.data.extend_from_slice(tri);
+                front.nrows += 3;
+                if self.has_colors() {
+                    front.colors.extend_from_slice(&self.colors[3 * i..3 * i + 3]);
+                }
+            }
+        }
+        front
+    }
+}
+
 // mul
 impl Matrix {
     /// Returns (x, y) of a matrix based on ncols and i
@@ -125,7 +314,12 @@ impl Matrix {
                 .zip(other.col_iter(c))
                 .fold(0.0, |sum, (a, b)| sum + a * b);
         }
-        Matrix::new(frows, fcols, fdata)
+        let mut m = Matrix::new(frows, fcols, fdata);
+        m.tessellation = self.tessellation;
+        // each row of the result comes from the same row of self
+        m.colors = self.colors.clone();
+        m.pen = self.pen;
+        m
     }
 
     pub fn transposed_mul(&self, other: &Self) -> Self {
@@ -139,7 +333,9 @@ impl Matrix {
                 .zip(other.row_iter(r))
                 .fold(0.0, |sum, (a, b)| sum + a * b);
         }
-        Matrix::new(frows, fcols, fdata)
+        let mut m = Matrix::new(frows, fcols, fdata);
+        m.tessellation = self.tessellation;
+        m
     }
 
     pub fn mul_mut_b(a: &Matrix, b: &mut Matrix) {
@@ -163,14 +359,8 @@ impl Matrix {
     pub fn to_ident(&mut self) {
         let ncols = self.ncols;
         for (i, d) in self.data.iter_mut().enumerate() {
-            *d = if {
-                let (r, c) = Matrix::index_to_rc(i, ncols);
-                r == c
-            } {
-                1.0
-            } else {
-                0.0
-            }
+            let (r, c) = Matrix::index_to_rc(i, ncols);
+            *d = if r == c { 1.0 } else { 0.0 }
         }
     }
 }
@@ -249,6 +439,114 @@ impl Matrix {
     }
 }
 
+// inspect transformation matrices
+impl Matrix {
+    /// The largest factor by which this 4 by 4 transformation scales any axis
+    pub fn max_scale(&self) -> f64 {
+        assert!(self.nrows >= 3 && self.ncols >= 3, "Not a transformation matrix");
+        // points are rows, so row i of the upper 3 by 3 is where axis i goes
+        (0..3)
+            .map(|r| self.row_iter(r).take(3).map(|d| d * d).sum::<f64>().sqrt())
+            .fold(0.0, f64::max)
+    }
+
+    /// Apply this 4 by 4 transformation to a single point
+    pub fn transform_point(&self, p: Vec3) -> Vec3 {
+        assert!(self.nrows == 4 && self.ncols == 4, "Not a transformation matrix");
+        let d = &self.data;
+        // the point is a row [x, y, z, 1] multiplied on the left
+        (
+            p.0 * d[0] + p.1 * d[4] + p.2 * d[8] + d[12],
+            p.0 * d[1] + p.1 * d[5] + p.2 * d[9] + d[13],
+            p.0 * d[2] + p.1 * d[6] + p.2 * d[10] + d[14],
+        )
+    }
+
+    /// Transform a unit normal of a surface, so that it stays at right angles to the surface
+    ///
+    /// Moves leave normals alone, and scaling more along one axis tips them the other way.
+    pub fn transform_normal(&self, n: Vec3) -> Vec3 {
+        assert!(self.nrows == 4 && self.ncols == 4, "Not a transformation matrix");
+        let a = |i: usize, j: usize| self.data[4 * i + j];
+        // the cofactors are the inverse transposed, times the determinant
+        let cof = |i: usize, j: usize| {
+            let (i1, i2, j1, j2) = ((i + 1) % 3, (i + 2) % 3, (j + 1) % 3, (j + 2) % 3);
+            a(i1, j1) * a(i2, j2) - a(i1, j2) * a(i2, j1)
+        };
+        let det: f64 = (0..3).map(|j| a(0, j) * cof(0, j)).sum();
+        let n = [n.0, n.1, n.2];
+        let out = |j: usize| (0..3).map(|i| n[i] * cof(i, j)).sum::<f64>() * det.signum();
+        vector::normalize((out(0), out(1), out(2)))
+    }
+
+    /// How much a transformation scales lengths, on average over every direction
+    ///
+    /// This is the cube root of how much it scales volumes, so rotations and moves count as 1
+    /// and `scale(2, 2, 2)` as 2. Good for keeping stroke widths in proportion to a zoom.
+    pub fn scale_factor(&self) -> f64 {
+        assert!(self.nrows == 4 && self.ncols == 4, "Not a transformation matrix");
+        let d = &self.data;
+        let det = d[0] * (d[5] * d[10] - d[6] * d[9]) - d[1] * (d[4] * d[10] - d[6] * d[8])
+            + d[2] * (d[4] * d[9] - d[5] * d[8]);
+        det.abs().cbrt()
+    }
+}
+
+// bounds
+impl Matrix {
+    /// Smallest and largest (x, y, z) over all points, or None for an empty matrix
+    pub fn bounds(&self) -> Option<(Vec3, Vec3)> {
+        let mut rows = self.iter_by_row();
+        let first = rows.next()?;
+        let start = ((first[0], first[1], first[2]), (first[0], first[1], first[2]));
+        Some(rows.fold(start, |(lo, hi), r| {
+            (
+                (lo.0.min(r[0]), lo.1.min(r[1]), lo.2.min(r[2])),
+                (hi.0.max(r[0]), hi.1.max(r[1]), hi.2.max(r[2])),
+            )
+        }))
+    }
+
+    /// Convex hull of all points, looking down the z axis
+    ///
+    /// Returns a closed polygon, counter clockwise, with its first point repeated at the end
+    pub fn convex_hull(&self) -> Vec<(f64, f64)> {
+        let points: Vec<(f64, f64)> = self.iter_by_row().map(|r| (r[0], r[1])).collect();
+        polygon::convex_hull(&points)
+    }
+
+    /// The transformation that centers this geometry on a `width` by `height` canvas
+    /// and scales it up or down to fill it, leaving `margin` pixels on every side
+    ///
+    /// The scale is the same on every axis, so shapes keep their proportions
+    pub fn fit_to(&self, width: f64, height: f64, margin: f64) -> Matrix {
+        let (lo, hi) = match self.bounds() {
+            Some(b) => b,
+            None => return Matrix::ident(4),
+        };
+        let (w, h) = (hi.0 - lo.0, hi.1 - lo.1);
+        let (room_w, room_h) = (width - 2.0 * margin, height - 2.0 * margin);
+        let scale = match (w > 0.0, h > 0.0) {
+            (true, true) => (room_w / w).min(room_h / h),
+            (true, false) => room_w / w,
+            (false, true) => room_h / h,
+            (false, false) => 1.0,
+        };
+        let center = ((lo.0 + hi.0) / 2.0, (lo.1 + hi.1) / 2.0, (lo.2 + hi.2) / 2.0);
+        Matrix::mv(-center.0, -center.1, -center.2)
+            .mul(&Matrix::scale(scale, scale, scale))
+            .mul(&Matrix::mv(width / 2.0, height / 2.0, 0.0))
+    }
+}
+
+// bake transformations
+impl Matrix {
+    /// Transform every point of this edge or polygon matrix by `t`, in place
+    pub fn bake_transform(&mut self, t: &Matrix) {
+        *self = self.mul(t);
+    }
+}
+
 // print Matrix
 impl fmt::Display for Matrix {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -270,6 +568,300 @@ impl fmt::Display for Matrix {
     }
 }
 
+// draw solids
+impl Matrix {
+    /// A corner of an axis aligned box, on the low (0) or high (1) side of each axis
+    #[allow(clippy::too_many_arguments)]
+    fn box_corner(
+        corner: (f64, f64, f64),
+        width: f64,
+        height: f64,
+        depth: f64,
+        ix: usize,
+        iy: usize,
+        iz: usize,
+    ) -> [f64; 3] {
+        let (x, y, z) = corner;
+        [
+            x + width * ix as f64,
+            y - height * (1 - iy) as f64,
+            z - depth * (1 - iz) as f64,
+        ]
+    }
+
+    /// Add the 12 edges of an axis aligned box
+    /// # Arguments
+    /// `corner` - The top left front corner (x, y, z); the box extends toward +x, -y and -z
+    /// `width`, `height`, `depth` - Size along x, y and z
+    pub fn add_box(&mut self, corner: (f64, f64, f64), width: f64, height: f64, depth: f64) {
+        let at = |ix, iy, iz| Matrix::box_corner(corner, width, height, depth, ix, iy, iz);
+        for a in 0..2 {
+            for b in 0..2 {
+                let edges = [
+                    (at(0, a, b), at(1, a, b)),
+                    (at(a, 0, b), at(a, 1, b)),
+                    (at(a, b, 0), at(a, b, 1)),
+                ];
+                for (p, q) in edges.iter() {
+                    self.append_edge(&[p[0], p[1], p[2], q[0], q[1], q[2]]);
+                }
+            }
+        }
+    }
+
+    /// Add the 12 triangles of an axis aligned box, wound counter clockwise when seen from outside
+    ///
+    /// Takes the same arguments as `add_box`
+    pub fn add_box_polygons(&mut self, corner: (f64, f64, f64), width: f64, height: f64, depth: f64) {
+        let at = |ix, iy, iz| Matrix::box_corner(corner, width, height, depth, ix, iy, iz);
+        let faces = [
+            // front, back
+            [at(0, 0, 1), at(1, 0, 1), at(1, 1, 1), at(0, 1, 1)],
+            [at(1, 0, 0), at(0, 0, 0), at(0, 1, 0), at(1, 1, 0)],
+            // right, left
+            [at(1, 0, 1), at(1, 0, 0), at(1, 1, 0), at(1, 1, 1)],
+            [at(0, 0, 0), at(0, 0, 1), at(0, 1, 1), at(0, 1, 0)],
+            // top, bottom
+            [at(0, 1, 1), at(1, 1, 1), at(1, 1, 0), at(0, 1, 0)],
+            [at(0, 0, 0), at(1, 0, 0), at(1, 0, 1), at(0, 0, 1)],
+        ];
+        for [a, b, c, d] in faces.iter() {
+            self.append_polygon(&[a[0], a[1], a[2], b[0], b[1], b[2], c[0], c[1], c[2]]);
+            self.append_polygon(&[a[0], a[1], a[2], c[0], c[1], c[2], d[0], d[1], d[2]]);
+        }
+    }
+}
+
+// draw surfaces
+impl Matrix {
+    /// Add a surface in the given mode
+    /// # Arguments
+    /// `nu`, `nv` - Number of steps in u and in v
+    pub fn add_surface(&mut self, surface: &impl Surface, nu: usize, nv: usize, mode: SurfaceMode) {
+        self.add_grid(&surface.grid(nu, nv), mode);
+    }
+
+    /// Add every grid point of a surface as an edge from the point to itself
+    ///
+    /// Points on seams and poles are repeated
+    pub fn add_surface_points(&mut self, surface: &impl Surface, nu: usize, nv: usize) {
+        self.add_grid(&surface.grid(nu, nv), SurfaceMode::Points);
+    }
+
+    /// Add a surface as a wireframe of edges along its u and v grid lines
+    /// # Arguments
+    /// `nu`, `nv` - Number of steps in u and in v
+    pub fn add_surface_wireframe(&mut self, surface: &impl Surface, nu: usize, nv: usize) {
+        self.add_grid(&surface.grid(nu, nv), SurfaceMode::Wireframe);
+    }
+
+    /// Add a surface as triangles, two for every cell of the grid
+    ///
+    /// Triangles are wound counter clockwise when looking at the side where
+    /// u increases to the right and v increases up.
+    /// Triangles that collapse, as at the poles of a sphere, are left out.
+    /// # Arguments
+    /// `nu`, `nv` - Number of steps in u and in v
+    pub fn add_surface_polygons(&mut self, surface: &impl Surface, nu: usize, nv: usize) {
+        self.add_grid(&surface.grid(nu, nv), SurfaceMode::Polygons);
+    }
+
+    /// Add every face of a mesh as a triangle, with the colors of its vertices
+    pub fn add_mesh(&mut self, mesh: &Mesh) {
+        for (face, [a, b, c]) in mesh.faces.iter().zip(mesh.faces_iter()) {
+            self.append_polygon(&[a.0, a.1, a.2, b.0, b.1, b.2, c.0, c.1, c.2]);
+            for (k, &i) in face.iter().enumerate() {
+                if let Some(color) = mesh.vertex_color(i) {
+                    self.set_color(self.nrows - 3 + k, Some(color));
+                }
+            }
+        }
+    }
+
+    /// Add a grid of points, where `grid[i][j]` neighbors `grid[i + 1][j]` and `grid[i][j + 1]`
+    ///
+    /// Rows play the part of u and columns of v, as in `Surface::grid`
+    pub fn add_grid(&mut self, grid: &[Vec<(f64, f64, f64)>], mode: SurfaceMode) {
+        match mode {
+            SurfaceMode::Points => {
+                for p in grid.iter().flatten() {
+                    self.append_edge(&[p.0, p.1, p.2, p.0, p.1, p.2]);
+                }
+            }
+            SurfaceMode::Wireframe => {
+                for (i, row) in grid.iter().enumerate() {
+                    for (j, &p) in row.iter().enumerate() {
+                        if let Some(&q) = grid.get(i + 1).and_then(|next| next.get(j)) {
+                            self.append_edge(&[p.0, p.1, p.2, q.0, q.1, q.2]);
+                        }
+                        if let Some(&q) = row.get(j + 1) {
+                            self.append_edge(&[p.0, p.1, p.2, q.0, q.1, q.2]);
+                        }
+                    }
+                }
+            }
+            SurfaceMode::Polygons => {
+                for w in grid.windows(2) {
+                    let (row, next) = (&w[0], &w[1]);
+                    for j in 1..row.len().min(next.len()) {
+                        let (p00, p10) = (row[j - 1], next[j - 1]);
+                        let (p01, p11) = (row[j], next[j]);
+                        for &(a, b, c) in [(p00, p10, p11), (p00, p11, p01)].iter() {
+                            if a != b && b != c && c != a {
+                                self.append_polygon(&[a.0, a.1, a.2, b.0, b.1, b.2, c.0, c.1, c.2]);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Add a sphere with center c `(x, y, z)` and radius `r`
+    ///
+    /// The sphere is a semicircle rotated a full turn about the x axis.
+    /// Triangles face outward.
+    /// # Arguments
+    /// `steps` - Number of steps along the semicircle and around the rotation
+    pub fn add_sphere(&mut self, c: (f64, f64, f64), r: f64, steps: usize, mode: SurfaceMode) {
+        let (x, y, z) = c;
+        let sphere = ParametricSurface::new(|u: f64, v: f64| {
+            // keep the poles and the seam exact, so their points are shared
+            let circ_sin = (u.min(1.0 - u) * consts::PI).sin();
+            let rot = (v % 1.0) * 2.0 * consts::PI;
+            (
+                r * (u * consts::PI).cos() + x,
+                r * circ_sin * rot.cos() + y,
+                r * circ_sin * rot.sin() + z,
+            )
+        });
+        self.add_surface(&sphere, steps, steps, mode);
+    }
+
+    /// Add a torus with center c `(x, y, z)` around the y axis, with a tube of radius `r0`
+    /// on a ring of radius `r1`
+    ///
+    /// The torus is a circle revolved a full turn. Triangles face outward.
+    /// # Arguments
+    /// `steps` - Number of steps around the circle and around the rotation
+    pub fn add_torus(&mut self, c: Vec3, r0: f64, r1: f64, steps: usize, mode: SurfaceMode) {
+        let circle: Vec<(f64, f64)> = (0..=steps)
+            .map(|i| {
+                // end where it started, so the seam is shared
+                let a = (i % steps) as f64 / steps as f64 * 2.0 * consts::PI;
+                (r1 + r0 * a.cos(), r0 * a.sin())
+            })
+            .collect();
+        let mut torus = Matrix::new(0, 4, vec![]);
+        torus.add_revolution(&circle, Axis::Y, steps, mode);
+        self.append_matrix(&torus.mul(&Matrix::mv(c.0, c.1, c.2)));
+    }
+
+    /// Revolve a profile in the xy plane a full turn about `axis`
+    ///
+    /// Triangles face outward when the axis is on the left of the profile,
+    /// as for a profile going up on the right of the y axis.
+    /// # Arguments
+    /// `steps` - Number of steps around the rotation
+    pub fn add_revolution(
+        &mut self,
+        profile: &[(f64, f64)],
+        axis: Axis,
+        steps: usize,
+        mode: SurfaceMode,
+    ) {
+        self.add_grid(&surfaces::revolve(profile, axis, steps), mode);
+    }
+
+    /// Revolve a curve in the xy plane, sampled according to this matrix's tessellation
+    pub fn add_curve_revolution(
+        &mut self,
+        curve: &impl Curve,
+        axis: Axis,
+        steps: usize,
+        mode: SurfaceMode,
+    ) {
+        let profile = self.sampling(0.001).sample(curve);
+        self.add_revolution(&profile, axis, steps, mode);
+    }
+
+    /// Extrude a closed shape in the xy plane by `depth` along `direction`
+    ///
+    /// Triangles face outward for a counter clockwise shape extruded toward +z
+    /// # Arguments
+    /// `caps` - Whether to close the ends, in polygon mode
+    pub fn add_extrusion(
+        &mut self,
+        shape: &[(f64, f64)],
+        direction: Vec3,
+        depth: f64,
+        caps: bool,
+        mode: SurfaceMode,
+    ) {
+        self.add_tube(shape, &surfaces::extrude(shape, direction, depth), caps, mode);
+    }
+
+    /// Extrude every closed subpath of a path, tessellated according to this matrix's tessellation
+    pub fn add_path_extrusion(
+        &mut self,
+        path: &Path,
+        direction: Vec3,
+        depth: f64,
+        caps: bool,
+        mode: SurfaceMode,
+    ) {
+        for (pts, closed) in path.to_polylines(&self.sampling(0.001)) {
+            if closed {
+                self.add_extrusion(&pts, direction, depth, caps, mode);
+            }
+        }
+    }
+
+    /// Sweep a closed shape along a 3d polyline `spine`, making a tube
+    ///
+    /// The shape's x and y axes stay normal to the spine, twisting as little as possible
+    /// # Arguments
+    /// `caps` - Whether to close the ends, in polygon mode
+    pub fn add_sweep(
+        &mut self,
+        shape: &[(f64, f64)],
+        spine: &[Vec3],
+        caps: bool,
+        mode: SurfaceMode,
+    ) {
+        self.add_tube(shape, &surfaces::sweep(shape, spine), caps, mode);
+    }
+
+    fn add_tube(
+        &mut self,
+        shape: &[(f64, f64)],
+        grid: &[Vec<Vec3>],
+        caps: bool,
+        mode: SurfaceMode,
+    ) {
+        self.add_grid(grid, mode);
+        if caps && mode == SurfaceMode::Polygons {
+            for [a, b, c] in surfaces::caps(shape, grid).iter() {
+                self.append_polygon(&[a.0, a.1, a.2, b.0, b.1, b.2, c.0, c.1, c.2]);
+            }
+        }
+    }
+
+    /// Add a heightmap as terrain, with triangles facing up toward +z
+    pub fn add_heightmap(&mut self, heightmap: &Heightmap, mode: SurfaceMode) {
+        self.add_grid(&heightmap.grid(), mode);
+    }
+
+    /// Add the contour lines of a heightmap at each of `levels`, each on the plane z = level
+    pub fn add_contours(&mut self, heightmap: &Heightmap, levels: &[f64]) {
+        for &level in levels.iter() {
+            for (p, q) in heightmap.contours(level) {
+                self.append_edge(&[p.0, p.1, level, q.0, q.1, level]);
+            }
+        }
+    }
+}
+
 // draw parametric
 impl Matrix {
     /// Add a parametric curve
@@ -278,52 +870,328 @@ impl Matrix {
     /// `y` - Function that takes in `t` from 0 to 1 and produces y
     /// `z` - The z value that the curve will be on
     /// `step` - Controls the precision of the curves
-    pub fn add_parametric<F1, F2>(&mut self, xf: F1, yf: F2, z: f64, step: f64)
+    /// `closed` - Whether the curve ends where it starts, so the last edge should snap to the first point
+    pub fn add_parametric<F1, F2>(&mut self, xf: F1, yf: F2, z: f64, step: f64, closed: bool)
     where
         F1: Fn(f64) -> f64,
         F2: Fn(f64) -> f64,
     {
         let p = Parametric::new(xf, yf);
-        for points in p.points_iter(step).collect::<Vec<(f64, f64)>>().windows(2) {
-            let (x0, y0) = points[0];
-            let (x1, y1) = points[1];
-            self.append_edge(&vec![x0, y0, z, x1, y1, z]);
+        let mut points: Vec<(f64, f64)> = p.points_iter(step).collect();
+        if closed && !points.is_empty() {
+            // the last sample lands near t = 1 only if step divides 1 (up to rounding)
+            let last_t = (points.len() - 1) as f64 * step;
+            let first = points[0];
+            if 1.0 - last_t < step / 2.0 {
+                *points.last_mut().unwrap() = first;
+            } else {
+                points.push(first);
+            }
+        }
+        self.add_polyline(&points, z);
+    }
+
+    /// Add any curve, sampled from t = 0 to 1 according to this matrix's tessellation, or every
+    /// 0.001 in t if it has none
+    /// # Arguments
+    /// `curve` - The curve to sample
+    /// `z` - The z value that the curve will be on
+    pub fn add_curve(&mut self, curve: &impl Curve, z: f64) {
+        let points = self.sampling(0.001).sample(curve);
+        self.add_polyline(&points, z);
+    }
+
+    /// Add a curve whose color follows `gradient` from t = 0 to 1
+    ///
+    /// Every point gets its color, so lines blend smoothly between points
+    pub fn add_curve_gradient(
+        &mut self,
+        curve: &impl Curve,
+        z: f64,
+        gradient: &Gradient,
+        along: Along,
+    ) {
+        let samples = self.sampling(0.001).sample_with_t(curve, 1.0);
+        let points: Vec<(f64, f64)> = samples.iter().map(|(_t, p)| *p).collect();
+        let colors: Vec<RGB> = match along {
+            Along::T => samples.iter().map(|(t, _p)| gradient.at(*t)).collect(),
+            Along::ArcLength => {
+                let total = polyline::length(&points);
+                let mut s = 0.0;
+                let mut colors = vec![gradient.at(0.0)];
+                for w in points.windows(2) {
+                    s += (w[1].0 - w[0].0).hypot(w[1].1 - w[0].1);
+                    colors.push(gradient.at(if total > 0.0 { s / total } else { 0.0 }));
+                }
+                colors
+            }
+        };
+        self.add_polyline_colors(&points, &colors, z);
+    }
+
+    /// Add edges connecting each point to the next on the plane `z`, with a color for each point
+    pub fn add_polyline_colors(&mut self, pts: &[(f64, f64)], colors: &[RGB], z: f64) {
+        assert_eq!(pts.len(), colors.len(), "Every point needs a color");
+        for i in 1..pts.len() {
+            self.add_polyline(&pts[i - 1..=i], z);
+            self.set_color(self.nrows - 2, Some(colors[i - 1]));
+            self.set_color(self.nrows - 1, Some(colors[i]));
+        }
+    }
+
+    /// Add a dashed curve, with the dash pattern measured along the curve
+    pub fn add_curve_dashed(&mut self, curve: &impl Curve, z: f64, dash: &Dash) {
+        let points = self.sampling(0.001).sample(curve);
+        for piece in dash.apply(&points) {
+            self.add_polyline(&piece, z);
+        }
+    }
+
+    /// Add a control polygon after `iterations` rounds of `smoothing`
+    ///
+    /// A closed polygon also gets the edge from its last point back to its first
+    pub fn add_smoothed_polyline(
+        &mut self,
+        pts: &[(f64, f64)],
+        smoothing: Smoothing,
+        iterations: usize,
+        closed: bool,
+        z: f64,
+    ) {
+        let points = smoothing.apply(pts, iterations, closed);
+        self.add_polyline(&points, z);
+    }
+
+    /// Add a curve that wobbles like it was drawn by hand, see `polyline::perturb_with_noise`
+    ///
+    /// The curve is resampled every 2 pixels so the noise has points to move
+    pub fn add_curve_sketched(
+        &mut self,
+        curve: &impl Curve,
+        z: f64,
+        amplitude: f64,
+        frequency: f64,
+        seed: u32,
+    ) {
+        let mut points = polyline::resample(&self.sampling(0.001).sample(curve), 2.0);
+        let n = points.len();
+        let closed = n > 2 && {
+            let (a, b) = (points[0], points[n - 1]);
+            (a.0 - b.0).hypot(a.1 - b.1) < 1e-9
+        };
+        if closed {
+            // sampling rarely lands exactly back on the start
+            points[n - 1] = points[0];
+        }
+        let points = polyline::perturb_with_noise(&points, amplitude, frequency, seed, closed);
+        self.add_polyline(&points, z);
+    }
+
+    /// Add edges connecting each point to the next on the plane `z`
+    pub fn add_polyline(&mut self, pts: &[(f64, f64)], z: f64) {
+        for w in pts.windows(2) {
+            self.append_edge(&[w[0].0, w[0].1, z, w[1].0, w[1].1, z]);
         }
     }
 
     /// Add a circle with center c `(x, y, z)` and radius `r`
+    ///
+    /// With a length based tessellation, the number of segments follows the circumference
     pub fn add_circle(&mut self, c: (f64, f64, f64), r: f64) {
+        self.add_circle_scaled(c, r, 1.0);
+    }
+
+    /// Add a circle that will be transformed by `trans` before it is drawn
+    ///
+    /// Sampling accounts for how much `trans` scales the circle on screen
+    pub fn add_circle_transformed(&mut self, c: (f64, f64, f64), r: f64, trans: &Matrix) {
+        self.add_circle_scaled(c, r, trans.max_scale().max(f64::EPSILON));
+    }
+
+    fn add_circle_scaled(&mut self, c: (f64, f64, f64), r: f64, scale: f64) {
         let (x, y, z) = c;
-        self.add_parametric(
+        let circle = Parametric::new(
             |t: f64| r * (t * 2.0 * consts::PI).cos() + x,
             |t: f64| r * (t * 2.0 * consts::PI).sin() + y,
-            z,
-            0.001,
         );
+        let mut points = self.sampling(0.001).sample_scaled(&circle, scale);
+        if points.len() < 9 {
+            // even tiny circles should not turn into triangles
+            points = Tessellation::Segments(8).sample(&circle);
+        }
+        // snap the end back onto the start, which rounding may have missed
+        let first = points[0];
+        *points.last_mut().unwrap() = first;
+        self.add_polyline(&points, z);
+    }
+
+    /// Add an epicycloid, traced by a point `d` from the center of a circle of radius `r`
+    /// rolling around the outside of a circle of radius `big_r` centered at c `(x, y, z)`
+    ///
+    /// The curve is traced for as many turns as it takes to close, up to 100.
+    /// `d == r` gives the cusped epicycloid; other values give epitrochoids.
+    pub fn add_epicycloid(&mut self, c: (f64, f64, f64), big_r: f64, r: f64, d: f64) {
+        let (x, y, z) = c;
+        let k = (big_r + r) / r;
+        let turns = utils::turns_to_close(big_r / r, 100) as f64;
+        let curve = Parametric::new(
+            |t: f64| {
+                let a = t * turns * 2.0 * consts::PI;
+                (big_r + r) * a.cos() - d * (k * a).cos() + x
+            },
+            |t: f64| {
+                let a = t * turns * 2.0 * consts::PI;
+                (big_r + r) * a.sin() - d * (k * a).sin() + y
+            },
+        );
+        self.add_closed_curve(&curve, z);
+    }
+
+    /// Add a hypocycloid, traced by a point `d` from the center of a circle of radius `r`
+    /// rolling around the inside of a circle of radius `big_r` centered at c `(x, y, z)`
+    ///
+    /// The curve is traced for as many turns as it takes to close, up to 100.
+    /// `d == r` gives the cusped hypocycloid; other values give the spirograph hypotrochoids.
+    pub fn add_hypocycloid(&mut self, c: (f64, f64, f64), big_r: f64, r: f64, d: f64) {
+        let (x, y, z) = c;
+        let k = (big_r - r) / r;
+        let turns = utils::turns_to_close(big_r / r, 100) as f64;
+        let curve = Parametric::new(
+            |t: f64| {
+                let a = t * turns * 2.0 * consts::PI;
+                (big_r - r) * a.cos() + d * (k * a).cos() + x
+            },
+            |t: f64| {
+                let a = t * turns * 2.0 * consts::PI;
+                (big_r - r) * a.sin() - d * (k * a).sin() + y
+            },
+        );
+        self.add_closed_curve(&curve, z);
     }
 
+    /// Add a curve that ends where it starts, snapping the last point onto the first
+    fn add_closed_curve(&mut self, curve: &impl Curve, z: f64) {
+        let mut points = self.sampling(0.001).sample(curve);
+        let first = points[0];
+        *points.last_mut().unwrap() = first;
+        self.add_polyline(&points, z);
+    }
 
     /// Add a cubic Bezier curve
     /// # Arguments
     /// `p[0-3]` - control points
     pub fn add_bezier3(&mut self, p0: (f64, f64), p1: (f64, f64), p2: (f64, f64), p3: (f64, f64)) {
-
-        let (ax, bx, cx, dx) = utils::compute_bezier3_coef(p0.0, p1.0, p2.0, p3.0);
-        let (ay, by, cy, dy) = utils::compute_bezier3_coef(p0.1, p1.1, p2.1, p3.1);
-        self.add_parametric(
-            |t: f64| ax * t*t*t + bx * t * t + cx * t + dx, 
-            |t: f64| ay * t*t*t + by * t * t + cy * t + dy, 
-            0.0, 0.001);
+        self.add_curve(&Bezier3::new(p0, p1, p2, p3), 0.0);
     }
 
     pub fn add_hermite3(&mut self, p0: (f64, f64), p1: (f64, f64), r0: (f64, f64), r1: (f64, f64)) {
-        
-        let (ax, bx, cx, dx) = utils::compute_hermite3_coef(p0.0, p1.0, r0.0, r1.0);
-        let (ay, by, cy, dy) = utils::compute_hermite3_coef(p0.1, p1.1, r0.1, r1.1);
-        self.add_parametric(
-            |t: f64| ax * t*t*t + bx * t * t + cx * t + dx, 
-            |t: f64| ay * t*t*t + by * t * t + cy * t + dy, 
-            0.0, 0.0001);
+        let points = self.sampling(0.0001).sample(&Hermite3::new(p0, p1, r0, r1));
+        self.add_polyline(&points, 0.0);
+    }
+
+    /// Add a chain of hermite curves through `points`, with tangents derived from neighboring points
+    /// # Arguments
+    /// `tension` - 0 for a Catmull-Rom spline, 1 for straight segments
+    pub fn add_hermite_spline(&mut self, points: &[(f64, f64)], tension: f64) {
+        let tangents = utils::cardinal_tangents(points, tension);
+        for i in 1..points.len() {
+            self.add_hermite3(points[i - 1], points[i], tangents[i - 1], tangents[i]);
+        }
+    }
+
+    /// Add the drawing of an L-system after `iterations` rewrites, on the plane `z`
+    /// # Arguments
+    /// `start` - Where the turtle starts
+    /// `heading_deg` - Where the turtle faces, counter clockwise from the x axis
+    pub fn add_lsystem(
+        &mut self,
+        lsys: &LSystem,
+        iterations: usize,
+        start: (f64, f64),
+        heading_deg: f64,
+        z: f64,
+    ) {
+        for (p0, p1) in lsys.generate(iterations, start, heading_deg) {
+            self.append_edge(&[p0.0, p0.1, z, p1.0, p1.1, z]);
+        }
+    }
+
+    /// Add the lines of a fractal curve on the plane `z`
+    ///
+    /// Use `Fractal::fit_to` first to place it on the canvas
+    pub fn add_fractal(&mut self, fractal: &Fractal, z: f64) {
+        for (p0, p1) in fractal.lines.iter() {
+            self.append_edge(&[p0.0, p0.1, z, p1.0, p1.1, z]);
+        }
+    }
+
+    /// Tessellate a path into edges on the plane `z`
+    pub fn add_path(&mut self, path: &Path, z: f64) {
+        for (pts, _closed) in path.to_polylines(&self.sampling(0.001)) {
+            self.add_polyline(&pts, z);
+        }
+    }
+
+    /// Add the outlines of `text` in `font`, with the baseline starting at `origin` `(x, y, z)`
+    /// # Arguments
+    /// `size` - Height of the em square in pixels
+    #[cfg(feature = "truetype")]
+    pub fn add_text(
+        &mut self,
+        font: &Font,
+        text: &str,
+        origin: (f64, f64, f64),
+        size: f64,
+    ) -> std::io::Result<()> {
+        let path = font.text_path(text, (origin.0, origin.1), size)?;
+        self.add_path(&path, origin.2);
+        Ok(())
+    }
+
+    /// Add the outlines of `text` laid along `curve`, on the plane `z`
+    /// # Arguments
+    /// `start` - Distance along the curve, in pixels, at which the text starts
+    #[cfg(feature = "truetype")]
+    pub fn add_text_on_curve(
+        &mut self,
+        font: &Font,
+        text: &str,
+        curve: &impl Curve,
+        start: f64,
+        size: f64,
+        z: f64,
+    ) -> std::io::Result<()> {
+        let path = font.text_on_curve(text, curve, start, size)?;
+        self.add_path(&path, z);
+        Ok(())
+    }
+
+    /// Fill every closed subpath of a path with triangles on the plane `z`
+    ///
+    /// Each subpath should be a simple polygon once tessellated; holes are not cut out.
+    /// Triangles face the viewer looking down the z axis.
+    pub fn add_path_polygons(&mut self, path: &Path, z: f64) {
+        for (pts, closed) in path.to_polylines(&self.sampling(0.001)) {
+            if !closed {
+                continue;
+            }
+            let clockwise = polygon::signed_area(&pts) < 0.0;
+            for [a, b, c] in polygon::triangulate(&pts) {
+                let (b, c) = if clockwise { (c, b) } else { (b, c) };
+                self.append_polygon(&[a.0, a.1, z, b.0, b.1, z, c.0, c.1, z]);
+            }
+        }
+    }
+
+    /// Tessellate a path into dashed edges
+    ///
+    /// The pattern runs continuously over all segments of each subpath
+    pub fn add_path_dashed(&mut self, path: &Path, z: f64, dash: &Dash) {
+        for (pts, _closed) in path.to_polylines(&self.sampling(0.001)) {
+            for piece in dash.apply(&pts) {
+                self.add_polyline(&piece, z);
+            }
+        }
     }
 }
 
@@ -358,8 +1226,7 @@ mod tests {
         let mut m = Matrix::new(0, 4, vec![]);
         println!("m: {}", m);
         println!("Adding (1, 2, 4) and (5, 6, 7) to empty matrix",);
-        m.append_edge(&mut vec![1.0, 2.0, 4.0]);
-        m.append_edge(&mut vec![5.0, 6.0, 7.0]);
+        m.append_edge(&[1.0, 2.0, 4.0, 5.0, 6.0, 7.0]);
         println!("m: {}", m);
         assert!(
             matrix_equal(
@@ -370,6 +1237,183 @@ mod tests {
         );
     }
 
+    #[test]
+    fn circle_is_closed() {
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.add_circle((250.0, 250.0, 0.0), 100.0);
+        let rows: Vec<&[f64]> = m.iter_by_row().collect();
+        assert_eq!(rows[0], rows[rows.len() - 1]);
+    }
+
+    #[test]
+    fn curves_keep_their_steps_until_a_tessellation_is_set() {
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.add_bezier3((0.0, 0.0), (0.0, 10.0), (10.0, 10.0), (10.0, 0.0));
+        m.add_hermite3((0.0, 0.0), (10.0, 0.0), (5.0, 5.0), (5.0, -5.0));
+        // two rows per edge
+        assert_eq!(2 * (1000 + 10000), m.nrows);
+        assert_eq!(None, m.tessellation());
+
+        m.set_tessellation(Tessellation::Segments(10));
+        let mut scaled = m.mul(&Matrix::scale(2.0, 2.0, 1.0));
+        scaled.add_hermite3((0.0, 0.0), (10.0, 0.0), (5.0, 5.0), (5.0, -5.0));
+        assert_eq!(m.nrows + 20, scaled.nrows);
+    }
+
+    #[test]
+    fn circle_segments_follow_size() {
+        let mut small = Matrix::new(0, 4, vec![]);
+        small.set_tessellation(Tessellation::MaxSegmentLength(2.0));
+        let (mut large, mut scaled) = (small.clone(), small.clone());
+        small.add_circle((0.0, 0.0, 0.0), 2.0);
+        large.add_circle((0.0, 0.0, 0.0), 200.0);
+        // two rows per edge
+        assert_eq!(8, small.nrows / 2);
+        assert!(large.nrows / 2 > 600 && large.nrows / 2 < 700);
+
+        scaled.add_circle_transformed((0.0, 0.0, 0.0), 2.0, &Matrix::scale(100.0, 100.0, 1.0));
+        assert_eq!(large.nrows, scaled.nrows);
+    }
+
+    #[test]
+    fn spirograph_closes() {
+        assert_eq!(3, utils::turns_to_close(5.0 / 3.0, 100));
+        assert_eq!(1, utils::turns_to_close(4.0, 100));
+        assert_eq!(7, utils::turns_to_close(2.5 / 3.5, 100));
+
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.add_hypocycloid((0.0, 0.0, 0.0), 5.0, 3.0, 5.0);
+        let rows: Vec<&[f64]> = m.iter_by_row().collect();
+        assert_eq!(rows[0], rows[rows.len() - 1]);
+        // the pen starts at R - r + d along the x axis
+        assert!((rows[0][0] - 7.0).abs() < 1e-9 && rows[0][1].abs() < 1e-9);
+    }
+
+    #[test]
+    fn surface_grid_counts() {
+        let plane = ParametricSurface::new(|u, v| (u * 10.0, v * 10.0, 0.0));
+        let mut wire = Matrix::new(0, 4, vec![]);
+        wire.add_surface_wireframe(&plane, 4, 3);
+        // 5 lines of 3 edges plus 4 lines of 4 edges
+        assert_eq!(2 * (5 * 3 + 4 * 4), wire.nrows);
+
+        let cone = ParametricSurface::new(|u, v| {
+            let a = u * 2.0 * consts::PI;
+            (v * a.cos(), v * a.sin(), v)
+        });
+        let mut polys = Matrix::new(0, 4, vec![]);
+        polys.add_surface_polygons(&cone, 8, 2);
+        // the triangles touching the tip collapse
+        assert_eq!(3 * (8 * 2 * 2 - 8), polys.nrows);
+    }
+
+    #[test]
+    fn box_faces_point_out() {
+        let mut edges = Matrix::new(0, 4, vec![]);
+        edges.add_box((0.0, 0.0, 0.0), 2.0, 2.0, 2.0);
+        assert_eq!(24, edges.nrows);
+
+        let mut polys = Matrix::new(0, 4, vec![]);
+        polys.add_box_polygons((0.0, 0.0, 0.0), 2.0, 2.0, 2.0);
+        assert_eq!(36, polys.nrows);
+        let rows: Vec<&[f64]> = polys.iter_by_row().collect();
+        for tri in rows.chunks(3) {
+            let (a, b, c) = (tri[0], tri[1], tri[2]);
+            let u = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+            let v = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+            let n = [
+                u[1] * v[2] - u[2] * v[1],
+                u[2] * v[0] - u[0] * v[2],
+                u[0] * v[1] - u[1] * v[0],
+            ];
+            // the box is centered on (1, -1, -1)
+            let out = [a[0] - 1.0, a[1] + 1.0, a[2] + 1.0];
+            assert!(n[0] * out[0] + n[1] * out[1] + n[2] * out[2] > 0.0);
+        }
+    }
+
+    #[test]
+    fn torus_faces_point_out() {
+        let mut polys = Matrix::new(0, 4, vec![]);
+        polys.add_torus((1.0, 2.0, 3.0), 1.0, 4.0, 12, SurfaceMode::Polygons);
+        let rows: Vec<&[f64]> = polys.iter_by_row().collect();
+        assert!(!rows.is_empty());
+        for (tri, n) in rows.chunks(3).zip(polys.polygon_normals()) {
+            // from the middle of the tube nearest the triangle
+            let (x, y, z) = (tri[0][0] - 1.0, tri[0][1] - 2.0, tri[0][2] - 3.0);
+            let ring = 4.0 / x.hypot(z);
+            let out = (x - x * ring, y, z - z * ring);
+            assert!(vector::dot(n, out) > 0.0, "{:?} {:?}", tri, n);
+        }
+    }
+
+    #[test]
+    fn sphere_faces_point_out() {
+        let mut points = Matrix::new(0, 4, vec![]);
+        points.add_sphere((1.0, 2.0, 3.0), 5.0, 10, SurfaceMode::Points);
+        assert_eq!(2 * 11 * 11, points.nrows);
+        for row in points.iter_by_row() {
+            let d = (row[0] - 1.0).hypot(row[1] - 2.0).hypot(row[2] - 3.0);
+            assert!((d - 5.0).abs() < 1e-9);
+        }
+
+        let mut polys = Matrix::new(0, 4, vec![]);
+        polys.add_sphere((0.0, 0.0, 0.0), 5.0, 10, SurfaceMode::Polygons);
+        let rows: Vec<&[f64]> = polys.iter_by_row().collect();
+        for tri in rows.chunks(3) {
+            let (a, b, c) = (tri[0], tri[1], tri[2]);
+            let u = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+            let v = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+            let n = [
+                u[1] * v[2] - u[2] * v[1],
+                u[2] * v[0] - u[0] * v[2],
+                u[0] * v[1] - u[1] * v[0],
+            ];
+            assert!(n[0] * a[0] + n[1] * a[1] + n[2] * a[2] > 0.0);
+        }
+    }
+
+    #[test]
+    fn culling_keeps_front_half() {
+        let mut polys = Matrix::new(0, 4, vec![]);
+        polys.add_box_polygons((0.0, 0.0, 0.0), 1.0, 1.0, 1.0);
+        let front = polys.cull_backfaces((0.0, 0.0, 1.0));
+        // only the two triangles of the front face look straight at the viewer
+        assert_eq!(6, front.nrows);
+        assert!(front.iter_by_row().all(|r| r[2] == 0.0));
+        // seen from a corner, three faces show
+        assert_eq!(18, polys.cull_backfaces((1.0, 1.0, 1.0)).nrows);
+    }
+
+    #[test]
+    fn transform_point_matches_mul() {
+        let t = Matrix::rotatez(30.0)
+            .mul(&Matrix::scale(2.0, 3.0, 4.0))
+            .mul(&Matrix::mv(1.0, 2.0, 3.0));
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.append_edge(&[1.0, 2.0, 3.0, -4.0, 5.0, 6.0]);
+        m.bake_transform(&t);
+        let p = t.transform_point((-4.0, 5.0, 6.0));
+        let row: Vec<f64> = m.row_iter(1).cloned().collect();
+        assert!((row[0] - p.0).abs() < 1e-9);
+        assert!((row[1] - p.1).abs() < 1e-9);
+        assert!((row[2] - p.2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn fit_to_canvas() {
+        let mut m = Matrix::new(0, 4, vec![]);
+        assert!(m.bounds().is_none());
+        m.append_edge(&[-3.0, 1.0, 2.0, 1.0, 2.0, -2.0]);
+        assert_eq!(Some(((-3.0, 1.0, -2.0), (1.0, 2.0, 2.0))), m.bounds());
+
+        m.bake_transform(&m.fit_to(500.0, 300.0, 10.0));
+        let ((x0, y0, _), (x1, y1, _)) = m.bounds().unwrap();
+        // as wide as the canvas allows, and centered
+        assert!((x0 - 10.0).abs() < 1e-9 && (x1 - 490.0).abs() < 1e-9);
+        assert!((y0 - 90.0).abs() < 1e-9 && (y1 - 210.0).abs() < 1e-9);
+    }
+
     #[test]
     fn multiply_with_method() {
         let m1 = Matrix::new(2, 3, vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
@@ -433,4 +1477,77 @@ mod tests {
             "1 x 1 matrix edge case"
         );
     }
+
+    #[test]
+    fn gradient_along_curve() {
+        // speeds up along x, so t and distance disagree
+        let curve = Parametric::new(|t| 100.0 * t * t, |_t| 0.0);
+        let gradient = Gradient::even(&[RGB::RED, RGB::BLUE]);
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.set_tessellation(Tessellation::Segments(10));
+        m.add_curve_gradient(&curve, 0.0, &gradient, Along::T);
+        assert_eq!(20, m.nrows);
+        assert_eq!(Some(RGB::RED), m.color(0));
+        // the end of the fifth edge is at t = 0.5, a quarter of the way along
+        assert_eq!(Some(RGB::RED.lerp(RGB::BLUE, 0.5)), m.color(9));
+        assert_eq!(Some(RGB::BLUE), m.color(19));
+
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.set_tessellation(Tessellation::Segments(10));
+        m.add_curve_gradient(&curve, 0.0, &gradient, Along::ArcLength);
+        assert_eq!(Some(RGB::RED.lerp(RGB::BLUE, 0.25)), m.color(9));
+    }
+
+    #[test]
+    fn hermite_splines_pass_through_their_points() {
+        let points = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)];
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.add_hermite_spline(&points, 0.0);
+        // 3 curves with as many edges each
+        let rows = m.nrows / 3;
+        assert_eq!(3 * rows, m.nrows);
+        for (i, p) in points.iter().enumerate() {
+            let row = (i * rows).min(m.nrows - 1);
+            let (x, y) = (m.get(row, 0).unwrap(), m.get(row, 1).unwrap());
+            assert!((x - p.0).abs() < 1e-9 && (y - p.1).abs() < 1e-9, "{:?} at {}", p, i);
+        }
+    }
+
+    #[test]
+    fn smoothed_polylines_close_when_asked() {
+        let square = [(0.0, 0.0), (8.0, 0.0), (8.0, 8.0), (0.0, 8.0)];
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.add_smoothed_polyline(&square, Smoothing::Chaikin, 1, false, 0.0);
+        // 8 points, 7 edges
+        assert_eq!(14, m.nrows);
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.add_smoothed_polyline(&square, Smoothing::Chaikin, 1, true, 0.0);
+        assert_eq!(16, m.nrows);
+        assert_eq!((m.get(0, 0), m.get(0, 1)), (m.get(15, 0), m.get(15, 1)));
+        let mut m = Matrix::new(0, 4, vec![]);
+        let four_point = Smoothing::FourPoint { tension: 1.0 / 16.0 };
+        m.add_smoothed_polyline(&square, four_point, 1, true, 0.0);
+        assert_eq!(16, m.nrows);
+        assert_eq!((Some(8.0), Some(0.0)), (m.get(4, 0), m.get(4, 1)));
+    }
+
+    #[test]
+    fn sketched_curves_stay_near_the_curve() {
+        let circle = Parametric::new(|t| 50.0 * (consts::TAU * t).cos(), |t| {
+            50.0 * (consts::TAU * t).sin()
+        });
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.add_curve_sketched(&circle, 0.0, 4.0, 0.05, 3);
+        let mut again = Matrix::new(0, 4, vec![]);
+        again.add_curve_sketched(&circle, 0.0, 4.0, 0.05, 3);
+        assert_eq!(m.nrows, again.nrows);
+        for row in 0..m.nrows {
+            let (x, y) = (m.get(row, 0).unwrap(), m.get(row, 1).unwrap());
+            assert_eq!((Some(x), Some(y)), (again.get(row, 0), again.get(row, 1)));
+            assert!((x.hypot(y) - 50.0).abs() <= 4.0 + 1e-9, "({}, {}) is off the circle", x, y);
+        }
+        // still closed
+        let last = m.nrows - 1;
+        assert_eq!((m.get(0, 0), m.get(0, 1)), (m.get(last, 0), m.get(last, 1)));
+    }
 }
@@ -0,0 +1,679 @@
+#![allow(dead_code)]
+
+//! MDL, the scene language of the course, with a stack of coordinate systems, lit shapes
+//! and knobs for animation
+//!
+//! A file is read in three steps: every line is split into `Token`s, the definitions of
+//! constants, lights and knobs go into a `SymbolTable`, and everything else becomes an `Op`.
+//! Names are checked against the table before anything is drawn, and coordinate systems
+//! against the `save_coord_system`s before them, so a misspelled name is reported by its
+//! line. Comments start with `//`.
+//!
+//! ```text
+//! constants shiny_red 0.2 0.8 0.5  0.1 0.1 0.5  0.1 0.1 0.5
+//! light sun 255 255 255 1 1 1
+//! push
+//! move 250 250 0
+//! rotate y 30 spin
+//! sphere shiny_red 0 0 0 100
+//! frames 24
+//! basename ball
+//! vary spin 0 23 0 1
+//! ```
+//!
+//! Every shape is drawn on its own as soon as it comes, transformed by the top of the stack.
+//! With more than one frame, each frame is drawn with the knobs where `vary` puts them and
+//! saved in the `anim` directory, and `display` and `save` are skipped.
+
+use std::collections::{HashMap, HashSet};
+use std::io;
+
+use super::animation::{FrameSequence, FrameSink};
+use super::colors::RGB;
+use super::matrix::Matrix;
+use super::obj;
+use super::parametrics::SurfaceMode;
+use super::scene::Geometry;
+use super::script::Axis;
+use super::shading::{Light, Material, Shading};
+use super::vector::Vec3;
+use super::PPMImg;
+
+/// Steps around spheres and tori
+const STEPS: usize = 24;
+
+/// A word or a number on a line
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    Number(f64),
+    Word(String),
+}
+
+/// Split a line into tokens, up to a `//` comment
+pub fn tokenize(line: &str) -> Vec<Token> {
+    let code = line.split("//").next().unwrap_or("");
+    code.split_whitespace()
+        .map(|w| match w.parse() {
+            Ok(n) => Token::Number(n),
+            Err(_) => Token::Word(w.to_string()),
+        })
+        .collect()
+}
+
+/// What a name stands for
+#[derive(Clone, Debug, PartialEq)]
+pub enum Symbol {
+    /// What shapes are made of, from `constants`
+    Constants(Material),
+    /// A light, from `light`
+    Light(Light),
+    /// A knob to scale transformations by, with its value outside of animations
+    Knob(f64),
+}
+
+/// Every name defined in a file
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SymbolTable {
+    pub symbols: HashMap<String, Symbol>,
+}
+
+impl SymbolTable {
+    pub fn get(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.get(name)
+    }
+
+    pub fn constants(&self, name: &str) -> Option<Material> {
+        match self.get(name) {
+            Some(Symbol::Constants(m)) => Some(*m),
+            _ => None,
+        }
+    }
+
+    /// The lights, in no particular order
+    pub fn lights(&self) -> Vec<Light> {
+        self.symbols
+            .values()
+            .filter_map(|s| match s {
+                Symbol::Light(l) => Some(*l),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// How a value changes over the frames of an animation
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Vary {
+    pub frames: (usize, usize),
+    pub values: (f64, f64),
+}
+
+/// One operation, in the order they are run
+#[derive(Clone, Debug, PartialEq)]
+pub enum Op {
+    /// Copy the top of the stack onto the stack
+    Push,
+    Pop,
+    /// Transformations, scaled by a knob if they name one, applied to the top of the stack
+    Move { by: Vec3, knob: Option<String> },
+    Scale { by: Vec3, knob: Option<String> },
+    Rotate { axis: Axis, degrees: f64, knob: Option<String> },
+    /// Keep the top of the stack under a name, for shapes to be drawn in later
+    SaveCoordSystem(String),
+    Sphere { constants: Option<String>, center: Vec3, r: f64, coords: Option<String> },
+    Torus { constants: Option<String>, center: Vec3, r0: f64, r1: f64, coords: Option<String> },
+    /// A box from its top left front corner, as for `Matrix::add_box_polygons`
+    Box { constants: Option<String>, corner: Vec3, size: Vec3, coords: Option<String> },
+    Line { constants: Option<String>, p0: Vec3, p1: Vec3, coords: Option<String> },
+    /// The faces of an OBJ file
+    Mesh { constants: Option<String>, filepath: String, coords: Option<String> },
+    /// How shapes are shaded from now on; None draws them as wireframes
+    Shading(Option<Shading>),
+    Display,
+    Save(String),
+}
+
+/// A whole MDL file, ready to run
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Mdl {
+    pub ops: Vec<Op>,
+    pub symbols: SymbolTable,
+    /// Ambient light, from `ambient`
+    pub ambient: Option<RGB>,
+    /// How many frames to draw, 1 unless set by `frames`
+    pub frames: usize,
+    /// Start of the names of the frames, from `basename`
+    pub basename: Option<String>,
+    /// Every `vary`, by the knob it moves
+    pub varies: Vec<(String, Vary)>,
+}
+
+/// Read an MDL file, see `read_mdl`
+pub fn open_mdl(filepath: &str) -> io::Result<Mdl> {
+    read_mdl(&std::fs::read_to_string(filepath)?)
+}
+
+/// Read the symbols and operations of an MDL file
+pub fn read_mdl(text: &str) -> io::Result<Mdl> {
+    let mut mdl = Mdl {
+        frames: 1,
+        ..Mdl::default()
+    };
+    // lines of the operations, to report names that are used but never defined
+    let mut lines = vec![];
+    for (n, line) in text.lines().enumerate() {
+        let tokens = tokenize(line);
+        let mut args = Args {
+            tokens: &tokens,
+            at: 1,
+            line: n,
+        };
+        let command = match tokens.first() {
+            Some(Token::Word(w)) => w.as_str(),
+            Some(Token::Number(_)) => return Err(args.invalid("starts with a number")),
+            None => continue,
+        };
+        let constants = |args: &mut Args| args.word();
+        let op = match command {
+            "push" => Some(Op::Push),
+            "pop" => Some(Op::Pop),
+            "move" | "scale" => {
+                let by = args.vector()?;
+                let knob = args.word();
+                Some(if command == "move" {
+                    Op::Move { by, knob }
+                } else {
+                    Op::Scale { by, knob }
+                })
+            }
+            "rotate" => {
+                let axis = match args.word().as_deref() {
+                    Some("x") => Axis::X,
+                    Some("y") => Axis::Y,
+                    Some("z") => Axis::Z,
+                    _ => return Err(args.invalid("needs an axis of x, y or z")),
+                };
+                let degrees = args.number()?;
+                let knob = args.word();
+                Some(Op::Rotate { axis, degrees, knob })
+            }
+            "save_coord_system" => Some(Op::SaveCoordSystem(args.name()?)),
+            "sphere" => Some(Op::Sphere {
+                constants: constants(&mut args),
+                center: args.vector()?,
+                r: args.number()?,
+                coords: args.word(),
+            }),
+            "torus" => Some(Op::Torus {
+                constants: constants(&mut args),
+                center: args.vector()?,
+                r0: args.number()?,
+                r1: args.number()?,
+                coords: args.word(),
+            }),
+            "box" => Some(Op::Box {
+                constants: constants(&mut args),
+                corner: args.vector()?,
+                size: args.vector()?,
+                coords: args.word(),
+            }),
+            "line" => Some(Op::Line {
+                constants: constants(&mut args),
+                p0: args.vector()?,
+                p1: args.vector()?,
+                coords: args.word(),
+            }),
+            "mesh" => {
+                let constants = constants(&mut args);
+                let filepath = args.name()?;
+                Some(Op::Mesh {
+                    constants,
+                    filepath: filepath.trim_start_matches(':').to_string(),
+                    coords: args.word(),
+                })
+            }
+            "constants" => {
+                let name = args.name()?;
+                let material = args.constants()?;
+                mdl.symbols.symbols.insert(name, Symbol::Constants(material));
+                None
+            }
+            "light" => {
+                let name = args.name()?;
+                let color = args.color()?;
+                let direction = args.vector()?;
+                let light = Light::Directional {
+                    direction,
+                    color,
+                    shadows: false,
+                };
+                mdl.symbols.symbols.insert(name, Symbol::Light(light));
+                None
+            }
+            "ambient" => {
+                mdl.ambient = Some(args.color()?);
+                None
+            }
+            "shading" => match args.name()?.as_str() {
+                "flat" => Some(Op::Shading(Some(Shading::Flat))),
+                "gouraud" => Some(Op::Shading(Some(Shading::Gouraud))),
+                "phong" => Some(Op::Shading(Some(Shading::Phong))),
+                "wireframe" => Some(Op::Shading(None)),
+                _ => return Err(args.invalid("needs flat, gouraud, phong or wireframe")),
+            },
+            "frames" => {
+                mdl.frames = args.count()?;
+                None
+            }
+            "basename" => {
+                mdl.basename = Some(args.name()?);
+                None
+            }
+            "vary" => {
+                let knob = args.name()?;
+                let frames = (args.count()?, args.count()?);
+                let values = (args.number()?, args.number()?);
+                if frames.0 > frames.1 {
+                    return Err(args.invalid("needs the first frame to come before the last"));
+                }
+                mdl.symbols.symbols.entry(knob.clone()).or_insert(Symbol::Knob(1.0));
+                mdl.varies.push((knob, Vary { frames, values }));
+                None
+            }
+            "set" => {
+                let knob = args.name()?;
+                let value = args.number()?;
+                mdl.symbols.symbols.insert(knob, Symbol::Knob(value));
+                None
+            }
+            "setknobs" => {
+                let value = args.number()?;
+                for symbol in mdl.symbols.symbols.values_mut() {
+                    if let Symbol::Knob(v) = symbol {
+                        *v = value;
+                    }
+                }
+                None
+            }
+            "display" => Some(Op::Display),
+            "save" => Some(Op::Save(args.name()?)),
+            _ => return Err(args.invalid(&format!("has an unknown command `{}`", command))),
+        };
+        args.finish()?;
+        if let Some(op) = op {
+            mdl.ops.push(op);
+            lines.push(n);
+        }
+    }
+
+    let mut saved = HashSet::new();
+    for (op, n) in mdl.ops.iter().zip(lines) {
+        if let Op::SaveCoordSystem(name) = op {
+            saved.insert(name.as_str());
+        }
+        let (constants, knob, coords) = op.names();
+        let undefined = constants
+            .filter(|c| mdl.symbols.constants(c).is_none())
+            .map(|c| ("constants", c))
+            .or_else(|| {
+                knob.filter(|k| !matches!(mdl.symbols.get(k), Some(Symbol::Knob(_))))
+                    .map(|k| ("knob", k))
+            })
+            .or_else(|| coords.filter(|c| !saved.contains(c)).map(|c| ("coordinate system", c)));
+        if let Some((kind, name)) = undefined {
+            let message = format!(
+                "Line {} of the MDL file uses {} `{}`, which is not defined",
+                n + 1,
+                kind,
+                name
+            );
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+        }
+    }
+    for (knob, vary) in mdl.varies.iter() {
+        if vary.frames.1 >= mdl.frames {
+            let message = format!("`{}` is varied past the last of {} frames", knob, mdl.frames);
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+        }
+    }
+    Ok(mdl)
+}
+
+/// The arguments after the command on a line
+struct Args<'a> {
+    tokens: &'a [Token],
+    at: usize,
+    line: usize,
+}
+
+impl Args<'_> {
+    fn invalid(&self, what: &str) -> io::Error {
+        let message = format!("Line {} of the MDL file {}", self.line + 1, what);
+        io::Error::new(io::ErrorKind::InvalidData, message)
+    }
+
+    /// The next token if it is a word, for optional names
+    fn word(&mut self) -> Option<String> {
+        match self.tokens.get(self.at) {
+            Some(Token::Word(w)) => {
+                self.at += 1;
+                Some(w.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn name(&mut self) -> io::Result<String> {
+        self.word().ok_or_else(|| self.invalid("needs a name"))
+    }
+
+    fn number(&mut self) -> io::Result<f64> {
+        match self.tokens.get(self.at) {
+            Some(Token::Number(x)) => {
+                self.at += 1;
+                Ok(*x)
+            }
+            _ => Err(self.invalid("needs more numbers")),
+        }
+    }
+
+    fn count(&mut self) -> io::Result<usize> {
+        let x = self.number()?;
+        if x < 0.0 || x.fract() != 0.0 {
+            return Err(self.invalid("needs a whole number"));
+        }
+        Ok(x as usize)
+    }
+
+    fn vector(&mut self) -> io::Result<Vec3> {
+        Ok((self.number()?, self.number()?, self.number()?))
+    }
+
+    fn color(&mut self) -> io::Result<RGB> {
+        let (r, g, b) = self.vector()?;
+        let channel = |c: f64| c.round().clamp(0.0, 255.0) as u16;
+        Ok(RGB::new(channel(r), channel(g), channel(b)))
+    }
+
+    /// Ambient, diffuse and specular for red, then green, then blue, and maybe a color
+    ///
+    /// A material has one of each for all channels, so they are averaged, and the diffuse
+    /// ones tint the color instead, unless a color is given.
+    fn constants(&mut self) -> io::Result<Material> {
+        let mut k = [[0.0; 3]; 3];
+        for channel in k.iter_mut() {
+            *channel = [self.number()?, self.number()?, self.number()?];
+        }
+        let [ambient, diffuse, specular] = [0, 1, 2].map(|i| [k[0][i], k[1][i], k[2][i]]);
+        let kd = diffuse.iter().copied().fold(0.0, f64::max);
+        let color = if self.at < self.tokens.len() {
+            self.color()?
+        } else {
+            let tint = |d: f64| if kd > 0.0 { (d / kd * 255.0).round() as u16 } else { 255 };
+            RGB::new(tint(diffuse[0]), tint(diffuse[1]), tint(diffuse[2]))
+        };
+        let mean = |k: [f64; 3]| (k[0] + k[1] + k[2]) / 3.0;
+        Ok(Material {
+            ka: mean(ambient),
+            kd,
+            ks: mean(specular),
+            shininess: 8.0,
+            color,
+        })
+    }
+
+    fn finish(&self) -> io::Result<()> {
+        if self.at < self.tokens.len() {
+            return Err(self.invalid("has too many arguments"));
+        }
+        Ok(())
+    }
+}
+
+impl Op {
+    /// The names of the constants, the knob and the coordinate system the operation uses,
+    /// if it uses any
+    fn names(&self) -> (Option<&str>, Option<&str>, Option<&str>) {
+        match self {
+            Op::Move { knob, .. } | Op::Scale { knob, .. } | Op::Rotate { knob, .. } => {
+                (None, knob.as_deref(), None)
+            }
+            Op::Sphere { constants, coords, .. }
+            | Op::Torus { constants, coords, .. }
+            | Op::Box { constants, coords, .. }
+            | Op::Line { constants, coords, .. }
+            | Op::Mesh { constants, coords, .. } => (constants.as_deref(), None, coords.as_deref()),
+            _ => (None, None, None),
+        }
+    }
+}
+
+impl Mdl {
+    /// The value of every knob in frame `frame`
+    ///
+    /// Knobs keep the value they were set to, except over the frames a `vary` spans, where
+    /// they go evenly from its first value to its last.
+    pub fn knobs(&self, frame: usize) -> HashMap<String, f64> {
+        let mut knobs: HashMap<String, f64> = self
+            .symbols
+            .symbols
+            .iter()
+            .filter_map(|(name, s)| match s {
+                Symbol::Knob(v) => Some((name.clone(), *v)),
+                _ => None,
+            })
+            .collect();
+        for (knob, vary) in self.varies.iter() {
+            let (f0, f1) = vary.frames;
+            if (f0..=f1).contains(&frame) {
+                let t = if f1 > f0 { (frame - f0) as f64 / (f1 - f0) as f64 } else { 0.0 };
+                knobs.insert(knob.clone(), vary.values.0 + (vary.values.1 - vary.values.0) * t);
+            }
+        }
+        knobs
+    }
+
+    /// The lights shapes are lit by: the ambient light and every light, or
+    /// `Light::standard` if there are none
+    pub fn lights(&self) -> Vec<Light> {
+        let mut lights = self.symbols.lights();
+        if lights.is_empty() && self.ambient.is_none() {
+            return Light::standard();
+        }
+        lights.extend(self.ambient.map(Light::Ambient));
+        lights
+    }
+
+    /// Run the file on a 500 by 500 image with y going up and a depth buffer, as in the course
+    pub fn run(&self) -> io::Result<()> {
+        let mut img = PPMImg::new(500, 500, 255);
+        img.set_world_bounds(0.0, 0.0, 499.0, 499.0);
+        img.depth_test(true);
+        self.run_on(&mut img)
+    }
+
+    /// Run the file on `img`, drawing every frame in turn if it is animated
+    pub fn run_on(&self, img: &mut PPMImg) -> io::Result<()> {
+        if self.frames <= 1 {
+            return self.draw_frame(img, 0);
+        }
+        let basename = self.basename.as_deref().unwrap_or("frame");
+        let frames = FrameSequence::new("anim", basename, "png", true)?;
+        self.animate(img, Box::new(frames))
+    }
+
+    /// Draw every frame on a clear `img` in turn, sending each to `sink`
+    pub fn animate(&self, img: &mut PPMImg, mut sink: Box<dyn FrameSink>) -> io::Result<()> {
+        for frame in 0..self.frames {
+            img.clear();
+            self.draw_frame(img, frame)?;
+            sink.add_frame(img)?;
+        }
+        sink.finish()
+    }
+
+    /// Draw one frame of the file on `img`, over what is there
+    pub fn draw_frame(&self, img: &mut PPMImg, frame: usize) -> io::Result<()> {
+        let knobs = self.knobs(frame);
+        let knob = |name: &Option<String>| name.as_ref().map_or(1.0, |k| knobs[k]);
+        let lights = self.lights();
+        let mut stack = vec![Matrix::ident(4)];
+        let mut coord_systems: HashMap<&str, Matrix> = HashMap::new();
+        let mut shading = Some(Shading::Flat);
+        for op in self.ops.iter() {
+            let top = stack.last().expect("The stack always keeps its bottom");
+            let t = match op {
+                Op::Push => {
+                    stack.push(top.clone());
+                    continue;
+                }
+                Op::Pop => {
+                    if stack.len() > 1 {
+                        stack.pop();
+                    }
+                    continue;
+                }
+                Op::Move { by, knob: k } => {
+                    let s = knob(k);
+                    Matrix::mv(by.0 * s, by.1 * s, by.2 * s)
+                }
+                Op::Scale { by, knob: k } => {
+                    let s = knob(k);
+                    Matrix::scale(by.0 * s, by.1 * s, by.2 * s)
+                }
+                Op::Rotate { axis, degrees, knob: k } => {
+                    let degrees = degrees * knob(k);
+                    match axis {
+                        Axis::X => Matrix::rotatex(degrees),
+                        // the y matrix turns the other way from the x and z ones
+                        Axis::Y => Matrix::rotatey(-degrees),
+                        Axis::Z => Matrix::rotatez(degrees),
+                    }
+                }
+                Op::SaveCoordSystem(name) => {
+                    coord_systems.insert(name, top.clone());
+                    continue;
+                }
+                Op::Shading(s) => {
+                    shading = *s;
+                    continue;
+                }
+                Op::Display => {
+                    if self.frames <= 1 {
+                        img.display()?;
+                    }
+                    continue;
+                }
+                Op::Save(filepath) => {
+                    if self.frames <= 1 {
+                        img.save_as(filepath)?;
+                    }
+                    continue;
+                }
+                shape => {
+                    let (mut geometry, constants, coords) = self.shape(shape)?;
+                    // coordinate systems are checked to be saved before they are used
+                    let t = coords.map_or(top, |c| &coord_systems[c.as_str()]);
+                    geometry.bake_transform(t);
+                    let material = constants.and_then(|c| self.symbols.constants(c));
+                    match shading {
+                        Some(shading) => {
+                            let material = material.unwrap_or_default();
+                            img.shade_geometry(&geometry, &material, &lights, shading)
+                        }
+                        None => img.render_geometry(&geometry, true),
+                    }
+                    continue;
+                }
+            };
+            // transformations apply to shapes in the coordinate system they are given in
+            let top = stack.last_mut().expect("The stack always keeps its bottom");
+            *top = t.mul(top);
+        }
+        Ok(())
+    }
+
+    /// The geometry of a shape, with the names of its constants and coordinate system
+    fn shape<'a>(
+        &self,
+        op: &'a Op,
+    ) -> io::Result<(Geometry, Option<&'a String>, Option<&'a String>)> {
+        let mut m = Matrix::new(0, 4, vec![]);
+        let (constants, coords) = match op {
+            Op::Sphere { constants, center, r, coords } => {
+                m.add_sphere(*center, *r, STEPS, SurfaceMode::Polygons);
+                (constants, coords)
+            }
+            Op::Torus { constants, center, r0, r1, coords } => {
+                m.add_torus(*center, *r0, *r1, STEPS, SurfaceMode::Polygons);
+                (constants, coords)
+            }
+            Op::Box { constants, corner, size, coords } => {
+                m.add_box_polygons(*corner, size.0, size.1, size.2);
+                (constants, coords)
+            }
+            Op::Line { constants, p0, p1, coords } => {
+                m.append_edge(&[p0.0, p0.1, p0.2, p1.0, p1.1, p1.2]);
+                return Ok((Geometry::Edges(m), constants.as_ref(), coords.as_ref()));
+            }
+            Op::Mesh { constants, filepath, coords } => {
+                let mesh = obj::open_obj(filepath)?;
+                return Ok((Geometry::Mesh(mesh), constants.as_ref(), coords.as_ref()));
+            }
+            _ => unreachable!("Only shapes have geometry"),
+        };
+        Ok((Geometry::Polygons(m), constants.as_ref(), coords.as_ref()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scenes_read_and_draw() {
+        let text = "\
+            // a red ball and a spinning box\n\
+            constants red 0.2 1 0  0 0 0  0 0 0\n\
+            light sun 255 255 255 0 0 1\n\
+            push\n\
+            move 100 100 0\n\
+            sphere red 0 0 0 50\n\
+            pop\n\
+            push\n\
+            rotate z 90 spin\n\
+            box 300 300 0 50 50 50\n\
+            frames 5\n\
+            vary spin 0 4 0 1\n";
+        let mdl = read_mdl(text).unwrap();
+        assert_eq!(7, mdl.ops.len());
+        assert_eq!(Some(0.5), mdl.knobs(2).get("spin").copied());
+        let red = mdl.symbols.constants("red").unwrap();
+        assert_eq!((RGB::RED, 1.0), (red.color, red.kd));
+
+        let mut img = PPMImg::new(500, 500, 255);
+        img.set_world_bounds(0.0, 0.0, 499.0, 499.0);
+        mdl.draw_frame(&mut img, 0).unwrap();
+        let ball = img.pixel(100, 499 - 100).unwrap();
+        assert!(ball.red > 200 && ball.green == 0, "{:?}", ball);
+        assert_eq!(Some(RGB::WHITE), img.pixel(320, 499 - 280));
+        // turned a quarter turn about the origin by the last frame, off the image
+        img.clear();
+        mdl.draw_frame(&mut img, 4).unwrap();
+        assert_eq!(Some(RGB::BLACK), img.pixel(320, 499 - 280));
+
+        let error = read_mdl("sphere blue 0 0 0 1\n").unwrap_err();
+        assert_eq!(
+            "Line 1 of the MDL file uses constants `blue`, which is not defined",
+            error.to_string()
+        );
+        let error = read_mdl("sphere 0 0 0 1 tilted\nsave_coord_system tilted\n").unwrap_err();
+        assert_eq!(
+            "Line 1 of the MDL file uses coordinate system `tilted`, which is not defined",
+            error.to_string()
+        );
+        assert!(read_mdl("save_coord_system tilted\nsphere 0 0 0 1 tilted\n").is_ok());
+        let error = read_mdl("push\nmove 1 2\n").unwrap_err();
+        assert_eq!("Line 2 of the MDL file needs more numbers", error.to_string());
+    }
+}
@@ -0,0 +1,479 @@
+#![allow(dead_code)]
+
+use std::collections::{HashMap, HashSet};
+
+use super::colors::{Gradient, RGB};
+use super::matrix::Matrix;
+use super::vector::{self, Vec3};
+
+/// A triangle mesh that stores each vertex once, with faces indexing into the vertex list
+///
+/// Faces are wound counter clockwise when seen from the front, as in a polygon matrix.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Mesh {
+    pub vertices: Vec<Vec3>,
+    pub faces: Vec<[usize; 3]>,
+    /// Normals the mesh is shaded with, see `corner_normals`
+    pub normals: Normals,
+    /// Edges kept sharp whatever the angle between their faces, by the indices of their ends
+    pub hard_edges: Vec<[usize; 2]>,
+    /// Colors of the vertices, blended across the faces when filled; uncolored vertices, and
+    /// vertices past the end, are drawn in the image's `fg_color`
+    pub colors: Vec<Option<RGB>>,
+}
+
+/// Where the normals at the corners of the faces of a mesh come from, for smooth shading
+#[derive(Clone, Debug, PartialEq)]
+pub enum Normals {
+    /// Every corner takes the normal of its face, for a faceted look
+    Faceted,
+    /// Normals are averaged across edges, except hard edges and edges where the faces meet
+    /// at more than `crease` degrees, so a cylinder has smooth sides and sharp rims
+    Smooth { crease: f64 },
+    /// Given normals, one for every corner of every face, as read from OBJ files
+    Explicit(Vec<[Vec3; 3]>),
+}
+
+impl Default for Normals {
+    fn default() -> Self {
+        Normals::Smooth { crease: 60.0 }
+    }
+}
+
+impl Mesh {
+    pub fn new() -> Self {
+        Mesh::default()
+    }
+
+    /// Add a vertex, returning its index
+    pub fn add_vertex(&mut self, v: Vec3) -> usize {
+        self.vertices.push(v);
+        self.vertices.len() - 1
+    }
+
+    pub fn add_face(&mut self, a: usize, b: usize, c: usize) {
+        assert!(
+            a.max(b).max(c) < self.vertices.len(),
+            "Face refers to a missing vertex"
+        );
+        self.faces.push([a, b, c]);
+        let vertices = &self.vertices;
+        if let Normals::Explicit(normals) = &mut self.normals {
+            let [a, b, c] = [a, b, c].map(|i| vertices[i]);
+            normals.push([vector::normalize(vector::triangle_normal(a, b, c)); 3]);
+        }
+    }
+
+    /// Color of vertex `i`, if it has one
+    pub fn vertex_color(&self, i: usize) -> Option<RGB> {
+        self.colors.get(i).copied().flatten()
+    }
+
+    pub fn set_vertex_color(&mut self, i: usize, color: Option<RGB>) {
+        assert!(i < self.vertices.len(), "Index out of bound");
+        if self.colors.len() <= i {
+            self.colors.resize(i + 1, None);
+        }
+        self.colors[i] = color;
+    }
+
+    /// Color every vertex by where it is
+    pub fn color_vertices(&mut self, color: impl Fn(Vec3) -> RGB) {
+        self.colors = self.vertices.iter().map(|&v| Some(color(v))).collect();
+    }
+
+    /// Color every vertex by its height along z, from the lowest at 0 to the highest at 1, as
+    /// for the terrain of a heightmap
+    pub fn color_by_height(&mut self, gradient: &Gradient) {
+        let (lo, hi) = self
+            .vertices
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| (lo.min(v.2), hi.max(v.2)));
+        let range = if hi > lo { hi - lo } else { 1.0 };
+        self.color_vertices(|v| gradient.at((v.2 - lo) / range));
+    }
+
+    /// Keep the edge between vertices `a` and `b` sharp when shading
+    pub fn mark_hard_edge(&mut self, a: usize, b: usize) {
+        self.hard_edges.push([a, b]);
+    }
+
+    /// Build a mesh from a polygon matrix, merging vertices at exactly the same position
+    ///
+    /// Merged vertices keep the color of the first of their points.
+    pub fn from_polygons(polygons: &Matrix) -> Self {
+        let mut mesh = Mesh::new();
+        let mut seen = HashMap::new();
+        let mut face = [0; 3];
+        for (i, row) in polygons.iter_by_row().enumerate() {
+            let v = (row[0], row[1], row[2]);
+            face[i % 3] = *seen.entry(vertex_key(v)).or_insert_with(|| {
+                let added = mesh.add_vertex(v);
+                if let Some(color) = polygons.color(i) {
+                    mesh.set_vertex_color(added, Some(color));
+                }
+                added
+            });
+            if i % 3 == 2 {
+                mesh.faces.push(face);
+            }
+        }
+        mesh
+    }
+
+    /// Expand the mesh back into a polygon matrix, three points per triangle
+    pub fn to_polygons(&self) -> Matrix {
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.add_mesh(self);
+        m
+    }
+
+    /// Transform every vertex by `t`, in place
+    pub fn bake_transform(&mut self, t: &Matrix) {
+        for v in self.vertices.iter_mut() {
+            *v = t.transform_point(*v);
+        }
+        if let Normals::Explicit(normals) = &mut self.normals {
+            for n in normals.iter_mut().flatten() {
+                *n = t.transform_normal(*n);
+            }
+        }
+    }
+
+    /// Unit normal of every face
+    pub fn compute_face_normals(&self) -> Vec<Vec3> {
+        self.faces_iter()
+            .map(|[a, b, c]| vector::normalize(vector::triangle_normal(a, b, c)))
+            .collect()
+    }
+
+    /// Unit normal at every vertex, averaging the normals of the faces around it weighted by area
+    ///
+    /// Vertices that no face uses get a zero normal
+    pub fn compute_vertex_normals(&self) -> Vec<Vec3> {
+        let mut normals = vec![(0.0, 0.0, 0.0); self.vertices.len()];
+        for (f, [a, b, c]) in self.faces.iter().zip(self.faces_iter()) {
+            // the cross product is already as long as twice the area
+            let n = vector::triangle_normal(a, b, c);
+            for &i in f.iter() {
+                normals[i] = vector::add(normals[i], n);
+            }
+        }
+        normals.into_iter().map(vector::normalize).collect()
+    }
+
+    /// Unit normal at every corner of every face, as set by `normals`
+    pub fn corner_normals(&self) -> Vec<[Vec3; 3]> {
+        let crease = match &self.normals {
+            Normals::Explicit(normals) => return normals.clone(),
+            Normals::Faceted => {
+                return self.compute_face_normals().into_iter().map(|n| [n; 3]).collect();
+            }
+            Normals::Smooth { crease } => crease.to_radians().cos(),
+        };
+        let units = self.compute_face_normals();
+        let edge_key = |a: usize, b: usize| (a.min(b), a.max(b));
+        let hard: HashSet<_> = self.hard_edges.iter().map(|&[a, b]| edge_key(a, b)).collect();
+        let mut around = vec![vec![]; self.vertices.len()];
+        for (f, face) in self.faces.iter().enumerate() {
+            for &v in face.iter() {
+                around[v].push(f);
+            }
+        }
+
+        let mut normals = vec![[(0.0, 0.0, 0.0); 3]; self.faces.len()];
+        for (v, fs) in around.iter().enumerate() {
+            // faces around v joined across smooth edges share one normal there
+            let mut group: Vec<usize> = (0..fs.len()).collect();
+            fn root(group: &mut [usize], mut i: usize) -> usize {
+                while group[i] != i {
+                    group[i] = group[group[i]];
+                    i = group[i];
+                }
+                i
+            }
+            for i in 0..fs.len() {
+                for j in i + 1..fs.len() {
+                    let (f, g) = (self.faces[fs[i]], self.faces[fs[j]]);
+                    let shared = f.iter().find(|&&w| w != v && g.contains(&w));
+                    let smooth = shared.is_some_and(|&w| !hard.contains(&edge_key(v, w)))
+                        && vector::dot(units[fs[i]], units[fs[j]]) >= crease;
+                    if smooth {
+                        let (ri, rj) = (root(&mut group, i), root(&mut group, j));
+                        group[ri] = rj;
+                    }
+                }
+            }
+            // weighted by the angle of each face at v, so that how the faces are cut into
+            // triangles does not matter
+            let mut sums = vec![(0.0, 0.0, 0.0); fs.len()];
+            for (i, &f) in fs.iter().enumerate() {
+                let others: Vec<Vec3> = self.faces[f]
+                    .iter()
+                    .filter(|&&u| u != v)
+                    .map(|&u| vector::normalize(vector::sub(self.vertices[u], self.vertices[v])))
+                    .collect();
+                let angle = match others.as_slice() {
+                    &[a, b] => vector::dot(a, b).clamp(-1.0, 1.0).acos(),
+                    _ => 0.0,
+                };
+                let r = root(&mut group, i);
+                sums[r] = vector::add(sums[r], vector::scale(units[f], angle));
+            }
+            for (i, &f) in fs.iter().enumerate() {
+                let corner = self.faces[f].iter().position(|&u| u == v).unwrap();
+                normals[f][corner] = vector::normalize(sums[root(&mut group, i)]);
+            }
+        }
+        normals
+    }
+
+    /// Iterate over the corners of every face
+    pub fn faces_iter(&self) -> impl Iterator<Item = [Vec3; 3]> + '_ {
+        self.faces
+            .iter()
+            .map(move |f| [self.vertices[f[0]], self.vertices[f[1]], self.vertices[f[2]]])
+    }
+}
+
+// subdivision
+impl Mesh {
+    /// Refine the mesh with Loop subdivision, splitting every triangle into 4 per iteration
+    ///
+    /// The result approaches a smooth surface. Boundary edges follow the boundary curve,
+    /// and vertices where the mesh is not a manifold stay put.
+    pub fn loop_subdivide(&self, iterations: usize) -> Mesh {
+        let mut mesh = self.clone();
+        for _ in 0..iterations {
+            mesh = mesh.loop_step();
+        }
+        mesh
+    }
+
+    fn loop_step(&self) -> Mesh {
+        let edge_key = |a: usize, b: usize| (a.min(b), a.max(b));
+        // for every edge, the corners of the faces across it
+        let mut opposite: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        for &[a, b, c] in self.faces.iter() {
+            opposite.entry(edge_key(a, b)).or_default().push(c);
+            opposite.entry(edge_key(b, c)).or_default().push(a);
+            opposite.entry(edge_key(c, a)).or_default().push(b);
+        }
+        let mut neighbors = vec![vec![]; self.vertices.len()];
+        let mut boundary = vec![vec![]; self.vertices.len()];
+        for (&(a, b), across) in opposite.iter() {
+            neighbors[a].push(b);
+            neighbors[b].push(a);
+            if across.len() == 1 {
+                boundary[a].push(b);
+                boundary[b].push(a);
+            }
+        }
+
+        let sum = |ids: &[usize]| {
+            ids.iter()
+                .fold((0.0, 0.0, 0.0), |acc, &i| vector::add(acc, self.vertices[i]))
+        };
+        let mut next = Mesh::new();
+        next.colors = self.colors.clone();
+        for (i, &v) in self.vertices.iter().enumerate() {
+            let moved = match (boundary[i].len(), neighbors[i].len()) {
+                (0, 0) => v,
+                (0, n) => {
+                    let beta = if n == 3 { 3.0 / 16.0 } else { 3.0 / (8.0 * n as f64) };
+                    vector::add(
+                        vector::scale(v, 1.0 - n as f64 * beta),
+                        vector::scale(sum(&neighbors[i]), beta),
+                    )
+                }
+                (2, _) => vector::add(
+                    vector::scale(v, 0.75),
+                    vector::scale(sum(&boundary[i]), 0.125),
+                ),
+                _ => v,
+            };
+            next.add_vertex(moved);
+        }
+
+        // new vertices are numbered in the order their edges are first met, to stay deterministic
+        let mut midpoints = HashMap::new();
+        let mut midpoint = |next: &mut Mesh, a: usize, b: usize| -> usize {
+            *midpoints.entry(edge_key(a, b)).or_insert_with(|| {
+                let ends = vector::add(self.vertices[a], self.vertices[b]);
+                let p = match opposite[&edge_key(a, b)].as_slice() {
+                    &[c, d] => vector::add(
+                        vector::scale(ends, 0.375),
+                        vector::scale(sum(&[c, d]), 0.125),
+                    ),
+                    _ => vector::scale(ends, 0.5),
+                };
+                let added = next.add_vertex(p);
+                let color = match (self.vertex_color(a), self.vertex_color(b)) {
+                    (Some(ca), Some(cb)) => Some(ca.lerp(cb, 0.5)),
+                    (ca, cb) => ca.or(cb),
+                };
+                if color.is_some() {
+                    next.set_vertex_color(added, color);
+                }
+                added
+            })
+        };
+        for &[a, b, c] in self.faces.iter() {
+            let ab = midpoint(&mut next, a, b);
+            let bc = midpoint(&mut next, b, c);
+            let ca = midpoint(&mut next, c, a);
+            next.faces.push([a, ab, ca]);
+            next.faces.push([ab, b, bc]);
+            next.faces.push([ca, bc, c]);
+            next.faces.push([ab, bc, ca]);
+        }
+        // given normals no longer fit, but hard edges are split in two
+        if !matches!(self.normals, Normals::Explicit(_)) {
+            next.normals = self.normals.clone();
+        }
+        for &[a, b] in self.hard_edges.iter() {
+            if let Some(&ab) = midpoints.get(&edge_key(a, b)) {
+                next.hard_edges.extend([[a, ab], [ab, b]]);
+            }
+        }
+        next
+    }
+}
+
+/// Hashable key for merging vertices at exactly the same position
+pub(crate) fn vertex_key(v: Vec3) -> (u64, u64, u64) {
+    // -0.0 and 0.0 should be the same vertex, and adding 0.0 turns one into the other
+    ((v.0 + 0.0).to_bits(), (v.1 + 0.0).to_bits(), (v.2 + 0.0).to_bits())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::parametrics::SurfaceMode;
+
+    #[test]
+    fn box_shares_corners() {
+        let mut polys = Matrix::new(0, 4, vec![]);
+        polys.add_box_polygons((0.0, 0.0, 0.0), 1.0, 2.0, 3.0);
+        let mesh = Mesh::from_polygons(&polys);
+        assert_eq!(8, mesh.vertices.len());
+        assert_eq!(12, mesh.faces.len());
+
+        for (n, [a, b, c]) in mesh.compute_face_normals().iter().zip(mesh.faces_iter()) {
+            // each face normal is along one axis, pointing away from the center (0.5, -1, -1.5)
+            assert!((vector::length(*n) - 1.0).abs() < 1e-9);
+            let center = vector::scale(vector::add(a, vector::add(b, c)), 1.0 / 3.0);
+            assert!(vector::dot(*n, vector::sub(center, (0.5, -1.0, -1.5))) > 0.0);
+        }
+
+        let back = mesh.to_polygons();
+        let rows: Vec<&[f64]> = polys.iter_by_row().collect();
+        assert_eq!(rows, back.iter_by_row().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn sphere_vertex_normals_point_out() {
+        let mut polys = Matrix::new(0, 4, vec![]);
+        polys.add_sphere((0.0, 0.0, 0.0), 3.0, 20, SurfaceMode::Polygons);
+        let mesh = Mesh::from_polygons(&polys);
+        // the seam and the poles are shared
+        assert_eq!(20 * 19 + 2, mesh.vertices.len());
+        for (v, n) in mesh.vertices.iter().zip(mesh.compute_vertex_normals()) {
+            assert!(vector::dot(vector::normalize(*v), n) > 0.99);
+        }
+    }
+
+    #[test]
+    fn loop_subdivision_smooths_tetrahedron() {
+        let mut tetra = Mesh::new();
+        let corners = [(1.0, 1.0, 1.0), (1.0, -1.0, -1.0), (-1.0, 1.0, -1.0), (-1.0, -1.0, 1.0)];
+        for &v in corners.iter() {
+            tetra.add_vertex(v);
+        }
+        for &[a, b, c] in [[0, 1, 2], [0, 3, 1], [0, 2, 3], [1, 3, 2]].iter() {
+            tetra.add_face(a, b, c);
+        }
+        let fine = tetra.loop_subdivide(2);
+        assert_eq!(4 * 16, fine.faces.len());
+        // euler characteristic of a sphere: v - e + f = 2, with e = 3f / 2
+        assert_eq!(2 + 3 * 64 / 2 - 64, fine.vertices.len());
+        // the corners are pulled in, but the shape stays centered
+        let center = fine.vertices.iter().fold((0.0, 0.0, 0.0), |a, v| vector::add(a, *v));
+        assert!(vector::length(center) < 1e-9);
+        assert!(fine.vertices.iter().all(|v| vector::length(*v) < 3f64.sqrt() - 0.1));
+    }
+
+    #[test]
+    fn creases_keep_rims_sharp() {
+        // a six sided prism along z, with caps
+        let n = 6;
+        let mut prism = Mesh::new();
+        for z in [0.0, 1.0] {
+            for i in 0..n {
+                let a = (i as f64 * 60.0).to_radians();
+                prism.add_vertex((a.cos(), a.sin(), z));
+            }
+        }
+        let bottom = prism.add_vertex((0.0, 0.0, 0.0));
+        let top = prism.add_vertex((0.0, 0.0, 1.0));
+        for i in 0..n {
+            let j = (i + 1) % n;
+            prism.add_face(i, j, n + j);
+            prism.add_face(i, n + j, n + i);
+            prism.add_face(bottom, j, i);
+            prism.add_face(top, n + i, n + j);
+        }
+        let at = |mesh: &Mesh, v: usize| -> Vec<Vec3> {
+            let normals = mesh.corner_normals();
+            let corners = mesh.faces.iter().copied().zip(normals);
+            let mine = |(f, n): ([usize; 3], [Vec3; 3])| {
+                (0..3).filter(move |&k| f[k] == v).map(move |k| n[k])
+            };
+            corners.flat_map(mine).collect()
+        };
+        let close = |a: Vec3, b: Vec3| vector::length(vector::sub(a, b)) < 1e-9;
+
+        // smooth around the sides, sharp at the rim
+        let normals = at(&prism, 0);
+        assert_eq!(5, normals.len());
+        let (side, cap) = ((1.0, 0.0, 0.0), (0.0, 0.0, -1.0));
+        assert_eq!(3, normals.iter().filter(|&&n| close(n, side)).count());
+        assert_eq!(2, normals.iter().filter(|&&n| close(n, cap)).count());
+
+        prism.mark_hard_edge(0, n);
+        let normals = at(&prism, 0);
+        assert!(!normals.iter().any(|&n| close(n, side)));
+        prism.normals = Normals::Smooth { crease: 100.0 };
+        assert!(!at(&prism, 0).iter().any(|&n| close(n, cap)));
+
+        // given normals turn with the mesh, staying at right angles to it
+        let s = 0.5f64.sqrt();
+        prism.normals = Normals::Explicit(vec![[(s, s, 0.0); 3]; prism.faces.len()]);
+        prism.bake_transform(&Matrix::scale(2.0, 1.0, 1.0));
+        let turned = prism.corner_normals()[0][0];
+        assert!(close(vector::normalize((1.0, 2.0, 0.0)), turned), "{:?}", turned);
+    }
+
+    #[test]
+    fn vertex_colors_follow_the_mesh() {
+        let mut polys = Matrix::new(0, 4, vec![]);
+        polys.set_pen(Some(RGB::RED));
+        polys.append_polygon(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+        polys.set_pen(Some(RGB::BLUE));
+        polys.append_polygon(&[1.0, 0.0, 0.0, 1.0, 1.0, 2.0, 0.0, 1.0, 0.0]);
+        let mesh = Mesh::from_polygons(&polys);
+        assert_eq!(4, mesh.vertices.len());
+        assert_eq!(Some(RGB::RED), mesh.vertex_color(1));
+        assert_eq!(Some(RGB::BLUE), mesh.vertex_color(3));
+        assert_eq!(Some(RGB::BLUE), mesh.to_polygons().color(4));
+
+        let finer = mesh.loop_subdivide(1);
+        assert_eq!(finer.vertices.len(), finer.colors.len());
+        assert!(finer.colors.contains(&Some(RGB::RED.lerp(RGB::BLUE, 0.5))));
+
+        let mut terrain = mesh;
+        terrain.color_by_height(&Gradient::even(&[RGB::GREEN, RGB::WHITE]));
+        assert_eq!(Some(RGB::GREEN), terrain.vertex_color(0));
+        assert_eq!(Some(RGB::WHITE), terrain.vertex_color(3));
+    }
+}
@@ -1,18 +1,73 @@
 #![allow(dead_code)]
 
+pub mod animation;
+pub mod bitmap_font;
+pub mod bmp;
+pub mod clip;
 pub mod colors;
+pub mod csg;
+pub mod curves;
+pub mod dash;
+pub mod diff;
+pub mod dither;
+pub mod effects;
+pub mod expr;
+pub mod fitting;
+#[cfg(feature = "truetype")]
+pub mod font;
+pub mod fractals;
+pub mod gif;
+pub mod hdr;
+pub mod heightmap;
+pub mod intersect;
+pub mod layers;
+pub mod lsystem;
 pub mod matrix;
+pub mod mdl;
+pub mod mesh;
+pub mod obj;
+pub mod overdraw;
+pub mod palette;
 pub mod parametrics;
-pub mod parser;
+pub mod path;
+pub mod ply;
+pub mod png;
+pub mod polygon;
+pub mod polyline;
+#[cfg(feature = "preview")]
+pub mod preview;
+pub mod raytrace;
+pub mod resize;
+pub mod scene;
+pub mod script;
+pub mod shading;
+pub mod stroke;
+pub mod surfaces;
+pub mod svg;
+pub mod target;
+pub mod terminal;
+pub mod tessellation;
+pub mod tga;
+pub mod tiles;
 pub mod utils;
+pub mod vector;
+pub mod video;
+#[cfg(feature = "preview")]
+pub mod viewer;
 
+use rayon::prelude::*;
 use std::convert::Into;
 use std::convert::TryInto;
 
 use std::io::{self, prelude::Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Fills with fewer pixels than this are not worth spreading across threads
+const PARALLEL_FILL_PIXELS: i32 = 1 << 16;
 
 // re-exports
-pub use colors::{HSL, RGB};
+pub use colors::{BlendMode, HSL, RGB, RGBA};
 pub use matrix::Matrix;
 
 // internal use
@@ -22,13 +77,42 @@ pub struct PPMImg {
     height: u32,
     width: u32,
     depth: u16, // max = 2^16
-    pub x_wrap: bool,
-    pub y_wrap: bool,
+    /// What happens to pixels drawn past the left and right edges, see `OutOfBounds`
+    pub x_bounds: OutOfBounds,
+    /// What happens to pixels drawn past the top and bottom edges
+    pub y_bounds: OutOfBounds,
     pub fg_color: RGB,
     pub bg_color: RGB,
+    /// Opacity of everything drawn, from 0 to 1; below 1, drawing blends with what is there
+    pub fg_alpha: f64,
+    /// Blend and average colors in linear light, treating the stored colors as sRGB
+    ///
+    /// Anti-aliased edges and translucent colors come out as bright as they should, instead of too
+    /// dark. Colors are still stored and written as sRGB.
+    pub linear_light: bool,
+    /// How drawing combines with what is there, at the opacity of `fg_alpha`
+    pub blend_mode: BlendMode,
+    /// Fades edges and outlines towards a color with depth, see `Fog`
+    pub fog: Option<Fog>,
     data: Vec<RGB>,
+    /// Opacity of every pixel, for images that start out transparent
+    alpha: Option<Vec<f64>>,
+    /// Scale and offset from world to pixel coordinates, (sx, ox, sy, oy)
+    viewport: Option<(f64, f64, f64, f64)>,
+    /// Samples per pixel along each side, 1 unless the image is supersampled
+    samples: u32,
+    /// What `clear` goes back to, when the background is an image
+    background: Option<Vec<RGB>>,
+    /// How many times each pixel has been drawn, while counting, see `count_writes`
+    writes: Option<Vec<u32>>,
+    /// Depth of the nearest surface filled at each pixel, while depth testing, see
+    /// `depth_test`
+    zbuffer: Option<Vec<f64>>,
 }
 
+/// The image being drawn on
+pub type Canvas = PPMImg;
+
 // impl constructor and exporter
 impl PPMImg {
     /// Createa new PPMImg
@@ -42,199 +126,596 @@ impl PPMImg {
             height,
             width,
             depth,
-            x_wrap: false,
-            y_wrap: false,
+            x_bounds: OutOfBounds::Clip,
+            y_bounds: OutOfBounds::Clip,
             fg_color: RGB::gray(depth),
             bg_color,
+            fg_alpha: 1.0,
+            linear_light: false,
+            blend_mode: BlendMode::Normal,
+            fog: None,
+            alpha: None,
+            viewport: None,
+            samples: 1,
+            background: None,
+            writes: None,
+            zbuffer: None,
             data: vec![bg_color; (width * height).try_into().unwrap()],
         }
     }
 
-    pub fn write_binary(&self, filepath: &str) -> io::Result<()> {
-        let mut file = create_file(filepath);
-        writeln!(file, "P6")?;
-        writeln!(file, "{} {} {}", self.width, self.height, self.depth)?;
-        if self.depth < 256 {
-            for t in self.data.iter() {
-                file.write(&[t.green as u8])?;
-                file.write(&[t.green as u8])?;
-                file.write(&[t.blue as u8])?;
+    /// Create a new PPMImg where nothing has been drawn yet, so every pixel is see through
+    ///
+    /// Drawing builds up the opacity of each pixel, for compositing with `RGBA::over`.
+    /// Saving keeps only the colors.
+    pub fn new_transparent(height: u32, width: u32, depth: u16) -> PPMImg {
+        let mut img = Self::new(height, width, depth);
+        img.alpha = Some(vec![0.0; img.data.len()]);
+        img
+    }
+
+    /// Create a new PPMImg that draws at `samples` times the resolution along each side, and is
+    /// box filtered down to `width` by `height` when it is written
+    ///
+    /// Lines, fills and strokes take coordinates in output pixels, and come out anti-aliased.
+    /// Anything that works on single pixels, like `plot`, `pixel` and flood fills, works on
+    /// samples instead, as do `width` and `height`.
+    pub fn new_supersampled(height: u32, width: u32, depth: u16, samples: u32) -> PPMImg {
+        assert!(samples > 0, "Need at least one sample per pixel");
+        let mut img = Self::new(height * samples, width * samples, depth);
+        img.samples = samples;
+        img
+    }
+
+    /// The image at its output size, averaging each block of samples into one pixel
+    pub fn downsample(&self) -> PPMImg {
+        let s = self.samples;
+        let (width, height) = (self.width / s, self.height / s);
+        let mut out = PPMImg::new_with_bg(height, width, self.depth, self.bg_color);
+        out.linear_light = self.linear_light;
+        let mut out_alpha = vec![];
+        let n = (s * s) as f64;
+        for y in 0..height {
+            for x in 0..width {
+                let block = (0..s * s).map(|i| {
+                    (((y * s + i / s) * self.width + x * s + i % s) as usize, 1.0 / n)
+                });
+                let (color, alpha) = self.average(block);
+                if let Some(color) = color {
+                    out.data[(y * width + x) as usize] = color;
+                }
+                out_alpha.push(alpha);
             }
-        } else {
-            for t in self.data.iter() {
-                file.write_all(&(t.red.to_be_bytes()))?;
-                file.write_all(&(t.green.to_be_bytes()))?;
-                file.write_all(&(t.blue.to_be_bytes()))?;
+        }
+        if self.alpha.is_some() {
+            out.alpha = Some(out_alpha);
+        }
+        out
+    }
+
+    /// Weighted average of the pixels at some indices, with their total weight times opacity
+    ///
+    /// With transparency, each pixel counts as much as it is opaque; the color is None when
+    /// nothing is there. Averages in linear light when `linear_light` is set.
+    fn average(&self, pixels: impl Iterator<Item = (usize, f64)>) -> (Option<RGB>, f64) {
+        let (mut sum, mut weight) = ([0.0; 3], 0.0);
+        for (index, k) in pixels {
+            let k = k * self.alpha.as_ref().map_or(1.0, |alpha| alpha[index]);
+            let c = self.data[index];
+            let channels = if self.linear_light {
+                c.to_linear(self.depth)
+            } else {
+                [c.red as f64, c.green as f64, c.blue as f64]
+            };
+            for (s, c) in sum.iter_mut().zip(channels.iter()) {
+                *s += c * k;
             }
+            weight += k;
         }
+        if weight <= 0.0 {
+            return (None, weight);
+        }
+        let mean = sum.map(|s| s / weight);
+        let color = if self.linear_light {
+            RGB::from_linear(mean, self.depth)
+        } else {
+            let [r, g, b] = mean.map(|c| c.round() as u16);
+            RGB::new(r, g, b)
+        };
+        (Some(color), weight)
+    }
 
-        file.flush()?;
-        Ok(())
+    /// Saves the image as a binary P6 ppm
+    pub fn save(&self, filepath: &str) -> io::Result<()> {
+        self.write_binary(filepath)
+    }
+
+    pub fn write_binary(&self, filepath: &str) -> io::Result<()> {
+        let mut file = create_file(filepath)?;
+        self.write_p6(&mut file)?;
+        file.flush()
+    }
+
+    /// Writes a binary P6 ppm, with two bytes per channel when `depth` is over 255
+    pub fn write_p6(&self, w: &mut impl Write) -> io::Result<()> {
+        if self.samples > 1 {
+            return self.downsample().write_p6(w);
+        }
+        writeln!(w, "P6")?;
+        writeln!(w, "{} {} {}", self.width, self.height, self.depth)?;
+        let wide = self.depth > 255;
+        let mut bytes = Vec::with_capacity(self.data.len() * if wide { 6 } else { 3 });
+        for t in self.data.iter() {
+            for &c in [t.red, t.green, t.blue].iter() {
+                if wide {
+                    bytes.extend_from_slice(&c.to_be_bytes());
+                } else {
+                    bytes.push(c as u8);
+                }
+            }
+        }
+        w.write_all(&bytes)
     }
+
+    /// Writes a plain text P3 ppm, which is slow and big but easy to read
     pub fn write_ascii(&self, filepath: &str) -> io::Result<()> {
-        let mut file = create_file(filepath);
-        writeln!(file, "P3")?;
-        writeln!(file, "{} {} {}", self.width, self.height, self.depth)?;
+        let mut file = create_file(filepath)?;
+        self.write_p3(&mut file)?;
+        file.flush()
+    }
+
+    pub fn write_p3(&self, w: &mut impl Write) -> io::Result<()> {
+        if self.samples > 1 {
+            return self.downsample().write_p3(w);
+        }
+        writeln!(w, "P3")?;
+        writeln!(w, "{} {} {}", self.width, self.height, self.depth)?;
         for t in self.data.iter() {
-            writeln!(file, "{} {} {}", t.red, t.green, t.blue)?;
+            writeln!(w, "{} {} {}", t.red, t.green, t.blue)?;
         }
-        file.flush()?;
         Ok(())
     }
 }
 
-// clear
+// reading images
 impl PPMImg {
-    pub fn clear(&mut self) {
-        let bg = self.bg_color;
-        for d in self.data.iter_mut() {
-            *d = bg;
+    /// Reads a ppm or png image, telling them apart by their first bytes
+    pub fn open(filepath: &str) -> io::Result<PPMImg> {
+        let bytes = std::fs::read(filepath)?;
+        if bytes.starts_with(b"\x89PNG") {
+            png::read_png(&bytes)
+        } else {
+            PPMImg::from_ppm(&bytes)
+        }
+    }
+
+    /// Decodes a P2, P3, P5 or P6 ppm, in plain text or binary
+    pub fn from_ppm(bytes: &[u8]) -> io::Result<PPMImg> {
+        let bad = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+        let magic = bytes.get(..2).ok_or_else(|| bad("Not a ppm file"))?;
+        let (gray, binary) = match magic {
+            b"P2" => (true, false),
+            b"P3" => (false, false),
+            b"P5" => (true, true),
+            b"P6" => (false, true),
+            _ => return Err(bad("Only P2, P3, P5 and P6 ppm files can be read")),
+        };
+        // numbers are separated by whitespace, and comments go from a # to the end of a line
+        let token = |pos: &mut usize| -> io::Result<u32> {
+            loop {
+                match bytes.get(*pos) {
+                    Some(b'#') => {
+                        while bytes.get(*pos).is_some_and(|&b| b != b'\n') {
+                            *pos += 1;
+                        }
+                    }
+                    Some(b) if b.is_ascii_whitespace() => *pos += 1,
+                    _ => break,
+                }
+            }
+            let start = *pos;
+            while bytes.get(*pos).is_some_and(|b| b.is_ascii_digit()) {
+                *pos += 1;
+            }
+            std::str::from_utf8(&bytes[start..*pos])
+                .ok()
+                .and_then(|t| t.parse().ok())
+                .ok_or_else(|| bad("ppm file is cut short"))
+        };
+        let mut pos = 2;
+        let (width, height, depth) = (token(&mut pos)?, token(&mut pos)?, token(&mut pos)?);
+        if depth == 0 || depth > 65535 {
+            return Err(bad("ppm depth must be from 1 to 65535"));
+        }
+        if width == 0 || height == 0 {
+            return Err(bad("ppm must be at least 1 by 1"));
         }
+        let channels = if gray { 1 } else { 3 };
+        let count = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|n| n.checked_mul(channels))
+            .ok_or_else(|| bad("ppm is too big"))?;
+        let samples: Vec<u16> = if binary {
+            // a single whitespace byte before the data
+            let data = bytes.get(pos + 1..).unwrap_or(&[]);
+            let size = if depth > 255 { 2 } else { 1 };
+            if data.len() / size < count {
+                return Err(bad("ppm data is cut short"));
+            }
+            data.chunks(size)
+                .take(count)
+                .map(|c| if size == 2 { u16::from_be_bytes([c[0], c[1]]) } else { c[0] as u16 })
+                .collect()
+        } else {
+            (0..count).map(|_| token(&mut pos).map(|v| v as u16)).collect::<io::Result<_>>()?
+        };
+        let mut img = PPMImg::new(height, width, depth as u16);
+        for (pixel, c) in img.data.iter_mut().zip(samples.chunks(channels)) {
+            *pixel = if gray { RGB::new(c[0], c[0], c[0]) } else { RGB::new(c[0], c[1], c[2]) };
+        }
+        Ok(img)
     }
 }
 
-// implement point plotting
+// external viewers and converters
 impl PPMImg {
-    pub fn plot(&mut self, x: i32, y: i32) -> () {
-        if let Some(index) = self.index(x, y) {
-            self.data[index] = self.fg_color;
+    /// Saves the image in the format its extension names
+    ///
+    /// ppm, png, bmp and tga are written directly, anything else by piping through ImageMagick
+    pub fn save_as(&self, filepath: &str) -> io::Result<()> {
+        if self.samples > 1 {
+            return self.downsample().save_as(filepath);
+        }
+        let ext = Path::new(filepath)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match ext.as_deref() {
+            Some("ppm") => self.write_binary(filepath),
+            Some("png") => png::save_png(filepath, self),
+            Some("bmp") => bmp::save_bmp(filepath, self),
+            Some("tga") => tga::save_tga(filepath, self, tga::TgaFormat::Rle),
+            _ => self.pipe_to(&[("magick", &["-", filepath]), ("convert", &["-", filepath])]),
         }
     }
 
-    /// Returns Some(index) if index exists. Otherwise None.
-    fn index(&self, x: i32, y: i32) -> Option<usize> {
-        let (width, height) = (
-            self.width.try_into().unwrap(),
-            self.height.try_into().unwrap(),
-        );
-        if (!self.x_wrap && (x < 0 || x >= width)) || (!self.y_wrap && (y < 0 || y >= height)) {
-            return None;
+    /// Shows the image with ImageMagick's `display`, waiting until its window is closed
+    pub fn display(&self) -> io::Result<()> {
+        self.pipe_to(&[("magick", &["display", "-"]), ("display", &["-"])])
+    }
+
+    /// Prints the image to the terminal in 24 bit color, at two pixels per character
+    pub fn print_to_terminal(&self) -> io::Result<()> {
+        let stdout = io::stdout();
+        let mut w = io::BufWriter::new(stdout.lock());
+        if self.samples > 1 {
+            terminal::write_ansi(&mut w, &self.downsample())?;
+        } else {
+            terminal::write_ansi(&mut w, self)?;
         }
+        w.flush()
+    }
 
-        let x = if x >= width {
-            x % width
-        } else if x < 0 {
-            let r = x % width;
-            if r != 0 {
-                r + width
-            } else {
-                r
-            }
+    /// Prints the image to the terminal as Sixel graphics, for terminals that support them
+    pub fn print_sixel(&self) -> io::Result<()> {
+        let stdout = io::stdout();
+        let mut w = io::BufWriter::new(stdout.lock());
+        if self.samples > 1 {
+            terminal::write_sixel(&mut w, &self.downsample(), dither::Dither::FloydSteinberg)?;
         } else {
-            x
-        };
-        let y = if y >= height {
-            y % height
-        } else if y < 0 {
-            let r = y % height;
-            if r != 0 {
-                r + height
-            } else {
-                r
+            terminal::write_sixel(&mut w, self, dither::Dither::FloydSteinberg)?;
+        }
+        w.flush()
+    }
+
+    /// Writes the image as a ppm to the standard input of the first of `programs` there is
+    fn pipe_to(&self, programs: &[(&str, &[&str])]) -> io::Result<()> {
+        for (program, args) in programs.iter() {
+            let spawned = Command::new(program)
+                .args(args.iter())
+                .stdin(Stdio::piped())
+                .spawn();
+            let mut child = match spawned {
+                Ok(child) => child,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            let mut stdin = child.stdin.take().expect("stdin was piped");
+            self.write_p6(&mut stdin)?;
+            drop(stdin);
+            let status = child.wait()?;
+            if !status.success() {
+                return Err(io::Error::other(format!("{} failed with {}", program, status)));
             }
-        } else {
-            y
-        };
+            return Ok(());
+        }
+        let names: Vec<&str> = programs.iter().map(|(p, _)| *p).collect();
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("None of {} could be found; is ImageMagick installed?", names.join(", ")),
+        ))
+    }
+}
 
-        // now we know that x and y are positive, we can cast without worry
-        Some((y * self.width as i32 + x).try_into().unwrap())
+// resize and crop
+impl PPMImg {
+    /// A copy of the image stretched to `width` by `height`, see `resize::Filter`
+    ///
+    /// A supersampled image is downsampled first.
+    pub fn resize(&self, width: u32, height: u32, filter: resize::Filter) -> PPMImg {
+        if self.samples > 1 {
+            return self.downsample().resize(width, height, filter);
+        }
+        resize::resize(self, width, height, filter)
+    }
+
+    /// A copy of the pixels inside `rect`, trimmed to the image
+    ///
+    /// A supersampled image is downsampled first.
+    pub fn crop(&self, rect: resize::Rect) -> PPMImg {
+        if self.samples > 1 {
+            return self.downsample().crop(rect);
+        }
+        resize::crop(self, rect)
+    }
+
+    /// Saves the pixels inside `rect`, in the format the extension names
+    pub fn save_region(&self, rect: resize::Rect, filepath: &str) -> io::Result<()> {
+        self.crop(rect).save_as(filepath)
     }
 }
 
-// impl line algorithm
+// world coordinates
 impl PPMImg {
-    /// Draw a line from (x0, y0) to (x1, y1)
-    /// #### impl note:
-    ///    Always add 2A or 2B when updating D. Half of that value will distort line
-    pub fn draw_line(&mut self, x0: f64, y0: f64, x1: f64, y1: f64) {
-        // swap variables if needed, since we are always going from left to right
-        let (x0, y0, x1, y1) = if x0 > x1 {
-            (x1, y1, x0, y0)
-        } else {
-            (x0, y0, x1, y1)
-        };
+    /// Draw in world coordinates from now on, with y going up
+    ///
+    /// The rectangle from (xmin, ymin) to (xmax, ymax) is stretched over the whole image, with
+    /// its corners on the corner pixels. Lines, fills and strokes all go through the mapping;
+    /// stroke widths stay in pixels, unless they are set to be in the world.
+    pub fn set_world_bounds(&mut self, xmin: f64, ymin: f64, xmax: f64, ymax: f64) {
+        assert!(xmin != xmax && ymin != ymax, "World bounds must not be empty");
+        let (width, height) = (self.width / self.samples, self.height / self.samples);
+        let sx = (width as f64 - 1.0) / (xmax - xmin);
+        let sy = -(height as f64 - 1.0) / (ymax - ymin);
+        self.viewport = Some((sx, -xmin * sx, sy, -ymax * sy));
+    }
 
-        // force conversion into ints for processing & plotting
-        let (x0, y0, x1, y1) = (
-            x0.round() as i32,
-            y0.round() as i32,
-            x1.round() as i32,
-            y1.round() as i32,
-        );
+    /// Go back to drawing in pixel coordinates
+    pub fn clear_world_bounds(&mut self) {
+        self.viewport = None;
+    }
 
-        // calculate  values and then truncate
-        let (dy, ndx) = (y1 - y0, -(x1 - x0));
+    /// Pixel coordinates of a point in world coordinates
+    pub fn world_to_screen(&self, x: f64, y: f64) -> (f64, f64) {
+        let (x, y) = match self.viewport {
+            Some((sx, ox, sy, oy)) => (x * sx + ox, y * sy + oy),
+            None => (x, y),
+        };
+        // the center of a pixel is the center of its block of samples
+        let s = self.samples as f64;
+        (x * s + (s - 1.0) / 2.0, y * s + (s - 1.0) / 2.0)
+    }
+
+    fn all_to_screen(&self, points: &[(f64, f64)]) -> Vec<(f64, f64)> {
+        points.iter().map(|p| self.world_to_screen(p.0, p.1)).collect()
+    }
 
-        // deal with special s:
-        if ndx == 0 {
-            // vertical line
-            let (y0, y1) = if y0 < y1 { (y0, y1) } else { (y1, y0) };
+    /// World coordinates of a point in pixel coordinates
+    pub fn screen_to_world(&self, x: f64, y: f64) -> (f64, f64) {
+        let s = self.samples as f64;
+        let (x, y) = ((x - (s - 1.0) / 2.0) / s, (y - (s - 1.0) / 2.0) / s);
+        match self.viewport {
+            Some((sx, ox, sy, oy)) => ((x - ox) / sx, (y - oy) / sy),
+            None => (x, y),
+        }
+    }
+}
 
-            for y in y0..=y1 {
-                self.plot(x0, y);
+// clear
+impl PPMImg {
+    pub fn clear(&mut self) {
+        if let Some(zbuffer) = self.zbuffer.as_mut() {
+            zbuffer.iter_mut().for_each(|z| *z = f64::NEG_INFINITY);
+        }
+        if let Some(background) = self.background.as_ref() {
+            self.data.copy_from_slice(background);
+            if let Some(alpha) = self.alpha.as_mut() {
+                alpha.iter_mut().for_each(|a| *a = 1.0);
             }
+            return;
+        }
+        let bg = self.bg_color;
+        for d in self.data.iter_mut() {
+            *d = bg;
+        }
+        if let Some(alpha) = self.alpha.as_mut() {
+            alpha.iter_mut().for_each(|a| *a = 0.0);
+        }
+    }
+}
 
-            return ();
+// implement point plotting
+impl PPMImg {
+    pub fn plot(&mut self, x: i32, y: i32) {
+        self.plot_color(x, y, self.fg_color);
+    }
+
+    /// Plots one pixel in `color`, at the opacity `fg_alpha`
+    pub fn plot_color(&mut self, x: i32, y: i32, color: RGB) {
+        if let Some(index) = self.plot_index(x, y) {
+            self.put(index, color.with_alpha(1.0));
         }
+    }
 
-        if dy == 0 {
-            // horizontal line
-            // x vals are already in the right order, so we don't flip
-            for x in x0..=x1 {
-                self.plot(x, y0);
-            }
-            return ();
+    /// Plots one pixel over what is there, at the color's opacity times `fg_alpha`
+    pub fn plot_rgba(&mut self, x: i32, y: i32, color: RGBA) {
+        if let Some(index) = self.plot_index(x, y) {
+            self.put(index, color);
         }
+    }
 
-        // find A and B
-        // let m  = -dely as f64 / ndelx as f64;
+    /// Every drawn pixel ends up here, to be composited
+    fn put(&mut self, index: usize, color: RGBA) {
+        let blend = self.blend();
+        let dst_alpha = self.alpha.as_mut().map(|a| &mut a[index]);
+        blend.put(&mut self.data[index], dst_alpha, color);
+        self.count_write(index);
+    }
 
-        let (x, mut y) = (x0, y0);
+    fn count_write(&mut self, index: usize) {
+        if let Some(writes) = self.writes.as_mut() {
+            writes[index] += 1;
+        }
+    }
 
-        if (y1 - y0).abs() < (x1 - x0).abs() {
-            // octant 1 and 8
-            let mut d = 2 * dy + ndx;
-            let (y_inc, dy) = if dy > 0 {
-                // octant 1
-                (1, dy)
-            } else {
-                // octant 8
-                // dy is (-) in octant 8, so flip it to balance out with ndx
-                (-1, -dy)
-            };
+    fn blend(&self) -> Blend {
+        Blend {
+            fg_alpha: self.fg_alpha,
+            linear_light: self.linear_light,
+            mode: self.blend_mode,
+            depth: self.depth,
+        }
+    }
 
-            for x in x0..=x1 {
-                self.plot(x, y);
-                if d > 0 {
-                    y += y_inc;
-                    d += 2 * ndx;
-                }
-                d += 2 * dy;
-            }
-        } else {
-            // octant 2 and 7
-            // flipping x and y should work out
+    /// Color and opacity at (x, y), or None if that is off the image
+    pub fn pixel_rgba(&self, x: i32, y: i32) -> Option<RGBA> {
+        self.index(x, y).map(|i| {
+            let alpha = self.alpha.as_ref().map_or(1.0, |a| a[i]);
+            self.data[i].with_alpha(alpha)
+        })
+    }
 
-            let mut d = 2 * -ndx - dy;
+    /// Color at (x, y), or None if that is off the image
+    pub fn pixel(&self, x: i32, y: i32) -> Option<RGB> {
+        self.index(x, y).map(|i| self.data[i])
+    }
 
-            let (x_inc, mut x, ystart, yend, dy) = if dy > 0 {
-                // octant 2
-                (1, x, y0, y1, dy)
-            } else {
-                // octant 7
-                // swap -x and y to reflect over y=-x into octant 8
-                (-1, x - ndx, y1, y0, -dy)
-            };
+    pub fn width(&self) -> u32 {
+        self.width
+    }
 
-            for y in ystart..=yend {
-                self.plot(x, y);
-                if d > 0 {
-                    x += x_inc;
-                    d -= 2 * dy;
-                }
-                d -= 2 * ndx;
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn depth(&self) -> u16 {
+        self.depth
+    }
+
+    /// Iterate over the rows of pixels, from the top
+    pub fn rows(&self) -> impl Iterator<Item = &[RGB]> + '_ {
+        self.data.chunks(self.width as usize)
+    }
+
+    /// Returns Some(index) if index exists. Otherwise None.
+    ///
+    /// Points off the image are wrapped or mirrored back onto it, depending on the bounds.
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        let (width, height) = (self.width as i32, self.height as i32);
+        let x = self.x_bounds.place(x, width)?;
+        let y = self.y_bounds.place(y, height)?;
+        // now we know that x and y are positive, we can cast without worry
+        Some((y * width + x) as usize)
+    }
+
+    /// The index of a pixel about to be drawn, as for `index`
+    ///
+    /// Panics if the point is off the image along an axis with `OutOfBounds::Error`.
+    fn plot_index(&self, x: i32, y: i32) -> Option<usize> {
+        let index = self.index(x, y);
+        if index.is_none() {
+            let off = |v: i32, n: u32| v < 0 || v >= n as i32;
+            let x_err = self.x_bounds == OutOfBounds::Error && off(x, self.width);
+            let y_err = self.y_bounds == OutOfBounds::Error && off(y, self.height);
+            if x_err || y_err {
+                panic!("Pixel ({}, {}) is off the {}x{} image", x, y, self.width, self.height);
             }
         }
+        index
+    }
+
+    /// Whether drawing is trimmed to the image along x and along y, rather than going on past
+    /// the edges to be wrapped, mirrored or reported
+    fn clips(&self) -> (bool, bool) {
+        (self.x_bounds == OutOfBounds::Clip, self.y_bounds == OutOfBounds::Clip)
+    }
+
+    /// Use the same bounds along both axes
+    pub fn set_bounds(&mut self, bounds: OutOfBounds) {
+        self.x_bounds = bounds;
+        self.y_bounds = bounds;
+    }
+}
+
+/// Rows `rows` of a per pixel buffer that may be off, `n` of them either way
+fn row_chunks<T>(
+    buf: Option<&mut Vec<T>>,
+    rows: std::ops::Range<usize>,
+    width: usize,
+    n: usize,
+) -> Vec<Option<&mut [T]>> {
+    match buf {
+        Some(buf) => buf[rows].chunks_mut(width).map(Some).collect(),
+        None => (0..n).map(|_| None).collect(),
+    }
+}
+
+/// How pixels are composited, apart from the image so that rows can be painted in parallel
+#[derive(Copy, Clone)]
+struct Blend {
+    fg_alpha: f64,
+    linear_light: bool,
+    mode: BlendMode,
+    depth: u16,
+}
+
+impl Blend {
+    fn put(self, dst: &mut RGB, dst_alpha: Option<&mut f64>, color: RGBA) {
+        let mut alpha = (color.alpha * self.fg_alpha).clamp(0.0, 1.0);
+        if let Some(dst_alpha) = dst_alpha {
+            // source over a translucent pixel: the new color's share of the total opacity
+            let total = alpha + *dst_alpha * (1.0 - alpha);
+            *dst_alpha = total;
+            alpha = if total > 0.0 { alpha / total } else { 0.0 };
+        }
+        let color = self.mode.mix(color.rgb(), *dst, self.depth, self.linear_light);
+        *dst = if alpha >= 1.0 {
+            color
+        } else if self.linear_light {
+            color.with_alpha(alpha).over_linear(*dst, self.depth)
+        } else {
+            color.with_alpha(alpha).over(*dst)
+        };
+    }
+}
+
+// impl line algorithm
+impl PPMImg {
+    /// Draw a line from (x0, y0) to (x1, y1)
+    /// #### impl note:
+    ///    Always add 2A or 2B when updating D. Half of that value will distort line
+    pub fn draw_line(&mut self, x0: f64, y0: f64, x1: f64, y1: f64) {
+        let fg = self.fg_color;
+        self.draw_line_blend(x0, y0, x1, y1, fg, fg);
+    }
+
+    /// Draw a line whose color blends from `c0` at (x0, y0) to `c1` at (x1, y1)
+    pub fn draw_line_blend(&mut self, x0: f64, y0: f64, x1: f64, y1: f64, c0: RGB, c1: RGB) {
+        let ((x0, y0), (x1, y1)) = (self.world_to_screen(x0, y0), self.world_to_screen(x1, y1));
+        // only walk the part on the image, to the edges of the outermost pixels
+        let inf = f64::INFINITY;
+        let (clip_x, clip_y) = self.clips();
+        let (xmin, xmax) = if clip_x { (-0.5, self.width as f64 - 0.5) } else { (-inf, inf) };
+        let (ymin, ymax) = if clip_y { (-0.5, self.height as f64 - 0.5) } else { (-inf, inf) };
+        let (t0, t1) = match clip::clip_line((x0, y0), (x1, y1), (xmin, ymin), (xmax, ymax)) {
+            Some(range) => range,
+            None => return,
+        };
+        if c0 == c1 {
+            walk_line(x0, y0, x1, y1, (t0, t1), |x, y, _| self.plot_color(x, y, c0));
+        } else {
+            walk_line(x0, y0, x1, y1, (t0, t1), |x, y, t| self.plot_color(x, y, c0.lerp(c1, t)));
+        }
     }
 
     /// Draw a line from (x0, y0) with a certain magnitude and angle
@@ -253,7 +734,54 @@ impl PPMImg {
         let (x1, y1) = (x0 + dx, y0 + dy);
 
         self.draw_line(x0, y0, x1, y1);
-        return (x1, y1);
+        (x1, y1)
+    }
+}
+
+/// Bresenham's algorithm from (x0, y0) to (x1, y1), calling `plot` with every pixel and how far
+/// along the line it is, from 0 to 1
+///
+/// Only the pixels in the range of t in `part` are plotted, but they are the same pixels the whole
+/// line would have, so that a line clipped to one image lines up with the rest of it on another.
+fn walk_line(
+    x0: f64,
+    y0: f64,
+    x1: f64,
+    y1: f64,
+    part: (f64, f64),
+    mut plot: impl FnMut(i32, i32, f64),
+) {
+    // force conversion into ints for processing & plotting
+    let (x0, y0, x1, y1) = (
+        x0.round() as i64,
+        y0.round() as i64,
+        x1.round() as i64,
+        y1.round() as i64,
+    );
+
+    // every octant is a reflection of octant 1: step along the longer axis every time,
+    // and along the shorter one whenever the error says so
+    let (dx, dy) = ((x1 - x0).abs(), (y1 - y0).abs());
+    let (x_inc, y_inc) = ((x1 - x0).signum(), (y1 - y0).signum());
+    let steep = dy > dx;
+    let (long, short) = if steep { (dy, dx) } else { (dx, dy) };
+    if long == 0 {
+        plot(x0 as i32, y0 as i32, 0.0);
+        return;
+    }
+
+    // a step past each end of the part, for the rounding of the endpoints
+    let first = ((part.0 * long as f64).floor() as i64 - 1).max(0);
+    let last = ((part.1 * long as f64).ceil() as i64 + 1).min(long);
+    for step in first..=last {
+        // how many times the error has gone past zero in `step` steps of the loop
+        let across = (2 * short * step + long - 1) / (2 * long);
+        let (x, y) = if steep {
+            (x0 + across * x_inc, y0 + step * y_inc)
+        } else {
+            (x0 + step * x_inc, y0 + across * y_inc)
+        };
+        plot(x as i32, y as i32, step as f64 / long as f64);
     }
 }
 
@@ -287,9 +815,9 @@ impl Turtle {
     pub fn forward(&mut self, steps: i32) {
         let (x0, y0) = (self.x, self.y);
         let (dx, dy) = polar_to_xy(steps.into(), self.angle_deg);
-        let (x1, y1) = (x0 as f64 + dx, y0 as f64 + dy);
+        let (x1, y1) = (x0 + dx, y0 + dy);
         if self.pen_down {
-            self.img.draw_line(x0 as f64, y0 as f64, x1, y1);
+            self.img.draw_line(x0, y0, x1, y1);
         }
         self.x = x1;
         self.y = y1;
@@ -304,12 +832,12 @@ impl Turtle {
     }
 
     pub fn get_color(&self) -> RGB {
-        return self.img.fg_color;
+        self.img.fg_color
     }
 
     pub fn move_to(&mut self, x: f64, y: f64) {
         if self.pen_down {
-            self.img.draw_line(self.x as f64, self.y as f64, x, y);
+            self.img.draw_line(self.x, self.y, x, y);
         }
         self.x = x;
         self.y = y;
@@ -319,7 +847,7 @@ impl Turtle {
     ///
     /// This method will move the turtle
     pub fn get_ppm_img(self) -> PPMImg {
-        return self.img;
+        self.img
     }
 }
 
@@ -327,17 +855,656 @@ impl Turtle {
 impl PPMImg {
     /// Draws an edge matrix
     ///
-    /// Number of edges must be a multiple of 2
+    /// Number of edges must be a multiple of 2.
+    /// Points with a color blend along their edges, and the rest use `fg_color`.
     pub fn render_edge_matrix(&mut self, m: &Matrix) {
-        let mut iter = m.iter_by_row();
-        while let Some(point) = iter.next() {
-            let (x0, y0, _z0) = (point[0], point[1], point[2]);
-            let (x1, y1, _z1) = match iter.next() {
-                Some(p1) => (p1[0], p1[1], p1[2]),
+        let mut iter = m.iter_by_row().enumerate();
+        while let Some((i, point)) = iter.next() {
+            let (x0, y0, z0) = (point[0], point[1], point[2]);
+            let (x1, y1, z1) = match iter.next() {
+                Some((_, p1)) => (p1[0], p1[1], p1[2]),
                 None => panic!("Number of edges must be a multiple of 2"),
             };
 
-            self.draw_line(x0, y0, x1, y1);
+            if m.has_colors() || self.fog.is_some() {
+                let (c0, c1) = (self.row_color(m, i), self.row_color(m, i + 1));
+                self.draw_line_blend(x0, y0, x1, y1, self.fogged(c0, z0), self.fogged(c1, z1));
+            } else {
+                self.draw_line(x0, y0, x1, y1);
+            }
+        }
+    }
+
+    /// Color of a row of `m`, falling back to `fg_color`
+    fn row_color(&self, m: &Matrix, row: usize) -> RGB {
+        m.color(row).unwrap_or(self.fg_color)
+    }
+
+    /// A color seen through the fog at depth `z`
+    fn fogged(&self, color: RGB, z: f64) -> RGB {
+        match self.fog {
+            Some(fog) => color.lerp(fog.color, fog.amount(z)),
+            None => color,
+        }
+    }
+
+    /// Draws an edge matrix in `color`, leaving `fg_color` as it was
+    pub fn draw_lines(&mut self, m: &Matrix, color: RGB) {
+        let fg = self.fg_color;
+        self.fg_color = color;
+        self.render_edge_matrix(m);
+        self.fg_color = fg;
+    }
+}
+
+// draw polygon matrix
+impl PPMImg {
+    /// Draws the outline of every triangle in a polygon matrix
+    ///
+    /// With `cull` set, triangles facing away from the viewer, who looks down the z axis, are skipped
+    pub fn render_polygon_matrix(&mut self, m: &Matrix, cull: bool) {
+        let front;
+        let m = if cull {
+            front = m.cull_backfaces((0.0, 0.0, 1.0));
+            &front
+        } else {
+            m
+        };
+        let rows: Vec<&[f64]> = m.iter_by_row().collect();
+        for (i, t) in rows.chunks(3).enumerate() {
+            assert_eq!(3, t.len(), "Number of points must be a multiple of 3");
+            for (a, b) in [(0, 1), (1, 2), (2, 0)].iter().copied() {
+                let (ca, cb) = (self.row_color(m, 3 * i + a), self.row_color(m, 3 * i + b));
+                let (ca, cb) = (self.fogged(ca, t[a][2]), self.fogged(cb, t[b][2]));
+                self.draw_line_blend(t[a][0], t[a][1], t[b][0], t[b][1], ca, cb);
+            }
+        }
+    }
+
+    /// Fills every triangle in a polygon matrix, blending the colors of its points across it
+    ///
+    /// Triangles are drawn from back to front, by the mean z of their points, and culled as
+    /// for `render_polygon_matrix`; with `depth_test` on, only the nearest surface shows at
+    /// every pixel even where triangles cut through each other. Meshes keep their vertex colors
+    /// through `Mesh::to_polygons`.
+    pub fn fill_polygon_matrix(&mut self, m: &Matrix, cull: bool) {
+        let front;
+        let m = if cull {
+            front = m.cull_backfaces((0.0, 0.0, 1.0));
+            &front
+        } else {
+            m
+        };
+        let rows: Vec<&[f64]> = m.iter_by_row().collect();
+        assert_eq!(0, rows.len() % 3, "Number of points must be a multiple of 3");
+        let mut triangles: Vec<(f64, usize)> = rows
+            .chunks_exact(3)
+            .enumerate()
+            .map(|(i, t)| ((t[0][2] + t[1][2] + t[2][2]) / 3.0, i))
+            .collect();
+        triangles.sort_by(|a, b| a.0.partial_cmp(&b.0).expect("Polygon must not have NaN points"));
+        for (_, i) in triangles {
+            let t = &rows[3 * i..3 * i + 3];
+            let colors = [0, 1, 2].map(|k| self.fogged(self.row_color(m, 3 * i + k), t[k][2]));
+            let screen = [0, 1, 2].map(|k| self.world_to_screen(t[k][0], t[k][1]));
+            let z = [t[0][2], t[1][2], t[2][2]];
+            self.fill_screen_triangle(screen, Some(z), |w| RGB::blend3(colors, w));
+        }
+    }
+}
+
+// draw scene
+impl PPMImg {
+    /// Draws every edge and every triangle outline of a scene, instances included
+    pub fn render_scene(&mut self, scene: &scene::Scene, cull: bool) {
+        for g in scene.transformed_objects() {
+            self.render_geometry(&g, cull);
+        }
+        for instance in scene.instances.iter() {
+            for g in instance.copies() {
+                self.render_geometry(&g, cull);
+            }
+        }
+    }
+
+    /// Draws one piece of geometry, as it is
+    pub fn render_geometry(&mut self, g: &scene::Geometry, cull: bool) {
+        match g {
+            scene::Geometry::Edges(m) => self.render_edge_matrix(m),
+            scene::Geometry::Polygons(m) => self.render_polygon_matrix(m, cull),
+            scene::Geometry::Mesh(mesh) => self.render_polygon_matrix(&mesh.to_polygons(), cull),
+            sphere @ scene::Geometry::Sphere { .. } => {
+                let m = sphere.sphere_polygons().expect("Spheres are made of triangles");
+                self.render_polygon_matrix(&m, cull)
+            }
+        }
+    }
+}
+
+// fill polygons
+impl PPMImg {
+    /// Fills a polygon with `fg_color`, by scanlines with the even odd rule
+    ///
+    /// A pixel is filled when the point at its coordinates is inside, with left and bottom
+    /// edges counted as inside and right and top edges not, so pieces sharing an edge do not
+    /// overlap.
+    /// The polygon may or may not repeat its first point at the end.
+    pub fn fill_polygon(&mut self, polygon: &[(f64, f64)]) {
+        let polygon = self.all_to_screen(polygon);
+        self.fill_screen_polygon(&polygon);
+    }
+
+    /// Fills a triangle, blending the colors of its corners across it by barycentric weights
+    pub fn fill_triangle_colors(&mut self, triangle: [(f64, f64); 3], colors: [RGB; 3]) {
+        let screen = triangle.map(|p| self.world_to_screen(p.0, p.1));
+        self.fill_screen_triangle(screen, None, |w| RGB::blend3(colors, w));
+    }
+
+    /// Fills a triangle in pixel coordinates, in the color `color_at` gives for how much each
+    /// corner counts at a pixel
+    ///
+    /// With the depth `z` of every corner, pixels are depth tested, see `depth_test`.
+    fn fill_screen_triangle(
+        &mut self,
+        triangle: [(f64, f64); 3],
+        z: Option<[f64; 3]>,
+        color_at: impl Fn([f64; 3]) -> RGB + Sync,
+    ) {
+        let [a, b, c] = triangle;
+        let area = (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0);
+        if area == 0.0 {
+            return;
+        }
+        // how much each corner counts at a pixel, from the areas across from it
+        let weights = |x: i32, y: i32| {
+            let (px, py) = (x as f64, y as f64);
+            let wb = ((px - a.0) * (c.1 - a.1) - (py - a.1) * (c.0 - a.0)) / area;
+            let wc = ((b.0 - a.0) * (py - a.1) - (b.1 - a.1) * (px - a.0)) / area;
+            [1.0 - wb - wc, wb, wc]
+        };
+        let spans = self.polygon_spans(&triangle);
+        self.paint_spans(
+            &spans,
+            true,
+            |x, y| z.map(|z| (0..3).map(|k| weights(x, y)[k] * z[k]).sum()),
+            |x, y| color_at(weights(x, y)),
+        );
+    }
+
+    fn fill_screen_polygon(&mut self, polygon: &[(f64, f64)]) {
+        let spans = self.polygon_spans(polygon);
+        let color = self.fg_color;
+        self.paint_spans(&spans, true, |_, _| None, |_, _| color);
+    }
+
+    /// The runs of pixels (y, x0, x1) inside a polygon in pixel coordinates, top to bottom
+    fn polygon_spans(&self, polygon: &[(f64, f64)]) -> Vec<(i32, i32, i32)> {
+        // trim to the image, along the axes that clip
+        let inf = f64::INFINITY;
+        let (clip_x, clip_y) = self.clips();
+        let (xmin, xmax) = if clip_x { (-0.5, self.width as f64) } else { (-inf, inf) };
+        let (ymin, ymax) = if clip_y { (-0.5, self.height as f64) } else { (-inf, inf) };
+        let clipped = clip::clip_polygon(polygon, (xmin, ymin), (xmax, ymax));
+        let polygon = clipped.as_slice();
+        if polygon.len() < 3 {
+            return vec![];
+        }
+        let (mut ymin, mut ymax) = (f64::INFINITY, f64::NEG_INFINITY);
+        for p in polygon.iter() {
+            ymin = ymin.min(p.1);
+            ymax = ymax.max(p.1);
+        }
+        let (mut y0, mut y1) = (ymin.ceil() as i32, ymax.floor() as i32);
+        if clip_y {
+            y0 = y0.max(0);
+            y1 = y1.min(self.height as i32 - 1);
+        }
+        let (mut xs, mut spans) = (vec![], vec![]);
+        for y in y0..=y1 {
+            let yf = y as f64;
+            xs.clear();
+            for (i, &p) in polygon.iter().enumerate() {
+                let q = polygon[(i + 1) % polygon.len()];
+                // each edge holds its lower end but not its upper one, so vertices count once
+                if (p.1 <= yf && yf < q.1) || (q.1 <= yf && yf < p.1) {
+                    xs.push(p.0 + (yf - p.1) / (q.1 - p.1) * (q.0 - p.0));
+                }
+            }
+            xs.sort_by(|a, b| a.partial_cmp(b).expect("Polygon must not have NaN points"));
+            for span in xs.chunks_exact(2) {
+                let (mut x0, mut x1) = (span[0].ceil() as i32, span[1].ceil() as i32 - 1);
+                if clip_x {
+                    x0 = x0.max(0);
+                    x1 = x1.min(self.width as i32 - 1);
+                }
+                if x0 <= x1 {
+                    spans.push((y, x0, x1));
+                }
+            }
+        }
+        spans
+    }
+
+    /// Paints runs of pixels (y, x0, x1), which go from top to bottom, in the color `color_at`
+    /// gives for each pixel
+    ///
+    /// Pixels that `depth_at` gives a depth for are depth tested, see `depth_test`. Big fills on
+    /// images that clip are painted on rayon's threads a row at a time, unless `parallel` is
+    /// false.
+    fn paint_spans(
+        &mut self,
+        spans: &[(i32, i32, i32)],
+        parallel: bool,
+        depth_at: impl Fn(i32, i32) -> Option<f64> + Sync,
+        color_at: impl Fn(i32, i32) -> RGB + Sync,
+    ) {
+        let pixels: i32 = spans.iter().map(|&(_, x0, x1)| x1 - x0 + 1).sum();
+        let serial = !parallel || self.clips() != (true, true);
+        if serial || pixels < PARALLEL_FILL_PIXELS {
+            for &(y, x0, x1) in spans.iter() {
+                for x in x0..=x1 {
+                    if let Some(index) = self.plot_index(x, y) {
+                        let nearest = self.zbuffer.as_mut().map(|z| &mut z[index]);
+                        if nearer(nearest, depth_at(x, y)) {
+                            self.put(index, color_at(x, y).with_alpha(1.0));
+                        }
+                    }
+                }
+            }
+            return;
+        }
+        let (width, blend) = (self.width as usize, self.blend());
+        // clipped spans are all on the image, so only rows y0 to y1 are touched
+        let (y0, y1) = (spans[0].0, spans[spans.len() - 1].0);
+        let rows = y0 as usize * width..(y1 as usize + 1) * width;
+        let mut row_spans: Vec<&[(i32, i32, i32)]> = vec![&[]; (y1 - y0 + 1) as usize];
+        for row in spans.chunk_by(|a, b| a.0 == b.0) {
+            row_spans[(row[0].0 - y0) as usize] = row;
+        }
+        let n = row_spans.len();
+        let alpha_rows = row_chunks(self.alpha.as_mut(), rows.clone(), width, n);
+        let write_rows = row_chunks(self.writes.as_mut(), rows.clone(), width, n);
+        let depth_rows = row_chunks(self.zbuffer.as_mut(), rows.clone(), width, n);
+        self.data[rows]
+            .par_chunks_mut(width)
+            .zip(alpha_rows)
+            .zip(write_rows)
+            .zip(depth_rows)
+            .zip(row_spans)
+            .for_each(|((((data, mut alpha), mut writes), mut depth), row)| {
+                for &(y, x0, x1) in row {
+                    for x in x0..=x1 {
+                        let i = x as usize;
+                        if !nearer(depth.as_deref_mut().map(|z| &mut z[i]), depth_at(x, y)) {
+                            continue;
+                        }
+                        let color = color_at(x, y).with_alpha(1.0);
+                        blend.put(&mut data[i], alpha.as_deref_mut().map(|a| &mut a[i]), color);
+                        if let Some(writes) = writes.as_deref_mut() {
+                            writes[i] += 1;
+                        }
+                    }
+                }
+            });
+    }
+}
+
+// depth buffer
+impl PPMImg {
+    /// Start depth testing with nothing drawn yet, or stop
+    ///
+    /// While it is on, triangles filled in 3D only cover a pixel where they are nearer than
+    /// what was filled there before, nearer meaning a higher z as the viewer looks down the z
+    /// axis. Flat fills, lines and text are drawn over everything, and leave depths alone.
+    /// `clear` starts the depths over.
+    pub fn depth_test(&mut self, on: bool) {
+        self.zbuffer = if on { Some(vec![f64::NEG_INFINITY; self.data.len()]) } else { None };
+    }
+
+    /// Depth of the nearest surface filled at (x, y), if depth testing is on and anything was
+    /// filled there
+    pub fn depth_at(&self, x: i32, y: i32) -> Option<f64> {
+        let z = self.zbuffer.as_ref()?[self.index(x, y)?];
+        Some(z).filter(|z| z.is_finite())
+    }
+}
+
+/// Whether a surface at depth `z` is in front of the `nearest` one so far, which it then
+/// replaces; without a depth buffer or a depth, everything is
+fn nearer(nearest: Option<&mut f64>, z: Option<f64>) -> bool {
+    match (nearest, z) {
+        (Some(nearest), Some(z)) => {
+            if z <= *nearest {
+                return false;
+            }
+            *nearest = z;
+            true
+        }
+        _ => true,
+    }
+}
+
+// circles and ellipses
+impl PPMImg {
+    /// Draws a circle of radius `r` around (cx, cy) with `fg_color`, pixel by pixel with the
+    /// midpoint algorithm
+    ///
+    /// World coordinates that stretch one axis more than the other make it an ellipse.
+    pub fn draw_circle(&mut self, cx: f64, cy: f64, r: f64) {
+        let (c, rx, ry) = self.screen_radii(cx, cy, r, r);
+        if rx != ry {
+            self.ellipse_pixels(c, rx, ry);
+            return;
+        }
+        let (mut x, mut y) = (0, rx);
+        let mut d = 1 - rx;
+        while x <= y {
+            self.plot_quadrants(c, x, y);
+            if x != y {
+                self.plot_quadrants(c, y, x);
+            }
+            x += 1;
+            if d < 0 {
+                d += 2 * x + 1;
+            } else {
+                y -= 1;
+                d += 2 * (x - y) + 1;
+            }
+        }
+    }
+
+    /// Draws an axis aligned ellipse with radii `rx` and `ry` around (cx, cy) with `fg_color`,
+    /// pixel by pixel with the midpoint algorithm
+    pub fn draw_ellipse(&mut self, cx: f64, cy: f64, rx: f64, ry: f64) {
+        let (c, rx, ry) = self.screen_radii(cx, cy, rx, ry);
+        self.ellipse_pixels(c, rx, ry);
+    }
+
+    /// The center pixel and the radii in pixels of a circle or ellipse in world coordinates
+    fn screen_radii(&self, cx: f64, cy: f64, rx: f64, ry: f64) -> ((i64, i64), i64, i64) {
+        let (x, y) = self.world_to_screen(cx, cy);
+        let (x1, _) = self.world_to_screen(cx + rx, cy);
+        let (_, y1) = self.world_to_screen(cx, cy + ry);
+        let c = (x.round() as i64, y.round() as i64);
+        (c, (x1 - x).abs().round() as i64, (y1 - y).abs().round() as i64)
+    }
+
+    fn ellipse_pixels(&mut self, c: (i64, i64), rx: i64, ry: i64) {
+        let (a2, b2) = (rx * rx, ry * ry);
+        let (mut x, mut y) = (0, ry);
+        // four times the error at the midpoint between the next two candidates, first while
+        // the curve is flatter than 45 degrees and x steps every time
+        let mut d = 4 * b2 - 4 * a2 * ry + a2;
+        while b2 * x < a2 * y {
+            self.plot_quadrants(c, x, y);
+            if d >= 0 {
+                d += 4 * a2 * (2 - 2 * y);
+                y -= 1;
+            }
+            d += 4 * b2 * (2 * x + 3);
+            x += 1;
+        }
+        // then y steps every time
+        d = b2 * (2 * x + 1) * (2 * x + 1) + 4 * a2 * (y - 1) * (y - 1) - 4 * a2 * b2;
+        while y > 0 {
+            self.plot_quadrants(c, x, y);
+            if d <= 0 {
+                d += 4 * b2 * (2 * x + 2);
+                x += 1;
+            }
+            d += 4 * a2 * (3 - 2 * y);
+            y -= 1;
+        }
+        // very flat ellipses are left short of their ends
+        for x in x..=rx {
+            self.plot_quadrants(c, x, 0);
+        }
+    }
+
+    /// Plots (x, y) reflected into every quadrant around `c`, each pixel once
+    fn plot_quadrants(&mut self, c: (i64, i64), x: i64, y: i64) {
+        let color = self.fg_color;
+        let xs: &[i64] = if x == 0 { &[0] } else { &[x, -x] };
+        let ys: &[i64] = if y == 0 { &[0] } else { &[y, -y] };
+        for &dx in xs {
+            for &dy in ys {
+                self.plot_color((c.0 + dx) as i32, (c.1 + dy) as i32, color);
+            }
+        }
+    }
+}
+
+// thick lines
+impl PPMImg {
+    /// Draws a polyline as a thick line with `fg_color`
+    pub fn stroke_polyline(
+        &mut self,
+        points: &[(f64, f64)],
+        closed: bool,
+        stroke: &stroke::Stroke,
+    ) {
+        let points = self.all_to_screen(points);
+        let view = match (stroke.world_width, self.viewport) {
+            (true, Some((sx, _, sy, _))) => (sx * sy).abs().sqrt(),
+            _ => 1.0,
+        };
+        let stroke = stroke::Stroke {
+            width: stroke.width * view * self.samples as f64,
+            ..*stroke
+        };
+        for piece in stroke.outline(&points, closed).iter() {
+            self.fill_screen_polygon(piece);
+        }
+    }
+
+    /// Draws an edge matrix as thick lines
+    ///
+    /// Edges that start where the one before ends are joined into one polyline, so that curves
+    /// get proper joins; a polyline that comes back to its start is closed.
+    /// Each edge takes the color of its first point, and a change of color starts a new polyline.
+    pub fn stroke_edge_matrix(&mut self, m: &Matrix, stroke: &stroke::Stroke) {
+        let mut polyline: Vec<(f64, f64)> = vec![];
+        let mut color = self.fg_color;
+        let mut iter = m.iter_by_row().enumerate();
+        while let Some((i, p0)) = iter.next() {
+            let (_, p1) = iter.next().expect("Number of edges must be a multiple of 2");
+            let (p0, p1) = ((p0[0], p0[1]), (p1[0], p1[1]));
+            let c = self.row_color(m, i);
+            if polyline.last() != Some(&p0) || c != color {
+                self.stroke_joined(&polyline, stroke, color);
+                polyline = vec![p0];
+                color = c;
+            }
+            polyline.push(p1);
+        }
+        self.stroke_joined(&polyline, stroke, color);
+    }
+
+    fn stroke_joined(&mut self, polyline: &[(f64, f64)], stroke: &stroke::Stroke, color: RGB) {
+        let closed = polyline.len() > 2 && polyline.first() == polyline.last();
+        let fg = self.fg_color;
+        self.fg_color = color;
+        self.stroke_polyline(polyline, closed, stroke);
+        self.fg_color = fg;
+    }
+}
+
+// backgrounds and guides
+impl PPMImg {
+    /// Paints every pixel `color`, which becomes the background for `clear`
+    pub fn fill_background(&mut self, color: RGB) {
+        self.bg_color = color;
+        self.background = None;
+        self.data.iter_mut().for_each(|d| *d = color);
+        if let Some(alpha) = self.alpha.as_mut() {
+            alpha.iter_mut().for_each(|a| *a = 1.0);
+        }
+    }
+
+    /// Paints `src` stretched over the whole image, as a background to trace over or composite
+    /// onto, which becomes what `clear` goes back to
+    ///
+    /// Where `src` is see through, the background color shows.
+    pub fn fill_background_image(&mut self, src: &PPMImg) {
+        let resized;
+        let src = if src.width != self.width || src.height != self.height {
+            resized = resize::resize(src, self.width, self.height, resize::Filter::Bilinear);
+            &resized
+        } else {
+            src
+        };
+        let scale = self.depth as f64 / src.depth as f64;
+        for (i, pixel) in self.data.iter_mut().enumerate() {
+            let a = src.alpha.as_ref().map_or(1.0, |alpha| alpha[i]);
+            *pixel = (src.data[i] * scale).with_alpha(a).over(self.bg_color);
+        }
+        if let Some(alpha) = self.alpha.as_mut() {
+            alpha.iter_mut().for_each(|a| *a = 1.0);
+        }
+        self.background = Some(self.data.clone());
+    }
+
+    /// Paints `gradient` from the top of the image at 0 to the bottom at 1, like a sky, which
+    /// becomes what `clear` goes back to
+    pub fn fill_background_gradient(&mut self, gradient: &colors::Gradient) {
+        let (width, last) = (self.width as usize, self.height.max(2) as f64 - 1.0);
+        for (y, row) in self.data.chunks_mut(width).enumerate() {
+            let color = gradient.at(y as f64 / last);
+            row.iter_mut().for_each(|d| *d = color);
+        }
+        if let Some(alpha) = self.alpha.as_mut() {
+            alpha.iter_mut().for_each(|a| *a = 1.0);
+        }
+        self.background = Some(self.data.clone());
+    }
+
+    /// Paints the image in squares of `size` pixels, starting with `c0` at the top left
+    pub fn fill_checkerboard(&mut self, size: u32, c0: RGB, c1: RGB) {
+        assert!(size > 0, "Squares must be at least a pixel");
+        let size = size * self.samples;
+        let width = self.width;
+        for (i, d) in self.data.iter_mut().enumerate() {
+            let (x, y) = (i as u32 % width / size, i as u32 / width / size);
+            *d = if (x + y) % 2 == 0 { c0 } else { c1 };
+        }
+        if let Some(alpha) = self.alpha.as_mut() {
+            alpha.iter_mut().for_each(|a| *a = 1.0);
+        }
+    }
+
+    /// Draws lines every `spacing` apart across the whole image, through the origin
+    ///
+    /// Spacing is in world coordinates when they are set, as for everything else drawn.
+    pub fn draw_grid(&mut self, spacing: f64, color: RGB) {
+        assert!(spacing > 0.0, "Grid spacing must be positive");
+        let ((xmin, ymin), (xmax, ymax)) = self.visible_world();
+        let steps = |min: f64, max: f64| {
+            ((min / spacing).ceil() as i64..=(max / spacing).floor() as i64)
+                .map(move |i| i as f64 * spacing)
+        };
+        for x in steps(xmin, xmax) {
+            self.draw_line_blend(x, ymin, x, ymax, color, color);
+        }
+        for y in steps(ymin, ymax) {
+            self.draw_line_blend(xmin, y, xmax, y, color, color);
+        }
+    }
+
+    /// Draws the x and y axes across the whole image
+    pub fn draw_axes(&mut self, color: RGB) {
+        let ((xmin, ymin), (xmax, ymax)) = self.visible_world();
+        self.draw_line_blend(xmin, 0.0, xmax, 0.0, color, color);
+        self.draw_line_blend(0.0, ymin, 0.0, ymax, color, color);
+    }
+
+    /// Corners of the part of the world that is on the image, as (min, max)
+    fn visible_world(&self) -> ((f64, f64), (f64, f64)) {
+        let (x0, y0) = self.screen_to_world(-0.5, -0.5);
+        let (x1, y1) = self.screen_to_world(self.width as f64 - 0.5, self.height as f64 - 0.5);
+        ((x0.min(x1), y0.min(y1)), (x0.max(x1), y0.max(y1)))
+    }
+}
+
+// per pixel effects
+impl PPMImg {
+    /// Replaces every pixel by what `f` makes of its position and color
+    ///
+    /// Positions are of the image's own pixels, so a supersampled image passes each sample.
+    /// Transparency is left as it is.
+    pub fn map_pixels(&mut self, mut f: impl FnMut(i32, i32, RGB) -> RGB) {
+        let width = self.width as usize;
+        for (i, color) in self.data.iter_mut().enumerate() {
+            *color = f((i % width) as i32, (i / width) as i32, *color);
+        }
+    }
+
+    /// Runs post-processing passes over the image, in order; see `effects`
+    pub fn post_process(&mut self, passes: &[&dyn effects::Effect]) {
+        for pass in passes.iter() {
+            pass.apply(self);
+        }
+    }
+}
+
+// text
+impl PPMImg {
+    /// Draws `text` in the built in 5 by 7 pixel font, with the top left of the first glyph at
+    /// the pixel at (x, y)
+    ///
+    /// Glyphs stay the same size in pixels under world coordinates; see `bitmap_font` for the
+    /// layout.
+    pub fn draw_text(&mut self, x: f64, y: f64, text: &str, color: RGB) {
+        let s = self.samples as i32;
+        let (sx, sy) = self.world_to_screen(x, y);
+        // the top left sample of the pixel
+        let half = (s - 1) as f64 / 2.0;
+        let (x0, y0) = ((sx - half).round() as i32, (sy - half).round() as i32);
+        for (dx, dy) in bitmap_font::pixels(text) {
+            for i in 0..s * s {
+                self.plot_color(x0 + dx * s + i % s, y0 + dy * s + i / s, color);
+            }
+        }
+    }
+}
+
+// sprites
+impl PPMImg {
+    /// Copies `src` onto the image, with its top left at the pixel at (x, y), leaving out
+    /// whatever falls off the edges
+    ///
+    /// Like text, `src` keeps its size in pixels under world coordinates. Its colors are drawn
+    /// like everything else, at `fg_alpha`.
+    pub fn blit(&mut self, src: &PPMImg, x: f64, y: f64, transparency: Transparency) {
+        if src.samples > 1 {
+            return self.blit(&src.downsample(), x, y, transparency);
+        }
+        let s = self.samples as i32;
+        let (sx, sy) = self.world_to_screen(x, y);
+        let half = (s - 1) as f64 / 2.0;
+        let (x0, y0) = ((sx - half).round() as i32, (sy - half).round() as i32);
+        let (w, h) = (src.width as i32 * s, src.height as i32 * s);
+        // the samples covered, trimmed to the image along the axes that clip
+        let (clip_x, clip_y) = self.clips();
+        let (xs, ys) = (
+            if clip_x { x0.max(0)..(x0 + w).min(self.width as i32) } else { x0..x0 + w },
+            if clip_y { y0.max(0)..(y0 + h).min(self.height as i32) } else { y0..y0 + h },
+        );
+        let scale = self.depth as f64 / src.depth as f64;
+        for py in ys {
+            for px in xs.clone() {
+                let i = ((py - y0) / s * src.width as i32 + (px - x0) / s) as usize;
+                let color = src.data[i];
+                let alpha = match transparency {
+                    Transparency::Opaque => 1.0,
+                    Transparency::ColorKey(key) if color == key => continue,
+                    Transparency::ColorKey(_) => 1.0,
+                    Transparency::Alpha => src.alpha.as_ref().map_or(1.0, |a| a[i]),
+                };
+                if let Some(index) = self.plot_index(px, py) {
+                    let color = if scale == 1.0 { color } else { color * scale };
+                    self.put(index, color.with_alpha(alpha));
+                }
+            }
         }
     }
 }
@@ -365,6 +1532,10 @@ impl PPMImg {
                     continue;
                 }
                 self.data[index] = fcolor;
+                if let Some(alpha) = self.alpha.as_mut() {
+                    alpha[index] = 1.0;
+                }
+                self.count_write(index);
                 points.push((x + 1, y));
                 points.push((x, y + 1));
                 points.push((x - 1, y));
@@ -373,4 +1544,510 @@ impl PPMImg {
             assert!(points.len() <= (self.width * self.height).try_into().unwrap());
         }
     }
+
+    /// Fill the region around (x, y) with `color`, the way a paint bucket does
+    ///
+    /// The region is every pixel reachable from (x, y) through pixels whose channels are all
+    /// within `tolerance` of the color at (x, y).
+    pub fn flood_fill(
+        &mut self,
+        x: i32,
+        y: i32,
+        color: RGB,
+        connectivity: Connectivity,
+        tolerance: u16,
+    ) {
+        let seed = match self.index(x, y) {
+            Some(index) => self.data[index],
+            None => return,
+        };
+        let close = |c: RGB| {
+            c.red.abs_diff(seed.red) <= tolerance
+                && c.green.abs_diff(seed.green) <= tolerance
+                && c.blue.abs_diff(seed.blue) <= tolerance
+        };
+        let neighbors: &[(i32, i32)] = match connectivity {
+            Connectivity::Four => &[(1, 0), (0, 1), (-1, 0), (0, -1)],
+            Connectivity::Eight => &[
+                (1, 0),
+                (1, 1),
+                (0, 1),
+                (-1, 1),
+                (-1, 0),
+                (-1, -1),
+                (0, -1),
+                (1, -1),
+            ],
+        };
+        // the fill color may itself be within tolerance, so remember where we have been
+        let mut seen = vec![false; self.data.len()];
+        let mut points = vec![(x, y)];
+        while let Some((x, y)) = points.pop() {
+            if let Some(index) = self.index(x, y) {
+                if seen[index] || !close(self.data[index]) {
+                    continue;
+                }
+                seen[index] = true;
+                self.put(index, color.into());
+                points.extend(neighbors.iter().map(|(dx, dy)| (x + dx, y + dy)));
+            }
+        }
+    }
+}
+
+/// Which pixels count as touching, for filling
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Connectivity {
+    /// Only the pixels beside, above and below
+    Four,
+    /// Diagonals as well
+    Eight,
+}
+
+/// Depth cueing, fading what is far away into a color, usually the background
+///
+/// The viewer looks down the z axis from +z, so the fog starts at z of `front` and hides
+/// everything at `back` and behind, fading linearly in between.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Fog {
+    pub color: RGB,
+    pub front: f64,
+    pub back: f64,
+}
+
+impl Fog {
+    pub fn new(color: RGB, front: f64, back: f64) -> Self {
+        Fog { color, front, back }
+    }
+
+    /// How much of a color at depth `z` is fog, from 0 to 1
+    pub fn amount(&self, z: f64) -> f64 {
+        if self.front == self.back {
+            return if z > self.front { 0.0 } else { 1.0 };
+        }
+        ((self.front - z) / (self.front - self.back)).clamp(0.0, 1.0)
+    }
+}
+
+/// What happens to pixels drawn past an edge of the image
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OutOfBounds {
+    /// They are left out
+    Clip,
+    /// They come back in on the other side, as if the image were a torus
+    Wrap,
+    /// They are reflected back in at the edge, so that drawings fold over
+    Mirror,
+    /// Drawing them panics, to catch drawings that are not meant to leave the image
+    Error,
+}
+
+impl OutOfBounds {
+    /// Where `v` lands along an axis of `n` pixels, if anywhere
+    fn place(self, v: i32, n: i32) -> Option<i32> {
+        match self {
+            _ if (0..n).contains(&v) => Some(v),
+            OutOfBounds::Clip | OutOfBounds::Error => None,
+            OutOfBounds::Wrap => Some(v.rem_euclid(n)),
+            OutOfBounds::Mirror => {
+                let v = v.rem_euclid(2 * n);
+                Some(if v < n { v } else { 2 * n - 1 - v })
+            }
+        }
+    }
+}
+
+/// Which pixels of a sprite are left out when it is blitted
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Transparency {
+    /// Every pixel is copied
+    Opaque,
+    /// Pixels of this color are left out, for sprites without transparency of their own
+    ColorKey(RGB),
+    /// Pixels are blended by their opacity, for sprites drawn with `new_transparent` or read
+    /// from a png with transparency
+    Alpha,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_in_every_octant() {
+        let red = RGB::new(255, 0, 0);
+        let ends = [(9, 4), (4, 9), (-4, 9), (-9, 4), (-9, -4), (-4, -9), (4, -9), (9, -4)];
+        for &(dx, dy) in ends.iter() {
+            let mut canvas = Canvas::new(21, 21, 255);
+            let mut m = Matrix::new(0, 4, vec![]);
+            m.append_edge(&[10.0, 10.0, 0.0, 10.0 + dx as f64, 10.0 + dy as f64, 0.0]);
+            canvas.draw_lines(&m, red);
+
+            assert_eq!(Some(red), canvas.pixel(10, 10));
+            assert_eq!(Some(red), canvas.pixel(10 + dx, 10 + dy));
+            // one pixel per step along the longer axis
+            let lit = canvas.data.iter().filter(|&&c| c == red).count();
+            assert_eq!(9 + 1, lit);
+            assert!(canvas.fg_color != red);
+        }
+    }
+
+    #[test]
+    fn p6_and_p3_agree() {
+        let mut img = PPMImg::new(1, 2, 255);
+        img.fg_color = RGB::new(200, 100, 50);
+        img.plot(1, 0);
+
+        let mut p6 = vec![];
+        img.write_p6(&mut p6).unwrap();
+        assert_eq!(b"P6\n2 1 255\n\0\0\0\xc8\x64\x32".to_vec(), p6);
+        let mut p3 = vec![];
+        img.write_p3(&mut p3).unwrap();
+        assert_eq!("P3\n2 1 255\n0 0 0\n200 100 50\n", String::from_utf8(p3).unwrap());
+    }
+
+    #[test]
+    fn missing_programs_are_reported() {
+        let img = PPMImg::new(1, 1, 255);
+        let err = img.pipe_to(&[("no-such-program-here", &[])]).unwrap_err();
+        assert_eq!(io::ErrorKind::NotFound, err.kind());
+    }
+
+    #[test]
+    fn thick_lines_fill_their_outline() {
+        let mut img = PPMImg::new(40, 40, 255);
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.append_edge(&[10.0, 10.0, 0.0, 30.0, 10.0, 0.0]);
+        img.stroke_edge_matrix(&m, &stroke::Stroke::new(4.0));
+        // as many columns and rows as it is long and wide
+        let lit = img.data.iter().filter(|&&c| c == img.fg_color).count();
+        assert_eq!(20 * 4, lit);
+        assert_eq!(Some(img.fg_color), img.pixel(10, 8));
+        assert_eq!(Some(img.fg_color), img.pixel(29, 11));
+        assert_eq!(Some(img.bg_color), img.pixel(30, 12));
+    }
+
+    #[test]
+    fn flood_fill_stops_at_lines() {
+        // a diagonal line splits the image for 4 connected fills, but lets 8 connected ones through
+        let mut img = PPMImg::new(10, 10, 255);
+        img.draw_line(0.0, 0.0, 9.0, 9.0);
+        let red = RGB::new(255, 0, 0);
+        img.flood_fill(9, 0, red, Connectivity::Four, 0);
+        assert_eq!(45, img.data.iter().filter(|&&c| c == red).count());
+        img.flood_fill(9, 0, RGB::gray(0), Connectivity::Four, 0);
+
+        img.flood_fill(9, 0, red, Connectivity::Eight, 0);
+        assert_eq!(90, img.data.iter().filter(|&&c| c == red).count());
+        // with enough tolerance the line goes too
+        img.flood_fill(0, 0, red, Connectivity::Four, 255);
+        assert!(img.data.iter().all(|&c| c == red));
+    }
+
+    #[test]
+    fn edges_use_their_colors() {
+        let mut img = PPMImg::new(10, 10, 255);
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.append_edge(&[0.0, 0.0, 0.0, 9.0, 0.0, 0.0]);
+        m.set_pen(Some(RGB::RED));
+        m.append_edge(&[0.0, 5.0, 0.0, 9.0, 5.0, 0.0]);
+        m.set_color(3, Some(RGB::BLUE));
+        let m = m.mul(&Matrix::mv(0.0, 1.0, 0.0));
+        img.render_edge_matrix(&m);
+
+        assert_eq!(Some(img.fg_color), img.pixel(3, 1));
+        assert_eq!(Some(RGB::RED), img.pixel(0, 6));
+        assert_eq!(Some(RGB::BLUE), img.pixel(9, 6));
+        assert_eq!(Some(RGB::new(142, 0, 113)), img.pixel(4, 6));
+    }
+
+    #[test]
+    fn translucent_lines_accumulate() {
+        let mut img = PPMImg::new(10, 10, 255);
+        img.fg_color = RGB::RED;
+        img.fg_alpha = 0.5;
+        img.draw_line(0.0, 5.0, 9.0, 5.0);
+        img.draw_line(5.0, 0.0, 5.0, 9.0);
+        assert_eq!(Some(RGB::new(128, 0, 0)), img.pixel(0, 5));
+        assert_eq!(Some(RGB::new(192, 0, 0)), img.pixel(5, 5));
+
+        img.fg_alpha = 1.0;
+        img.plot_rgba(0, 0, RGB::WHITE.with_alpha(0.25));
+        assert_eq!(Some(RGB::gray(64)), img.pixel(0, 0));
+    }
+
+    #[test]
+    fn world_bounds_flip_y() {
+        let mut img = PPMImg::new(11, 21, 255);
+        img.set_world_bounds(-1.0, 0.0, 1.0, 1.0);
+        assert_eq!((10.0, 10.0), img.world_to_screen(0.0, 0.0));
+        assert_eq!((20.0, 0.0), img.world_to_screen(1.0, 1.0));
+        assert_eq!((0.5, 0.25), img.screen_to_world(15.0, 7.5));
+
+        img.draw_line(-1.0, 1.0, 1.0, 1.0);
+        assert_eq!(Some(img.fg_color), img.pixel(0, 0));
+        assert_eq!(Some(img.fg_color), img.pixel(20, 0));
+    }
+
+    #[test]
+    fn far_off_lines_are_clipped() {
+        let mut img = PPMImg::new(10, 10, 255);
+        // walking this pixel by pixel would take a billion steps
+        img.draw_line(-1e9, 5.0, 1e9, 5.0);
+        assert!((0..10).all(|x| img.pixel(x, 5) == Some(img.fg_color)));
+        img.draw_line(-1e9, -1e9, -1e9, 1e9);
+        assert_eq!(10, img.data.iter().filter(|&&c| c == img.fg_color).count());
+    }
+
+    #[test]
+    fn off_image_pixels_follow_the_bounds() {
+        let mut img = PPMImg::new(2, 3, 255);
+        img.plot_color(-1, 0, RGB::RED);
+        assert!(img.data.iter().all(|&c| c == RGB::BLACK));
+        img.x_bounds = OutOfBounds::Wrap;
+        img.plot_color(-1, 0, RGB::RED);
+        assert_eq!(Some(RGB::RED), img.pixel(2, 0));
+        img.set_bounds(OutOfBounds::Mirror);
+        img.plot_color(4, -1, RGB::BLUE);
+        img.draw_line_blend(-2.0, 1.0, 0.0, 1.0, RGB::GREEN, RGB::GREEN);
+        assert_eq!(Some(RGB::BLUE), img.pixel(1, 0));
+        assert_eq!(vec![RGB::GREEN, RGB::GREEN, RGB::BLACK], img.rows().nth(1).unwrap());
+
+        img.set_bounds(OutOfBounds::Error);
+        img.plot_color(2, 1, RGB::RED);
+        let off = std::panic::catch_unwind(move || img.draw_line(0.0, 0.0, 3.0, 0.0));
+        assert!(off.is_err());
+    }
+
+    #[test]
+    fn supersampling_averages_edges() {
+        let mut img = PPMImg::new_supersampled(2, 3, 255, 4);
+        assert_eq!((8, 12), (img.height(), img.width()));
+        // covers the first pixel and the left half of the second
+        img.fill_polygon(&[(-0.5, -0.5), (1.0, -0.5), (1.0, 1.5), (-0.5, 1.5)]);
+        let small = img.downsample();
+        assert_eq!((2, 3), (small.height(), small.width()));
+        assert_eq!(Some(RGB::gray(255)), small.pixel(0, 1));
+        assert_eq!(Some(RGB::gray(128)), small.pixel(1, 1));
+        assert_eq!(Some(RGB::gray(0)), small.pixel(2, 1));
+
+        let mut p6 = vec![];
+        img.write_p6(&mut p6).unwrap();
+        assert!(p6.starts_with(b"P6\n3 2 255\n"));
+        assert_eq!(11 + 2 * 3 * 3, p6.len());
+    }
+
+    #[test]
+    fn circles_and_ellipses_are_pixel_exact() {
+        let mut img = PPMImg::new(9, 9, 255);
+        // half opacity, so that a pixel plotted twice would come out brighter
+        img.fg_color = RGB::WHITE;
+        img.fg_alpha = 0.5;
+        img.draw_circle(4.0, 4.0, 3.0);
+        let lit: Vec<(i32, i32)> = (0..81)
+            .map(|i| (i % 9, i / 9))
+            .filter(|&(x, y)| img.pixel(x, y) != Some(RGB::BLACK))
+            .collect();
+        assert_eq!(16, lit.len());
+        assert!(lit.iter().all(|&(x, y)| img.pixel(x, y) == Some(RGB::gray(128))));
+        assert!(lit.contains(&(4, 1)) && lit.contains(&(7, 4)) && lit.contains(&(6, 6)));
+
+        let mut img = PPMImg::new(5, 21, 255);
+        img.fg_color = RGB::RED;
+        img.draw_ellipse(10.0, 2.0, 10.0, 1.0);
+        assert_eq!(Some(RGB::RED), img.pixel(0, 2));
+        assert_eq!(Some(RGB::RED), img.pixel(20, 2));
+        assert_eq!(Some(RGB::RED), img.pixel(10, 1));
+        assert_eq!(Some(RGB::BLACK), img.pixel(10, 2));
+    }
+
+    #[test]
+    fn ppm_files_read_back() {
+        let mut img = PPMImg::new(2, 3, 1000);
+        img.plot_color(1, 0, RGB::new(1000, 5, 300));
+        let mut bytes = vec![];
+        img.write_p6(&mut bytes).unwrap();
+        let back = PPMImg::from_ppm(&bytes).unwrap();
+        assert_eq!((3, 2, 1000), (back.width(), back.height(), back.depth()));
+        assert_eq!(img.data, back.data);
+
+        let text = b"P2 # a comment\n2 1\n# another\n9\n3 9\n";
+        let back = PPMImg::from_ppm(text).unwrap();
+        assert_eq!(vec![RGB::new(3, 3, 3), RGB::new(9, 9, 9)], back.data);
+        assert!(PPMImg::from_ppm(b"P3 2 1 255 0 0").is_err());
+        // sizes that would overflow when multiplied
+        let huge = PPMImg::from_ppm(b"P6 4294967295 4294967295 255\n\0\0\0");
+        assert_eq!(Some(io::ErrorKind::InvalidData), huge.err().map(|e| e.kind()));
+        assert!(PPMImg::from_ppm(b"P6 0 5 255\n").is_err());
+    }
+
+    #[test]
+    fn background_images_come_back_on_clear() {
+        let mut photo = PPMImg::new_transparent(1, 2, 255);
+        photo.plot_color(0, 0, RGB::GREEN);
+        let mut img = PPMImg::new_with_bg(1, 2, 255, RGB::BLUE);
+        img.fill_background_image(&photo);
+        img.plot_color(0, 0, RGB::RED);
+        img.clear();
+        assert_eq!(vec![RGB::GREEN, RGB::BLUE], img.data);
+        img.fill_background(RGB::WHITE);
+        img.clear();
+        assert_eq!(vec![RGB::WHITE; 2], img.data);
+    }
+
+    #[test]
+    fn point_colors_blend_across_triangles() {
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.append_polygon(&[0.0, 0.0, 0.0, 0.0, 20.0, 0.0, 20.0, 0.0, 0.0]);
+        for (row, color) in [RGB::RED, RGB::GREEN, RGB::BLUE].iter().enumerate() {
+            m.set_color(row, Some(*color));
+        }
+        let mut img = PPMImg::new(21, 21, 255);
+        img.fill_polygon_matrix(&m, false);
+        assert_eq!(Some(RGB::RED), img.pixel(0, 0));
+        let (green, blue) = (img.pixel(1, 18).unwrap(), img.pixel(18, 1).unwrap());
+        assert!(green.green > 200 && green.red < 30 && green.blue < 30, "{:?}", green);
+        assert!(blue.blue > 200 && blue.red < 30 && blue.green < 30, "{:?}", blue);
+        assert_eq!(Some(RGB::new(128, 64, 64)), img.pixel(5, 5));
+        // culled from the front, as the triangle winds clockwise
+        let mut culled = PPMImg::new(21, 21, 255);
+        culled.fill_polygon_matrix(&m, true);
+        assert_eq!(Some(RGB::BLACK), culled.pixel(5, 5));
+    }
+
+    #[test]
+    fn sprites_are_keyed_and_clipped() {
+        let mut sprite = PPMImg::new_with_bg(2, 2, 255, RGB::MAGENTA);
+        sprite.plot_color(1, 1, RGB::RED);
+        sprite.plot_color(0, 1, RGB::BLUE);
+        let mut img = PPMImg::new(3, 3, 255);
+        // hanging off the bottom right corner
+        img.blit(&sprite, 2.0, 1.0, Transparency::ColorKey(RGB::MAGENTA));
+        assert_eq!(Some(RGB::BLUE), img.pixel(2, 2));
+        assert_eq!(Some(RGB::BLACK), img.pixel(2, 1));
+        img.blit(&sprite, -1.0, 0.0, Transparency::Opaque);
+        assert_eq!(Some(RGB::MAGENTA), img.pixel(0, 0));
+        assert_eq!(Some(RGB::RED), img.pixel(0, 1));
+
+        let mut glass = PPMImg::new_transparent(1, 1, 255);
+        glass.fg_alpha = 0.5;
+        glass.plot_color(0, 0, RGB::WHITE);
+        img.blit(&glass, 1.0, 1.0, Transparency::Alpha);
+        assert_eq!(Some(RGB::gray(128)), img.pixel(1, 1));
+    }
+
+    #[test]
+    fn fog_fades_with_depth() {
+        let fog = Fog::new(RGB::BLUE, 0.0, -10.0);
+        assert_eq!((0.0, 0.5, 1.0), (fog.amount(3.0), fog.amount(-5.0), fog.amount(-20.0)));
+        let mut img = PPMImg::new(1, 11, 255);
+        img.fog = Some(fog);
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.append_edge(&[0.0, 0.0, 0.0, 10.0, 0.0, -10.0]);
+        img.draw_lines(&m, RGB::WHITE);
+        assert_eq!(Some(RGB::WHITE), img.pixel(0, 0));
+        assert_eq!(Some(RGB::new(128, 128, 255)), img.pixel(5, 0));
+        assert_eq!(Some(RGB::BLUE), img.pixel(10, 0));
+    }
+
+    #[test]
+    fn additive_strokes_build_up() {
+        let mut img = PPMImg::new(1, 3, 255);
+        img.blend_mode = BlendMode::Add;
+        img.fg_color = RGB::new(100, 0, 50);
+        img.draw_line(0.0, 0.0, 2.0, 0.0);
+        img.draw_line(1.0, 0.0, 2.0, 0.0);
+        assert_eq!(Some(RGB::new(100, 0, 50)), img.pixel(0, 0));
+        assert_eq!(Some(RGB::new(200, 0, 100)), img.pixel(2, 0));
+    }
+
+    #[test]
+    fn world_widths_grow_with_the_zoom() {
+        let column = |world_width: bool| {
+            let mut img = PPMImg::new(21, 21, 255);
+            img.set_world_bounds(0.0, 0.0, 5.0, 5.0);
+            let stroke = stroke::Stroke::new(1.0).world_width(world_width);
+            img.stroke_polyline(&[(0.0, 2.5), (5.0, 2.5)], false, &stroke);
+            (0..21).filter(|&y| img.pixel(10, y) == Some(RGB::WHITE)).count()
+        };
+        // four pixels to a unit
+        assert_eq!((1, 4), (column(false), column(true)));
+    }
+
+    #[test]
+    fn text_is_stamped_in_pixels() {
+        let mut img = PPMImg::new(10, 20, 255);
+        img.draw_text(1.0, 2.0, "|-", RGB::RED);
+        // the bar down the middle of the first glyph, the dash across the second
+        assert!((2..9).all(|y| img.pixel(3, y) == Some(RGB::RED)));
+        assert!((7..12).all(|x| img.pixel(x, 5) == Some(RGB::RED)));
+        assert_eq!(Some(RGB::BLACK), img.pixel(2, 5));
+    }
+
+    #[test]
+    fn grid_lines_follow_the_world() {
+        let mut img = PPMImg::new(11, 11, 255);
+        img.fill_checkerboard(2, RGB::WHITE, RGB::GRAY);
+        assert_eq!(Some(RGB::GRAY), img.pixel(2, 1));
+        img.fill_background(RGB::BLACK);
+        img.set_world_bounds(-5.0, -5.0, 5.0, 5.0);
+        img.draw_grid(2.5, RGB::BLUE);
+        img.draw_axes(RGB::RED);
+        // world x = 2.5 is pixel 7.5, rounded to 8
+        assert_eq!(Some(RGB::BLUE), img.pixel(8, 0));
+        assert_eq!(Some(RGB::RED), img.pixel(5, 0));
+        assert_eq!(Some(RGB::RED), img.pixel(0, 5));
+        assert_eq!(Some(RGB::BLACK), img.pixel(1, 1));
+    }
+
+    #[test]
+    fn parallel_fills_match_serial_ones() {
+        let triangle = [(-20.0, 10.0), (400.0, 150.0), (30.0, 290.0)];
+        let mut serial = PPMImg::new_transparent(300, 300, 255);
+        serial.fg_alpha = 0.5;
+        let mut parallel = PPMImg::new_transparent(300, 300, 255);
+        parallel.fg_alpha = 0.5;
+        serial.count_writes(true);
+        parallel.count_writes(true);
+        let spans = serial.polygon_spans(&triangle);
+        serial.paint_spans(&spans, false, |_, _| None, |x, _| RGB::gray(x as u16 % 256));
+        parallel.paint_spans(&spans, true, |_, _| None, |x, _| RGB::gray(x as u16 % 256));
+        assert_eq!(0.5, serial.pixel_rgba(100, 150).unwrap().alpha);
+        assert_eq!(Some(RGB::gray(100)), serial.pixel(100, 150));
+        assert_eq!(serial.data, parallel.data);
+        assert_eq!(serial.alpha, parallel.alpha);
+        assert_eq!(serial.writes, parallel.writes);
+        assert_eq!(Some(1), parallel.writes_at(100, 150));
+    }
+
+    #[test]
+    fn depth_test_keeps_the_nearest_surface() {
+        // a flat triangle at z = 0, and one cutting through it, from z = -10 on the left to 10
+        // on the right; by mean z the flat one is in front everywhere
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.set_pen(Some(RGB::RED));
+        m.append_polygon(&[0.0, 0.0, 0.0, 400.0, 0.0, 0.0, 0.0, 400.0, 0.0]);
+        m.set_pen(Some(RGB::BLUE));
+        m.append_polygon(&[0.0, 0.0, -10.0, 400.0, 0.0, 10.0, 0.0, 400.0, -10.0]);
+        // big enough to be filled across threads
+        let mut img = PPMImg::new(400, 400, 255);
+        img.fill_polygon_matrix(&m, false);
+        assert_eq!(Some(RGB::RED), img.pixel(300, 50));
+        assert_eq!(None, img.depth_at(300, 50));
+
+        img.depth_test(true);
+        img.clear();
+        img.fill_polygon_matrix(&m, false);
+        assert_eq!(Some(RGB::RED), img.pixel(100, 50));
+        assert_eq!(Some(RGB::BLUE), img.pixel(300, 50));
+        assert_eq!(Some(5.0), img.depth_at(300, 50));
+        assert_eq!(None, img.depth_at(399, 399));
+
+        // and the same on one thread, which images that do not clip are filled on
+        let mut serial = PPMImg::new(400, 400, 255);
+        serial.x_bounds = OutOfBounds::Error;
+        serial.depth_test(true);
+        serial.fill_polygon_matrix(&m, false);
+        assert!(img.data == serial.data && img.zbuffer == serial.zbuffer);
+    }
 }
@@ -0,0 +1,188 @@
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::io::{self, prelude::Write};
+
+use super::matrix::Matrix;
+use super::mesh::{vertex_key, Mesh, Normals};
+use super::utils::create_file;
+use super::vector::{self, Vec3};
+
+/// Write triangles and edges as a Wavefront OBJ file
+///
+/// Vertices at the same position are written once and shared
+/// # Arguments
+/// `polygons` - Polygon matrix, three points per triangle
+/// `edges` - Edge matrix, two points per line
+pub fn save_obj(filepath: &str, polygons: &Matrix, edges: &Matrix) -> io::Result<()> {
+    let mut file = create_file(filepath)?;
+    write_obj(&mut file, polygons, edges)?;
+    file.flush()
+}
+
+/// Write triangles and edges in the Wavefront OBJ format, see `save_obj`
+pub fn write_obj(w: &mut impl Write, polygons: &Matrix, edges: &Matrix) -> io::Result<()> {
+    let mut mesh = Mesh::from_polygons(polygons);
+    let mut seen: HashMap<_, _> = mesh
+        .vertices
+        .iter()
+        .enumerate()
+        .map(|(i, v)| (vertex_key(*v), i))
+        .collect();
+    let mut lines = vec![];
+    let mut line = [0; 2];
+    for (i, row) in edges.iter_by_row().enumerate() {
+        let v = (row[0], row[1], row[2]);
+        line[i % 2] = *seen
+            .entry(vertex_key(v))
+            .or_insert_with(|| mesh.add_vertex(v));
+        if i % 2 == 1 {
+            lines.push(line);
+        }
+    }
+
+    writeln!(
+        w,
+        "# {} vertices, {} faces, {} lines",
+        mesh.vertices.len(),
+        mesh.faces.len(),
+        lines.len()
+    )?;
+    for v in mesh.vertices.iter() {
+        writeln!(w, "v {} {} {}", v.0, v.1, v.2)?;
+    }
+    // obj indices start at 1
+    for f in mesh.faces.iter() {
+        writeln!(w, "f {} {} {}", f[0] + 1, f[1] + 1, f[2] + 1)?;
+    }
+    for l in lines.iter() {
+        writeln!(w, "l {} {}", l[0] + 1, l[1] + 1)?;
+    }
+    Ok(())
+}
+
+/// Read the faces of a Wavefront OBJ file as a mesh, see `read_obj`
+pub fn open_obj(filepath: &str) -> io::Result<Mesh> {
+    read_obj(&std::fs::read_to_string(filepath)?)
+}
+
+/// Read the vertices and faces of a Wavefront OBJ file, fanning faces of more than three
+/// corners into triangles
+///
+/// If any face gives normals with `f v//vn` or `f v/vt/vn`, the mesh is shaded with them, and
+/// faces that give none take their own normal. Everything else, like lines, texture
+/// coordinates and materials, is skipped.
+pub fn read_obj(text: &str) -> io::Result<Mesh> {
+    let mut mesh = Mesh::new();
+    let mut normals: Vec<Vec3> = vec![];
+    let mut corner_normals: Vec<[Option<Vec3>; 3]> = vec![];
+    for (n, line) in text.lines().enumerate() {
+        let invalid = |what: &str| {
+            let message = format!("Line {} of the OBJ file {}", n + 1, what);
+            io::Error::new(io::ErrorKind::InvalidData, message)
+        };
+        let mut words = line.split_whitespace();
+        let keyword = match words.next() {
+            Some(k) => k,
+            None => continue,
+        };
+        let numbers = |words: std::str::SplitWhitespace| -> io::Result<Vec3> {
+            let v: Vec<f64> = words
+                .take(3)
+                .map(|w| w.parse().map_err(|_| invalid("has a bad number")))
+                .collect::<io::Result<_>>()?;
+            match v.as_slice() {
+                &[x, y, z] => Ok((x, y, z)),
+                _ => Err(invalid("needs three numbers")),
+            }
+        };
+        match keyword {
+            "v" => {
+                mesh.add_vertex(numbers(words)?);
+            }
+            "vn" => normals.push(vector::normalize(numbers(words)?)),
+            "f" => {
+                // obj indices start at 1, and negative ones count back from the last
+                let index = |word: Option<&str>, len: usize| -> io::Result<Option<usize>> {
+                    let word = match word {
+                        Some(w) if !w.is_empty() => w,
+                        _ => return Ok(None),
+                    };
+                    let i: i64 = word.parse().map_err(|_| invalid("has a bad index"))?;
+                    let i = if i < 0 { len as i64 + i } else { i - 1 };
+                    if i < 0 || i >= len as i64 {
+                        return Err(invalid("refers to something missing"));
+                    }
+                    Ok(Some(i as usize))
+                };
+                let mut corners = vec![];
+                for word in words {
+                    let mut parts = word.split('/');
+                    let v = index(parts.next(), mesh.vertices.len())?;
+                    let vn = index(parts.nth(1), normals.len())?;
+                    corners.push((v.ok_or_else(|| invalid("has a corner without a vertex"))?, vn));
+                }
+                if corners.len() < 3 {
+                    return Err(invalid("has a face of fewer than 3 corners"));
+                }
+                for i in 1..corners.len() - 1 {
+                    let face = [corners[0], corners[i], corners[i + 1]];
+                    mesh.add_face(face[0].0, face[1].0, face[2].0);
+                    corner_normals.push(face.map(|(_, vn)| vn.map(|vn| normals[vn])));
+                }
+            }
+            _ => {}
+        }
+    }
+    if corner_normals.iter().flatten().any(|n| n.is_some()) {
+        let faces = mesh.compute_face_normals();
+        let given = corner_normals.iter().zip(faces);
+        mesh.normals = Normals::Explicit(given.map(|(c, f)| c.map(|n| n.unwrap_or(f))).collect());
+    }
+    Ok(mesh)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shares_vertices_between_faces_and_lines() {
+        let mut polygons = Matrix::new(0, 4, vec![]);
+        polygons.append_polygon(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+        let mut edges = Matrix::new(0, 4, vec![]);
+        edges.append_edge(&[1.0, 0.0, 0.0, 0.0, 0.0, 1.5]);
+
+        let mut out = vec![];
+        write_obj(&mut out, &polygons, &edges).unwrap();
+        let expected = [
+            "# 4 vertices, 1 faces, 1 lines",
+            "v 0 0 0",
+            "v 1 0 0",
+            "v 0 1 0",
+            "v 0 0 1.5",
+            "f 1 2 3",
+            "l 2 4",
+        ];
+        assert_eq!(expected.join("\n") + "\n", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn faces_and_normals_read_back() {
+        let text = "# a square and a triangle\n\
+                    v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+                    vn 0 0 2\nvn 0 1 1\n\
+                    f 1//1 2//1 3//2 4//1\n\
+                    f -4 -3 -1\n";
+        let mesh = read_obj(text).unwrap();
+        assert_eq!(vec![[0, 1, 2], [0, 2, 3], [0, 1, 3]], mesh.faces);
+        let (up, tilted) = ((0.0, 0.0, 1.0), vector::normalize((0.0, 1.0, 1.0)));
+        let expected = vec![[up, up, tilted], [up, tilted, up], [up; 3]];
+        assert_eq!(Normals::Explicit(expected), mesh.normals);
+
+        let plain = read_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+        assert_eq!(Normals::default(), plain.normals);
+        let err = read_obj("v 0 0 0\nf 1 2 5\n").unwrap_err();
+        assert_eq!("Line 2 of the OBJ file refers to something missing", err.to_string());
+    }
+}
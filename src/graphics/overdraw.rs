@@ -0,0 +1,123 @@
+#![allow(dead_code)]
+
+//! Counting how often every pixel is drawn, to find wasted work
+
+use std::fmt;
+
+use super::colors::Gradient;
+use super::{PPMImg, RGB};
+
+/// What was drawn while writes were being counted
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OverdrawStats {
+    /// Pixels drawn at least once
+    pub pixels_drawn: usize,
+    /// Pixels drawn more than once
+    pub pixels_overdrawn: usize,
+    /// Every write to every pixel
+    pub writes: u64,
+    /// Writes to the most drawn pixel
+    pub max_writes: u32,
+    pub total_pixels: usize,
+}
+
+impl OverdrawStats {
+    /// Writes per pixel drawn, 1 when nothing is drawn twice
+    pub fn mean_writes(&self) -> f64 {
+        if self.pixels_drawn == 0 {
+            0.0
+        } else {
+            self.writes as f64 / self.pixels_drawn as f64
+        }
+    }
+}
+
+impl fmt::Display for OverdrawStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "pixels drawn:      {} of {}", self.pixels_drawn, self.total_pixels)?;
+        writeln!(f, "drawn over again:  {}", self.pixels_overdrawn)?;
+        writeln!(f, "writes:            {}", self.writes)?;
+        let mean = self.mean_writes();
+        writeln!(f, "writes per pixel:  {:.2} on average, {} at most", mean, self.max_writes)
+    }
+}
+
+impl PPMImg {
+    /// Start counting how many times each pixel is drawn, from zero, or stop
+    ///
+    /// Everything that blends a color into a pixel counts, like lines, fills, text and blits;
+    /// clearing and painting backgrounds do not.
+    pub fn count_writes(&mut self, on: bool) {
+        self.writes = if on { Some(vec![0; self.data.len()]) } else { None };
+    }
+
+    /// Writes to the pixel at (x, y), if they are being counted
+    pub fn writes_at(&self, x: i32, y: i32) -> Option<u32> {
+        let i = self.index(x, y)?;
+        self.writes.as_ref().map(|w| w[i])
+    }
+
+    pub fn overdraw_stats(&self) -> Option<OverdrawStats> {
+        let writes = self.writes.as_ref()?;
+        Some(OverdrawStats {
+            pixels_drawn: writes.iter().filter(|&&n| n > 0).count(),
+            pixels_overdrawn: writes.iter().filter(|&&n| n > 1).count(),
+            writes: writes.iter().map(|&n| n as u64).sum(),
+            max_writes: writes.iter().copied().max().unwrap_or(0),
+            total_pixels: writes.len(),
+        })
+    }
+
+    /// The write counts as an image, black where nothing was drawn, blue where pixels were
+    /// drawn once, then through red and yellow to white at the most drawn pixel
+    pub fn overdraw_heatmap(&self) -> Option<PPMImg> {
+        let writes = self.writes.as_ref()?;
+        let heat = Gradient::even(&[RGB::BLUE, RGB::RED, RGB::YELLOW, RGB::WHITE]);
+        let max = writes.iter().copied().max().unwrap_or(0);
+        let mut img = PPMImg::new(self.height, self.width, 255);
+        for (pixel, &n) in img.data.iter_mut().zip(writes.iter()) {
+            *pixel = match n {
+                0 => RGB::BLACK,
+                _ if max == 1 => heat.at(0.0),
+                n => heat.at((n - 1) as f64 / (max - 1) as f64),
+            };
+        }
+        Some(img)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::Matrix;
+
+    #[test]
+    fn overdraw_shows_dense_curves() {
+        let mut img = PPMImg::new(20, 20, 255);
+        assert!(img.overdraw_stats().is_none());
+        img.count_writes(true);
+        img.draw_line(0.0, 0.0, 19.0, 0.0);
+        let stats = img.overdraw_stats().unwrap();
+        let counts = (stats.pixels_drawn, stats.pixels_overdrawn, stats.writes, stats.max_writes);
+        assert_eq!((20, 0, 20, 1), counts);
+        assert_eq!(1.0, stats.mean_writes());
+
+        // a small circle made of far more segments than it has pixels
+        let mut m = Matrix::new(0, 4, vec![]);
+        let n = 500;
+        for i in 0..n {
+            let (a, b) = (i as f64 / n as f64, (i + 1) as f64 / n as f64);
+            let p = |t: f64| (10.0 + 4.0 * (t * 6.3).cos(), 10.0 + 4.0 * (t * 6.3).sin());
+            let (p, q) = (p(a), p(b));
+            m.append_edge(&[p.0, p.1, 0.0, q.0, q.1, 0.0]);
+        }
+        img.render_edge_matrix(&m);
+        let stats = img.overdraw_stats().unwrap();
+        assert!(stats.max_writes > 5 && stats.mean_writes() > 2.0, "{}", stats);
+        let heatmap = img.overdraw_heatmap().unwrap();
+        assert_eq!(Some(RGB::BLUE), heatmap.pixel(0, 0));
+        assert_eq!(Some(RGB::BLACK), heatmap.pixel(0, 19));
+        img.count_writes(false);
+        assert!(img.writes_at(0, 0).is_none());
+    }
+}
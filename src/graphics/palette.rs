@@ -0,0 +1,144 @@
+#![allow(dead_code)]
+
+//! Picking a few colors that stand for all the colors of an image
+
+use std::collections::HashMap;
+
+use super::{PPMImg, RGB};
+
+/// How an image with too many colors gets its palette
+#[derive(Clone, Debug, PartialEq)]
+pub enum Quantizer {
+    /// 6 levels of red and blue and 7 of green, the same for every image
+    Uniform,
+    /// Variance cut, fit to the colors of each image
+    VarianceCut,
+    /// A palette picked ahead of time, of 8 bit colors, so that every frame of an animation
+    /// uses the same colors; see `variance_cut`
+    Fixed(Vec<[u8; 3]>),
+}
+
+/// A palette of at most `n` colors for some 8 bit pixels, by variance cut
+///
+/// The colors are split into boxes, over and over cutting in two the box that is widest along
+/// some channel, where the two halves vary the least along it. Each box gives the average of
+/// its colors, weighted by how many pixels have them. Pixels with at most `n` colors get
+/// exactly those colors.
+pub fn variance_cut(pixels: &[[u8; 3]], n: usize) -> Vec<[u8; 3]> {
+    assert!(n > 0, "A palette needs at least one color");
+    let mut counts: HashMap<[u8; 3], u64> = HashMap::new();
+    for p in pixels.iter() {
+        *counts.entry(*p).or_insert(0) += 1;
+    }
+    // sorted, so that the same pixels always give the same palette
+    let mut colors: Vec<([u8; 3], u64)> = counts.into_iter().collect();
+    colors.sort_unstable();
+    if colors.len() <= n {
+        return colors.into_iter().map(|(c, _)| c).collect();
+    }
+
+    let widest = |b: &[([u8; 3], u64)]| {
+        (0..3)
+            .map(|ch| {
+                let (lo, hi) = b.iter().fold((255, 0), |(lo, hi), (c, _)| {
+                    (c[ch].min(lo), c[ch].max(hi))
+                });
+                (hi - lo, ch)
+            })
+            .max()
+            .unwrap()
+    };
+    let mut boxes = vec![colors];
+    while boxes.len() < n {
+        let (i, (range, ch)) = match boxes
+            .iter()
+            .enumerate()
+            .map(|(i, b)| (i, widest(b)))
+            .max_by_key(|&(_, w)| w.0)
+        {
+            Some(best) => best,
+            None => break,
+        };
+        if range == 0 {
+            break;
+        }
+        let mut b = boxes.swap_remove(i);
+        b.sort_by_key(|(c, _)| c[ch]);
+        // unlike a cut at the median, this keeps a big cluster in one piece
+        let total = b.iter().fold((0.0, 0.0, 0.0), |(w, s, q), &(c, k)| {
+            let (v, k) = (c[ch] as f64, k as f64);
+            (w + k, s + v * k, q + v * v * k)
+        });
+        let spread = |(w, s, q): (f64, f64, f64)| if w > 0.0 { q - s * s / w } else { 0.0 };
+        let (mut left, mut cut, mut best) = ((0.0, 0.0, 0.0), 1, f64::INFINITY);
+        for (k, &(c, count)) in b.iter().enumerate().take(b.len() - 1) {
+            let (v, count) = (c[ch] as f64, count as f64);
+            left = (left.0 + count, left.1 + v * count, left.2 + v * v * count);
+            let right = (total.0 - left.0, total.1 - left.1, total.2 - left.2);
+            let cost = spread(left) + spread(right);
+            if cost < best {
+                best = cost;
+                cut = k + 1;
+            }
+        }
+        let rest = b.split_off(cut);
+        boxes.push(b);
+        boxes.push(rest);
+    }
+    boxes
+        .iter()
+        .map(|b| {
+            let total = b.iter().map(|(_, k)| k).sum::<u64>() as f64;
+            [0, 1, 2].map(|ch| {
+                let sum: f64 = b.iter().map(|(c, k)| c[ch] as f64 * *k as f64).sum();
+                (sum / total).round() as u8
+            })
+        })
+        .collect()
+}
+
+impl PPMImg {
+    /// A palette of at most `n` colors that stand for the image, see `variance_cut`
+    ///
+    /// Good for color schemes shared by every frame of an animation, with `Quantizer::Fixed`.
+    pub fn palette(&self, n: usize) -> Vec<RGB> {
+        let pixels: Vec<[u8; 3]> = self
+            .rows()
+            .flat_map(|row| row.iter().map(|p| p.to_bytes(self.depth())))
+            .collect();
+        let scale = self.depth() as f64 / 255.0;
+        variance_cut(&pixels, n)
+            .into_iter()
+            .map(|[r, g, b]| RGB::new(r as u16, g as u16, b as u16) * scale)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::dither::Dither;
+    use crate::graphics::gif;
+
+    #[test]
+    fn variance_cut_finds_clusters() {
+        // a bigger reddish cluster and a smaller bluish one
+        let mut pixels = vec![];
+        for i in 0..300u32 {
+            pixels.push([200 + (i % 11) as u8, (i % 7) as u8, 10]);
+        }
+        for i in 0..100u32 {
+            pixels.push([5, 20 + (i % 5) as u8, 240]);
+        }
+        let mut palette = variance_cut(&pixels, 2);
+        palette.sort();
+        assert_eq!(vec![[5, 22, 240], [205, 3, 10]], palette);
+        assert_eq!(variance_cut(&pixels, 50), variance_cut(&pixels, 50));
+
+        // frames quantized to a fixed palette only use its colors
+        let fixed = Quantizer::Fixed(vec![[0, 0, 0], [255, 255, 255]]);
+        let (palette, indices) = gif::quantize(&pixels, 20, Dither::None, &fixed);
+        assert_eq!(vec![[0, 0, 0], [255, 255, 255]], palette);
+        assert_eq!(400, indices.len());
+    }
+}
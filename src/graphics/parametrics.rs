@@ -2,6 +2,201 @@
 
 use std::option::Option;
 
+/// Gauss-Legendre nodes and weights on [-1, 1] used by the adaptive quadrature
+const GAUSS5: [(f64, f64); 5] = [
+    (0.0, 0.568_888_888_888_888_9),
+    (-0.538_469_310_105_683, 0.478_628_670_499_366_5),
+    (0.538_469_310_105_683, 0.478_628_670_499_366_5),
+    (-0.906_179_845_938_664, 0.236_926_885_056_189_1),
+    (0.906_179_845_938_664, 0.236_926_885_056_189_1),
+];
+
+/// Max depth of the recursive length computations
+const MAX_DEPTH: u32 = 24;
+
+/// Max depth of adaptive flattening, which bounds a polyline to 2^16 segments
+const FLATTEN_DEPTH: u32 = 16;
+
+/// A 2d curve parametrized by t from 0.0 to 1.0
+pub trait Curve {
+    /// Return point (x, y) at input t
+    fn point_at(&self, t: f64) -> (f64, f64);
+
+    /// Return the derivative (dx/dt, dy/dt) at input t
+    ///
+    /// Defaults to a central difference, so override it when it is known exactly
+    fn derivative_at(&self, t: f64) -> (f64, f64) {
+        let h = 1e-6;
+        let (x0, y0) = self.point_at(t - h);
+        let (x1, y1) = self.point_at(t + h);
+        ((x1 - x0) / (2.0 * h), (y1 - y0) / (2.0 * h))
+    }
+
+    /// Length of the curve from `t0` to `t1`, within `tolerance`
+    ///
+    /// Defaults to recursive chord subdivision, which makes no assumption on smoothness
+    fn arc_length(&self, t0: f64, t1: f64, tolerance: f64) -> f64 {
+        let (p0, p1) = (self.point_at(t0), self.point_at(t1));
+        chord_length(self, t0, t1, p0, p1, tolerance, 0)
+    }
+
+    /// Length of the whole curve, within `tolerance`
+    fn length(&self, tolerance: f64) -> f64 {
+        self.arc_length(0.0, 1.0, tolerance)
+    }
+
+    /// Returns the t at which the curve has covered length `s` from t = 0
+    ///
+    /// `s` is clamped to the length of the curve
+    fn t_at_length(&self, s: f64, tolerance: f64) -> f64 {
+        let (mut lo, mut hi) = (0.0, 1.0);
+        if s <= 0.0 {
+            return lo;
+        }
+        if s >= self.length(tolerance) {
+            return hi;
+        }
+        // arc length only grows with t, so bisect
+        for _ in 0..50 {
+            let mid = (lo + hi) / 2.0;
+            if self.arc_length(0.0, mid, tolerance) < s {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+            if hi - lo < 1e-9 {
+                break;
+            }
+        }
+        (lo + hi) / 2.0
+    }
+
+    /// Returns `(t, point)` of the point on the curve closest to `p`
+    ///
+    /// The curve is sampled coarsely, then the best sample is refined with golden section search
+    fn closest_point(&self, p: (f64, f64)) -> (f64, (f64, f64)) {
+        let samples = 64;
+        let dist = |t: f64| distance(self.point_at(t), p);
+        let best = (0..=samples)
+            .map(|i| i as f64 / samples as f64)
+            .min_by(|a, b| dist(*a).partial_cmp(&dist(*b)).unwrap())
+            .unwrap();
+
+        let h = 1.0 / samples as f64;
+        let (mut lo, mut hi) = ((best - h).max(0.0), (best + h).min(1.0));
+        let ratio = (5.0_f64.sqrt() - 1.0) / 2.0;
+        while hi - lo > 1e-10 {
+            let (m0, m1) = (hi - ratio * (hi - lo), lo + ratio * (hi - lo));
+            if dist(m0) < dist(m1) {
+                hi = m1;
+            } else {
+                lo = m0;
+            }
+        }
+        let t = (lo + hi) / 2.0;
+        (t, self.point_at(t))
+    }
+}
+
+/// Sample a curve adaptively into a polyline, returning `(t, (x, y))` pairs in order of t
+///
+/// A span is split until its midpoint lies within `tolerance` of its chord
+pub fn flatten<C: Curve + ?Sized>(c: &C, tolerance: f64) -> Vec<(f64, (f64, f64))> {
+    let (p0, p1) = (c.point_at(0.0), c.point_at(1.0));
+    let mut points = vec![(0.0, p0)];
+    flatten_span(c, 0.0, 1.0, p0, p1, tolerance, 0, &mut points);
+    points
+}
+
+#[allow(clippy::too_many_arguments)]
+fn flatten_span<C: Curve + ?Sized>(
+    c: &C,
+    t0: f64,
+    t1: f64,
+    p0: (f64, f64),
+    p1: (f64, f64),
+    tolerance: f64,
+    depth: u32,
+    points: &mut Vec<(f64, (f64, f64))>,
+) {
+    let tm = (t0 + t1) / 2.0;
+    let pm = c.point_at(tm);
+    if depth >= FLATTEN_DEPTH || (depth >= 3 && distance_to_chord(pm, p0, p1) <= tolerance) {
+        points.push((t1, p1));
+    } else {
+        flatten_span(c, t0, tm, p0, pm, tolerance, depth + 1, points);
+        flatten_span(c, tm, t1, pm, p1, tolerance, depth + 1, points);
+    }
+}
+
+fn distance_to_chord(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let len = distance(a, b);
+    if len == 0.0 {
+        distance(p, a)
+    } else {
+        ((b.0 - a.0) * (a.1 - p.1) - (a.0 - p.0) * (b.1 - a.1)).abs() / len
+    }
+}
+
+fn distance(p0: (f64, f64), p1: (f64, f64)) -> f64 {
+    (p1.0 - p0.0).hypot(p1.1 - p0.1)
+}
+
+fn chord_length<C: Curve + ?Sized>(
+    c: &C,
+    t0: f64,
+    t1: f64,
+    p0: (f64, f64),
+    p1: (f64, f64),
+    tolerance: f64,
+    depth: u32,
+) -> f64 {
+    let tm = (t0 + t1) / 2.0;
+    let pm = c.point_at(tm);
+    let (whole, halves) = (distance(p0, p1), distance(p0, pm) + distance(pm, p1));
+    // always subdivide a few times so that closed or symmetric curves are not mistaken for points
+    if depth >= MAX_DEPTH || (depth >= 4 && halves - whole <= tolerance) {
+        halves
+    } else {
+        chord_length(c, t0, tm, p0, pm, tolerance / 2.0, depth + 1)
+            + chord_length(c, tm, t1, pm, p1, tolerance / 2.0, depth + 1)
+    }
+}
+
+/// Integrate the speed of `c` from `t0` to `t1` with adaptive Gauss-Legendre quadrature
+///
+/// Only use this for curves with a continuous derivative
+pub fn gauss_arc_length<C: Curve + ?Sized>(c: &C, t0: f64, t1: f64, tolerance: f64) -> f64 {
+    let whole = gauss_speed(c, t0, t1);
+    gauss_adaptive(c, t0, t1, whole, tolerance, 0)
+}
+
+fn gauss_speed<C: Curve + ?Sized>(c: &C, t0: f64, t1: f64) -> f64 {
+    let (half, mid) = ((t1 - t0) / 2.0, (t0 + t1) / 2.0);
+    GAUSS5.iter().fold(0.0, |sum, (x, w)| {
+        let (dx, dy) = c.derivative_at(mid + half * x);
+        sum + w * dx.hypot(dy)
+    }) * half
+}
+
+fn gauss_adaptive<C: Curve + ?Sized>(
+    c: &C,
+    t0: f64,
+    t1: f64,
+    whole: f64,
+    tolerance: f64,
+    depth: u32,
+) -> f64 {
+    let tm = (t0 + t1) / 2.0;
+    let (left, right) = (gauss_speed(c, t0, tm), gauss_speed(c, tm, t1));
+    if depth >= MAX_DEPTH || (left + right - whole).abs() <= tolerance {
+        left + right
+    } else {
+        gauss_adaptive(c, t0, tm, left, tolerance / 2.0, depth + 1)
+            + gauss_adaptive(c, tm, t1, right, tolerance / 2.0, depth + 1)
+    }
+}
+
 /// A type that represents a parametric equation from 0.0 to 1.0
 pub struct Parametric<F1, F2>
 where
@@ -33,6 +228,72 @@ where
     }
 }
 
+impl<F1, F2> Curve for Parametric<F1, F2>
+where
+    F1: Fn(f64) -> f64,
+    F2: Fn(f64) -> f64,
+{
+    fn point_at(&self, t: f64) -> (f64, f64) {
+        Parametric::point_at(self, t)
+    }
+}
+
+/// How a sampled surface is added to a matrix
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SurfaceMode {
+    /// Every grid point, as an edge from the point to itself
+    Points,
+    /// Edges along the grid lines
+    Wireframe,
+    /// Two triangles per grid cell
+    Polygons,
+}
+
+/// A 3d surface parametrized by u and v from 0.0 to 1.0
+pub trait Surface {
+    /// Return point (x, y, z) at (u, v)
+    fn point_at(&self, u: f64, v: f64) -> (f64, f64, f64);
+
+    /// Sample the surface on an evenly spaced grid, endpoints included
+    ///
+    /// Returns `nu + 1` rows of `nv + 1` points, with `grid[i][j]` at `(i / nu, j / nv)`
+    fn grid(&self, nu: usize, nv: usize) -> Vec<Vec<(f64, f64, f64)>> {
+        assert!(nu > 0 && nv > 0, "Surface needs at least one step each way");
+        (0..=nu)
+            .map(|i| {
+                let u = i as f64 / nu as f64;
+                (0..=nv).map(|j| self.point_at(u, j as f64 / nv as f64)).collect()
+            })
+            .collect()
+    }
+}
+
+/// A parametric surface, mapping (u, v) from 0.0 to 1.0 each to a point (x, y, z)
+pub struct ParametricSurface<F>
+where
+    F: Fn(f64, f64) -> (f64, f64, f64),
+{
+    f: F,
+}
+
+impl<F> ParametricSurface<F>
+where
+    F: Fn(f64, f64) -> (f64, f64, f64),
+{
+    pub fn new(f: F) -> Self {
+        ParametricSurface { f }
+    }
+}
+
+impl<F> Surface for ParametricSurface<F>
+where
+    F: Fn(f64, f64) -> (f64, f64, f64),
+{
+    fn point_at(&self, u: f64, v: f64) -> (f64, f64, f64) {
+        (self.f)(u, v)
+    }
+}
+
 pub struct ParametricIter<'a, F1, F2>
 where
     F1: Fn(f64) -> f64,
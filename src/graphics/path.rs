@@ -0,0 +1,188 @@
+#![allow(dead_code)]
+
+use super::curves::{Arc, Bezier3, Line};
+use super::parametrics::Curve;
+use super::tessellation::Tessellation;
+
+/// One piece of a path
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Segment {
+    Line(Line),
+    Arc(Arc),
+    Cubic(Bezier3),
+}
+
+impl Curve for Segment {
+    fn point_at(&self, t: f64) -> (f64, f64) {
+        match self {
+            Segment::Line(c) => c.point_at(t),
+            Segment::Arc(c) => c.point_at(t),
+            Segment::Cubic(c) => c.point_at(t),
+        }
+    }
+
+    fn derivative_at(&self, t: f64) -> (f64, f64) {
+        match self {
+            Segment::Line(c) => c.derivative_at(t),
+            Segment::Arc(c) => c.derivative_at(t),
+            Segment::Cubic(c) => c.derivative_at(t),
+        }
+    }
+
+    fn arc_length(&self, t0: f64, t1: f64, tolerance: f64) -> f64 {
+        match self {
+            Segment::Line(c) => c.arc_length(t0, t1, tolerance),
+            Segment::Arc(c) => c.arc_length(t0, t1, tolerance),
+            Segment::Cubic(c) => c.arc_length(t0, t1, tolerance),
+        }
+    }
+}
+
+/// A connected run of segments, started by a moveto
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubPath {
+    pub start: (f64, f64),
+    pub segments: Vec<Segment>,
+    pub closed: bool,
+}
+
+/// A drawing made of subpaths, in the style of PostScript and SVG
+///
+/// The structure is kept until the path is tessellated into an edge matrix
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Path {
+    subpaths: Vec<SubPath>,
+}
+
+impl Path {
+    pub fn new() -> Self {
+        Path { subpaths: vec![] }
+    }
+
+    pub fn subpaths(&self) -> &[SubPath] {
+        &self.subpaths
+    }
+
+    /// The end of the last segment, or the origin for an empty path
+    pub fn current_point(&self) -> (f64, f64) {
+        match self.subpaths.last() {
+            Some(sp) => match sp.segments.last() {
+                Some(seg) => seg.point_at(1.0),
+                None => sp.start,
+            },
+            None => (0.0, 0.0),
+        }
+    }
+
+    /// Start a new subpath at (x, y)
+    pub fn move_to(&mut self, x: f64, y: f64) -> &mut Self {
+        self.subpaths.push(SubPath {
+            start: (x, y),
+            segments: vec![],
+            closed: false,
+        });
+        self
+    }
+
+    /// Add a line from the current point to (x, y)
+    pub fn line_to(&mut self, x: f64, y: f64) -> &mut Self {
+        let p0 = self.current_point();
+        self.push(Segment::Line(Line::new(p0, (x, y))))
+    }
+
+    /// Add a cubic bezier from the current point to `p`, with control points `c1` and `c2`
+    pub fn curve_to(&mut self, c1: (f64, f64), c2: (f64, f64), p: (f64, f64)) -> &mut Self {
+        let p0 = self.current_point();
+        self.push(Segment::Cubic(Bezier3::new(p0, c1, c2, p)))
+    }
+
+    /// Add an arc around `center`
+    ///
+    /// A line is added from the current point to the start of the arc if they differ
+    pub fn arc(&mut self, center: (f64, f64), r: f64, start_deg: f64, sweep_deg: f64) -> &mut Self {
+        let arc = Arc::new(center, r, start_deg, sweep_deg);
+        let start = arc.point_at(0.0);
+        if self.subpaths.is_empty() {
+            self.move_to(start.0, start.1);
+        } else if self.current_point() != start {
+            self.line_to(start.0, start.1);
+        }
+        self.push(Segment::Arc(arc))
+    }
+
+    /// Close the current subpath with a line back to its start
+    pub fn close_path(&mut self) -> &mut Self {
+        if let Some(sp) = self.subpaths.last() {
+            let start = sp.start;
+            if self.current_point() != start {
+                self.line_to(start.0, start.1);
+            }
+            self.subpaths.last_mut().unwrap().closed = true;
+        }
+        self
+    }
+
+    /// Add a segment to the last subpath, or to a new one if there is none or it is closed
+    ///
+    /// As in SVG, drawing on after a closepath starts again from where the closed subpath began
+    fn push(&mut self, seg: Segment) -> &mut Self {
+        if self.subpaths.last().is_none_or(|sp| sp.closed) {
+            let p = seg.point_at(0.0);
+            self.move_to(p.0, p.1);
+        }
+        self.subpaths.last_mut().unwrap().segments.push(seg);
+        self
+    }
+
+    /// Sample every subpath into a polyline, returned with whether it is closed
+    ///
+    /// Lines are kept as single edges, curves are sampled with `tessellation`
+    pub fn to_polylines(&self, tessellation: &Tessellation) -> Vec<(Vec<(f64, f64)>, bool)> {
+        self.subpaths
+            .iter()
+            .map(|sp| {
+                let mut pts = vec![sp.start];
+                for seg in sp.segments.iter() {
+                    match seg {
+                        Segment::Line(l) => pts.push(l.p1),
+                        _ => pts.extend(tessellation.sample(seg).into_iter().skip(1)),
+                    }
+                }
+                (pts, sp.closed)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subpaths_start_and_close() {
+        let mut path = Path::new();
+        path.move_to(0.0, 0.0).line_to(10.0, 0.0).line_to(10.0, 10.0).close_path();
+        // already back at the start, so no closing line is needed
+        path.move_to(20.0, 0.0).line_to(30.0, 0.0).line_to(20.0, 0.0).close_path();
+        assert_eq!(2, path.subpaths().len());
+        assert_eq!(3, path.subpaths()[0].segments.len());
+        assert_eq!(2, path.subpaths()[1].segments.len());
+        assert!(path.subpaths().iter().all(|sp| sp.closed));
+
+        // drawing on after a close starts a new subpath where the closed one began
+        path.line_to(20.0, 5.0);
+        let last = path.subpaths().last().unwrap();
+        assert_eq!(3, path.subpaths().len());
+        assert_eq!(((20.0, 0.0), false), (last.start, last.closed));
+
+        let polylines = path.to_polylines(&Tessellation::Segments(4));
+        let square = vec![(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 0.0)];
+        assert_eq!((square, true), polylines[0]);
+        assert_eq!((vec![(20.0, 0.0), (20.0, 5.0)], false), polylines[2]);
+
+        let mut curved = Path::new();
+        curved.curve_to((0.0, 1.0), (1.0, 1.0), (1.0, 0.0));
+        let (pts, closed) = &curved.to_polylines(&Tessellation::Segments(4))[0];
+        assert_eq!((5, false), (pts.len(), *closed));
+    }
+}
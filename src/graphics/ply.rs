@@ -0,0 +1,140 @@
+#![allow(dead_code)]
+
+use std::io::{self, prelude::Write};
+
+use super::colors::RGB;
+use super::mesh::Mesh;
+use super::utils::create_file;
+use super::vector::Vec3;
+
+/// Encoding of the body of a PLY file
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+}
+
+/// Write a mesh as a PLY file, optionally with a color and a normal for every vertex
+///
+/// A mesh without faces is written as a point cloud.
+/// Colors are written as bytes, so channels are expected to be at most 255.
+pub fn save_ply(
+    filepath: &str,
+    mesh: &Mesh,
+    colors: Option<&[RGB]>,
+    normals: Option<&[Vec3]>,
+    format: PlyFormat,
+) -> io::Result<()> {
+    let mut file = create_file(filepath)?;
+    write_ply(&mut file, mesh, colors, normals, format)?;
+    file.flush()
+}
+
+/// Write a mesh in the PLY format, see `save_ply`
+pub fn write_ply(
+    w: &mut impl Write,
+    mesh: &Mesh,
+    colors: Option<&[RGB]>,
+    normals: Option<&[Vec3]>,
+    format: PlyFormat,
+) -> io::Result<()> {
+    let n = mesh.vertices.len();
+    if colors.is_some_and(|c| c.len() != n) || normals.is_some_and(|v| v.len() != n) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Need exactly one color and one normal per vertex",
+        ));
+    }
+
+    writeln!(w, "ply")?;
+    match format {
+        PlyFormat::Ascii => writeln!(w, "format ascii 1.0")?,
+        PlyFormat::BinaryLittleEndian => writeln!(w, "format binary_little_endian 1.0")?,
+    }
+    writeln!(w, "element vertex {}", n)?;
+    writeln!(w, "property float x\nproperty float y\nproperty float z")?;
+    if normals.is_some() {
+        writeln!(w, "property float nx\nproperty float ny\nproperty float nz")?;
+    }
+    if colors.is_some() {
+        writeln!(w, "property uchar red\nproperty uchar green\nproperty uchar blue")?;
+    }
+    writeln!(w, "element face {}", mesh.faces.len())?;
+    writeln!(w, "property list uchar int vertex_indices")?;
+    writeln!(w, "end_header")?;
+
+    let byte = |c: u16| c.min(255) as u8;
+    for (i, v) in mesh.vertices.iter().enumerate() {
+        let mut floats = vec![v.0, v.1, v.2];
+        if let Some(normals) = normals {
+            floats.extend_from_slice(&[normals[i].0, normals[i].1, normals[i].2]);
+        }
+        let rgb = colors.map(|c| [byte(c[i].red), byte(c[i].green), byte(c[i].blue)]);
+        match format {
+            PlyFormat::Ascii => {
+                let mut fields: Vec<String> =
+                    floats.iter().map(|f| (*f as f32).to_string()).collect();
+                if let Some(rgb) = rgb {
+                    fields.extend(rgb.iter().map(|c| c.to_string()));
+                }
+                writeln!(w, "{}", fields.join(" "))?;
+            }
+            PlyFormat::BinaryLittleEndian => {
+                for f in floats.iter() {
+                    w.write_all(&(*f as f32).to_le_bytes())?;
+                }
+                if let Some(rgb) = rgb {
+                    w.write_all(&rgb)?;
+                }
+            }
+        }
+    }
+    for f in mesh.faces.iter() {
+        match format {
+            PlyFormat::Ascii => writeln!(w, "3 {} {} {}", f[0], f[1], f[2])?,
+            PlyFormat::BinaryLittleEndian => {
+                w.write_all(&[3])?;
+                for i in f.iter() {
+                    w.write_all(&(*i as i32).to_le_bytes())?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangle() -> Mesh {
+        let mut mesh = Mesh::new();
+        let a = mesh.add_vertex((0.0, 0.0, 0.0));
+        let b = mesh.add_vertex((1.0, 0.0, 0.0));
+        let c = mesh.add_vertex((0.0, 2.5, 0.0));
+        mesh.add_face(a, b, c);
+        mesh
+    }
+
+    #[test]
+    fn ascii_with_colors() {
+        let colors = [RGB::new(255, 0, 0), RGB::new(0, 255, 0), RGB::new(0, 0, 300)];
+        let mut out = vec![];
+        write_ply(&mut out, &triangle(), Some(&colors), None, PlyFormat::Ascii).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let body: Vec<&str> = text.split("end_header\n").nth(1).unwrap().lines().collect();
+        assert_eq!(vec!["0 0 0 255 0 0", "1 0 0 0 255 0", "0 2.5 0 0 0 255", "3 0 1 2"], body);
+    }
+
+    #[test]
+    fn binary_sizes() {
+        let normals = [(0.0, 0.0, 1.0); 3];
+        let mut out = vec![];
+        let format = PlyFormat::BinaryLittleEndian;
+        write_ply(&mut out, &triangle(), None, Some(&normals), format).unwrap();
+        let header_end = out.windows(11).position(|w| w == b"end_header\n").unwrap() + 11;
+        // 6 floats per vertex, then a count byte and 3 ints for the face
+        assert_eq!(3 * 6 * 4 + 1 + 3 * 4, out.len() - header_end);
+        assert!(write_ply(&mut out, &triangle(), None, Some(&normals[..2]), format).is_err());
+    }
+}
@@ -0,0 +1,844 @@
+#![allow(dead_code)]
+
+//! PNG and animated PNG output, with a small deflate encoder of its own, and PNG input
+
+use std::io::{self, prelude::Write};
+
+use super::gif::Repeat;
+use super::utils::create_file;
+use super::{PPMImg, RGB};
+
+/// Save an image as an 8 bit RGB PNG
+pub fn save_png(filepath: &str, img: &PPMImg) -> io::Result<()> {
+    let mut file = create_file(filepath)?;
+    write_png(&mut file, img)?;
+    file.flush()
+}
+
+/// Write an image as an 8 bit RGB PNG, see `save_png`
+pub fn write_png(w: &mut impl Write, img: &PPMImg) -> io::Result<()> {
+    check_size(img.width(), img.height())?;
+    w.write_all(SIGNATURE)?;
+    write_chunk(w, b"IHDR", &ihdr(img.width(), img.height()))?;
+    write_chunk(w, b"IDAT", &zlib_compress(&filtered_rows(img)))?;
+    write_chunk(w, b"IEND", &[])
+}
+
+/// Read a PNG file, see `read_png`
+pub fn open_png(filepath: &str) -> io::Result<PPMImg> {
+    read_png(&std::fs::read(filepath)?)
+}
+
+/// Decode a PNG of any color type and bit depth, except interlaced ones
+///
+/// 16 bit images keep their precision with a depth of 65535, others get a depth of 255.
+/// Images with transparency, from an alpha channel or a `tRNS` chunk, come out with it.
+pub fn read_png(bytes: &[u8]) -> io::Result<PPMImg> {
+    if !bytes.starts_with(SIGNATURE) {
+        return Err(corrupt("Not a PNG file"));
+    }
+    let (mut header, mut palette, mut trns, mut idat) = (None, &[][..], None, vec![]);
+    let mut i = SIGNATURE.len();
+    while i + 12 <= bytes.len() {
+        let len = u32::from_be_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        let end = i + 8 + len as usize;
+        if end + 4 > bytes.len() {
+            return Err(corrupt("PNG chunk runs past the end of the file"));
+        }
+        let (kind, data) = (&bytes[i + 4..i + 8], &bytes[i + 8..end]);
+        if crc32(&bytes[i + 4..end]).to_be_bytes() != bytes[end..end + 4] {
+            return Err(corrupt("PNG chunk fails its crc"));
+        }
+        match kind {
+            b"IHDR" if data.len() == 13 => header = Some(data),
+            b"PLTE" => palette = data,
+            b"tRNS" => trns = Some(data),
+            b"IDAT" => idat.extend_from_slice(data),
+            b"IEND" => break,
+            _ => {}
+        }
+        i = end + 4;
+    }
+    let header = header.ok_or_else(|| corrupt("PNG has no header"))?;
+    let be32 = |b: &[u8]| u32::from_be_bytes([b[0], b[1], b[2], b[3]]);
+    let (width, height) = (be32(&header[0..4]), be32(&header[4..8]));
+    check_size(width, height).map_err(|_| corrupt("PNG has a size of 0 or over 2^31"))?;
+    let (bit_depth, color_type, interlace) = (header[8] as usize, header[9], header[12]);
+    if interlace != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Interlaced PNGs cannot be read",
+        ));
+    }
+    let channels = match (color_type, bit_depth) {
+        (0, 1) | (0, 2) | (0, 4) | (0, 8) | (0, 16) => 1,
+        (3, 1) | (3, 2) | (3, 4) | (3, 8) => 1,
+        (2, 8) | (2, 16) => 3,
+        (4, 8) | (4, 16) => 2,
+        (6, 8) | (6, 16) => 4,
+        _ => return Err(corrupt("PNG has an unknown color type or bit depth")),
+    };
+    let raw = zlib_decompress(&idat)?;
+    let stride = (width as usize * channels * bit_depth).div_ceil(8);
+    if raw.len() < (stride + 1) * height as usize {
+        return Err(corrupt("PNG image data is too short"));
+    }
+    let rows = unfilter(&raw, stride, height as usize, (channels * bit_depth).div_ceil(8))?;
+
+    let max = (1u32 << bit_depth) - 1;
+    let depth: u16 = if bit_depth == 16 { 65535 } else { 255 };
+    let raw_sample = |row: &[u8], i: usize| -> u32 {
+        match bit_depth {
+            16 => u16::from_be_bytes([row[2 * i], row[2 * i + 1]]) as u32,
+            8 => row[i] as u32,
+            _ => {
+                let bit = i * bit_depth;
+                (row[bit / 8] >> (8 - bit_depth - bit % 8)) as u32 & max
+            }
+        }
+    };
+    // samples scaled to the depth of the image, or palette indices
+    let sample = |row: &[u8], i: usize| -> u16 {
+        let v = raw_sample(row, i);
+        if color_type == 3 {
+            v as u16
+        } else {
+            (v * depth as u32 / max) as u16
+        }
+    };
+    // the one color that is see through, for types without an alpha channel
+    let key: Option<Vec<u32>> = match (color_type, trns) {
+        (0, Some(t)) | (2, Some(t)) if t.len() >= channels * 2 => Some(
+            t.chunks(2)
+                .take(channels)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]) as u32)
+                .collect(),
+        ),
+        _ => None,
+    };
+
+    let mut img = PPMImg::new(height, width, depth);
+    let has_alpha = color_type >= 4 || trns.is_some();
+    let mut alpha = Vec::with_capacity(if has_alpha { img.data.len() } else { 0 });
+    for (y, row) in rows.chunks(stride).enumerate() {
+        for x in 0..width as usize {
+            let i = x * channels;
+            let (color, a) = match color_type {
+                0 | 4 => {
+                    let v = sample(row, i);
+                    let a = if color_type == 4 { sample(row, i + 1) } else { depth };
+                    (RGB::new(v, v, v), a)
+                }
+                3 => {
+                    let index = sample(row, i) as usize;
+                    let rgb = palette
+                        .get(index * 3..index * 3 + 3)
+                        .ok_or_else(|| corrupt("PNG pixel is past the end of the palette"))?;
+                    let a = trns.and_then(|t| t.get(index)).map_or(255, |&a| a as u16);
+                    (RGB::new(rgb[0] as u16, rgb[1] as u16, rgb[2] as u16), a)
+                }
+                _ => {
+                    let [r, g, b] = [0, 1, 2].map(|c| sample(row, i + c));
+                    let a = if color_type == 6 { sample(row, i + 3) } else { depth };
+                    (RGB::new(r, g, b), a)
+                }
+            };
+            let a = match &key {
+                Some(key) if (0..key.len()).all(|c| raw_sample(row, i + c) == key[c]) => 0,
+                _ => a,
+            };
+            img.data[y * width as usize + x] = color;
+            if has_alpha {
+                alpha.push(a as f64 / depth as f64);
+            }
+        }
+    }
+    if has_alpha {
+        img.alpha = Some(alpha);
+    }
+    Ok(img)
+}
+
+/// PNGs are from 1 to 2^31 - 1 pixels on each side
+fn check_size(width: u32, height: u32) -> io::Result<()> {
+    let fits = |n: u32| n > 0 && n <= i32::MAX as u32;
+    if fits(width) && fits(height) {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("A PNG can't be {} by {} pixels", width, height),
+        ))
+    }
+}
+
+fn corrupt(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Undo the filter in front of every row, `bpp` being the bytes in a pixel, rounded up
+fn unfilter(raw: &[u8], stride: usize, height: usize, bpp: usize) -> io::Result<Vec<u8>> {
+    let mut out = vec![0u8; stride * height];
+    for y in 0..height {
+        let filter = raw[y * (stride + 1)];
+        let line = &raw[y * (stride + 1) + 1..(y + 1) * (stride + 1)];
+        let (done, rest) = out.split_at_mut(y * stride);
+        let prev = if y > 0 { &done[(y - 1) * stride..] } else { &[][..] };
+        let cur = &mut rest[..stride];
+        for i in 0..stride {
+            let a = if i >= bpp { cur[i - bpp] } else { 0 };
+            let b = prev.get(i).copied().unwrap_or(0);
+            let c = if i >= bpp { prev.get(i - bpp).copied().unwrap_or(0) } else { 0 };
+            let predict = match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => paeth(a, b, c),
+                _ => return Err(corrupt("PNG row has an unknown filter")),
+            };
+            cur[i] = line[i].wrapping_add(predict);
+        }
+    }
+    Ok(out)
+}
+
+/// Collects frames of the same size into an animated PNG, keeping all 24 bits of color
+///
+/// The frame count goes at the start of the file, so frames are compressed and kept in memory
+/// until `finish` writes everything out.
+pub struct ApngWriter<W: Write> {
+    w: W,
+    width: u32,
+    height: u32,
+    /// Time each frame is shown for, in hundredths of a second
+    pub delay: u16,
+    repeat: Repeat,
+    frames: Vec<Vec<u8>>,
+}
+
+impl<W: Write> ApngWriter<W> {
+    /// # Arguments
+    /// `delay` - Time each frame is shown for, in hundredths of a second
+    /// `repeat` - How many times the animation plays
+    pub fn new(w: W, width: u32, height: u32, delay: u16, repeat: Repeat) -> Self {
+        ApngWriter {
+            w,
+            width,
+            height,
+            delay,
+            repeat,
+            frames: vec![],
+        }
+    }
+
+    /// Add a frame, which must be as big as the animation
+    pub fn add_frame(&mut self, img: &PPMImg) -> io::Result<()> {
+        if img.width() != self.width || img.height() != self.height {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Every frame must be as big as the animation",
+            ));
+        }
+        check_size(img.width(), img.height())?;
+        self.frames.push(zlib_compress(&filtered_rows(img)));
+        Ok(())
+    }
+
+    /// Write out the animation, giving back the writer
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.frames.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "An animation needs at least one frame",
+            ));
+        }
+        let w = &mut self.w;
+        w.write_all(SIGNATURE)?;
+        write_chunk(w, b"IHDR", &ihdr(self.width, self.height))?;
+
+        let plays: u32 = match self.repeat {
+            Repeat::Once => 1,
+            Repeat::Forever => 0,
+            Repeat::Times(n) => n as u32 + 1,
+        };
+        let mut actl = (self.frames.len() as u32).to_be_bytes().to_vec();
+        actl.extend_from_slice(&plays.to_be_bytes());
+        write_chunk(w, b"acTL", &actl)?;
+
+        // frame controls and frame data share one sequence of numbers
+        let mut seq = 0u32;
+        for (i, data) in self.frames.iter().enumerate() {
+            let mut fctl = seq.to_be_bytes().to_vec();
+            for v in [self.width, self.height, 0, 0].iter() {
+                fctl.extend_from_slice(&v.to_be_bytes());
+            }
+            fctl.extend_from_slice(&self.delay.to_be_bytes());
+            fctl.extend_from_slice(&100u16.to_be_bytes());
+            // leave the frame as it is, and replace what was there
+            fctl.extend_from_slice(&[0, 0]);
+            write_chunk(w, b"fcTL", &fctl)?;
+            seq += 1;
+
+            // the first frame is also the still image for viewers without animation
+            if i == 0 {
+                write_chunk(w, b"IDAT", data)?;
+            } else {
+                let mut fdat = seq.to_be_bytes().to_vec();
+                fdat.extend_from_slice(data);
+                write_chunk(w, b"fdAT", &fdat)?;
+                seq += 1;
+            }
+        }
+        write_chunk(w, b"IEND", &[])?;
+        self.w.flush()?;
+        Ok(self.w)
+    }
+}
+
+const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+fn ihdr(width: u32, height: u32) -> Vec<u8> {
+    let mut data = width.to_be_bytes().to_vec();
+    data.extend_from_slice(&height.to_be_bytes());
+    // 8 bits per channel, truecolor, deflate, adaptive filters, no interlacing
+    data.extend_from_slice(&[8, 2, 0, 0, 0]);
+    data
+}
+
+fn write_chunk(w: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    w.write_all(&(data.len() as u32).to_be_bytes())?;
+    w.write_all(kind)?;
+    w.write_all(data)?;
+    let crc = crc32(&[&kind[..], data].concat());
+    w.write_all(&crc.to_be_bytes())
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in data.iter() {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { 0xedb8_8320 ^ (crc >> 1) } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &x in chunk.iter() {
+            a += x as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+/// Every row with a filter byte in front, choosing for each row the filter that leaves the
+/// smallest differences
+fn filtered_rows(img: &PPMImg) -> Vec<u8> {
+    let stride = img.width() as usize * 3;
+    let mut out = Vec::with_capacity((stride + 1) * img.height() as usize);
+    let mut prev = vec![0u8; stride];
+    let mut candidates = vec![vec![0u8; stride]; 5];
+    for row in img.rows() {
+        let cur: Vec<u8> = row.iter().flat_map(|p| p.to_bytes(img.depth())).collect();
+        for (filter, out) in candidates.iter_mut().enumerate() {
+            for i in 0..stride {
+                let a = if i >= 3 { cur[i - 3] } else { 0 };
+                let (b, c) = (prev[i], if i >= 3 { prev[i - 3] } else { 0 });
+                let predict = match filter {
+                    0 => 0,
+                    1 => a,
+                    2 => b,
+                    3 => ((a as u16 + b as u16) / 2) as u8,
+                    _ => paeth(a, b, c),
+                };
+                out[i] = cur[i].wrapping_sub(predict);
+            }
+        }
+        let cost = |f: &Vec<u8>| f.iter().map(|&x| (x as i8).unsigned_abs() as u32).sum::<u32>();
+        let best = (0..5).min_by_key(|&f| cost(&candidates[f])).unwrap();
+        out.push(best as u8);
+        out.extend_from_slice(&candidates[best]);
+        prev = cur;
+    }
+    out
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Bits packed from the lowest up, as deflate wants them
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u32,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, n: u32) {
+        self.acc |= value << self.bits;
+        self.bits += n;
+        while self.bits >= 8 {
+            self.out.push(self.acc as u8);
+            self.acc >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    /// Huffman codes go in from their highest bit
+    fn write_code(&mut self, code: u32, n: u32) {
+        self.write(code.reverse_bits() >> (32 - n), n);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.out.push(self.acc as u8);
+        }
+        self.out
+    }
+}
+
+const LEN_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115,
+    131, 163, 195, 227, 258,
+];
+const LEN_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// A literal or end of block symbol in the fixed Huffman code
+fn write_literal(bw: &mut BitWriter, sym: u16) {
+    let sym = sym as u32;
+    match sym {
+        0..=143 => bw.write_code(0x30 + sym, 8),
+        144..=255 => bw.write_code(0x190 + sym - 144, 9),
+        256..=279 => bw.write_code(sym - 256, 7),
+        _ => bw.write_code(0xc0 + sym - 280, 8),
+    }
+}
+
+fn write_match(bw: &mut BitWriter, len: usize, dist: usize) {
+    let l = LEN_BASE.iter().rposition(|&b| b as usize <= len).unwrap();
+    write_literal(bw, 257 + l as u16);
+    bw.write((len - LEN_BASE[l] as usize) as u32, LEN_EXTRA[l] as u32);
+    let d = DIST_BASE.iter().rposition(|&b| b as usize <= dist).unwrap();
+    bw.write_code(d as u32, 5);
+    bw.write((dist - DIST_BASE[d] as usize) as u32, DIST_EXTRA[d] as u32);
+}
+
+/// A zlib stream holding one deflate block with the fixed Huffman code, matching repeated runs
+/// through hash chains
+fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    const WINDOW: usize = 32768;
+    const MAX_CHAIN: usize = 64;
+    const MAX_LEN: usize = 258;
+    const HASH_SIZE: usize = 1 << 15;
+
+    let mut bw = BitWriter {
+        out: vec![0x78, 0x01],
+        acc: 0,
+        bits: 0,
+    };
+    // last block, fixed codes
+    bw.write(1, 1);
+    bw.write(1, 2);
+
+    let hash = |i: usize| {
+        ((data[i] as usize) << 10 ^ (data[i + 1] as usize) << 5 ^ data[i + 2] as usize)
+            % HASH_SIZE
+    };
+    // most recent position of every hash, and for every position the one before with its hash
+    let mut head = vec![usize::MAX; HASH_SIZE];
+    let mut prev = vec![usize::MAX; data.len()];
+    let insert = |i: usize, head: &mut [usize], prev: &mut [usize]| {
+        if i + 2 < data.len() {
+            let h = hash(i);
+            prev[i] = head[h];
+            head[h] = i;
+        }
+    };
+
+    let mut i = 0;
+    while i < data.len() {
+        let (mut best_len, mut best_dist) = (0, 0);
+        if i + 2 < data.len() {
+            let mut cand = head[hash(i)];
+            let max_len = MAX_LEN.min(data.len() - i);
+            for _ in 0..MAX_CHAIN {
+                if cand == usize::MAX || i - cand > WINDOW {
+                    break;
+                }
+                let len = (0..max_len).take_while(|&k| data[cand + k] == data[i + k]).count();
+                if len > best_len {
+                    best_len = len;
+                    best_dist = i - cand;
+                    if len == max_len {
+                        break;
+                    }
+                }
+                cand = prev[cand];
+            }
+        }
+        if best_len >= 3 {
+            write_match(&mut bw, best_len, best_dist);
+            for k in i..i + best_len {
+                insert(k, &mut head, &mut prev);
+            }
+            i += best_len;
+        } else {
+            write_literal(&mut bw, data[i] as u16);
+            insert(i, &mut head, &mut prev);
+            i += 1;
+        }
+    }
+    write_literal(&mut bw, 256);
+
+    let mut out = bw.finish();
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+/// Bits read from the lowest up, as deflate packs them
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, n: usize) -> io::Result<u32> {
+        let mut v = 0;
+        for k in 0..n {
+            let byte = self.data.get(self.pos / 8);
+            let byte = byte.ok_or_else(|| corrupt("Deflate data ends early"))?;
+            v |= ((byte >> (self.pos % 8)) as u32 & 1) << k;
+            self.pos += 1;
+        }
+        Ok(v)
+    }
+
+    /// Skip to the start of the next byte
+    fn align(&mut self) {
+        self.pos = self.pos.div_ceil(8) * 8;
+    }
+}
+
+/// A canonical Huffman code, as the number of codes of each length and the symbols in order of
+/// their codes
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; 16];
+        for &l in lengths.iter() {
+            counts[l as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut symbols: Vec<u16> = (0..lengths.len() as u16)
+            .filter(|&s| lengths[s as usize] > 0)
+            .collect();
+        symbols.sort_by_key(|&s| lengths[s as usize]);
+        Huffman { counts, symbols }
+    }
+
+    /// Read a code from its highest bit, one bit at a time
+    fn decode(&self, br: &mut BitReader) -> io::Result<u16> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..16 {
+            code |= br.bits(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(corrupt("Deflate data has a bad Huffman code"))
+    }
+}
+
+/// The data in a zlib stream, checking its checksum
+fn zlib_decompress(zlib: &[u8]) -> io::Result<Vec<u8>> {
+    if zlib.len() < 6
+        || zlib[0] & 0x0f != 8
+        || !u16::from_be_bytes([zlib[0], zlib[1]]).is_multiple_of(31)
+    {
+        return Err(corrupt("Not a zlib stream"));
+    }
+    if zlib[1] & 0x20 != 0 {
+        return Err(corrupt("zlib streams with a preset dictionary are not supported"));
+    }
+    let mut br = BitReader { data: &zlib[2..], pos: 0 };
+    let out = inflate(&mut br)?;
+    br.align();
+    let end = 2 + br.pos / 8;
+    match zlib.get(end..end + 4) {
+        Some(check) if check == adler32(&out).to_be_bytes() => Ok(out),
+        _ => Err(corrupt("zlib stream fails its checksum")),
+    }
+}
+
+fn inflate(br: &mut BitReader) -> io::Result<Vec<u8>> {
+    let mut out: Vec<u8> = vec![];
+    loop {
+        let last = br.bits(1)? == 1;
+        match br.bits(2)? {
+            0 => {
+                br.align();
+                let len = br.bits(16)? as usize;
+                if br.bits(16)? as usize != !len & 0xffff {
+                    return Err(corrupt("Stored deflate block has a bad length"));
+                }
+                for _ in 0..len {
+                    out.push(br.bits(8)? as u8);
+                }
+            }
+            1 => {
+                let mut lengths = [8u8; 288];
+                lengths[144..256].iter_mut().for_each(|l| *l = 9);
+                lengths[256..280].iter_mut().for_each(|l| *l = 7);
+                inflate_block(br, &mut out, &Huffman::new(&lengths), &Huffman::new(&[5; 30]))?;
+            }
+            2 => {
+                let (lit, dist) = read_dynamic_codes(br)?;
+                inflate_block(br, &mut out, &lit, &dist)?;
+            }
+            _ => return Err(corrupt("Deflate block has an unknown type")),
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+/// The literal and distance codes at the start of a block with dynamic Huffman codes
+fn read_dynamic_codes(br: &mut BitReader) -> io::Result<(Huffman, Huffman)> {
+    const ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+    let nlit = br.bits(5)? as usize + 257;
+    let ndist = br.bits(5)? as usize + 1;
+    let ncode = br.bits(4)? as usize + 4;
+    let mut code_lengths = [0u8; 19];
+    for &i in ORDER.iter().take(ncode) {
+        code_lengths[i] = br.bits(3)? as u8;
+    }
+    let code = Huffman::new(&code_lengths);
+    let mut lengths = vec![];
+    while lengths.len() < nlit + ndist {
+        let (value, repeat) = match code.decode(br)? {
+            sym @ 0..=15 => (sym as u8, 1),
+            16 => {
+                let prev = lengths.last().ok_or_else(|| corrupt("Deflate data repeats nothing"))?;
+                (*prev, 3 + br.bits(2)?)
+            }
+            17 => (0, 3 + br.bits(3)?),
+            _ => (0, 11 + br.bits(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+    if lengths.len() > nlit + ndist {
+        return Err(corrupt("Deflate lengths run past the codes"));
+    }
+    Ok((Huffman::new(&lengths[..nlit]), Huffman::new(&lengths[nlit..])))
+}
+
+fn inflate_block(
+    br: &mut BitReader,
+    out: &mut Vec<u8>,
+    lit: &Huffman,
+    dist: &Huffman,
+) -> io::Result<()> {
+    loop {
+        let sym = lit.decode(br)? as usize;
+        match sym {
+            0..=255 => out.push(sym as u8),
+            256 => return Ok(()),
+            257..=285 => {
+                let l = sym - 257;
+                let len = LEN_BASE[l] as usize + br.bits(LEN_EXTRA[l] as usize)? as usize;
+                let d = dist.decode(br)? as usize;
+                if d >= 30 {
+                    return Err(corrupt("Deflate data has a bad distance"));
+                }
+                let back = DIST_BASE[d] as usize + br.bits(DIST_EXTRA[d] as usize)? as usize;
+                if back > out.len() {
+                    return Err(corrupt("Deflate data reaches back before its start"));
+                }
+                for _ in 0..len {
+                    out.push(out[out.len() - back]);
+                }
+            }
+            _ => return Err(corrupt("Deflate data has a bad length")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Inflate a stream of fixed Huffman blocks, to check the encoder against
+    fn inflate_fixed(zlib: &[u8]) -> Vec<u8> {
+        let data = &zlib[2..zlib.len() - 4];
+        let mut pos = 0;
+        let mut bits = |n: usize| {
+            let v = (0..n).fold(0, |acc, k| {
+                acc | (((data[(pos + k) / 8] >> ((pos + k) % 8)) & 1) as usize) << k
+            });
+            pos += n;
+            v
+        };
+        // Huffman codes are read one bit at a time from their highest bit
+        let code = |n: usize, bits: &mut dyn FnMut(usize) -> usize| {
+            (0..n).fold(0, |acc, _| acc << 1 | bits(1))
+        };
+        assert_eq!((1, 1), (bits(1), bits(2)));
+        let mut out: Vec<u8> = vec![];
+        loop {
+            let mut c = code(7, &mut bits);
+            let sym = if c <= 0x17 {
+                c + 256
+            } else {
+                c = c << 1 | bits(1);
+                if (0x30..=0xbf).contains(&c) {
+                    c - 0x30
+                } else if (0xc0..=0xc7).contains(&c) {
+                    c - 0xc0 + 280
+                } else {
+                    (c << 1 | bits(1)) - 0x190 + 144
+                }
+            };
+            match sym {
+                0..=255 => out.push(sym as u8),
+                256 => break,
+                _ => {
+                    let l = sym - 257;
+                    let len = LEN_BASE[l] as usize + bits(LEN_EXTRA[l] as usize);
+                    let d = code(5, &mut bits);
+                    let dist = DIST_BASE[d] as usize + bits(DIST_EXTRA[d] as usize);
+                    for _ in 0..len {
+                        out.push(out[out.len() - dist]);
+                    }
+                }
+            }
+        }
+        assert_eq!(adler32(&out).to_be_bytes(), zlib[zlib.len() - 4..]);
+        out
+    }
+
+    #[test]
+    fn deflate_round_trip() {
+        let mut data: Vec<u8> = (0..70000u32).map(|i| (i * 7 % 251) as u8).collect();
+        data.extend(vec![9; 1000]);
+        data.extend(b"abcabcabcabcd".iter());
+        let z = zlib_compress(&data);
+        assert!(z.len() < data.len() / 10);
+        assert_eq!(data, inflate_fixed(&z));
+        assert_eq!(Vec::<u8>::new(), inflate_fixed(&zlib_compress(&[])));
+        // the check value from the PNG spec's IEND chunk
+        assert_eq!(0xae42_6082, crc32(b"IEND"));
+    }
+
+    #[test]
+    fn apng_chunks() {
+        let mut apng = ApngWriter::new(vec![], 3, 2, 4, Repeat::Forever);
+        let mut frame = PPMImg::new(2, 3, 255);
+        for _ in 0..3 {
+            apng.add_frame(&frame).unwrap();
+            frame.plot(1, 1);
+        }
+        let bytes = apng.finish().unwrap();
+
+        // walk the chunks, checking every crc
+        let mut kinds = vec![];
+        let mut i = 8;
+        while i < bytes.len() {
+            let len = u32::from_be_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+            let body = &bytes[i + 4..i + 8 + len as usize];
+            let crc = &bytes[i + 8 + len as usize..i + 12 + len as usize];
+            assert_eq!(&crc32(body).to_be_bytes(), crc);
+            kinds.push(String::from_utf8(body[..4].to_vec()).unwrap());
+            i += 12 + len as usize;
+        }
+        let expected = ["IHDR", "acTL", "fcTL", "IDAT", "fcTL", "fdAT", "fcTL", "fdAT", "IEND"];
+        assert_eq!(expected.to_vec(), kinds);
+    }
+
+    #[test]
+    fn png_files_read_back() {
+        let mut img = PPMImg::new(4, 5, 255);
+        img.fg_color = RGB::ORANGE;
+        img.draw_line(0.0, 0.0, 4.0, 3.0);
+        let mut bytes = vec![];
+        write_png(&mut bytes, &img).unwrap();
+        let back = read_png(&bytes).unwrap();
+        assert_eq!((5, 4, 255), (back.width(), back.height(), back.depth()));
+        assert!(back.data == img.data && back.alpha.is_none());
+
+        // a 3 by 1 palette image at 2 bits a pixel, with the middle color see through
+        let mut bytes = SIGNATURE.to_vec();
+        let mut header = ihdr(3, 1);
+        header[8..10].copy_from_slice(&[2, 3]);
+        write_chunk(&mut bytes, b"IHDR", &header).unwrap();
+        write_chunk(&mut bytes, b"PLTE", &[255, 0, 0, 0, 255, 0, 0, 0, 255]).unwrap();
+        write_chunk(&mut bytes, b"tRNS", &[255, 0]).unwrap();
+        write_chunk(&mut bytes, b"IDAT", &zlib_compress(&[0, 0b1001_0000])).unwrap();
+        write_chunk(&mut bytes, b"IEND", &[]).unwrap();
+        let back = read_png(&bytes).unwrap();
+        assert_eq!(vec![RGB::BLUE, RGB::GREEN, RGB::RED], back.data);
+        assert_eq!(Some(vec![1.0, 0.0, 1.0]), back.alpha);
+
+        let empty = PPMImg::new(5, 0, 255);
+        assert!(write_png(&mut vec![], &empty).is_err());
+        let mut bytes = SIGNATURE.to_vec();
+        write_chunk(&mut bytes, b"IHDR", &ihdr(0, 1)).unwrap();
+        write_chunk(&mut bytes, b"IDAT", &zlib_compress(&[0])).unwrap();
+        assert!(read_png(&bytes).is_err());
+    }
+
+    #[test]
+    fn inflate_dynamic_and_stored_blocks() {
+        // from zlib at level 9, which picks dynamic Huffman codes
+        let z = [
+            0x78, 0xda, 0xe5, 0xcc, 0x81, 0x0d, 0xc0, 0x20, 0x08, 0x00, 0xb0, 0x5b, 0xd9, 0x44,
+            0x20, 0x0a, 0x62, 0x50, 0x78, 0x7f, 0x87, 0xac, 0x07, 0x14, 0xb8, 0x47, 0xfa, 0x03,
+            0x16, 0x56, 0xb0, 0xce, 0x6e, 0x78, 0x1b, 0xe8, 0x62, 0xb7, 0x97, 0x40, 0x24, 0xdf,
+            0x64, 0x2e, 0x84, 0xa9, 0x38, 0xe5, 0x94, 0x57, 0x0e, 0x93, 0xed, 0x34, 0xba, 0x3b,
+            0xa9, 0xc4, 0xd5, 0xd8, 0x48, 0xf0, 0x8f, 0xe4, 0x03, 0x57, 0xc2, 0x7e, 0x7e,
+        ];
+        let data: Vec<u8> = (0..300usize).map(|i| ((i * i * 7 + i / 3) % 23 + 97) as u8).collect();
+        assert_eq!(data, zlib_decompress(&z).unwrap());
+
+        let mut stored = vec![0x78, 0x01, 0x01, 3, 0, !3, !0];
+        stored.extend_from_slice(b"abc");
+        stored.extend_from_slice(&adler32(b"abc").to_be_bytes());
+        assert_eq!(b"abc".to_vec(), zlib_decompress(&stored).unwrap());
+        stored[7] = b'x';
+        assert!(zlib_decompress(&stored).is_err());
+    }
+}
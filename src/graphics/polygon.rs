@@ -0,0 +1,157 @@
+#![allow(dead_code)]
+
+type Point = (f64, f64);
+
+/// Twice the signed area of the triangle `o a b`, positive when it turns counter clockwise
+fn cross(o: Point, a: Point, b: Point) -> f64 {
+    (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+}
+
+/// Convex hull of a set of points by Andrew's monotone chain
+///
+/// Returns the hull counter clockwise, closed by repeating its first point.
+/// Points on the hull's edges are left out.
+pub fn convex_hull(points: &[Point]) -> Vec<Point> {
+    let mut pts = points.to_vec();
+    pts.sort_by(|a, b| a.partial_cmp(b).expect("Points must not be NaN"));
+    pts.dedup();
+    if pts.len() < 3 {
+        return pts;
+    }
+
+    let mut hull: Vec<Point> = Vec::with_capacity(pts.len() + 1);
+    // lower hull left to right, then upper hull right to left
+    for pass in 0..2 {
+        let start = hull.len();
+        let mut add = |p: Point| {
+            while hull.len() >= start + 2 {
+                let n = hull.len();
+                if cross(hull[n - 2], hull[n - 1], p) > 0.0 {
+                    break;
+                }
+                hull.pop();
+            }
+            hull.push(p);
+        };
+        if pass == 0 {
+            pts.iter().for_each(|&p| add(p));
+        } else {
+            pts.iter().rev().for_each(|&p| add(p));
+        }
+        // each chain ends where the other begins
+        hull.pop();
+    }
+    hull.push(hull[0]);
+    hull
+}
+
+/// Twice the signed area of a polygon, positive when it goes counter clockwise
+pub fn signed_area(polygon: &[Point]) -> f64 {
+    let n = polygon.len();
+    (0..n)
+        .map(|i| {
+            let (a, b) = (polygon[i], polygon[(i + 1) % n]);
+            a.0 * b.1 - b.0 * a.1
+        })
+        .sum()
+}
+
+/// Split a simple polygon into triangles by ear clipping, as indices into `polygon`
+///
+/// The polygon may go either way around and may repeat its first point at the end.
+/// Triangles keep the winding of the polygon. Holes are not supported.
+pub fn triangulate_indices(polygon: &[Point]) -> Vec<[usize; 3]> {
+    let mut n = polygon.len();
+    if n > 1 && polygon[0] == polygon[n - 1] {
+        n -= 1;
+    }
+    let mut tris = vec![];
+    if n < 3 {
+        return tris;
+    }
+    let sign = signed_area(&polygon[..n]).signum();
+    let mut left: Vec<usize> = (0..n).collect();
+
+    let inside = |p: Point, a: Point, b: Point, c: Point| {
+        sign * cross(a, b, p) >= 0.0 && sign * cross(b, c, p) >= 0.0 && sign * cross(c, a, p) >= 0.0
+    };
+    while left.len() > 3 {
+        let m = left.len();
+        let ear = (0..m).find(|&i| {
+            let (a, b, c) = (left[(i + m - 1) % m], left[i], left[(i + 1) % m]);
+            let (pa, pb, pc) = (polygon[a], polygon[b], polygon[c]);
+            // the corner must be convex, with no other corner inside it
+            sign * cross(pa, pb, pc) > 0.0
+                && left.iter().all(|&j| {
+                    let p = polygon[j];
+                    // corners repeated elsewhere in the polygon touch the ear without blocking it
+                    let corner = j == a || j == b || j == c || p == pa || p == pb || p == pc;
+                    corner || !inside(p, pa, pb, pc)
+                })
+        });
+        match ear {
+            Some(i) => {
+                tris.push([left[(i + m - 1) % m], left[i], left[(i + 1) % m]]);
+                left.remove(i);
+            }
+            // only degenerate corners are left, which clip to nothing
+            None => {
+                left.remove(0);
+            }
+        }
+    }
+    let (pa, pb, pc) = (polygon[left[0]], polygon[left[1]], polygon[left[2]]);
+    if sign * cross(pa, pb, pc) > 0.0 {
+        tris.push([left[0], left[1], left[2]]);
+    }
+    tris
+}
+
+/// Split a simple polygon into triangles by ear clipping, see `triangulate_indices`
+pub fn triangulate(polygon: &[Point]) -> Vec<[Point; 3]> {
+    triangulate_indices(polygon)
+        .into_iter()
+        .map(|[a, b, c]| [polygon[a], polygon[b], polygon[c]])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hull_of_square_with_inside_points() {
+        let pts = [
+            (0.0, 0.0),
+            (2.0, 2.0),
+            (1.0, 1.0),
+            (2.0, 0.0),
+            (0.0, 2.0),
+            (1.0, 0.0),
+            (0.5, 1.5),
+        ];
+        assert_eq!(
+            vec![(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0), (0.0, 0.0)],
+            convex_hull(&pts)
+        );
+    }
+
+    #[test]
+    fn triangulate_concave_polygon() {
+        // an L shape, clockwise, with the closing point repeated
+        let l = [
+            (0.0, 0.0),
+            (0.0, 2.0),
+            (1.0, 2.0),
+            (1.0, 1.0),
+            (2.0, 1.0),
+            (2.0, 0.0),
+            (0.0, 0.0),
+        ];
+        let tris = triangulate(&l);
+        assert_eq!(4, tris.len());
+        let area: f64 = tris.iter().map(|t| signed_area(t)).sum();
+        assert!((area - signed_area(&l)).abs() < 1e-9);
+        assert!(tris.iter().all(|t| signed_area(t) < 0.0));
+    }
+}
@@ -0,0 +1,322 @@
+#![allow(dead_code)]
+
+use noise::{NoiseFn, Perlin, Seedable};
+
+type Point = (f64, f64);
+
+fn lerp(a: Point, b: Point, t: f64) -> Point {
+    (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+}
+
+/// How `Matrix::add_smoothed_polyline` smooths a control polygon
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Smoothing {
+    /// Cut corners, see `chaikin`
+    Chaikin,
+    /// Pass through the points, see `four_point`
+    FourPoint { tension: f64 },
+}
+
+impl Smoothing {
+    pub fn apply(&self, points: &[Point], iterations: usize, closed: bool) -> Vec<Point> {
+        match *self {
+            Smoothing::Chaikin => chaikin(points, iterations, closed),
+            Smoothing::FourPoint { tension } => four_point(points, iterations, tension, closed),
+        }
+    }
+}
+
+/// Smooth a control polygon with Chaikin's corner cutting
+///
+/// Every iteration replaces each edge with points at 1/4 and 3/4 along it.
+/// Open polygons keep their endpoints; closed polygons come back closed,
+/// with the first point repeated at the end.
+pub fn chaikin(points: &[Point], iterations: usize, closed: bool) -> Vec<Point> {
+    let mut pts = strip_closing_point(points, closed);
+    for _ in 0..iterations {
+        if pts.len() < 3 {
+            break;
+        }
+        let n = pts.len();
+        let edges = if closed { n } else { n - 1 };
+        let mut next = Vec::with_capacity(edges * 2 + 2);
+        if !closed {
+            next.push(pts[0]);
+        }
+        for i in 0..edges {
+            let (a, b) = (pts[i], pts[(i + 1) % n]);
+            next.push(lerp(a, b, 0.25));
+            next.push(lerp(a, b, 0.75));
+        }
+        if !closed {
+            next.push(pts[n - 1]);
+        }
+        pts = next;
+    }
+    close_if(pts, closed)
+}
+
+/// Smooth a control polygon with the 4 point interpolatory scheme (Dyn, Levin, Gregory)
+///
+/// Unlike Chaikin, the original points stay on the curve.
+/// `tension` of 1/16 gives the classic C1 scheme.
+pub fn four_point(points: &[Point], iterations: usize, tension: f64, closed: bool) -> Vec<Point> {
+    let mut pts = strip_closing_point(points, closed);
+    for _ in 0..iterations {
+        if pts.len() < 3 {
+            break;
+        }
+        let n = pts.len();
+        // open ends reuse the nearest point in place of the missing neighbor
+        let at = |i: isize| -> Point {
+            if closed {
+                pts[i.rem_euclid(n as isize) as usize]
+            } else {
+                pts[i.clamp(0, n as isize - 1) as usize]
+            }
+        };
+        let edges = if closed { n } else { n - 1 };
+        let mut next = Vec::with_capacity(edges * 2 + 1);
+        for i in 0..edges as isize {
+            let (p0, p1, p2, p3) = (at(i - 1), at(i), at(i + 1), at(i + 2));
+            next.push(p1);
+            next.push((
+                (0.5 + tension) * (p1.0 + p2.0) - tension * (p0.0 + p3.0),
+                (0.5 + tension) * (p1.1 + p2.1) - tension * (p0.1 + p3.1),
+            ));
+        }
+        if !closed {
+            next.push(pts[n - 1]);
+        }
+        pts = next;
+    }
+    close_if(pts, closed)
+}
+
+fn distance(a: Point, b: Point) -> f64 {
+    (b.0 - a.0).hypot(b.1 - a.1)
+}
+
+/// Total length of a polyline
+pub fn length(points: &[Point]) -> f64 {
+    points.windows(2).map(|w| distance(w[0], w[1])).sum()
+}
+
+/// Resample a polyline so that consecutive points are `spacing` apart along it
+///
+/// The first and last points are kept, so the last gap may be shorter
+pub fn resample(points: &[Point], spacing: f64) -> Vec<Point> {
+    assert!(spacing > 0.0, "Spacing must be positive");
+    let mut out = vec![];
+    let (first, last) = match (points.first(), points.last()) {
+        (Some(f), Some(l)) => (*f, *l),
+        _ => return out,
+    };
+    out.push(first);
+    // distance left until the next sample
+    let mut left = spacing;
+    for w in points.windows(2) {
+        let (mut p, q) = (w[0], w[1]);
+        let mut seg = distance(p, q);
+        while seg >= left {
+            let f = left / seg;
+            p = (p.0 + (q.0 - p.0) * f, p.1 + (q.1 - p.1) * f);
+            out.push(p);
+            seg -= left;
+            left = spacing;
+        }
+        left -= seg;
+    }
+    if out.last() != Some(&last) {
+        if left > spacing * 1e-9 {
+            out.push(last);
+        } else {
+            *out.last_mut().unwrap() = last;
+        }
+    }
+    out
+}
+
+/// Smooth a polyline by averaging each point with its neighbors
+///
+/// `radius` neighbors on each side are used; the window shrinks near open ends so they stay put
+pub fn moving_average(points: &[Point], radius: usize, closed: bool) -> Vec<Point> {
+    let pts = strip_closing_point(points, closed);
+    let n = pts.len();
+    let smoothed = (0..n)
+        .map(|i| {
+            if !closed && (i == 0 || i == n - 1) {
+                return pts[i];
+            }
+            let r = if closed {
+                radius.min((n - 1) / 2)
+            } else {
+                radius.min(i).min(n - 1 - i)
+            };
+            let (mut x, mut y) = (0.0, 0.0);
+            for k in 0..=2 * r {
+                let p = pts[(i + n + k - r) % n];
+                x += p.0;
+                y += p.1;
+            }
+            let count = (2 * r + 1) as f64;
+            (x / count, y / count)
+        })
+        .collect();
+    close_if(smoothed, closed)
+}
+
+/// Laplacian smoothing: move each point `lambda` of the way toward the midpoint of its neighbors
+///
+/// Open ends stay put. A `lambda` in (0, 1] is stable.
+pub fn laplacian(points: &[Point], lambda: f64, iterations: usize, closed: bool) -> Vec<Point> {
+    let mut pts = strip_closing_point(points, closed);
+    let n = pts.len();
+    if n < 3 {
+        return close_if(pts, closed);
+    }
+    for _ in 0..iterations {
+        pts = (0..n)
+            .map(|i| {
+                if !closed && (i == 0 || i == n - 1) {
+                    return pts[i];
+                }
+                let (a, b) = (pts[(i + n - 1) % n], pts[(i + 1) % n]);
+                let p = pts[i];
+                (
+                    p.0 + lambda * ((a.0 + b.0) / 2.0 - p.0),
+                    p.1 + lambda * ((a.1 + b.1) / 2.0 - p.1),
+                )
+            })
+            .collect();
+    }
+    close_if(pts, closed)
+}
+
+/// Push every point along the polyline's normal by 1d Perlin noise, for a hand drawn look
+///
+/// Noise is sampled by distance along the polyline, so resample it first if the points are sparse
+/// # Arguments
+/// `amplitude` - Largest offset in pixels
+/// `frequency` - Wiggles per pixel of length; around 0.02 to 0.1 looks hand drawn
+/// `seed` - Different seeds give different strokes
+pub fn perturb_with_noise(
+    points: &[Point],
+    amplitude: f64,
+    frequency: f64,
+    seed: u32,
+    closed: bool,
+) -> Vec<Point> {
+    let pts = strip_closing_point(points, closed);
+    let n = pts.len();
+    if n < 2 {
+        return close_if(pts, closed);
+    }
+    let noise = Perlin::new().set_seed(seed);
+    let mut s = 0.0;
+    let perturbed = (0..n)
+        .map(|i| {
+            if i > 0 {
+                s += distance(pts[i - 1], pts[i]);
+            }
+            let (prev, next) = if closed {
+                (pts[(i + n - 1) % n], pts[(i + 1) % n])
+            } else {
+                (pts[i.saturating_sub(1)], pts[(i + 1).min(n - 1)])
+            };
+            let (tx, ty) = (next.0 - prev.0, next.1 - prev.1);
+            let len = tx.hypot(ty);
+            if len == 0.0 {
+                return pts[i];
+            }
+            // perlin noise is 0 on integer lattice points, so sample between them
+            let offset = amplitude * noise.get([s * frequency, 0.5]).clamp(-1.0, 1.0);
+            (pts[i].0 - ty / len * offset, pts[i].1 + tx / len * offset)
+        })
+        .collect();
+    close_if(perturbed, closed)
+}
+
+/// Closed input may or may not repeat its first point at the end; drop it if it does
+fn strip_closing_point(points: &[Point], closed: bool) -> Vec<Point> {
+    let mut pts = points.to_vec();
+    if closed && pts.len() > 1 && pts.first() == pts.last() {
+        pts.pop();
+    }
+    pts
+}
+
+fn close_if(mut pts: Vec<Point>, closed: bool) -> Vec<Point> {
+    if closed && !pts.is_empty() {
+        pts.push(pts[0]);
+    }
+    pts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resample_even_spacing() {
+        let pts = resample(&[(0.0, 0.0), (3.0, 0.0), (3.0, 4.5)], 1.5);
+        assert_eq!(6, pts.len());
+        assert_eq!((3.0, 0.0), pts[2]);
+        assert_eq!((3.0, 4.5), pts[5]);
+        for w in pts.windows(2) {
+            assert!((distance(w[0], w[1]) - 1.5).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn smoothing_keeps_open_ends() {
+        let pts = vec![(0.0, 0.0), (1.0, 5.0), (2.0, -5.0), (3.0, 5.0), (4.0, 0.0)];
+        for smoothed in [moving_average(&pts, 1, false), laplacian(&pts, 0.5, 3, false)].iter() {
+            assert_eq!(pts.len(), smoothed.len());
+            assert_eq!(pts[0], smoothed[0]);
+            assert_eq!(pts[4], smoothed[4]);
+            assert!(smoothed[2].1.abs() < 5.0);
+        }
+    }
+
+    #[test]
+    fn subdivision_cuts_corners_or_keeps_points() {
+        let pts = [(0.0, 0.0), (4.0, 0.0), (4.0, 4.0)];
+        let cut = vec![(0.0, 0.0), (1.0, 0.0), (3.0, 0.0), (4.0, 1.0), (4.0, 3.0), (4.0, 4.0)];
+        assert_eq!(cut, chaikin(&pts, 1, false));
+        // every iteration doubles the points, open or closed
+        assert_eq!(12, chaikin(&pts, 2, false).len());
+        let closed = chaikin(&pts, 2, true);
+        assert_eq!(12 + 1, closed.len());
+        assert_eq!((1.5, 0.0), closed[0]);
+        assert_eq!(closed[0], closed[12]);
+        assert_eq!(chaikin(&pts, 1, true), chaikin(&[pts[0], pts[1], pts[2], pts[0]], 1, true));
+
+        let open = four_point(&pts, 2, 1.0 / 16.0, false);
+        assert_eq!(9, open.len());
+        let closed = four_point(&pts, 1, 1.0 / 16.0, true);
+        assert_eq!(7, closed.len());
+        for (i, p) in pts.iter().enumerate() {
+            assert_eq!(*p, open[4 * i]);
+            assert_eq!(*p, closed[2 * i]);
+        }
+        // the classic weights for a new point between the middle two of four
+        let bump = [(0.0, 0.0), (1.0, 1.0), (2.0, 1.0), (3.0, 0.0)];
+        let line = four_point(&bump, 1, 1.0 / 16.0, false);
+        assert_eq!((1.5, 1.125), line[3]);
+    }
+
+    #[test]
+    fn noise_stays_within_its_amplitude() {
+        let pts = resample(&[(0.0, 0.0), (200.0, 0.0)], 1.0);
+        assert_eq!(pts, perturb_with_noise(&pts, 0.0, 0.05, 7, false));
+        let wobbly = perturb_with_noise(&pts, 3.0, 0.05, 7, false);
+        assert_eq!(wobbly, perturb_with_noise(&pts, 3.0, 0.05, 7, false));
+        assert_ne!(wobbly, perturb_with_noise(&pts, 3.0, 0.05, 8, false));
+        // a straight line along x only moves along y
+        for (p, q) in pts.iter().zip(wobbly.iter()) {
+            assert!(p.0 == q.0 && (q.1 - p.1).abs() <= 3.0, "{:?} moved to {:?}", p, q);
+        }
+        assert!(wobbly.iter().any(|q| q.1.abs() > 0.5));
+    }
+}
@@ -0,0 +1,115 @@
+#![allow(dead_code)]
+
+//! A window that shows frames as they are drawn, instead of saving and opening files
+//!
+//! The window comes from minifb, which the `preview` feature pulls in.
+
+use std::io;
+
+use minifb::{Window, WindowOptions};
+
+use super::PPMImg;
+
+/// A preview window for frames of one size
+pub struct Preview {
+    window: Window,
+    /// The last frame shown, packed the way minifb takes it
+    buffer: Vec<u32>,
+    width: u32,
+    height: u32,
+}
+
+impl Preview {
+    /// Open a window showing up to `fps` frames per second
+    pub fn open(width: u32, height: u32, fps: u32) -> io::Result<Self> {
+        let mut window = Window::new(
+            "preview",
+            width as usize,
+            height as usize,
+            WindowOptions::default(),
+        )
+        .map_err(|e| io::Error::other(format!("Could not open a preview window: {}", e)))?;
+        window.set_target_fps(fps.max(1) as usize);
+        Ok(Preview {
+            window,
+            buffer: vec![0; width as usize * height as usize],
+            width,
+            height,
+        })
+    }
+
+    /// Show a frame, which must be as big as the window
+    ///
+    /// Returns false once the window has been closed, so that render loops can stop.
+    pub fn show(&mut self, img: &PPMImg) -> io::Result<bool> {
+        if img.samples > 1 {
+            return self.show(&img.downsample());
+        }
+        if img.width() != self.width || img.height() != self.height {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Every frame must be as big as the preview",
+            ));
+        }
+        if !self.window.is_open() {
+            return Ok(false);
+        }
+        self.buffer = pack(img);
+        self.refresh()?;
+        Ok(self.window.is_open())
+    }
+
+    /// Put the last frame up again and take in input, without waiting for a new frame
+    pub fn refresh(&mut self) -> io::Result<()> {
+        let (w, h) = (self.width as usize, self.height as usize);
+        self.window
+            .update_with_buffer(&self.buffer, w, h)
+            .map_err(|e| io::Error::other(e.to_string()))
+    }
+
+    /// Whether the window is still open
+    pub fn is_open(&self) -> bool {
+        self.window.is_open()
+    }
+
+    /// The window itself, for reading the mouse and keyboard
+    pub fn window(&self) -> &Window {
+        &self.window
+    }
+
+    /// Keep showing the last frame until the window is closed
+    pub fn wait(mut self) -> io::Result<()> {
+        while self.window.is_open() {
+            self.refresh()?;
+        }
+        Ok(())
+    }
+}
+
+/// Every pixel as `0x00RRGGBB`, 8 bits a channel
+fn pack(img: &PPMImg) -> Vec<u32> {
+    img.rows()
+        .flat_map(|row| row.iter())
+        .map(|p| {
+            let [r, g, b] = p.to_bytes(img.depth());
+            (r as u32) << 16 | (g as u32) << 8 | b as u32
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::RGB;
+
+    #[test]
+    fn frames_are_packed_row_by_row() {
+        let mut img = PPMImg::new(2, 3, 1023);
+        img.plot_color(2, 0, RGB::new(1023, 0, 0));
+        img.plot_color(0, 1, RGB::new(0, 512, 1023));
+        let words = pack(&img);
+        assert_eq!(6, words.len());
+        assert_eq!(0xff0000, words[2]);
+        assert_eq!(0x0080ff, words[3]);
+    }
+}
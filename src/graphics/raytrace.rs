@@ -0,0 +1,338 @@
+#![allow(dead_code)]
+
+//! A second renderer for scenes, tracing a ray from every pixel
+//!
+//! Rays go down the z axis, the way the viewer of `PPMImg::shade_scene` looks, so a traced
+//! image lines up with a shaded one pixel for pixel and can be held against it. Spheres are
+//! round here rather than made of triangles, and lights that cast shadows are blocked by
+//! anything in the way, not only over the scene's ground. Every ray is tested against every
+//! shape, so tracing is for stills and comparisons rather than animation.
+
+use std::thread;
+
+use super::mesh::Mesh;
+use super::scene::{Geometry, Scene};
+use super::shading::{Light, Material, Shading};
+use super::vector::{self, Vec3};
+use super::{PPMImg, RGB};
+
+/// How far a ray goes before it can hit anything, so surfaces do not shadow themselves
+const EPSILON: f64 = 1e-6;
+
+enum Shape {
+    Sphere {
+        center: Vec3,
+        radius: f64,
+    },
+    /// Corners, and the normals and colors there
+    Triangle {
+        corners: [Vec3; 3],
+        normals: [Vec3; 3],
+        colors: [Option<RGB>; 3],
+    },
+}
+
+struct Primitive {
+    shape: Shape,
+    material: Material,
+}
+
+/// Where a ray meets a surface
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Hit {
+    /// How far along the ray, in lengths of its direction
+    pub t: f64,
+    pub point: Vec3,
+    /// Unit normal of the surface there
+    pub normal: Vec3,
+    /// Color of the surface there, before it is lit
+    pub color: RGB,
+    pub material: Material,
+}
+
+/// The spheres and triangles of a scene, gathered to trace rays through
+pub struct Tracer {
+    primitives: Vec<Primitive>,
+    pub lights: Vec<Light>,
+}
+
+impl Tracer {
+    /// Gathers every object of `scene` and every copy of its instances, with its lights
+    ///
+    /// Pieces without a material of their own are made of `material`. With flat shading every
+    /// triangle takes the normal of its face and the color of its first point, as for
+    /// `PPMImg::shade_polygon_matrix`; otherwise normals and colors are blended as for Phong
+    /// shading. Edges are left out.
+    pub fn new(scene: &Scene, material: &Material, shading: Shading) -> Self {
+        let mut tracer = Tracer {
+            primitives: vec![],
+            lights: scene.lights.clone(),
+        };
+        let objects = scene.objects.iter().map(|obj| obj.material);
+        let objects = objects.zip(scene.transformed_objects());
+        let instanced = scene.instances.iter().flat_map(|instance| {
+            instance.copies().map(move |g| (instance.material, g))
+        });
+        for (own, g) in objects.chain(instanced) {
+            tracer.add(&g, own.unwrap_or(*material), shading);
+        }
+        tracer
+    }
+
+    /// Adds one piece of geometry, as it is
+    pub fn add(&mut self, g: &Geometry, material: Material, shading: Shading) {
+        let mesh = match g {
+            Geometry::Edges(_) => return,
+            &Geometry::Sphere { center, radius } => {
+                let shape = Shape::Sphere { center, radius };
+                self.primitives.push(Primitive { shape, material });
+                return;
+            }
+            Geometry::Polygons(m) if shading == Shading::Flat => {
+                let rows: Vec<&[f64]> = m.iter_by_row().collect();
+                for (i, t) in rows.chunks_exact(3).enumerate() {
+                    let corners = [0, 1, 2].map(|k| (t[k][0], t[k][1], t[k][2]));
+                    self.add_flat(corners, m.color(3 * i), material);
+                }
+                return;
+            }
+            Geometry::Polygons(m) => Mesh::from_polygons(m),
+            Geometry::Mesh(mesh) => mesh.clone(),
+        };
+        if shading == Shading::Flat {
+            for (face, corners) in mesh.faces.iter().zip(mesh.faces_iter()) {
+                self.add_flat(corners, mesh.vertex_color(face[0]), material);
+            }
+            return;
+        }
+        let faces = mesh.faces.iter().zip(mesh.faces_iter()).zip(mesh.corner_normals());
+        for ((face, corners), normals) in faces {
+            let colors = face.map(|i| mesh.vertex_color(i));
+            let shape = Shape::Triangle {
+                corners,
+                normals,
+                colors,
+            };
+            self.primitives.push(Primitive { shape, material });
+        }
+    }
+
+    fn add_flat(&mut self, corners: [Vec3; 3], color: Option<RGB>, material: Material) {
+        let [a, b, c] = corners;
+        let normal = vector::normalize(vector::triangle_normal(a, b, c));
+        let shape = Shape::Triangle {
+            corners,
+            normals: [normal; 3],
+            colors: [color; 3],
+        };
+        self.primitives.push(Primitive { shape, material });
+    }
+
+    /// The nearest surface a ray from `origin` along `direction` meets, closer than `max_t`
+    ///
+    /// With `cull` set, triangles seen from behind are passed through, as the rasterizer
+    /// skips them.
+    pub fn hit(&self, origin: Vec3, direction: Vec3, max_t: f64, cull: bool) -> Option<Hit> {
+        let mut nearest: Option<(f64, &Primitive, [f64; 3])> = None;
+        for p in self.primitives.iter() {
+            let found = match p.shape {
+                Shape::Sphere { center, radius } => {
+                    hit_sphere(center, radius, origin, direction).map(|t| (t, [0.0; 3]))
+                }
+                Shape::Triangle { corners, .. } => hit_triangle(corners, origin, direction, cull),
+            };
+            if let Some((t, weights)) = found {
+                if t < max_t && nearest.as_ref().is_none_or(|n| t < n.0) {
+                    nearest = Some((t, p, weights));
+                }
+            }
+        }
+        let (t, p, w) = nearest?;
+        let point = vector::add(origin, vector::scale(direction, t));
+        let (normal, color) = match p.shape {
+            Shape::Sphere { center, .. } => {
+                (vector::normalize(vector::sub(point, center)), p.material.color)
+            }
+            Shape::Triangle {
+                normals, colors, ..
+            } => {
+                let parts = (0..3).map(|k| vector::scale(normals[k], w[k]));
+                let normal = vector::normalize(parts.fold((0.0, 0.0, 0.0), vector::add));
+                let colors = colors.map(|c| c.unwrap_or(p.material.color));
+                (normal, RGB::blend3(colors, w))
+            }
+        };
+        Some(Hit {
+            t,
+            point,
+            normal,
+            color,
+            material: p.material,
+        })
+    }
+
+    /// Whether something stands between `point` and `light`
+    pub fn in_shadow(&self, point: Vec3, light: &Light) -> bool {
+        match light.toward(point) {
+            Some(toward) if light.casts_shadows() => {
+                self.hit(point, toward, light.distance(point), false).is_some()
+            }
+            _ => false,
+        }
+    }
+
+    /// The lit color seen along a ray, or None if it meets nothing
+    pub fn trace(&self, origin: Vec3, direction: Vec3, depth: u16) -> Option<RGB> {
+        let hit = self.hit(origin, direction, f64::INFINITY, true)?;
+        let lights: Vec<Light> = self
+            .lights
+            .iter()
+            .filter(|l| !self.in_shadow(hit.point, l))
+            .copied()
+            .collect();
+        Some(hit.material.shade(hit.color, &lights, hit.point, hit.normal, depth))
+    }
+
+    /// Highest z of any surface, where rays from the viewer start
+    fn top(&self) -> f64 {
+        let top = |p: &Primitive| match p.shape {
+            Shape::Sphere { center, radius } => center.2 + radius,
+            Shape::Triangle { corners, .. } => corners.iter().map(|c| c.2).fold(f64::MIN, f64::max),
+        };
+        self.primitives.iter().map(top).fold(0.0, f64::max)
+    }
+}
+
+/// How far along the ray it first meets the sphere from outside, or leaves it from inside
+fn hit_sphere(center: Vec3, radius: f64, origin: Vec3, direction: Vec3) -> Option<f64> {
+    let oc = vector::sub(origin, center);
+    let a = vector::dot(direction, direction);
+    let b = vector::dot(oc, direction);
+    let c = vector::dot(oc, oc) - radius * radius;
+    let discriminant = b * b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    [(-b - root) / a, (-b + root) / a].iter().copied().find(|&t| t > EPSILON)
+}
+
+/// How far along the ray it meets the triangle, with how much each corner counts there, by
+/// the Möller–Trumbore test
+fn hit_triangle(
+    [a, b, c]: [Vec3; 3],
+    origin: Vec3,
+    direction: Vec3,
+    cull: bool,
+) -> Option<(f64, [f64; 3])> {
+    let (e1, e2) = (vector::sub(b, a), vector::sub(c, a));
+    let p = vector::cross(direction, e2);
+    // positive when the ray meets the front, which winds counter clockwise
+    let det = vector::dot(e1, p);
+    if det.abs() < 1e-12 || (cull && det < 0.0) {
+        return None;
+    }
+    let s = vector::sub(origin, a);
+    let u = vector::dot(s, p) / det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = vector::cross(s, e1);
+    let v = vector::dot(direction, q) / det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = vector::dot(e2, q) / det;
+    (t > EPSILON).then_some((t, [1.0 - u - v, u, v]))
+}
+
+impl PPMImg {
+    /// Ray traces a scene, as a stand-in for `shade_scene` that gets spheres, shadows and
+    /// triangles cutting through each other right
+    ///
+    /// The background is painted first, and edges are drawn over the traced image as they
+    /// are. Pixels where rays meet nothing are left alone. Rows are traced in parallel.
+    pub fn trace_scene(&mut self, scene: &Scene, material: &Material, shading: Shading) {
+        scene.draw_background(self);
+        let tracer = Tracer::new(scene, material, shading);
+        let (width, height) = (self.width as usize, self.height as usize);
+        let (top, depth) = (tracer.top() + 1.0, self.depth);
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        let rows = height.div_ceil(threads).max(1);
+        let img = &*self;
+        let tracer = &tracer;
+        let traced: Vec<Option<RGB>> = thread::scope(|scope| {
+            let bands: Vec<_> = (0..height)
+                .step_by(rows)
+                .map(|y0| {
+                    scope.spawn(move || {
+                        let pixels = (y0..(y0 + rows).min(height))
+                            .flat_map(|y| (0..width).map(move |x| (x, y)));
+                        pixels
+                            .map(|(x, y)| {
+                                let (wx, wy) = img.screen_to_world(x as f64, y as f64);
+                                tracer.trace((wx, wy, top), (0.0, 0.0, -1.0), depth)
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            bands.into_iter().flat_map(|band| band.join().unwrap()).collect()
+        });
+        for (i, color) in traced.into_iter().enumerate() {
+            if let Some(color) = color {
+                self.plot_color((i % width) as i32, (i / width) as i32, color);
+            }
+        }
+
+        let objects = scene.transformed_objects();
+        let instanced = scene.instances.iter().flat_map(|instance| instance.copies());
+        for g in objects.chain(instanced) {
+            if let Geometry::Edges(m) = g {
+                self.render_edge_matrix(&m);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::matrix::Matrix;
+
+    #[test]
+    fn spheres_cast_shadows_on_what_is_behind() {
+        let mut floor = Matrix::new(0, 4, vec![]);
+        floor.append_polygon(&[-5.0, -5.0, -10.0, 25.0, -5.0, -10.0, 25.0, 25.0, -10.0]);
+        floor.append_polygon(&[-5.0, -5.0, -10.0, 25.0, 25.0, -10.0, -5.0, 25.0, -10.0]);
+        let mut scene = Scene::new();
+        scene.add_static(Geometry::Polygons(floor), &Matrix::ident(4));
+        let ball = Geometry::Sphere {
+            center: (0.0, 0.0, 0.0),
+            radius: 6.0,
+        };
+        scene.add_static(ball, &Matrix::mv(10.0, 10.0, 0.0));
+        scene.lights = vec![
+            Light::Ambient(RGB::gray(26)),
+            Light::Directional {
+                direction: (1.0, 0.0, 1.0),
+                color: RGB::WHITE,
+                shadows: true,
+            },
+        ];
+
+        let mut traced = PPMImg::new(21, 21, 255);
+        traced.trace_scene(&scene, &Material::default(), Shading::Phong);
+        let lit_floor = traced.pixel(19, 10).unwrap();
+        assert!((lit_floor.red as i32 - 206).abs() <= 1, "{:?}", lit_floor);
+        assert_eq!(Some(RGB::gray(26)), traced.pixel(2, 10));
+        assert_eq!(Some(lit_floor), traced.pixel(10, 10));
+
+        // the rasterizer sees the same ball, but no shadow without a ground
+        let mut shaded = PPMImg::new(21, 21, 255);
+        shaded.shade_scene(&scene, &Material::default(), Shading::Phong);
+        let (a, b) = (traced.pixel(12, 8).unwrap(), shaded.pixel(12, 8).unwrap());
+        assert!((a.red as i32 - b.red as i32).abs() < 8, "{:?} {:?}", a, b);
+        assert_eq!(Some(lit_floor), shaded.pixel(2, 10));
+    }
+}
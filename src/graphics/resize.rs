@@ -0,0 +1,137 @@
+#![allow(dead_code)]
+
+use super::PPMImg;
+
+/// How to pick colors when resizing
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Filter {
+    /// The closest source pixel, for a blocky look
+    Nearest,
+    /// Blend the four closest source pixels
+    Bilinear,
+    /// Average every source pixel under the new one, by how much of it is covered; best for
+    /// shrinking
+    Box,
+}
+
+/// A rectangle of pixels, with its top left corner at (x, y)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+}
+
+/// For every pixel along a side of `dst` pixels, the source pixels that make it up and their
+/// weights, which add up to 1
+fn weights(src: u32, dst: u32, filter: Filter) -> Vec<Vec<(usize, f64)>> {
+    let scale = src as f64 / dst as f64;
+    let last = src as usize - 1;
+    (0..dst)
+        .map(|i| match filter {
+            Filter::Nearest => vec![((((i as f64 + 0.5) * scale) as usize).min(last), 1.0)],
+            Filter::Bilinear => {
+                let x = ((i as f64 + 0.5) * scale - 0.5).clamp(0.0, last as f64);
+                let (j, t) = (x.floor() as usize, x.fract());
+                vec![(j, 1.0 - t), ((j + 1).min(last), t)]
+            }
+            Filter::Box => {
+                let (start, end) = (i as f64 * scale, (i + 1) as f64 * scale);
+                let stop = (end.ceil() as usize).min(src as usize);
+                (start.floor() as usize..stop)
+                    .map(|j| {
+                        let covered = end.min(j as f64 + 1.0) - start.max(j as f64);
+                        (j, covered / scale)
+                    })
+                    .collect()
+            }
+        })
+        .collect()
+}
+
+/// A copy of `img` stretched to `width` by `height`
+pub fn resize(img: &PPMImg, width: u32, height: u32, filter: Filter) -> PPMImg {
+    assert!(width > 0 && height > 0, "Cannot resize to an empty image");
+    let (wx, wy) = (weights(img.width, width, filter), weights(img.height, height, filter));
+    let mut out = PPMImg::new_with_bg(height, width, img.depth, img.bg_color);
+    out.fg_color = img.fg_color;
+    out.linear_light = img.linear_light;
+    let mut out_alpha = vec![];
+    for (y, row) in wy.iter().enumerate() {
+        for (x, col) in wx.iter().enumerate() {
+            let pixels = row.iter().flat_map(|&(sy, ky)| {
+                col.iter().map(move |&(sx, kx)| (sy * img.width as usize + sx, ky * kx))
+            });
+            let (color, alpha) = img.average(pixels);
+            if let Some(color) = color {
+                out.data[y * width as usize + x] = color;
+            }
+            out_alpha.push(alpha);
+        }
+    }
+    if img.alpha.is_some() {
+        out.alpha = Some(out_alpha);
+    }
+    out
+}
+
+/// A copy of the part of `img` inside `rect`, trimmed to the image
+pub fn crop(img: &PPMImg, rect: Rect) -> PPMImg {
+    let (x1, y1) = (
+        (rect.x + rect.width).min(img.width),
+        (rect.y + rect.height).min(img.height),
+    );
+    assert!(rect.x < x1 && rect.y < y1, "Crop rectangle is off the image");
+    let (width, height) = (x1 - rect.x, y1 - rect.y);
+    let mut out = PPMImg::new_with_bg(height, width, img.depth, img.bg_color);
+    out.fg_color = img.fg_color;
+    out.linear_light = img.linear_light;
+    let rows = (rect.y..y1)
+        .map(|y| (y * img.width) as usize)
+        .map(|start| start + rect.x as usize..start + x1 as usize);
+    out.data = rows.clone().flat_map(|r| img.data[r].iter().copied()).collect();
+    if let Some(alpha) = img.alpha.as_ref() {
+        out.alpha = Some(rows.flat_map(|r| alpha[r].iter().copied()).collect());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::RGB;
+
+    #[test]
+    fn shrink_and_crop() {
+        // a white left half and a black right half
+        let mut img = PPMImg::new(4, 4, 255);
+        img.fill_polygon(&[(-0.5, -0.5), (1.5, -0.5), (1.5, 3.5), (-0.5, 3.5)]);
+
+        let half = resize(&img, 2, 2, Filter::Box);
+        assert_eq!(Some(RGB::gray(255)), half.pixel(0, 1));
+        assert_eq!(Some(RGB::gray(0)), half.pixel(1, 1));
+        // one pixel straddles the middle, and gets half of each
+        let odd = resize(&img, 3, 1, Filter::Box);
+        assert_eq!(Some(RGB::gray(128)), odd.pixel(1, 0));
+        let big = resize(&img, 8, 8, Filter::Nearest);
+        assert_eq!(Some(RGB::gray(255)), big.pixel(3, 7));
+        assert_eq!(Some(RGB::gray(0)), big.pixel(4, 7));
+
+        let piece = crop(&img, Rect::new(1, 2, 10, 10));
+        assert_eq!((2, 3), (piece.height(), piece.width()));
+        assert_eq!(Some(RGB::gray(255)), piece.pixel(0, 0));
+        assert_eq!(Some(RGB::gray(0)), piece.pixel(1, 1));
+    }
+}
@@ -0,0 +1,328 @@
+#![allow(dead_code)]
+
+use std::fmt;
+use std::rc::Rc;
+
+use super::colors::{Gradient, RGB};
+use super::matrix::Matrix;
+use super::mesh::Mesh;
+use super::parametrics::SurfaceMode;
+use super::shading::{Ground, Light, Material};
+use super::vector::Vec3;
+use super::PPMImg;
+
+/// Steps around a sphere when it is drawn as triangles, see `Matrix::add_sphere`
+const SPHERE_STEPS: usize = 24;
+
+/// Anything that can be put in a scene
+#[derive(Clone, Debug)]
+pub enum Geometry {
+    /// Edge matrix, two points per line
+    Edges(Matrix),
+    /// Polygon matrix, three points per triangle
+    Polygons(Matrix),
+    Mesh(Mesh),
+    /// A true sphere for ray tracing, drawn as triangles everywhere else
+    Sphere { center: Vec3, radius: f64 },
+}
+
+impl Geometry {
+    /// Transform the geometry by `t`, in place
+    pub fn bake_transform(&mut self, t: &Matrix) {
+        match self {
+            Geometry::Edges(m) | Geometry::Polygons(m) => m.bake_transform(t),
+            Geometry::Mesh(mesh) => mesh.bake_transform(t),
+            // a sphere stays round, growing by the average scale
+            Geometry::Sphere { center, radius } => {
+                *center = t.transform_point(*center);
+                *radius *= t.scale_factor();
+            }
+        }
+    }
+
+    /// The triangles of a sphere, or None for other geometry
+    pub fn sphere_polygons(&self) -> Option<Matrix> {
+        match *self {
+            Geometry::Sphere { center, radius } => {
+                let mut m = Matrix::new(0, 4, vec![]);
+                m.add_sphere(center, radius, SPHERE_STEPS, SurfaceMode::Polygons);
+                Some(m)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// One prototype drawn many times, each time with its own transformation
+///
+/// Copies are only made one at a time as they are drawn, and prototypes can be shared
+/// between instances, so thousands of copies cost little memory.
+#[derive(Clone, Debug)]
+pub struct Instance {
+    pub prototype: Rc<Geometry>,
+    pub transforms: Vec<Matrix>,
+    /// What every copy is made of, when shaded
+    pub material: Option<Material>,
+}
+
+impl Instance {
+    pub fn new(prototype: Rc<Geometry>, transforms: Vec<Matrix>) -> Self {
+        Instance {
+            prototype,
+            transforms,
+            material: None,
+        }
+    }
+
+    /// Make every copy in turn
+    pub fn copies(&self) -> impl Iterator<Item = Geometry> + '_ {
+        self.transforms.iter().map(move |t| {
+            let mut g = (*self.prototype).clone();
+            g.bake_transform(t);
+            g
+        })
+    }
+}
+
+/// A piece of geometry with its own transformation
+///
+/// Static objects have their transformation baked in and no `transform`
+#[derive(Clone, Debug)]
+pub struct Object {
+    pub geometry: Geometry,
+    pub transform: Option<Matrix>,
+    /// What the object is made of, when shaded
+    pub material: Option<Material>,
+}
+
+/// What a scene is drawn over
+#[derive(Clone)]
+pub enum Background {
+    Solid(RGB),
+    /// From the top of the image at 0 to the bottom at 1
+    Vertical(Gradient),
+    /// Stretched over the whole image
+    Image(Rc<PPMImg>),
+}
+
+impl Background {
+    /// A sky of `top` fading into `horizon` at the bottom
+    pub fn sky(top: RGB, horizon: RGB) -> Self {
+        Background::Vertical(Gradient::even(&[top, horizon]))
+    }
+
+    /// Paints the background over all of `img`, which becomes what `clear` goes back to
+    pub fn paint(&self, img: &mut PPMImg) {
+        match self {
+            Background::Solid(color) => img.fill_background(*color),
+            Background::Vertical(gradient) => img.fill_background_gradient(gradient),
+            Background::Image(src) => img.fill_background_image(src),
+        }
+    }
+}
+
+impl fmt::Debug for Background {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Background::Solid(color) => f.debug_tuple("Solid").field(color).finish(),
+            Background::Vertical(gradient) => f.debug_tuple("Vertical").field(gradient).finish(),
+            Background::Image(src) => write!(f, "Image({}x{})", src.width(), src.height()),
+        }
+    }
+}
+
+/// A list of objects, some pre-transformed and some transformed every time they are drawn
+#[derive(Clone, Debug)]
+pub struct Scene {
+    pub objects: Vec<Object>,
+    pub instances: Vec<Instance>,
+    /// What the objects are lit by when shaded, `Light::standard` to start with
+    pub lights: Vec<Light>,
+    /// What shadows fall on, from lights that cast them
+    pub ground: Option<Ground>,
+    /// Painted by `draw_background` before anything else, or None to draw over what is there
+    pub background: Option<Background>,
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Scene {
+            objects: vec![],
+            instances: vec![],
+            lights: Light::standard(),
+            ground: None,
+            background: None,
+        }
+    }
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Scene::default()
+    }
+
+    /// Add an object that will not move, transforming it once now
+    ///
+    /// Returns the index of the object
+    pub fn add_static(&mut self, mut geometry: Geometry, t: &Matrix) -> usize {
+        geometry.bake_transform(t);
+        self.push(geometry, None)
+    }
+
+    /// Add an object whose transformation is kept apart, so it can change between frames
+    ///
+    /// Returns the index of the object
+    pub fn add_dynamic(&mut self, geometry: Geometry, t: Matrix) -> usize {
+        self.push(geometry, Some(t))
+    }
+
+    fn push(&mut self, geometry: Geometry, transform: Option<Matrix>) -> usize {
+        self.objects.push(Object {
+            geometry,
+            transform,
+            material: None,
+        });
+        self.objects.len() - 1
+    }
+
+    /// Add copies of a prototype, returning the index of the instance
+    pub fn add_instance(&mut self, instance: Instance) -> usize {
+        self.instances.push(instance);
+        self.instances.len() - 1
+    }
+
+    /// The transformation of a dynamic object, to change it in place
+    pub fn transform_mut(&mut self, i: usize) -> Option<&mut Matrix> {
+        self.objects[i].transform.as_mut()
+    }
+
+    /// Give object `i` a material of its own, for shading
+    pub fn set_material(&mut self, i: usize, material: Material) {
+        self.objects[i].material = Some(material);
+    }
+
+    /// Paint the background over `img`, if the scene has one
+    pub fn draw_background(&self, img: &mut PPMImg) {
+        if let Some(background) = self.background.as_ref() {
+            background.paint(img);
+        }
+    }
+
+    /// Bake the transformation of object `i` into its geometry, making it static
+    pub fn bake(&mut self, i: usize) {
+        let obj = &mut self.objects[i];
+        if let Some(t) = obj.transform.take() {
+            obj.geometry.bake_transform(&t);
+        }
+    }
+
+    /// Every object, transformed
+    pub fn transformed_objects(&self) -> impl Iterator<Item = Geometry> + '_ {
+        self.objects.iter().map(|obj| {
+            let mut g = obj.geometry.clone();
+            if let Some(t) = &obj.transform {
+                g.bake_transform(t);
+            }
+            g
+        })
+    }
+
+    /// All edges of the objects in the scene, transformed
+    ///
+    /// Instances are left out, since expanding them is what they avoid
+    pub fn edges(&self) -> Matrix {
+        self.collect(|g| match g {
+            Geometry::Edges(m) => Some(m.clone()),
+            _ => None,
+        })
+    }
+
+    /// All triangles of the objects in the scene, transformed, with meshes and spheres expanded
+    ///
+    /// Instances are left out, as for `edges`
+    pub fn polygons(&self) -> Matrix {
+        self.collect(|g| match g {
+            Geometry::Polygons(m) => Some(m.clone()),
+            Geometry::Mesh(mesh) => Some(mesh.to_polygons()),
+            Geometry::Sphere { .. } => g.sphere_polygons(),
+            _ => None,
+        })
+    }
+
+    fn collect(&self, select: impl Fn(&Geometry) -> Option<Matrix>) -> Matrix {
+        let mut all = Matrix::new(0, 4, vec![]);
+        for obj in self.objects.iter() {
+            if let Some(mut m) = select(&obj.geometry) {
+                if let Some(t) = &obj.transform {
+                    m.bake_transform(t);
+                }
+                all.append_matrix(&m);
+            }
+        }
+        all
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn static_and_dynamic_objects() {
+        let mut edge = Matrix::new(0, 4, vec![]);
+        edge.append_edge(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+        let mut scene = Scene::new();
+        scene.add_static(Geometry::Edges(edge.clone()), &Matrix::mv(10.0, 0.0, 0.0));
+        let spinning = scene.add_dynamic(Geometry::Edges(edge), Matrix::ident(4));
+
+        *scene.transform_mut(spinning).unwrap() = Matrix::rotatez(180.0);
+        let edges = scene.edges();
+        assert_eq!(4, edges.iter_by_row().count());
+        assert_eq!(Some(11.0), edges.get(1, 0));
+        assert!((edges.get(3, 0).unwrap() + 1.0).abs() < 1e-9);
+
+        scene.bake(spinning);
+        assert!(scene.transform_mut(spinning).is_none());
+        assert_eq!(edges.get(3, 0), scene.edges().get(3, 0));
+    }
+
+    #[test]
+    fn instances_share_prototype() {
+        let mut edge = Matrix::new(0, 4, vec![]);
+        edge.append_edge(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+        let proto = Rc::new(Geometry::Edges(edge));
+        let row: Vec<Matrix> = (0..100).map(|i| Matrix::mv(i as f64, 0.0, 0.0)).collect();
+        let trees = Instance::new(Rc::clone(&proto), row);
+        assert_eq!(2, Rc::strong_count(&proto));
+
+        let last = trees.copies().last().unwrap();
+        match last {
+            Geometry::Edges(m) => assert_eq!(Some(100.0), m.get(1, 0)),
+            _ => panic!("Copies keep the kind of their prototype"),
+        }
+    }
+
+    #[test]
+    fn backgrounds_paint_the_whole_image() {
+        let mut img = PPMImg::new(3, 2, 255);
+        let mut scene = Scene::new();
+        scene.draw_background(&mut img);
+        assert_eq!(Some(RGB::BLACK), img.pixel(1, 2));
+
+        scene.background = Some(Background::sky(RGB::BLUE, RGB::WHITE));
+        scene.draw_background(&mut img);
+        assert_eq!(Some(RGB::BLUE), img.pixel(1, 0));
+        assert_eq!(Some(RGB::BLUE.lerp(RGB::WHITE, 0.5)), img.pixel(0, 1));
+        assert_eq!(Some(RGB::WHITE), img.pixel(1, 2));
+        img.plot_color(0, 0, RGB::RED);
+        img.clear();
+        assert_eq!(Some(RGB::BLUE), img.pixel(0, 0));
+
+        let mut photo = PPMImg::new(1, 1, 255);
+        photo.fill_background(RGB::GREEN);
+        scene.background = Some(Background::Image(Rc::new(photo)));
+        scene.draw_background(&mut img);
+        assert_eq!(Some(RGB::GREEN), img.pixel(1, 2));
+        assert!(format!("{:?}", scene.background).contains("Image(1x1)"));
+    }
+}
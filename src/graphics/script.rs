@@ -0,0 +1,423 @@
+#![allow(dead_code)]
+
+//! The drawing language of the course, with a command on each line and its arguments, if it
+//! takes any, on the line after
+//!
+//! ```text
+//! line      x0 y0 z0 x1 y1 z1
+//! circle    cx cy cz r
+//! bezier    x0 y0 x1 y1 x2 y2 x3 y3
+//! hermite   x0 y0 x1 y1 rx0 ry0 rx1 ry1
+//! ident
+//! scale     sx sy sz
+//! move      tx ty tz
+//! rotate    axis degrees
+//! apply
+//! undo
+//! display
+//! save      file
+//! quit
+//! ```
+//!
+//! Lines starting with `#` or `\` are comments. A whole script is read into `Command`s
+//! first, so a mistake anywhere is reported by its line before anything is drawn, and then
+//! run by a `Script`.
+//!
+//! Numbers can also be expressions, see `expr`, with variables set on lines of their own:
+//!
+//! ```text
+//! r = 50 * 1.5
+//! circle
+//! 250 250 0 r
+//! line
+//! 0 0 0 (r * 2) r*2 0
+//! ```
+//!
+//! Arguments are split at spaces, so expressions with spaces in them go in parentheses.
+
+use std::io;
+
+use super::expr::{self, Variables};
+use super::matrix::Matrix;
+use super::vector::Vec3;
+use super::PPMImg;
+
+type Point = (f64, f64);
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+/// One command of a script, with its arguments
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    /// Add a line from (x0, y0, z0) to (x1, y1, z1) to the edges
+    Line([f64; 6]),
+    /// Add a circle, in the xy plane
+    Circle { center: Vec3, r: f64 },
+    /// Add a cubic Bézier curve through its four control points
+    Bezier([Point; 4]),
+    /// Add a cubic Hermite curve from p0 to p1, with rates r0 and r1 there
+    Hermite { p0: Point, p1: Point, r0: Point, r1: Point },
+    /// Start the transformation over
+    Ident,
+    Scale(Vec3),
+    Move(Vec3),
+    /// Turn about an axis by degrees, clockwise as seen from the positive end of the axis
+    Rotate(Axis, f64),
+    /// Transform the edges by the transformation
+    Apply,
+    /// Take back the last line or curve added, if any
+    Undo,
+    /// Draw the edges and show them
+    Display,
+    /// Draw the edges and save them to a file, in the format its extension names
+    Save(String),
+    /// Stop, skipping whatever comes after
+    Quit,
+}
+
+impl Command {
+    /// Whether the command adds a line or curve to the edges
+    pub fn adds_edges(&self) -> bool {
+        matches!(
+            self,
+            Command::Line(_) | Command::Circle { .. } | Command::Bezier(_) | Command::Hermite { .. }
+        )
+    }
+}
+
+/// Read a script from a file, see `read_script`
+pub fn open_script(filepath: &str) -> io::Result<Vec<Command>> {
+    read_script(&std::fs::read_to_string(filepath)?)
+}
+
+/// Read every command of a script, in order, up to a `quit`
+pub fn read_script(text: &str) -> io::Result<Vec<Command>> {
+    let mut commands = vec![];
+    let mut vars = Variables::new();
+    let mut lines = text.lines().enumerate();
+    while let Some((n, line)) = lines.next() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with('\\') {
+            continue;
+        }
+        let invalid = |n: usize, what: &str| {
+            let message = format!("Line {} of the script {}", n + 1, what);
+            io::Error::new(io::ErrorKind::InvalidData, message)
+        };
+        if let Some(assigned) = assign(line, &mut vars) {
+            assigned.map_err(|what| invalid(n, &what))?;
+            continue;
+        }
+        let (arg_n, args) = match takes_arguments(line).then(|| lines.next()) {
+            None => (n, ""),
+            Some(Some((m, args))) => (m, args.trim()),
+            Some(None) => {
+                return Err(invalid(n, &format!("needs arguments for `{}` after it", line)))
+            }
+        };
+        let command = parse_command(line, args, &vars).map_err(|e| match e {
+            Invalid::Command(what) => invalid(n, &what),
+            Invalid::Arguments(what) => invalid(arg_n, &what),
+        })?;
+        commands.push(command);
+        if line == "quit" {
+            break;
+        }
+    }
+    Ok(commands)
+}
+
+/// Read a command and its arguments from one line, as typed at a prompt
+///
+/// Returns None for blank lines, comments and variables, which are set in `vars`
+pub fn read_command(line: &str, vars: &mut Variables) -> io::Result<Option<Command>> {
+    let invalid = |what: String| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("The line {}", what))
+    };
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') || line.starts_with('\\') {
+        return Ok(None);
+    }
+    if let Some(assigned) = assign(line, vars) {
+        return assigned.map(|_| None).map_err(invalid);
+    }
+    let (command, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    parse_command(command, args.trim(), vars).map(Some).map_err(|e| {
+        let (Invalid::Command(what) | Invalid::Arguments(what)) = e;
+        invalid(what)
+    })
+}
+
+/// Set a variable from a line like `r = 50 * 1.5`, or return None for other lines
+fn assign(line: &str, vars: &mut Variables) -> Option<Result<(), String>> {
+    let (name, value) = line.split_once('=')?;
+    let name = name.trim();
+    if !expr::is_name(name) {
+        return Some(Err(format!("has a bad variable name `{}`", name)));
+    }
+    let value = expr::eval(value, vars).map_err(|e| format!("has {}", e));
+    Some(value.map(|value| {
+        vars.insert(name.to_string(), value);
+    }))
+}
+
+fn takes_arguments(command: &str) -> bool {
+    matches!(
+        command,
+        "line" | "circle" | "bezier" | "hermite" | "scale" | "move" | "rotate" | "save"
+    )
+}
+
+/// What is wrong with a command, on the line of the command or of its arguments
+enum Invalid {
+    Command(String),
+    Arguments(String),
+}
+
+fn parse_command(line: &str, args: &str, vars: &Variables) -> Result<Command, Invalid> {
+    let bad = |what: &str| Invalid::Arguments(what.to_string());
+    let number = |arg: &str| expr::eval(arg, vars).map_err(|e| bad(&format!("has {}", e)));
+    let numbers = |count: usize| -> Result<Vec<f64>, Invalid> {
+        let v: Vec<f64> = expr::split_arguments(args)
+            .into_iter()
+            .map(number)
+            .collect::<Result<_, _>>()?;
+        if v.len() != count {
+            return Err(bad(&format!("needs {} numbers for `{}`", count, line)));
+        }
+        Ok(v)
+    };
+    let points = |v: Vec<f64>| [(v[0], v[1]), (v[2], v[3]), (v[4], v[5]), (v[6], v[7])];
+    let command = match line {
+        "line" => {
+            let v = numbers(6)?;
+            Command::Line([v[0], v[1], v[2], v[3], v[4], v[5]])
+        }
+        "circle" => {
+            let v = numbers(4)?;
+            Command::Circle {
+                center: (v[0], v[1], v[2]),
+                r: v[3],
+            }
+        }
+        "bezier" => Command::Bezier(points(numbers(8)?)),
+        "hermite" => {
+            let [p0, p1, r0, r1] = points(numbers(8)?);
+            Command::Hermite { p0, p1, r0, r1 }
+        }
+        "ident" => Command::Ident,
+        "scale" => {
+            let v = numbers(3)?;
+            Command::Scale((v[0], v[1], v[2]))
+        }
+        "move" => {
+            let v = numbers(3)?;
+            Command::Move((v[0], v[1], v[2]))
+        }
+        "rotate" => {
+            let words = expr::split_arguments(args);
+            let axis = match words.first().copied() {
+                Some("x") => Axis::X,
+                Some("y") => Axis::Y,
+                Some("z") => Axis::Z,
+                _ => return Err(bad("needs an axis of x, y or z for `rotate`")),
+            };
+            let degrees = match words.as_slice() {
+                [_, degrees] => number(degrees),
+                _ => Err(bad("needs an axis and an angle for `rotate`")),
+            }?;
+            Command::Rotate(axis, degrees)
+        }
+        "apply" => Command::Apply,
+        "undo" => Command::Undo,
+        "display" => Command::Display,
+        "save" if !args.is_empty() => Command::Save(args.to_string()),
+        "save" => return Err(bad("needs a file name for `save`")),
+        "quit" => Command::Quit,
+        _ => return Err(Invalid::Command(format!("has an unknown command `{}`", line))),
+    };
+    Ok(command)
+}
+
+/// What a script draws with, kept from one command to the next
+pub struct Script {
+    /// Everything added so far
+    pub edges: Matrix,
+    /// Built up by `scale`, `move` and `rotate`, and applied to the edges by `apply`
+    pub transform: Matrix,
+    /// Where the edges are drawn for `display` and `save`
+    pub img: PPMImg,
+    /// How many rows the edges had before each line or curve was added
+    added: Vec<usize>,
+}
+
+impl Script {
+    /// A script drawing on a 500 by 500 image, with y going up as in the course
+    pub fn new() -> Self {
+        let mut img = PPMImg::new(500, 500, 255);
+        img.set_world_bounds(0.0, 0.0, 499.0, 499.0);
+        Script::with_image(img)
+    }
+
+    pub fn with_image(img: PPMImg) -> Self {
+        Script {
+            edges: Matrix::new(0, 4, vec![]),
+            transform: Matrix::ident(4),
+            img,
+            added: vec![],
+        }
+    }
+
+    /// Run commands in order, until the end or a `quit`
+    pub fn run(&mut self, commands: &[Command]) -> io::Result<()> {
+        for command in commands.iter() {
+            if !self.execute(command)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Run one command, returning whether to go on
+    pub fn execute(&mut self, command: &Command) -> io::Result<bool> {
+        if command.adds_edges() {
+            self.added.push(self.edges.nrows());
+        }
+        match command {
+            Command::Line(edge) => self.edges.append_edge(edge),
+            &Command::Circle { center, r } => {
+                self.edges.add_circle_transformed(center, r, &self.transform)
+            }
+            &Command::Bezier([p0, p1, p2, p3]) => self.edges.add_bezier3(p0, p1, p2, p3),
+            &Command::Hermite { p0, p1, r0, r1 } => self.edges.add_hermite3(p0, p1, r0, r1),
+            Command::Ident => self.transform = Matrix::ident(4),
+            &Command::Scale((x, y, z)) => {
+                self.transform = self.transform.mul(&Matrix::scale(x, y, z))
+            }
+            &Command::Move((x, y, z)) => self.transform = self.transform.mul(&Matrix::mv(x, y, z)),
+            &Command::Rotate(axis, degrees) => {
+                let rotate = match axis {
+                    Axis::X => Matrix::rotatex(degrees),
+                    // the y matrix turns the other way from the x and z ones
+                    Axis::Y => Matrix::rotatey(-degrees),
+                    Axis::Z => Matrix::rotatez(degrees),
+                };
+                self.transform = self.transform.mul(&rotate);
+            }
+            Command::Apply => self.edges = self.edges.mul(&self.transform),
+            Command::Undo => {
+                self.undo();
+            }
+            Command::Display => {
+                self.draw();
+                self.img.display()?;
+            }
+            Command::Save(filepath) => {
+                self.draw();
+                self.img.save_as(filepath)?;
+            }
+            Command::Quit => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Take back the last line or curve added, returning whether there was one
+    ///
+    /// Transformations applied since then stay applied to everything before it
+    pub fn undo(&mut self) -> bool {
+        match self.added.pop() {
+            Some(nrows) => {
+                self.edges.truncate(nrows);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Draw the edges on a clear image
+    pub fn draw(&mut self) {
+        self.img.clear();
+        self.img.render_edge_matrix(&self.edges);
+    }
+}
+
+impl Default for Script {
+    fn default() -> Self {
+        Script::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::RGB;
+
+    #[test]
+    fn scripts_read_and_run() {
+        let text = "# a square, turned\nline\n0 0 0 10 0 0\n\nident\nrotate\nz 90\nmove\n\
+                    20 20 0\napply\nbezier\n0 0 1 1 2 1 3 0\nquit\nline\n0 0 0 1 1 1\n";
+        let commands = read_script(text).unwrap();
+        assert_eq!(7, commands.len());
+        assert_eq!(Command::Rotate(Axis::Z, 90.0), commands[2]);
+        assert_eq!(Some(&Command::Quit), commands.last());
+
+        let mut script = Script::new();
+        script.run(&commands).unwrap();
+        // turned clockwise to (0, -10), then moved, and the curve after is not transformed
+        let end = script.edges.iter_by_row().nth(1).unwrap();
+        assert!((end[0] - 20.0).abs() < 1e-9 && (end[1] - 10.0).abs() < 1e-9, "{:?}", end);
+        assert!(script.edges.iter_by_row().count() > 2);
+        script.draw();
+        assert_eq!(Some(RGB::WHITE), script.img.pixel(20, 499 - 15));
+
+        let face = open_script("script").unwrap();
+        assert_eq!(Some(&Command::Save("face.png".to_string())), face.last());
+
+        let error = read_script("line\n0 0 0 1 1\n").unwrap_err();
+        assert_eq!("Line 2 of the script needs 6 numbers for `line`", error.to_string());
+        assert!(read_script("circle").unwrap_err().to_string().starts_with("Line 1 "));
+        let error = read_script("ident\ncircles\n1 1 1 1").unwrap_err();
+        assert_eq!("Line 2 of the script has an unknown command `circles`", error.to_string());
+    }
+
+    #[test]
+    fn variables_stand_for_numbers() {
+        let text = "r = 50 * 1.5\ncircle\n250 250 0 r\nr = r / 3\nrotate\nz (r + 5)\nline\n\
+                    0 0 0 (r * 2) -r 0\n";
+        let commands = read_script(text).unwrap();
+        let circle = Command::Circle { center: (250.0, 250.0, 0.0), r: 75.0 };
+        let line = Command::Line([0.0, 0.0, 0.0, 50.0, -25.0, 0.0]);
+        assert_eq!(vec![circle, Command::Rotate(Axis::Z, 30.0), line], commands);
+
+        let error = read_script("circle\n250 250 0 d\n").unwrap_err();
+        assert_eq!("Line 2 of the script has an unknown variable `d`", error.to_string());
+        let error = read_script("\n2r = 1\n").unwrap_err();
+        assert_eq!("Line 2 of the script has a bad variable name `2r`", error.to_string());
+        let mut vars = Variables::new();
+        assert_eq!(None, read_command("d = 2 ^ 3", &mut vars).unwrap());
+        let moved = read_command("move d 0 0", &mut vars).unwrap();
+        assert_eq!(Some(Command::Move((8.0, 0.0, 0.0))), moved);
+    }
+
+    #[test]
+    fn undo_takes_back_the_last_curve() {
+        let mut vars = Variables::new();
+        let circle = read_command("circle 250 250 0 50", &mut vars).unwrap().unwrap();
+        assert_eq!(Command::Circle { center: (250.0, 250.0, 0.0), r: 50.0 }, circle);
+        assert_eq!(None, read_command("  # nothing", &mut vars).unwrap());
+        let error = read_command("move 1 2", &mut vars).unwrap_err();
+        assert_eq!("The line needs 3 numbers for `move`", error.to_string());
+
+        let mut script = Script::new();
+        script.run(&[Command::Line([0.0; 6]), circle, Command::Undo]).unwrap();
+        assert_eq!(2, script.edges.nrows());
+        assert!(script.undo());
+        assert_eq!(0, script.edges.nrows());
+        assert!(!script.undo());
+    }
+}
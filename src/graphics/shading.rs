@@ -0,0 +1,551 @@
+#![allow(dead_code)]
+
+//! Filling triangles with shades of a color by how they face the lights
+
+use super::matrix::Matrix;
+use super::mesh::Mesh;
+use super::scene::{Geometry, Scene};
+use super::vector::{self, Vec3};
+use super::{PPMImg, RGB};
+
+/// A source of light for shading
+///
+/// Colors are taken to have depth 255, whatever the depth of the image; lights add up, and
+/// several lights at full strength can light a face past its own color.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Light {
+    /// Light from everywhere at once, reaching every face the same
+    Ambient(RGB),
+    /// Light from far away, like the sun, all coming along the same direction
+    Directional {
+        /// Direction from the scene toward the light; need not be of unit length
+        direction: Vec3,
+        color: RGB,
+        /// Whether objects cast shadows from this light onto the scene's ground
+        shadows: bool,
+    },
+    /// Light from a point, reaching each face from its own direction, without getting dimmer
+    /// with distance
+    Point {
+        position: Vec3,
+        color: RGB,
+        shadows: bool,
+    },
+}
+
+impl Light {
+    /// A dim ambient light and a light from the viewer, which light a face facing the viewer
+    /// to its own color
+    pub fn standard() -> Vec<Light> {
+        vec![
+            Light::Ambient(RGB::gray(26)),
+            Light::Directional {
+                direction: (0.0, 0.0, 1.0),
+                color: RGB::gray(229),
+                shadows: false,
+            },
+        ]
+    }
+
+    pub fn color(&self) -> RGB {
+        match *self {
+            Light::Ambient(color) => color,
+            Light::Directional { color, .. } | Light::Point { color, .. } => color,
+        }
+    }
+
+    /// Where the shadow of `point` falls on `ground`, if this light casts shadows there
+    pub fn shadow_of(&self, point: Vec3, ground: &Ground) -> Option<Vec3> {
+        let n = vector::normalize(ground.normal);
+        let height = |p: Vec3| vector::dot(n, vector::sub(p, ground.point));
+        let toward = match *self {
+            Light::Directional {
+                direction,
+                shadows: true,
+                ..
+            } => direction,
+            // only what is between the light and the ground casts a shadow
+            Light::Point {
+                position,
+                shadows: true,
+                ..
+            } if height(position) > height(point) => vector::sub(position, point),
+            _ => return None,
+        };
+        // along the light away from it, down to the ground
+        let down = vector::dot(n, toward);
+        if down <= 0.0 {
+            return None;
+        }
+        Some(vector::sub(point, vector::scale(toward, height(point) / down)))
+    }
+
+    /// Unit direction from `point` toward the light, or None for ambient light
+    pub fn toward(&self, point: Vec3) -> Option<Vec3> {
+        match *self {
+            Light::Ambient(_) => None,
+            Light::Directional { direction, .. } => Some(vector::normalize(direction)),
+            Light::Point { position, .. } => Some(vector::normalize(vector::sub(position, point))),
+        }
+    }
+
+    /// How far the light is from `point`, endlessly far unless it is a point light
+    pub fn distance(&self, point: Vec3) -> f64 {
+        match *self {
+            Light::Point { position, .. } => vector::length(vector::sub(position, point)),
+            _ => f64::INFINITY,
+        }
+    }
+
+    /// Whether objects block this light, for shadows
+    pub fn casts_shadows(&self) -> bool {
+        match *self {
+            Light::Ambient(_) => false,
+            Light::Directional { shadows, .. } | Light::Point { shadows, .. } => shadows,
+        }
+    }
+}
+
+/// A plane that objects cast shadows on, like a floor or a wall
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ground {
+    /// Any point on the plane
+    pub point: Vec3,
+    /// Normal of the plane, on the side of the lights; need not be of unit length
+    pub normal: Vec3,
+    /// How much of what is under a shadow is darkened, from 0 to 1
+    pub darkness: f64,
+}
+
+/// How a surface takes light, by the Phong reflection model
+///
+/// The viewer looks down the z axis, so highlights are where light bounces toward +z.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Material {
+    /// Share of ambient light reflected
+    pub ka: f64,
+    /// Share of light reflected evenly every way, by Lambert's law
+    pub kd: f64,
+    /// Share of light reflected as a highlight, in the color of the light
+    pub ks: f64,
+    /// How tight highlights are; higher is shinier
+    pub shininess: f64,
+    /// Color of the surface, unless the polygon matrix has colors
+    pub color: RGB,
+}
+
+impl Material {
+    /// No highlights at all, like chalk or paper
+    pub fn matte(color: RGB) -> Self {
+        Material {
+            ka: 1.0,
+            kd: 1.0,
+            ks: 0.0,
+            shininess: 1.0,
+            color,
+        }
+    }
+
+    /// Soft white highlights
+    pub fn plastic(color: RGB) -> Self {
+        Material {
+            ka: 1.0,
+            kd: 0.8,
+            ks: 0.5,
+            shininess: 20.0,
+            color,
+        }
+    }
+
+    /// Little even light and bright, tight highlights
+    pub fn metal(color: RGB) -> Self {
+        Material {
+            ka: 0.5,
+            kd: 0.3,
+            ks: 0.9,
+            shininess: 80.0,
+            color,
+        }
+    }
+
+    /// The shade of `color` at `point` on a surface with unit normal `normal`, lit by all of
+    /// `lights`, with channels up to `depth`
+    pub fn shade(
+        &self,
+        color: RGB,
+        lights: &[Light],
+        point: Vec3,
+        normal: Vec3,
+        depth: u16,
+    ) -> RGB {
+        let base = [color.red, color.green, color.blue].map(|c| c as f64 / depth as f64);
+        let mut light = [0.0; 3];
+        for l in lights.iter() {
+            let c = l.color();
+            let c = [c.red, c.green, c.blue].map(|c| c as f64 / 255.0);
+            let (diffuse, specular) = match l.toward(point) {
+                None => (self.ka, 0.0),
+                Some(toward) => {
+                    let lambert = vector::dot(normal, toward);
+                    if lambert <= 0.0 {
+                        continue;
+                    }
+                    // the light bounced off the surface, and how much of it heads to the viewer
+                    let bounced = vector::sub(vector::scale(normal, 2.0 * lambert), toward);
+                    let view = bounced.2.max(0.0).powf(self.shininess);
+                    (self.kd * lambert, self.ks * view)
+                }
+            };
+            (0..3).for_each(|i| light[i] += c[i] * (base[i] * diffuse + specular));
+        }
+        let channel = |v: f64| (v * depth as f64).round().min(depth as f64) as u16;
+        RGB::new(channel(light[0]), channel(light[1]), channel(light[2]))
+    }
+}
+
+/// Where the light on a triangle is worked out
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Shading {
+    /// Once, at the middle of the triangle, with the normal of its face
+    Flat,
+    /// At every corner, with the normal of the mesh there, blending the colors in between
+    Gouraud,
+    /// At every pixel, blending the normals of the corners, so highlights can fall inside
+    /// triangles
+    Phong,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material::matte(RGB::WHITE)
+    }
+}
+
+impl PPMImg {
+    /// Fills every triangle of a polygon matrix that faces the viewer with one shade of its
+    /// material, as lit by `lights` at its middle
+    ///
+    /// Triangles are filled from the farthest to the nearest, by the average z of their
+    /// corners, so nearer ones cover farther ones as long as they do not cut through each
+    /// other, or everywhere with `PPMImg::depth_test` on. A triangle takes the color of its
+    /// first point if the matrix has colors.
+    pub fn shade_polygon_matrix(&mut self, m: &Matrix, material: &Material, lights: &[Light]) {
+        let front = m.cull_backfaces((0.0, 0.0, 1.0));
+        let rows: Vec<&[f64]> = front.iter_by_row().collect();
+        let normals = front.polygon_normals();
+        let mut triangles: Vec<(f64, usize)> = rows
+            .chunks_exact(3)
+            .enumerate()
+            .map(|(i, t)| ((t[0][2] + t[1][2] + t[2][2]) / 3.0, i))
+            .collect();
+        triangles.sort_by(|a, b| a.0.partial_cmp(&b.0).expect("Polygon must not have NaN points"));
+
+        for (_, i) in triangles {
+            let t = &rows[3 * i..3 * i + 3];
+            let corners = t.iter().map(|p| (p[0], p[1], p[2]));
+            let middle = vector::scale(corners.fold((0.0, 0.0, 0.0), vector::add), 1.0 / 3.0);
+            let color = front.color(3 * i).unwrap_or(material.color);
+            let shade = material.shade(color, lights, middle, normals[i], self.depth);
+            let screen = [0, 1, 2].map(|k| self.world_to_screen(t[k][0], t[k][1]));
+            let z = [t[0][2], t[1][2], t[2][2]];
+            self.fill_screen_triangle(screen, Some(z), |_| shade);
+        }
+    }
+
+    /// Fills every triangle of a mesh that faces the viewer, lit by `lights`
+    ///
+    /// Gouraud and Phong shading follow the mesh's normals, see `Mesh::corner_normals`, and
+    /// blend the colors of its vertices, which take the place of the material's color.
+    /// Triangles are sorted by depth as for `shade_polygon_matrix`.
+    pub fn shade_mesh(
+        &mut self,
+        mesh: &Mesh,
+        material: &Material,
+        lights: &[Light],
+        shading: Shading,
+    ) {
+        if shading == Shading::Flat {
+            self.shade_polygon_matrix(&mesh.to_polygons(), material, lights);
+            return;
+        }
+        let corners: Vec<[Vec3; 3]> = mesh.faces_iter().collect();
+        let corner_normals = mesh.corner_normals();
+        let mut triangles: Vec<(f64, usize)> = corners
+            .iter()
+            .enumerate()
+            .filter(|(_, [a, b, c])| vector::triangle_normal(*a, *b, *c).2 > 0.0)
+            .map(|(i, t)| ((t[0].2 + t[1].2 + t[2].2) / 3.0, i))
+            .collect();
+        triangles.sort_by(|a, b| a.0.partial_cmp(&b.0).expect("Mesh must not have NaN points"));
+
+        let depth = self.depth;
+        for (_, i) in triangles {
+            let (t, normals) = (corners[i], corner_normals[i]);
+            let screen = t.map(|p| self.world_to_screen(p.0, p.1));
+            let base = mesh.faces[i].map(|v| mesh.vertex_color(v).unwrap_or(material.color));
+            let colors =
+                [0, 1, 2].map(|k| material.shade(base[k], lights, t[k], normals[k], depth));
+            self.fill_screen_triangle(screen, Some(t.map(|p| p.2)), |w| {
+                let blend = |v: [Vec3; 3]| {
+                    let parts = (0..3).map(|k| vector::scale(v[k], w[k]));
+                    parts.fold((0.0, 0.0, 0.0), vector::add)
+                };
+                match shading {
+                    Shading::Phong => {
+                        let normal = vector::normalize(blend(normals));
+                        material.shade(RGB::blend3(base, w), lights, blend(t), normal, depth)
+                    }
+                    _ => RGB::blend3(colors, w),
+                }
+            });
+        }
+    }
+
+    /// Shades every triangle of a scene, instances included, with the scene's lights, and
+    /// draws its edges as they are
+    ///
+    /// The scene's background is painted first, then shadows are cast, see `cast_shadows`.
+    /// Objects without a material of their own are shaded with `material`. Triangles are only
+    /// sorted by depth within each piece of geometry, so pieces should be added from back to
+    /// front.
+    pub fn shade_scene(&mut self, scene: &Scene, material: &Material, shading: Shading) {
+        scene.draw_background(self);
+        self.cast_shadows(scene);
+        let objects = scene.objects.iter().map(|obj| obj.material);
+        let objects = objects.zip(scene.transformed_objects());
+        let instanced = scene.instances.iter().flat_map(|instance| {
+            instance.copies().map(move |g| (instance.material, g))
+        });
+        for (own, g) in objects.chain(instanced) {
+            let material = own.as_ref().unwrap_or(material);
+            self.shade_geometry(&g, material, &scene.lights, shading);
+        }
+    }
+
+    /// Darkens the shadows of every triangle of a scene on its ground, for every light that
+    /// casts shadows, as planar shadows
+    ///
+    /// Shadows fall on whatever is already drawn, so a floor should be drawn before, and the
+    /// objects after. Nothing falls on the objects themselves.
+    pub fn cast_shadows(&mut self, scene: &Scene) {
+        let ground = match scene.ground {
+            Some(ground) => ground,
+            None => return,
+        };
+        let mut triangles = scene.polygons();
+        for instance in scene.instances.iter() {
+            for g in instance.copies() {
+                match g {
+                    Geometry::Polygons(m) => triangles.append_matrix(&m),
+                    Geometry::Mesh(mesh) => triangles.add_mesh(&mesh),
+                    Geometry::Sphere { .. } => {
+                        triangles.append_matrix(&g.sphere_polygons().unwrap())
+                    }
+                    Geometry::Edges(_) => {}
+                }
+            }
+        }
+        let rows: Vec<&[f64]> = triangles.iter_by_row().collect();
+        for light in scene.lights.iter() {
+            // every pixel is darkened once, however many triangles cover it
+            let mut shaded = vec![false; self.data.len()];
+            for t in rows.chunks_exact(3) {
+                let shadow: Option<Vec<(f64, f64)>> = t
+                    .iter()
+                    .map(|p| light.shadow_of((p[0], p[1], p[2]), &ground))
+                    .map(|p| p.map(|p| self.world_to_screen(p.0, p.1)))
+                    .collect();
+                for (y, x0, x1) in shadow.map_or(vec![], |s| self.polygon_spans(&s)) {
+                    for x in x0..=x1 {
+                        if let Some(i) = self.index(x, y) {
+                            shaded[i] = true;
+                        }
+                    }
+                }
+            }
+            for (i, _) in shaded.iter().enumerate().filter(|(_, &s)| s) {
+                self.put(i, RGB::BLACK.with_alpha(ground.darkness));
+            }
+        }
+    }
+
+    /// Shades one piece of geometry, as it is
+    ///
+    /// Polygon matrices are shaded smooth by turning them into meshes, which loses the colors
+    /// of their points.
+    pub fn shade_geometry(
+        &mut self,
+        g: &Geometry,
+        material: &Material,
+        lights: &[Light],
+        shading: Shading,
+    ) {
+        match g {
+            Geometry::Edges(m) => self.render_edge_matrix(m),
+            Geometry::Polygons(m) if shading == Shading::Flat => {
+                self.shade_polygon_matrix(m, material, lights)
+            }
+            Geometry::Polygons(m) => {
+                self.shade_mesh(&Mesh::from_polygons(m), material, lights, shading)
+            }
+            Geometry::Mesh(mesh) => self.shade_mesh(mesh, material, lights, shading),
+            Geometry::Sphere { .. } => {
+                let m = g.sphere_polygons().expect("Spheres are made of triangles");
+                self.shade_mesh(&Mesh::from_polygons(&m), material, lights, shading)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn faces_are_shaded_by_the_light() {
+        let mut img = PPMImg::new(20, 20, 255);
+        let mut cube = Matrix::new(0, 4, vec![]);
+        cube.add_box_polygons((-5.0, 5.0, 5.0), 10.0, 10.0, 10.0);
+        // turned so that three faces show, each at its own angle to the light
+        let turn = Matrix::rotatex(25.0).mul(&Matrix::rotatey(40.0));
+        let cube = cube.mul(&turn.mul(&Matrix::mv(10.0, 10.0, 0.0)));
+        img.shade_polygon_matrix(&cube, &Material::default(), &Light::standard());
+
+        let mut shades: Vec<RGB> = img.data.iter().copied().filter(|&c| c != RGB::BLACK).collect();
+        shades.sort_by_key(|c| c.red);
+        shades.dedup();
+        assert_eq!(3, shades.len(), "{:?}", shades);
+        assert!(shades.iter().all(|c| c.red == c.green && c.green == c.blue));
+
+        let matte = Material::default();
+        let lit = |lights: &[Light], n| matte.shade(RGB::WHITE, lights, (0.0, 0.0, 0.0), n, 255);
+        assert_eq!(RGB::WHITE, lit(&Light::standard(), (0.0, 0.0, 1.0)));
+        assert_eq!(RGB::gray(26), lit(&Light::standard(), (0.0, 0.0, -1.0)));
+        let lamps = [
+            Light::Point {
+                position: (10.0, 0.0, 10.0),
+                color: RGB::RED,
+                shadows: false,
+            },
+            Light::Directional {
+                direction: (0.0, -1.0, 0.0),
+                color: RGB::BLUE,
+                shadows: false,
+            },
+        ];
+        assert_eq!(RGB::new(180, 0, 0), lit(&lamps, (0.0, 0.0, 1.0)));
+    }
+
+    #[test]
+    fn each_kind_of_light_reaches_faces_its_own_way() {
+        let origin = (0.0, 0.0, 0.0);
+        let ambient = Light::Ambient(RGB::gray(51));
+        assert_eq!((RGB::gray(51), None), (ambient.color(), ambient.toward(origin)));
+
+        let sun = Light::Directional {
+            direction: (0.0, 0.0, 2.0),
+            color: RGB::WHITE,
+            shadows: false,
+        };
+        assert_eq!(Some((0.0, 0.0, 1.0)), sun.toward(origin));
+        assert_eq!(Some((0.0, 0.0, 1.0)), sun.toward((100.0, 0.0, 0.0)));
+
+        let lamp = Light::Point {
+            position: (0.0, 0.0, 10.0),
+            color: RGB::WHITE,
+            shadows: false,
+        };
+        assert_eq!(Some((0.0, 0.0, 1.0)), lamp.toward(origin));
+        assert_eq!(Some((0.0, 0.0, 1.0)), lamp.toward((0.0, 0.0, -90.0)));
+        // off to the side, the lamp is seen at 45 degrees
+        let (x, _, z) = lamp.toward((10.0, 0.0, 0.0)).unwrap();
+        assert!((x + 0.5f64.sqrt()).abs() < 1e-9 && (z - 0.5f64.sqrt()).abs() < 1e-9);
+
+        assert_eq!(Light::standard(), Scene::default().lights);
+    }
+
+    #[test]
+    fn materials_set_objects_apart() {
+        let lights = Light::standard();
+        let shade = |m: Material| m.shade(m.color, &lights, (0.0, 0.0, 0.0), (0.0, 0.0, 1.0), 255);
+        assert_eq!(RGB::RED, shade(Material::matte(RGB::RED)));
+        // the highlight is white, whatever the color
+        assert_eq!(RGB::new(255, 115, 115), shade(Material::plastic(RGB::RED)));
+
+        let mut scene = Scene::new();
+        for x in [0.0, 10.0] {
+            let mut block = Matrix::new(0, 4, vec![]);
+            block.add_box_polygons((x, 10.0, 0.0), 10.0, 10.0, 10.0);
+            scene.add_static(Geometry::Polygons(block), &Matrix::ident(4));
+        }
+        scene.set_material(1, Material::metal(RGB::BLUE));
+        let mut img = PPMImg::new(10, 20, 255);
+        img.set_world_bounds(0.0, 0.0, 20.0, 10.0);
+        img.shade_scene(&scene, &Material::matte(RGB::GREEN), Shading::Flat);
+        assert_eq!(Some(RGB::GREEN), img.pixel(8, 8));
+        assert_eq!(Some(RGB::new(206, 206, 255)), img.pixel(18, 8));
+    }
+
+    #[test]
+    fn phong_finds_highlights_between_corners() {
+        let mut square = Mesh::new();
+        for &(x, y) in [(0.0, 0.0), (20.0, 0.0), (20.0, 20.0), (0.0, 20.0)].iter() {
+            square.add_vertex((x, y, 0.0));
+        }
+        square.add_face(0, 1, 2);
+        square.add_face(0, 2, 3);
+        let lamp = [Light::Point {
+            position: (10.0, 10.0, 5.0),
+            color: RGB::WHITE,
+            shadows: false,
+        }];
+        let material = Material::plastic(RGB::gray(100));
+        let render = |shading| {
+            let mut img = PPMImg::new(21, 21, 255);
+            img.shade_mesh(&square, &material, &lamp, shading);
+            img
+        };
+        // every corner is as far from the light, so Gouraud shading misses the highlight
+        let gouraud = render(Shading::Gouraud);
+        assert_eq!(gouraud.pixel(10, 10), gouraud.pixel(1, 1));
+        let phong = render(Shading::Phong);
+        assert!(phong.pixel(10, 10).unwrap().red > gouraud.pixel(10, 10).unwrap().red + 100);
+        assert!(phong.pixel(1, 1).unwrap().red < phong.pixel(5, 5).unwrap().red);
+        assert_eq!(gouraud.pixel(0, 0), phong.pixel(0, 0));
+    }
+
+    #[test]
+    fn objects_cast_shadows_on_the_ground() {
+        let mut block = Matrix::new(0, 4, vec![]);
+        block.add_box_polygons((10.0, 8.0, 5.0), 4.0, 4.0, 1.0);
+        let mut scene = Scene::new();
+        scene.add_static(Geometry::Polygons(block), &Matrix::ident(4));
+        // a wall behind the block, lit from the right
+        scene.ground = Some(Ground {
+            point: (0.0, 0.0, 0.0),
+            normal: (0.0, 0.0, 1.0),
+            darkness: 0.5,
+        });
+        let light = |shadows| Light::Directional {
+            direction: (1.0, 0.0, 1.0),
+            color: RGB::WHITE,
+            shadows,
+        };
+        let draw = |scene: &Scene| {
+            let mut img = PPMImg::new_with_bg(20, 20, 255, RGB::WHITE);
+            img.shade_scene(scene, &Material::default(), Shading::Flat);
+            img
+        };
+
+        scene.lights = vec![light(true)];
+        let img = draw(&scene);
+        let shadow = img.pixel(7, 6).unwrap();
+        assert!(shadow.red < 150 && shadow.red == shadow.blue, "{:?}", shadow);
+        assert_eq!(Some(RGB::WHITE), img.pixel(3, 6));
+        assert_eq!(Some(RGB::WHITE), img.pixel(7, 12));
+        assert_eq!(Some(RGB::WHITE), img.pixel(16, 6));
+
+        scene.lights = vec![light(false)];
+        assert_eq!(Some(RGB::WHITE), draw(&scene).pixel(7, 6));
+    }
+}
@@ -0,0 +1,238 @@
+#![allow(dead_code)]
+
+use std::f64::consts::PI;
+
+type Point = (f64, f64);
+
+/// How two segments of a stroke meet at a corner
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LineJoin {
+    /// Sharp corners, cut off as a bevel when the point would be longer than `limit` times
+    /// half the width, like SVG's `stroke-miterlimit`
+    Miter(f64),
+    Round,
+    Bevel,
+}
+
+/// How the ends of an open stroke look
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LineCap {
+    /// Stops right at the endpoint
+    Butt,
+    Round,
+    /// Goes on past the endpoint by half the width
+    Square,
+}
+
+/// Width and shape of a thick line
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Stroke {
+    pub width: f64,
+    pub join: LineJoin,
+    pub cap: LineCap,
+    /// Whether the width is in world units, growing and shrinking with the world bounds of the
+    /// image, instead of in pixels
+    pub world_width: bool,
+}
+
+impl Stroke {
+    /// Mitered corners, cut off past 4 times the width as in SVG, and butt ends
+    pub fn new(width: f64) -> Self {
+        assert!(width > 0.0, "Stroke width must be positive");
+        Stroke {
+            width,
+            join: LineJoin::Miter(4.0),
+            cap: LineCap::Butt,
+            world_width: false,
+        }
+    }
+
+    pub fn join(mut self, join: LineJoin) -> Self {
+        self.join = join;
+        self
+    }
+
+    pub fn cap(mut self, cap: LineCap) -> Self {
+        self.cap = cap;
+        self
+    }
+
+    pub fn world_width(mut self, world_width: bool) -> Self {
+        self.world_width = world_width;
+        self
+    }
+
+    /// The stroke as wide as it would look after a transformation, so that lines keep their
+    /// weight in proportion when a scene is zoomed; see `Matrix::scale_factor`
+    pub fn transformed(self, t: &super::Matrix) -> Self {
+        Stroke {
+            width: self.width * t.scale_factor(),
+            ..self
+        }
+    }
+
+    /// Convex polygons that together cover the stroke of a polyline
+    ///
+    /// There is one quad per segment, one piece per join and one per round or square cap;
+    /// they overlap where they meet. Closed polylines may repeat their first point at the end.
+    pub fn outline(&self, points: &[Point], closed: bool) -> Vec<Vec<Point>> {
+        let mut pts: Vec<Point> = vec![];
+        for &p in points.iter() {
+            if pts.last() != Some(&p) {
+                pts.push(p);
+            }
+        }
+        if closed && pts.len() > 1 && pts.first() == pts.last() {
+            pts.pop();
+        }
+        let h = self.width / 2.0;
+        let mut pieces = vec![];
+        match pts.len() {
+            0 => return pieces,
+            1 => {
+                // a dot only shows with caps that reach past it
+                let p = pts[0];
+                match self.cap {
+                    LineCap::Butt => {}
+                    LineCap::Round => pieces.push(disk(p, h)),
+                    LineCap::Square => {
+                        pieces.push(vec![
+                            (p.0 - h, p.1 - h),
+                            (p.0 + h, p.1 - h),
+                            (p.0 + h, p.1 + h),
+                            (p.0 - h, p.1 + h),
+                        ]);
+                    }
+                }
+                return pieces;
+            }
+            _ => {}
+        }
+
+        let n = pts.len();
+        let segments = if closed { n } else { n - 1 };
+        for i in 0..segments {
+            let (mut p, mut q) = (pts[i], pts[(i + 1) % n]);
+            let d = direction(p, q);
+            if !closed && self.cap == LineCap::Square {
+                if i == 0 {
+                    p = (p.0 - d.0 * h, p.1 - d.1 * h);
+                }
+                if i == segments - 1 {
+                    q = (q.0 + d.0 * h, q.1 + d.1 * h);
+                }
+            }
+            let off = (-d.1 * h, d.0 * h);
+            pieces.push(vec![
+                (p.0 + off.0, p.1 + off.1),
+                (q.0 + off.0, q.1 + off.1),
+                (q.0 - off.0, q.1 - off.1),
+                (p.0 - off.0, p.1 - off.1),
+            ]);
+        }
+
+        let corners = if closed { 0..n } else { 1..n - 1 };
+        for i in corners {
+            let (a, p, b) = (pts[(i + n - 1) % n], pts[i], pts[(i + 1) % n]);
+            if let Some(piece) = self.join_piece(a, p, b) {
+                pieces.push(piece);
+            }
+        }
+
+        if !closed && self.cap == LineCap::Round {
+            pieces.push(disk(pts[0], h));
+            pieces.push(disk(pts[n - 1], h));
+        }
+        pieces
+    }
+
+    /// The piece filling the gap on the outside of the corner at `p`, from `a` on to `b`
+    fn join_piece(&self, a: Point, p: Point, b: Point) -> Option<Vec<Point>> {
+        let h = self.width / 2.0;
+        let (d0, d1) = (direction(a, p), direction(p, b));
+        let turn = d0.0 * d1.1 - d0.1 * d1.0;
+        if self.join == LineJoin::Round {
+            return Some(disk(p, h));
+        }
+        if turn.abs() < 1e-12 {
+            // going straight on needs nothing, and turning back has no corner to fill
+            return None;
+        }
+        // the outside of the corner is opposite the way it turns
+        let s = -turn.signum() * h;
+        let (n0, n1) = ((-d0.1, d0.0), (-d1.1, d1.0));
+        let (e0, e1) = ((p.0 + n0.0 * s, p.1 + n0.1 * s), (p.0 + n1.0 * s, p.1 + n1.1 * s));
+        if let LineJoin::Miter(limit) = self.join {
+            // the tip is along the bisector of the normals, 1 / cos(turn / 2) of a half width out
+            let k = s / (1.0 + n0.0 * n1.0 + n0.1 * n1.1);
+            let tip = (p.0 + (n0.0 + n1.0) * k, p.1 + (n0.1 + n1.1) * k);
+            if (tip.0 - p.0).hypot(tip.1 - p.1) <= limit * h {
+                return Some(vec![p, e0, tip, e1]);
+            }
+        }
+        Some(vec![p, e0, e1])
+    }
+}
+
+fn direction(p: Point, q: Point) -> Point {
+    let len = (q.0 - p.0).hypot(q.1 - p.1);
+    ((q.0 - p.0) / len, (q.1 - p.1) / len)
+}
+
+/// A polygon close enough to a circle of radius `r` that no pixel is off by one
+fn disk(c: Point, r: f64) -> Vec<Point> {
+    let n = ((PI * r).ceil() as usize).max(8);
+    (0..n)
+        .map(|i| {
+            let (s, co) = (2.0 * PI * i as f64 / n as f64).sin_cos();
+            (c.0 + r * co, c.1 + r * s)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::polygon::signed_area;
+
+    fn area(pieces: &[Vec<Point>]) -> f64 {
+        pieces.iter().map(|p| signed_area(p).abs() / 2.0).sum()
+    }
+
+    #[test]
+    fn caps_and_joins() {
+        let line = [(0.0, 0.0), (10.0, 0.0)];
+        let butt = Stroke::new(2.0);
+        assert!((area(&butt.outline(&line, false)) - 20.0).abs() < 1e-9);
+        let square = butt.cap(LineCap::Square);
+        assert!((area(&square.outline(&line, false)) - 24.0).abs() < 1e-9);
+
+        // a right angle: the miter adds a 1 by 1 square, the bevel half of that
+        let corner = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)];
+        let miter = butt.outline(&corner, false);
+        assert_eq!(3, miter.len());
+        assert!((signed_area(&miter[2]).abs() / 2.0 - 1.0).abs() < 1e-9);
+        assert!(miter[2].contains(&(11.0, -1.0)));
+        let bevel = butt.join(LineJoin::Bevel).outline(&corner, false);
+        assert!((signed_area(&bevel[2]).abs() / 2.0 - 0.5).abs() < 1e-9);
+
+        // a sharp turn goes past the miter limit
+        let sharp = [(0.0, 0.0), (10.0, 0.0), (0.0, 1.0)];
+        assert_eq!(3, butt.outline(&sharp, false)[2].len());
+
+        // closed squares join at every corner and have no caps
+        let square_path = [(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (0.0, 4.0), (0.0, 0.0)];
+        let round = Stroke::new(1.0).cap(LineCap::Round).join(LineJoin::Round);
+        assert_eq!(8, round.outline(&square_path, true).len());
+    }
+
+    #[test]
+    fn widths_follow_transforms() {
+        use crate::graphics::Matrix;
+        let zoom = Matrix::rotatez(30.0)
+            .mul(&Matrix::scale(3.0, 3.0, 3.0))
+            .mul(&Matrix::mv(5.0, -2.0, 1.0));
+        assert!((Stroke::new(2.0).transformed(&zoom).width - 6.0).abs() < 1e-9);
+        assert!((Matrix::scale(2.0, 4.0, 1.0).scale_factor() - 2.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,339 @@
+#![allow(dead_code)]
+
+use std::f64::consts;
+
+use super::parametrics::Surface;
+use super::polygon;
+use super::utils::{compute_bezier3_coef, compute_hermite3_coef};
+use super::vector::{self, Vec3};
+
+/// Rows of 3d points, where `grid[i][j]` neighbors `grid[i + 1][j]` and `grid[i][j + 1]`
+pub type Grid = Vec<Vec<Vec3>>;
+
+/// An axis to revolve around, through the origin
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Axis {
+    X,
+    Y,
+}
+
+/// Revolve a profile in the xy plane a full turn about `axis`, lathe style
+///
+/// Returns one row per profile point with `steps + 1` points each, so that
+/// triangles made from the grid face outward when the axis is on the left of the profile,
+/// as for a profile going up on the right of the y axis.
+pub fn revolve(profile: &[(f64, f64)], axis: Axis, steps: usize) -> Grid {
+    assert!(steps > 0, "Revolution needs at least one step");
+    profile
+        .iter()
+        .map(|&(x, y)| {
+            (0..=steps)
+                .map(|j| {
+                    let (sin, cos) = (j as f64 / steps as f64 * 2.0 * consts::PI).sin_cos();
+                    match axis {
+                        Axis::X => (x, y * cos, y * sin),
+                        Axis::Y => (x * cos, y, x * sin),
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// The points of a closed shape, ending with its first point
+fn closed_shape(shape: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    let mut pts = shape.to_vec();
+    if pts.len() > 1 && pts.first() != pts.last() {
+        pts.push(pts[0]);
+    }
+    pts
+}
+
+/// Extrude a closed shape in the xy plane by `depth` along `direction`
+///
+/// Returns one row per shape point, each going from the shape to its moved copy.
+/// Triangles made from the grid face outward for a counter clockwise shape extruded toward +z.
+pub fn extrude(shape: &[(f64, f64)], direction: Vec3, depth: f64) -> Grid {
+    let offset = vector::scale(vector::normalize(direction), depth);
+    closed_shape(shape)
+        .into_iter()
+        .map(|(x, y)| vec![(x, y, 0.0), vector::add((x, y, 0.0), offset)])
+        .collect()
+}
+
+/// Sweep a closed shape along a 3d polyline `spine`
+///
+/// The shape's x and y axes follow the spine on rotation minimizing frames,
+/// so the tube does not twist more than the spine makes it.
+/// Returns one row per shape point, each following the spine.
+/// Triangles made from the grid face outward for a counter clockwise shape.
+pub fn sweep(shape: &[(f64, f64)], spine: &[Vec3]) -> Grid {
+    let frames = rotation_minimizing_frames(spine);
+    closed_shape(shape)
+        .into_iter()
+        .map(|(x, y)| {
+            spine
+                .iter()
+                .zip(frames.iter())
+                .map(|(&p, &(n, b))| {
+                    vector::add(p, vector::add(vector::scale(n, x), vector::scale(b, y)))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Normal and binormal at every point of a polyline, by the double reflection method
+/// (Wang, Juttler, Zheng and Liu, 2008)
+fn rotation_minimizing_frames(spine: &[Vec3]) -> Vec<(Vec3, Vec3)> {
+    let n = spine.len();
+    assert!(n >= 2, "Spine needs at least two points");
+    let tangents: Vec<Vec3> = (0..n)
+        .map(|i| {
+            let (a, b) = (spine[i.saturating_sub(1)], spine[(i + 1).min(n - 1)]);
+            vector::normalize(vector::sub(b, a))
+        })
+        .collect();
+
+    // start with any normal, taken from the axis least aligned with the tangent
+    let t = tangents[0];
+    let axis = if t.0.abs() <= t.1.abs() && t.0.abs() <= t.2.abs() {
+        (1.0, 0.0, 0.0)
+    } else if t.1.abs() <= t.2.abs() {
+        (0.0, 1.0, 0.0)
+    } else {
+        (0.0, 0.0, 1.0)
+    };
+    let mut normal = vector::normalize(vector::sub(axis, vector::scale(t, vector::dot(axis, t))));
+
+    let reflect = |v: Vec3, across: Vec3, c: f64| {
+        vector::sub(v, vector::scale(across, 2.0 / c * vector::dot(across, v)))
+    };
+    let mut frames = Vec::with_capacity(n);
+    for i in 0..n {
+        frames.push((normal, vector::cross(tangents[i], normal)));
+        if i + 1 == n {
+            break;
+        }
+        let v1 = vector::sub(spine[i + 1], spine[i]);
+        let c1 = vector::dot(v1, v1);
+        if c1 == 0.0 {
+            continue;
+        }
+        let (rl, tl) = (reflect(normal, v1, c1), reflect(tangents[i], v1, c1));
+        let v2 = vector::sub(tangents[i + 1], tl);
+        let c2 = vector::dot(v2, v2);
+        normal = if c2 == 0.0 { rl } else { reflect(rl, v2, c2) };
+    }
+    frames
+}
+
+/// Triangles closing both ends of a grid made by `extrude` or `sweep` from `shape`, facing outward
+///
+/// The first and last point of every row make the two end shapes, which are
+/// triangulated in the plane of the shape, so any simple shape works.
+pub fn caps(shape: &[(f64, f64)], grid: &[Vec<Vec3>]) -> Vec<[Vec3; 3]> {
+    let mut tris = vec![];
+    if grid.is_empty() {
+        return tris;
+    }
+    let last = grid[0].len() - 1;
+    // rows line up with the points of the closed shape
+    for [a, b, c] in polygon::triangulate_indices(&closed_shape(shape)) {
+        tris.push([grid[a][0], grid[c][0], grid[b][0]]);
+        tris.push([grid[a][last], grid[b][last], grid[c][last]]);
+    }
+    tris
+}
+
+/// Turns the 4 control values of a cubic curve into its coefficients (a, b, c, d)
+type CubicCoef = fn(f64, f64, f64, f64) -> (f64, f64, f64, f64);
+
+/// Power basis coefficients of a bicubic patch, `c[n][m]` going with `u^(3 - n) v^(3 - m)`
+///
+/// `coef` is applied to the control values `g` along v, then along u
+fn bicubic_coef(g: &[[Vec3; 4]; 4], coef: CubicCoef) -> [[Vec3; 4]; 4] {
+    let apply = |p: [Vec3; 4]| -> [Vec3; 4] {
+        let (x, y, z) = (
+            coef(p[0].0, p[1].0, p[2].0, p[3].0),
+            coef(p[0].1, p[1].1, p[2].1, p[3].1),
+            coef(p[0].2, p[1].2, p[2].2, p[3].2),
+        );
+        [(x.0, y.0, z.0), (x.1, y.1, z.1), (x.2, y.2, z.2), (x.3, y.3, z.3)]
+    };
+    let rows = [apply(g[0]), apply(g[1]), apply(g[2]), apply(g[3])];
+    let mut c = [[(0.0, 0.0, 0.0); 4]; 4];
+    for m in 0..4 {
+        let col = apply([rows[0][m], rows[1][m], rows[2][m], rows[3][m]]);
+        for n in 0..4 {
+            c[n][m] = col[n];
+        }
+    }
+    c
+}
+
+fn eval_bicubic(c: &[[Vec3; 4]; 4], u: f64, v: f64) -> Vec3 {
+    let (us, vs) = ([u * u * u, u * u, u, 1.0], [v * v * v, v * v, v, 1.0]);
+    let mut p = (0.0, 0.0, 0.0);
+    for n in 0..4 {
+        for m in 0..4 {
+            p = vector::add(p, vector::scale(c[n][m], us[n] * vs[m]));
+        }
+    }
+    p
+}
+
+/// A bicubic Bezier patch with a 4 by 4 grid of control points
+///
+/// `points[i][j]` is the control point at row i along u and column j along v.
+/// The patch passes through the 4 corner points.
+pub struct BezierPatch {
+    pub points: [[Vec3; 4]; 4],
+    coef: [[Vec3; 4]; 4],
+}
+
+impl BezierPatch {
+    pub fn new(points: [[Vec3; 4]; 4]) -> Self {
+        BezierPatch {
+            points,
+            coef: bicubic_coef(&points, compute_bezier3_coef),
+        }
+    }
+}
+
+impl Surface for BezierPatch {
+    fn point_at(&self, u: f64, v: f64) -> Vec3 {
+        eval_bicubic(&self.coef, u, v)
+    }
+}
+
+/// A bicubic Hermite patch given by its corners and the derivatives there
+///
+/// Every array is indexed `[i][j]` for the corner at `(u, v) = (i, j)`.
+/// Zero twists give a patch whose edges alone decide its shape.
+pub struct HermitePatch {
+    pub corners: [[Vec3; 2]; 2],
+    /// Derivative along u at each corner
+    pub du: [[Vec3; 2]; 2],
+    /// Derivative along v at each corner
+    pub dv: [[Vec3; 2]; 2],
+    /// Mixed second derivative at each corner
+    pub twist: [[Vec3; 2]; 2],
+    coef: [[Vec3; 4]; 4],
+}
+
+impl HermitePatch {
+    pub fn new(
+        corners: [[Vec3; 2]; 2],
+        du: [[Vec3; 2]; 2],
+        dv: [[Vec3; 2]; 2],
+        twist: [[Vec3; 2]; 2],
+    ) -> Self {
+        // rows follow u as (p0, p1, r0, r1) and columns follow v the same way
+        let (p, pu) = (corners, du);
+        let g = [
+            [p[0][0], p[0][1], dv[0][0], dv[0][1]],
+            [p[1][0], p[1][1], dv[1][0], dv[1][1]],
+            [pu[0][0], pu[0][1], twist[0][0], twist[0][1]],
+            [pu[1][0], pu[1][1], twist[1][0], twist[1][1]],
+        ];
+        HermitePatch {
+            corners,
+            du,
+            dv,
+            twist,
+            coef: bicubic_coef(&g, compute_hermite3_coef),
+        }
+    }
+}
+
+impl Surface for HermitePatch {
+    fn point_at(&self, u: f64, v: f64) -> Vec3 {
+        eval_bicubic(&self.coef, u, v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn revolve_keeps_distance_to_axis() {
+        let grid = revolve(&[(3.0, 0.0), (3.0, 2.0), (1.0, 4.0)], Axis::Y, 12);
+        assert_eq!(3, grid.len());
+        for (row, r) in grid.iter().zip([3.0, 3.0, 1.0].iter()) {
+            assert_eq!(13, row.len());
+            for p in row.iter() {
+                assert!((p.0.hypot(p.2) - r).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn sweep_follows_a_bend() {
+        let square = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+        let spine: Vec<Vec3> = (0..=16)
+            .map(|i| {
+                let a = i as f64 / 16.0 * consts::FRAC_PI_2;
+                (10.0 * a.cos(), 10.0 * a.sin(), 0.0)
+            })
+            .collect();
+        let grid = sweep(&square, &spine);
+        assert_eq!(5, grid.len());
+        // every cross section stays around its spine point, in the plane normal to the spine
+        for j in 1..spine.len() - 1 {
+            let tangent = vector::sub(spine[j + 1], spine[j - 1]);
+            for row in grid.iter() {
+                let d = vector::sub(row[j], spine[j]);
+                assert!((vector::length(d) - 2f64.sqrt()).abs() < 1e-9);
+                assert!(vector::dot(d, tangent).abs() < 1e-9);
+            }
+        }
+
+        let prism = extrude(&square, (0.0, 0.0, 2.0), 3.0);
+        assert_eq!((1.0, 1.0, 3.0), prism[2][1]);
+        assert_eq!(4, caps(&square, &prism).len());
+    }
+
+    #[test]
+    fn bezier_patch_corners_and_plane() {
+        let mut points = [[(0.0, 0.0, 0.0); 4]; 4];
+        for (i, row) in points.iter_mut().enumerate() {
+            for (j, p) in row.iter_mut().enumerate() {
+                *p = (i as f64, j as f64, 0.0);
+            }
+        }
+        points[1][2].2 = 6.0;
+        let patch = BezierPatch::new(points);
+        assert_eq!((3.0, 0.0, 0.0), patch.point_at(1.0, 0.0));
+        assert_eq!((3.0, 3.0, 0.0), patch.point_at(1.0, 1.0));
+        // evenly spaced control points give an even parametrization
+        let p = patch.point_at(0.5, 0.25);
+        assert!((p.0 - 1.5).abs() < 1e-9 && (p.1 - 0.75).abs() < 1e-9);
+        // bernstein weights 3 u (1 - u)^2 * 3 v^2 (1 - v) at the bump
+        let (u, v) = (0.5f64, 0.25f64);
+        let w = 3.0 * u * (1.0 - u).powi(2) * 3.0 * v * v * (1.0 - v);
+        assert!((p.2 - 6.0 * w).abs() < 1e-9);
+    }
+
+    #[test]
+    fn hermite_patch_derivatives() {
+        let zero = [[(0.0, 0.0, 0.0); 2]; 2];
+        let corners = [
+            [(0.0, 0.0, 0.0), (0.0, 4.0, 0.0)],
+            [(4.0, 0.0, 0.0), (4.0, 4.0, 0.0)],
+        ];
+        let (du, dv) = ([[(4.0, 0.0, 0.0); 2]; 2], [[(0.0, 4.0, 0.0); 2]; 2]);
+        let flat = HermitePatch::new(corners, du, dv, zero);
+        let p = flat.point_at(0.3, 0.6);
+        assert!((p.0 - 1.2).abs() < 1e-9 && (p.1 - 2.4).abs() < 1e-9 && p.2.abs() < 1e-9);
+
+        // tilting the u derivative up at u = 0 lifts the patch near that edge only
+        let du = [[(4.0, 0.0, 3.0); 2], [(4.0, 0.0, 0.0); 2]];
+        let bent = HermitePatch::new(corners, du, dv, zero);
+        assert_eq!((4.0, 4.0, 0.0), bent.point_at(1.0, 1.0));
+        let h = 1e-6;
+        let dz = (bent.point_at(h, 0.5).2 - bent.point_at(0.0, 0.5).2) / h;
+        assert!((dz - 3.0).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,184 @@
+#![allow(dead_code)]
+
+//! SVG output, keeping lines and curves as vectors instead of pixels
+
+use std::fmt::Write as _;
+use std::io::{self, prelude::Write};
+
+use super::matrix::Matrix;
+use super::parametrics::Curve;
+use super::path::{Path, Segment};
+use super::utils::create_file;
+use super::RGB;
+
+/// An SVG document in pixel coordinates, with y going down as on the canvas
+///
+/// Colors are taken to have depth 255.
+pub struct Svg {
+    width: u32,
+    height: u32,
+    pub bg_color: Option<RGB>,
+    /// Width of the lines added from now on
+    pub stroke_width: f64,
+    elements: Vec<String>,
+}
+
+impl Svg {
+    pub fn new(width: u32, height: u32) -> Self {
+        Svg {
+            width,
+            height,
+            bg_color: None,
+            stroke_width: 1.0,
+            elements: vec![],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Remove everything added so far
+    pub fn clear(&mut self) {
+        self.elements.clear();
+    }
+
+    /// Add one pixel, as a square
+    pub fn add_pixel(&mut self, x: i32, y: i32, color: RGB) {
+        self.elements.push(format!(
+            r#"<rect x="{}" y="{}" width="1" height="1" fill="{}"/>"#,
+            x,
+            y,
+            color.to_hex()
+        ));
+    }
+
+    pub fn add_line(&mut self, x0: f64, y0: f64, x1: f64, y1: f64, color: RGB) {
+        self.add_element(&format!("M{} {} L{} {}", x0, y0, x1, y1), color);
+    }
+
+    fn add_element(&mut self, d: &str, color: RGB) {
+        self.elements.push(format!(
+            r#"<path d="{}" fill="none" stroke="{}" stroke-width="{}"/>"#,
+            d.trim_end(),
+            color.to_hex(),
+            self.stroke_width
+        ));
+    }
+
+    /// Add the edges of an edge matrix, joining edges that meet into one polyline
+    ///
+    /// Each edge takes the color of its first point, falling back to `color`.
+    pub fn add_edges(&mut self, m: &Matrix, color: RGB) {
+        // (color, path data, end of the last edge)
+        let mut current: Option<(RGB, String, (f64, f64))> = None;
+        let mut iter = m.iter_by_row().enumerate();
+        while let Some((i, p0)) = iter.next() {
+            let (_, p1) = iter.next().expect("Number of edges must be a multiple of 2");
+            let (p0, p1) = ((p0[0], p0[1]), (p1[0], p1[1]));
+            let c = m.color(i).unwrap_or(color);
+            match current.as_mut() {
+                Some((cc, d, end)) if *cc == c && *end == p0 => {
+                    write!(d, "L{} {} ", p1.0, p1.1).unwrap();
+                    *end = p1;
+                }
+                _ => {
+                    if let Some((cc, d, _)) = current.take() {
+                        self.add_element(&d, cc);
+                    }
+                    current = Some((c, format!("M{} {} L{} {} ", p0.0, p0.1, p1.0, p1.1), p1));
+                }
+            }
+        }
+        if let Some((c, d, _)) = current {
+            self.add_element(&d, c);
+        }
+    }
+
+    /// Add a path with its lines, arcs and cubics as they are, without tessellating
+    pub fn add_path(&mut self, path: &Path, color: RGB) {
+        let mut d = String::new();
+        for sp in path.subpaths().iter() {
+            write!(d, "M{} {} ", sp.start.0, sp.start.1).unwrap();
+            for seg in sp.segments.iter() {
+                match seg {
+                    Segment::Line(l) => write!(d, "L{} {} ", l.p1.0, l.p1.1).unwrap(),
+                    Segment::Cubic(c) => write!(
+                        d,
+                        "C{} {} {} {} {} {} ",
+                        c.p1.0, c.p1.1, c.p2.0, c.p2.1, c.p3.0, c.p3.1
+                    )
+                    .unwrap(),
+                    Segment::Arc(a) => {
+                        // SVG arcs cannot be full circles, so split into pieces of at most half a
+                        // turn; angles grow the same way in both, so positive sweeps are sweep 1
+                        let pieces = (a.sweep_deg.abs() / 180.0).ceil().max(1.0) as usize;
+                        let sweep = if a.sweep_deg > 0.0 { 1 } else { 0 };
+                        for k in 1..=pieces {
+                            let p = a.point_at(k as f64 / pieces as f64);
+                            let r = a.radius;
+                            write!(d, "A{} {} 0 0 {} {} {} ", r, r, sweep, p.0, p.1).unwrap();
+                        }
+                    }
+                }
+            }
+            if sp.closed {
+                d.push_str("Z ");
+            }
+        }
+        self.add_element(&d, color);
+    }
+
+    /// Write the document
+    pub fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        let (width, height) = (self.width, self.height);
+        write!(w, r#"<svg xmlns="http://www.w3.org/2000/svg""#)?;
+        write!(w, r#" width="{}" height="{}""#, width, height)?;
+        writeln!(w, r#" viewBox="0 0 {} {}">"#, width, height)?;
+        if let Some(bg) = self.bg_color {
+            writeln!(w, r#"<rect width="100%" height="100%" fill="{}"/>"#, bg.to_hex())?;
+        }
+        for e in self.elements.iter() {
+            writeln!(w, "{}", e)?;
+        }
+        writeln!(w, "</svg>")
+    }
+
+    pub fn save(&self, filepath: &str) -> io::Result<()> {
+        let mut file = create_file(filepath)?;
+        self.write(&mut file)?;
+        file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edges_join_and_curves_stay_curves() {
+        let mut svg = Svg::new(100, 50);
+        let mut m = Matrix::new(0, 4, vec![]);
+        m.append_edge(&[0.0, 0.0, 0.0, 10.0, 0.0, 0.0]);
+        m.append_edge(&[10.0, 0.0, 0.0, 10.0, 10.0, 0.0]);
+        m.append_edge(&[50.0, 50.0, 0.0, 60.0, 50.0, 0.0]);
+        svg.add_edges(&m, RGB::RED);
+        let mut path = Path::new();
+        path.move_to(0.0, 0.0)
+            .curve_to((1.0, 2.0), (3.0, 4.0), (5.0, 6.0))
+            .arc((20.0, 20.0), 5.0, 0.0, 360.0);
+        svg.add_path(&path, RGB::BLUE);
+
+        let mut out = vec![];
+        svg.write(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains(r##"d="M0 0 L10 0 L10 10" fill="none" stroke="#ff0000""##));
+        assert!(text.contains(r#"d="M50 50 L60 50""#));
+        assert!(text.contains("C1 2 3 4 5 6 L25 20 A5 5 0 0 1 15 20 A5 5 0 0 1 25 20"));
+        assert!(text.trim_end().ends_with("</svg>"));
+    }
+}
@@ -0,0 +1,161 @@
+#![allow(dead_code)]
+
+//! Something to draw on, so drawing code can be written once for every kind of output
+//!
+//! The canvas and SVG documents are targets themselves. The terminal and the preview window
+//! show whole images, so they are drawn on through a `Buffered` canvas that goes out on
+//! `flush`. Targets work as `Box<dyn RenderTarget>`, for picking the output when running.
+
+use std::io;
+
+use super::animation::FrameSink;
+use super::matrix::Matrix;
+use super::svg::Svg;
+use super::{walk_line, PPMImg, RGB};
+
+/// Somewhere pixels go, in pixel coordinates with y going down
+pub trait RenderTarget {
+    /// Width and height in pixels
+    fn dimensions(&self) -> (u32, u32);
+
+    fn plot(&mut self, x: i32, y: i32, color: RGB);
+
+    /// Start over from the background
+    fn clear(&mut self);
+
+    /// Send what was drawn to wherever it goes
+    fn flush(&mut self) -> io::Result<()>;
+
+    /// Draws a line with `plot`, for targets that have nothing better
+    fn draw_line(&mut self, x0: f64, y0: f64, x1: f64, y1: f64, color: RGB) {
+        walk_line(x0, y0, x1, y1, (0.0, 1.0), |x, y, _| self.plot(x, y, color));
+    }
+
+    /// Draws the edges of an edge matrix, each in the color of its first point or in `color`
+    fn draw_edges(&mut self, m: &Matrix, color: RGB) {
+        let mut iter = m.iter_by_row().enumerate();
+        while let Some((i, p0)) = iter.next() {
+            let (_, p1) = iter.next().expect("Number of edges must be a multiple of 2");
+            let c = m.color(i).unwrap_or(color);
+            self.draw_line(p0[0], p0[1], p1[0], p1[1], c);
+        }
+    }
+}
+
+impl RenderTarget for PPMImg {
+    fn dimensions(&self) -> (u32, u32) {
+        (self.width(), self.height())
+    }
+
+    fn plot(&mut self, x: i32, y: i32, color: RGB) {
+        self.plot_color(x, y, color);
+    }
+
+    fn clear(&mut self) {
+        PPMImg::clear(self);
+    }
+
+    /// The image stays in memory, to be saved when done
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Lines stay lines, and single pixels become little squares
+impl RenderTarget for Svg {
+    fn dimensions(&self) -> (u32, u32) {
+        (self.width(), self.height())
+    }
+
+    fn plot(&mut self, x: i32, y: i32, color: RGB) {
+        self.add_pixel(x, y, color);
+    }
+
+    fn clear(&mut self) {
+        Svg::clear(self);
+    }
+
+    /// The document stays in memory, to be saved when done
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn draw_line(&mut self, x0: f64, y0: f64, x1: f64, y1: f64, color: RGB) {
+        self.add_line(x0, y0, x1, y1, color);
+    }
+
+    fn draw_edges(&mut self, m: &Matrix, color: RGB) {
+        self.add_edges(m, color);
+    }
+}
+
+/// A canvas in front of a sink that takes whole images, like a `TerminalWriter` or a
+/// `Preview`; every `flush` hands the canvas to the sink as a frame
+pub struct Buffered<S: FrameSink> {
+    pub img: PPMImg,
+    pub sink: S,
+}
+
+impl<S: FrameSink> Buffered<S> {
+    pub fn new(img: PPMImg, sink: S) -> Self {
+        Buffered { img, sink }
+    }
+
+    /// Finish the sink, after the last `flush`
+    pub fn finish(self) -> io::Result<()> {
+        Box::new(self.sink).finish()
+    }
+}
+
+impl<S: FrameSink> RenderTarget for Buffered<S> {
+    fn dimensions(&self) -> (u32, u32) {
+        self.img.dimensions()
+    }
+
+    fn plot(&mut self, x: i32, y: i32, color: RGB) {
+        self.img.plot_color(x, y, color);
+    }
+
+    fn clear(&mut self) {
+        self.img.clear();
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.sink.add_frame(&self.img)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::terminal::{TerminalMode, TerminalWriter};
+
+    #[test]
+    fn one_drawing_for_every_target() {
+        let draw = |target: &mut dyn RenderTarget| {
+            let (w, h) = target.dimensions();
+            target.clear();
+            target.draw_line(0.0, 0.0, (w - 1) as f64, (h - 1) as f64, RGB::RED);
+            target.plot(0, 1, RGB::BLUE);
+            target.flush().unwrap();
+        };
+
+        let mut img = PPMImg::new(2, 2, 255);
+        let mut svg = Svg::new(2, 2);
+        let terminal = TerminalWriter::new(vec![], TerminalMode::Ansi);
+        let mut buffered = Buffered::new(PPMImg::new(2, 2, 255), terminal);
+        for target in [&mut img as &mut dyn RenderTarget, &mut svg, &mut buffered] {
+            draw(target);
+        }
+        assert_eq!(Some(RGB::RED), img.pixel(1, 1));
+        assert_eq!(Some(RGB::BLUE), img.pixel(0, 1));
+
+        let mut out = vec![];
+        svg.write(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains(r#"d="M0 0 L1 1""#) && text.contains(r#"<rect x="0" y="1""#));
+
+        let text = String::from_utf8(buffered.sink.into_inner()).unwrap();
+        assert!(text.starts_with("\x1b[2J\x1b[H\x1b[38;2;255;0;0m\x1b[48;2;0;0;255m"));
+    }
+}
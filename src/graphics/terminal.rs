@@ -0,0 +1,160 @@
+#![allow(dead_code)]
+
+//! Previews in the terminal, as colored blocks or as Sixel graphics
+
+use std::io::{self, prelude::Write};
+
+use super::dither::Dither;
+use super::gif;
+use super::palette::Quantizer;
+use super::PPMImg;
+
+/// Writes the image as rows of upper half blocks in 24 bit ANSI color, the top pixel of each
+/// cell in the foreground and the bottom one in the background
+///
+/// An odd last row leaves the bottom halves in the terminal's own background.
+pub fn write_ansi(w: &mut impl Write, img: &PPMImg) -> io::Result<()> {
+    let rows: Vec<_> = img.rows().collect();
+    for pair in rows.chunks(2) {
+        for (x, top) in pair[0].iter().enumerate() {
+            let [r, g, b] = top.to_bytes(img.depth());
+            write!(w, "\x1b[38;2;{};{};{}m", r, g, b)?;
+            match pair.get(1) {
+                Some(bottom) => {
+                    let [r, g, b] = bottom[x].to_bytes(img.depth());
+                    write!(w, "\x1b[48;2;{};{};{}m\u{2580}", r, g, b)?;
+                }
+                None => write!(w, "\x1b[49m\u{2580}")?,
+            }
+        }
+        writeln!(w, "\x1b[0m")?;
+    }
+    Ok(())
+}
+
+/// Writes the image as Sixel graphics, which xterm, mlterm, wezterm and others show inline at
+/// full resolution
+///
+/// Images with more than 256 colors are reduced to a fixed palette, as for GIFs.
+pub fn write_sixel(w: &mut impl Write, img: &PPMImg, dither: Dither) -> io::Result<()> {
+    let (width, height) = (img.width() as usize, img.height() as usize);
+    let pixels: Vec<[u8; 3]> = img
+        .rows()
+        .flat_map(|row| row.iter().map(|p| p.to_bytes(img.depth())))
+        .collect();
+    let (palette, indices) = gif::quantize(&pixels, width, dither, &Quantizer::Uniform);
+
+    write!(w, "\x1bPq\"1;1;{};{}", width, height)?;
+    for (i, c) in palette.iter().enumerate() {
+        let [r, g, b] = c.map(|v| (v as u32 * 100 + 127) / 255);
+        write!(w, "#{};2;{};{};{}", i, r, g, b)?;
+    }
+    // six rows at a time, one pass over the band for every color in it
+    for y0 in (0..height).step_by(6) {
+        if y0 > 0 {
+            write!(w, "-")?;
+        }
+        let mut sixels = vec![vec![0u8; width]; palette.len()];
+        for y in y0..(y0 + 6).min(height) {
+            for x in 0..width {
+                sixels[indices[y * width + x] as usize][x] |= 1 << (y - y0);
+            }
+        }
+        for (color, line) in sixels.iter().enumerate() {
+            let end = match line.iter().rposition(|&s| s != 0) {
+                Some(end) => end + 1,
+                None => continue,
+            };
+            write!(w, "#{}", color)?;
+            let mut x = 0;
+            while x < end {
+                let run = line[x..end].iter().take_while(|&&s| s == line[x]).count();
+                let c = (63 + line[x]) as char;
+                if run > 3 {
+                    write!(w, "!{}{}", run, c)?;
+                } else {
+                    (0..run).try_for_each(|_| write!(w, "{}", c))?;
+                }
+                x += run;
+            }
+            write!(w, "$")?;
+        }
+    }
+    write!(w, "\x1b\\")
+}
+
+/// How `TerminalWriter` shows images
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TerminalMode {
+    /// Half blocks, see `write_ansi`
+    Ansi,
+    /// Sixel graphics, see `write_sixel`
+    Sixel(Dither),
+}
+
+/// Shows image after image in a terminal, each one over the last, for animations
+pub struct TerminalWriter<W: Write> {
+    w: W,
+    pub mode: TerminalMode,
+    shown: bool,
+}
+
+impl<W: Write> TerminalWriter<W> {
+    pub fn new(w: W, mode: TerminalMode) -> Self {
+        TerminalWriter {
+            w,
+            mode,
+            shown: false,
+        }
+    }
+
+    /// Clears the screen before the first image, and moves back to the top left before the rest
+    pub fn show(&mut self, img: &PPMImg) -> io::Result<()> {
+        if img.samples > 1 {
+            return self.show(&img.downsample());
+        }
+        write!(self.w, "{}\x1b[H", if self.shown { "" } else { "\x1b[2J" })?;
+        self.shown = true;
+        match self.mode {
+            TerminalMode::Ansi => write_ansi(&mut self.w, img)?,
+            TerminalMode::Sixel(dither) => write_sixel(&mut self.w, img, dither)?,
+        }
+        self.w.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.w
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::RGB;
+
+    #[test]
+    fn cells_hold_two_pixels() {
+        let mut img = PPMImg::new(3, 2, 255);
+        img.plot_color(1, 0, RGB::RED);
+        img.plot_color(0, 2, RGB::new(1, 2, 3));
+        let mut out = vec![];
+        write_ansi(&mut out, &img).unwrap();
+        let expected = "\x1b[38;2;0;0;0m\x1b[48;2;0;0;0m\u{2580}\
+                        \x1b[38;2;255;0;0m\x1b[48;2;0;0;0m\u{2580}\x1b[0m\n\
+                        \x1b[38;2;1;2;3m\x1b[49m\u{2580}\
+                        \x1b[38;2;0;0;0m\x1b[49m\u{2580}\x1b[0m\n";
+        assert_eq!(expected, String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn sixel_bands_and_runs() {
+        let mut img = PPMImg::new(7, 5, 255);
+        img.plot_color(4, 0, RGB::RED);
+        let mut out = vec![];
+        write_sixel(&mut out, &img, Dither::None).unwrap();
+        // black everywhere but one pixel, then a second band of one row
+        let expected = "\x1bPq\"1;1;5;7#0;2;0;0;0#1;2;100;0;0\
+                        #0!4~}$#1!4?@$-#0!5@$\x1b\\";
+        assert_eq!(expected, String::from_utf8(out).unwrap());
+    }
+}
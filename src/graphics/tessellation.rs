@@ -0,0 +1,91 @@
+#![allow(dead_code)]
+
+use super::parametrics::{flatten, Curve};
+
+/// How curves are sampled into edges
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Tessellation {
+    /// Sample every `step` in t
+    Step(f64),
+    /// Split every curve into this many segments
+    Segments(usize),
+    /// Use as few segments as possible while keeping every segment under this length in pixels
+    MaxSegmentLength(f64),
+    /// Keep splitting until every segment is within this many pixels of the curve
+    Adaptive(f64),
+}
+
+impl Default for Tessellation {
+    fn default() -> Self {
+        Tessellation::Step(0.001)
+    }
+}
+
+impl Tessellation {
+    /// Sample a curve into points, from t = 0 to t = 1 inclusive
+    pub fn sample(&self, c: &(impl Curve + ?Sized)) -> Vec<(f64, f64)> {
+        self.sample_scaled(c, 1.0)
+    }
+
+    /// Sample a curve that will be drawn scaled up by `scale`
+    ///
+    /// Length and tolerance based settings are measured on screen, after scaling
+    pub fn sample_scaled(&self, c: &(impl Curve + ?Sized), scale: f64) -> Vec<(f64, f64)> {
+        self.sample_with_t(c, scale).into_iter().map(|(_t, p)| p).collect()
+    }
+
+    /// Sample a curve drawn scaled up by `scale`, keeping the t of every point
+    pub fn sample_with_t(&self, c: &(impl Curve + ?Sized), scale: f64) -> Vec<(f64, (f64, f64))> {
+        assert!(scale > 0.0, "Scale must be positive");
+        let n = match *self {
+            Tessellation::Step(step) => {
+                assert!(step > 0.0, "Step must be positive");
+                (1.0 / step).ceil() as usize
+            }
+            Tessellation::Segments(n) => n.max(1),
+            Tessellation::MaxSegmentLength(len) => {
+                assert!(len > 0.0, "Segment length must be positive");
+                let len = len / scale;
+                (c.length(len / 100.0) / len).ceil().max(1.0) as usize
+            }
+            Tessellation::Adaptive(tolerance) => {
+                let tolerance = tolerance / scale;
+                return flatten(c, tolerance);
+            }
+        };
+        (0..=n)
+            .map(|i| {
+                let t = i as f64 / n as f64;
+                (t, c.point_at(t))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::curves::{Arc, Line};
+
+    #[test]
+    fn every_setting_picks_its_segment_count() {
+        let line = Line::new((0.0, 0.0), (10.0, 0.0));
+        let segments = |t: Tessellation, scale: f64| t.sample_scaled(&line, scale).len() - 1;
+        assert_eq!(4, segments(Tessellation::Step(0.25), 1.0));
+        assert_eq!(4, segments(Tessellation::Step(0.3), 1.0));
+        assert_eq!(6, segments(Tessellation::Segments(6), 1.0));
+        assert_eq!(1, segments(Tessellation::Segments(0), 1.0));
+        assert_eq!(4, segments(Tessellation::MaxSegmentLength(3.0), 1.0));
+        assert_eq!(7, segments(Tessellation::MaxSegmentLength(3.0), 2.0));
+        // adaptive splits 3 times before it trusts the midpoint check
+        assert_eq!(8, segments(Tessellation::Adaptive(0.25), 1.0));
+
+        // adaptive segments of a circle bow out by no more than the tolerance
+        let arc = Arc::new((0.0, 0.0), 100.0, 0.0, 180.0);
+        let points = Tessellation::Adaptive(0.25).sample(&arc);
+        let n = points.len() - 1;
+        let bow = 100.0 * (1.0 - (std::f64::consts::PI / n as f64 / 2.0).cos());
+        assert!(n > 1 && bow <= 0.25, "{} segments bow out by {}", n, bow);
+        assert!(Tessellation::Adaptive(0.05).sample(&arc).len() > points.len());
+    }
+}
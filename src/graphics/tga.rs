@@ -0,0 +1,122 @@
+#![allow(dead_code)]
+
+use std::io::{self, prelude::Write};
+
+use super::utils::create_file;
+use super::PPMImg;
+
+/// Encoding of the pixels of a TGA file
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TgaFormat {
+    Raw,
+    /// Run length encoded, much smaller for images with flat areas
+    Rle,
+}
+
+/// Save an image as a 24 bit Targa file
+pub fn save_tga(filepath: &str, img: &PPMImg, format: TgaFormat) -> io::Result<()> {
+    let mut file = create_file(filepath)?;
+    write_tga(&mut file, img, format)?;
+    file.flush()
+}
+
+/// Write an image as a 24 bit Targa file, see `save_tga`
+pub fn write_tga(w: &mut impl Write, img: &PPMImg, format: TgaFormat) -> io::Result<()> {
+    let (width, height) = (img.width(), img.height());
+    if width > u16::MAX as u32 || height > u16::MAX as u32 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "TGA images are at most 65535 pixels each way",
+        ));
+    }
+    let image_type = match format {
+        TgaFormat::Raw => 2,
+        TgaFormat::Rle => 10,
+    };
+    // no id and no color map
+    w.write_all(&[0, 0, image_type, 0, 0, 0, 0, 0])?;
+    w.write_all(&[0; 4])?;
+    w.write_all(&(width as u16).to_le_bytes())?;
+    w.write_all(&(height as u16).to_le_bytes())?;
+    // 24 bits per pixel, with rows from the top
+    w.write_all(&[24, 0x20])?;
+
+    let mut bytes = vec![];
+    for row in img.rows() {
+        // each pixel is blue, green, red
+        let pixels: Vec<[u8; 3]> = row
+            .iter()
+            .map(|p| {
+                let [r, g, b] = p.to_bytes(img.depth());
+                [b, g, r]
+            })
+            .collect();
+        bytes.clear();
+        match format {
+            TgaFormat::Raw => pixels.iter().for_each(|p| bytes.extend_from_slice(p)),
+            TgaFormat::Rle => encode_rle_row(&pixels, &mut bytes),
+        }
+        w.write_all(&bytes)?;
+    }
+    Ok(())
+}
+
+/// Packets of up to 128 pixels, either one pixel repeated or pixels as they are
+///
+/// Packets stay within a row, as the format asks
+fn encode_rle_row(pixels: &[[u8; 3]], out: &mut Vec<u8>) {
+    let run_at = |i: usize| {
+        let p = pixels[i];
+        pixels[i..].iter().take(128).take_while(|&&q| q == p).count()
+    };
+    let mut i = 0;
+    while i < pixels.len() {
+        let run = run_at(i);
+        if run > 1 {
+            out.push(0x80 | (run - 1) as u8);
+            out.extend_from_slice(&pixels[i]);
+            i += run;
+        } else {
+            // raw pixels until the next run of two or more
+            let start = i;
+            while i < pixels.len() && i - start < 128 && run_at(i) == 1 {
+                i += 1;
+            }
+            out.push((i - start - 1) as u8);
+            pixels[start..i].iter().for_each(|p| out.extend_from_slice(p));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::RGB;
+
+    #[test]
+    fn rle_packets() {
+        // a row of 200 black pixels with one red pixel at 2
+        let mut img = PPMImg::new(1, 200, 255);
+        img.fg_color = RGB::new(255, 0, 0);
+        img.plot(2, 0);
+
+        let mut raw = vec![];
+        write_tga(&mut raw, &img, TgaFormat::Raw).unwrap();
+        assert_eq!(18 + 200 * 3, raw.len());
+        assert_eq!(&[0, 0, 255], &raw[18 + 6..18 + 9]);
+
+        let mut rle = vec![];
+        write_tga(&mut rle, &img, TgaFormat::Rle).unwrap();
+        assert_eq!(10, rle[2]);
+        let body = &rle[18..];
+        // 2 black, 1 red as is, then 128 and 69 black
+        let expected = [
+            vec![0x81, 0, 0, 0],
+            vec![0x00, 0, 0, 255],
+            vec![0xff, 0, 0, 0],
+            vec![0x80 | 68, 0, 0, 0],
+        ]
+        .concat();
+        assert_eq!(expected, body);
+    }
+}
@@ -0,0 +1,117 @@
+#![allow(dead_code)]
+
+//! Rendering images too big to keep in memory, one band of tiles at a time
+
+use std::io::{self, prelude::Write};
+
+use super::utils::create_file;
+use super::{PPMImg, RGB};
+
+/// A render of `width` by `height` pixels made of square tiles of `tile` pixels
+///
+/// The scene is drawn once for every tile, on an image that only covers that tile, and the
+/// tiles are written out a row at a time. Only one row of tiles is ever in memory.
+pub struct TiledRender {
+    pub width: u32,
+    pub height: u32,
+    pub depth: u16,
+    pub bg_color: RGB,
+    pub tile: u32,
+    /// World bounds over the whole render, as for `PPMImg::set_world_bounds`
+    pub world: Option<(f64, f64, f64, f64)>,
+}
+
+impl TiledRender {
+    pub fn new(width: u32, height: u32, depth: u16, tile: u32) -> Self {
+        assert!(tile > 0, "Tiles must be at least a pixel");
+        TiledRender {
+            width,
+            height,
+            depth,
+            bg_color: RGB::gray(0),
+            tile,
+            world: None,
+        }
+    }
+
+    /// Render into a binary P6 ppm at `filepath`, see `write_p6`
+    pub fn save(&self, filepath: &str, draw: impl FnMut(&mut PPMImg)) -> io::Result<()> {
+        let mut file = create_file(filepath)?;
+        self.write_p6(&mut file, draw)?;
+        file.flush()
+    }
+
+    /// Render into a binary P6 ppm, calling `draw` for every tile
+    ///
+    /// `draw` should draw the whole scene in the coordinates of the whole render; whatever
+    /// falls outside the tile is clipped. It must not set world bounds itself.
+    pub fn write_p6(
+        &self,
+        w: &mut impl Write,
+        mut draw: impl FnMut(&mut PPMImg),
+    ) -> io::Result<()> {
+        writeln!(w, "P6")?;
+        writeln!(w, "{} {} {}", self.width, self.height, self.depth)?;
+        let (sx, ox, sy, oy) = match self.world {
+            Some((xmin, ymin, xmax, ymax)) => {
+                let sx = (self.width as f64 - 1.0) / (xmax - xmin);
+                let sy = -(self.height as f64 - 1.0) / (ymax - ymin);
+                (sx, -xmin * sx, sy, -ymax * sy)
+            }
+            None => (1.0, 0.0, 1.0, 0.0),
+        };
+        let wide = self.depth > 255;
+        for ty in (0..self.height).step_by(self.tile as usize) {
+            let th = self.tile.min(self.height - ty);
+            let mut band = vec![];
+            for tx in (0..self.width).step_by(self.tile as usize) {
+                let tw = self.tile.min(self.width - tx);
+                let mut img = PPMImg::new_with_bg(th, tw, self.depth, self.bg_color);
+                img.viewport = Some((sx, ox - tx as f64, sy, oy - ty as f64));
+                draw(&mut img);
+                band.push(img);
+            }
+            let mut bytes = vec![];
+            for y in 0..th as usize {
+                for img in band.iter() {
+                    let row = &img.data[y * img.width as usize..(y + 1) * img.width as usize];
+                    for t in row.iter() {
+                        for &c in [t.red, t.green, t.blue].iter() {
+                            if wide {
+                                bytes.extend_from_slice(&c.to_be_bytes());
+                            } else {
+                                bytes.push(c as u8);
+                            }
+                        }
+                    }
+                }
+            }
+            w.write_all(&bytes)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles_stitch_into_the_whole() {
+        let draw = |img: &mut PPMImg| {
+            img.fill_polygon(&[(-4.0, -1.0), (5.0, 3.0), (0.0, 4.0)]);
+            img.draw_line_blend(-5.0, -5.0, 5.0, 3.0, RGB::RED, RGB::BLUE);
+        };
+        let mut whole = PPMImg::new(15, 20, 255);
+        whole.set_world_bounds(-5.0, -5.0, 5.0, 5.0);
+        draw(&mut whole);
+        let mut expected = vec![];
+        whole.write_p6(&mut expected).unwrap();
+
+        let mut tiled = TiledRender::new(20, 15, 255, 7);
+        tiled.world = Some((-5.0, -5.0, 5.0, 5.0));
+        let mut out = vec![];
+        tiled.write_p6(&mut out, draw).unwrap();
+        assert_eq!(expected, out);
+    }
+}
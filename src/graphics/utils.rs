@@ -1,14 +1,13 @@
 use std::fs::File;
-use std::io::BufWriter;
+use std::io::{self, BufWriter};
 use std::path::Path;
 
-pub fn create_file(filepath: &str) -> BufWriter<File> {
+/// Create a file for buffered writing, saying which file in the error if it can't be
+pub fn create_file(filepath: &str) -> io::Result<BufWriter<File>> {
     let path = Path::new(filepath);
-    let display = path.display();
-    match File::create(&path) {
-        Err(why) => panic!("Could not create {}: {}", display, why),
-        Ok(file) => BufWriter::new(file),
-    }
+    File::create(path).map(BufWriter::new).map_err(|why| {
+        io::Error::new(why.kind(), format!("Could not create {}: {}", path.display(), why))
+    })
 }
 
 pub fn polar_to_xy(mag: f64, angle_degrees: f64) -> (f64, f64) {
@@ -44,6 +43,42 @@ pub fn compute_hermite3_coef(p0: f64, p1: f64, r0: f64, r1: f64) -> (f64, f64, f
     )
 }
 
+/// Returns a tangent for each point, for use as hermite rates of change
+///
+/// Interior tangents are cardinal spline finite differences, `(1 - tension) * (p[i+1] - p[i-1]) / 2`,
+/// and the endpoints use one sided differences.
+/// A `tension` of 0 gives a Catmull-Rom spline.
+pub fn cardinal_tangents(points: &[(f64, f64)], tension: f64) -> Vec<(f64, f64)> {
+    let n = points.len();
+    if n < 2 {
+        return vec![(0.0, 0.0); n];
+    }
+    let k = 1.0 - tension;
+    (0..n)
+        .map(|i| {
+            let (prev, next, span) = match i {
+                0 => (points[0], points[1], 1.0),
+                _ if i == n - 1 => (points[n - 2], points[n - 1], 1.0),
+                _ => (points[i - 1], points[i + 1], 2.0),
+            };
+            (k * (next.0 - prev.0) / span, k * (next.1 - prev.1) / span)
+        })
+        .collect()
+}
+
+/// Returns the number of full turns after which a rolling circle comes back to where it started
+///
+/// That is the smallest `q` for which `ratio * q` is a whole number, found up to `max_turns`.
+/// For integer radii `R` and `r`, with `ratio = R / r`, this is `r / gcd(R, r)`.
+pub fn turns_to_close(ratio: f64, max_turns: u32) -> u32 {
+    (1..=max_turns)
+        .find(|q| {
+            let x = ratio * *q as f64;
+            (x - x.round()).abs() < 1e-6 * x.abs().max(1.0)
+        })
+        .unwrap_or(max_turns)
+}
+
 /// Returns a mapper function that maps value from one range to another
 /// https://stackoverflow.com/a/5732390
 pub fn mapper(instart: f64, inend: f64, outstart: f64, outend: f64) -> impl Fn(f64) -> f64
@@ -51,4 +86,27 @@ pub fn mapper(instart: f64, inend: f64, outstart: f64, outend: f64) -> impl Fn(f
     let slope = (outend - outstart) / (inend - instart);
     // move values into closure so they are captured by value, not ref
     move |x| outstart + slope * (x - instart)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files_that_cannot_be_created_are_errors() {
+        let error = create_file("no/such/directory/a.ppm").unwrap_err();
+        assert_eq!(io::ErrorKind::NotFound, error.kind());
+        assert!(error.to_string().starts_with("Could not create no/such/directory/a.ppm: "));
+    }
+
+    #[test]
+    fn cardinal_tangents_follow_neighbors() {
+        let points = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)];
+        // Catmull-Rom inside, one sided differences at the ends
+        let catmull_rom = vec![(10.0, 0.0), (5.0, 5.0), (-5.0, 5.0), (-10.0, 0.0)];
+        assert_eq!(catmull_rom, cardinal_tangents(&points, 0.0));
+        let tense = vec![(5.0, 0.0), (2.5, 2.5), (-2.5, 2.5), (-5.0, 0.0)];
+        assert_eq!(tense, cardinal_tangents(&points, 0.5));
+        assert_eq!(vec![(0.0, 0.0)], cardinal_tangents(&points[..1], 0.0));
+    }
+}
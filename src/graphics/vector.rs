@@ -0,0 +1,49 @@
+#![allow(dead_code)]
+
+/// A 3d point or direction
+pub type Vec3 = (f64, f64, f64);
+
+pub fn add(a: Vec3, b: Vec3) -> Vec3 {
+    (a.0 + b.0, a.1 + b.1, a.2 + b.2)
+}
+
+pub fn sub(a: Vec3, b: Vec3) -> Vec3 {
+    (a.0 - b.0, a.1 - b.1, a.2 - b.2)
+}
+
+pub fn scale(a: Vec3, k: f64) -> Vec3 {
+    (a.0 * k, a.1 * k, a.2 * k)
+}
+
+pub fn dot(a: Vec3, b: Vec3) -> f64 {
+    a.0 * b.0 + a.1 * b.1 + a.2 * b.2
+}
+
+pub fn cross(a: Vec3, b: Vec3) -> Vec3 {
+    (
+        a.1 * b.2 - a.2 * b.1,
+        a.2 * b.0 - a.0 * b.2,
+        a.0 * b.1 - a.1 * b.0,
+    )
+}
+
+pub fn length(a: Vec3) -> f64 {
+    dot(a, a).sqrt()
+}
+
+/// Scale `a` to unit length, leaving the zero vector alone
+pub fn normalize(a: Vec3) -> Vec3 {
+    let len = length(a);
+    if len == 0.0 {
+        a
+    } else {
+        scale(a, 1.0 / len)
+    }
+}
+
+/// Normal of the triangle `a b c`, with length twice its area
+///
+/// Points toward the side from which the corners go counter clockwise
+pub fn triangle_normal(a: Vec3, b: Vec3, c: Vec3) -> Vec3 {
+    cross(sub(b, a), sub(c, a))
+}
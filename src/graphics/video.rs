@@ -0,0 +1,94 @@
+#![allow(dead_code)]
+
+use std::io::{self, prelude::Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+use super::PPMImg;
+
+/// Streams frames to ffmpeg as raw RGB, which encodes them into a video
+///
+/// The container and codec follow from the extension of the output, e.g. .mp4 or .webm.
+/// Call `finish` after the last frame to wait for ffmpeg to write the file.
+pub struct Mp4Writer {
+    child: Child,
+    stdin: ChildStdin,
+    width: u32,
+    height: u32,
+}
+
+impl Mp4Writer {
+    /// Start ffmpeg writing to `filepath`, overwriting it if it exists
+    ///
+    /// # Arguments
+    /// `fps` - Frames per second
+    pub fn new(filepath: &str, width: u32, height: u32, fps: u32) -> io::Result<Self> {
+        Mp4Writer::spawn("ffmpeg", filepath, width, height, fps)
+    }
+
+    fn spawn(program: &str, filepath: &str, width: u32, height: u32, fps: u32) -> io::Result<Self> {
+        let size = format!("{}x{}", width, height);
+        let fps = fps.to_string();
+        let mut child = Command::new(program)
+            .args(["-y", "-loglevel", "error"])
+            .args(["-f", "rawvideo", "-pix_fmt", "rgb24", "-s", &size, "-r", &fps, "-i", "-"])
+            // most players want 4:2:0, which needs even sizes
+            .args(["-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2", "-pix_fmt", "yuv420p", filepath])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("{} could not be found; is it installed and on the PATH?", program),
+                ),
+                _ => e,
+            })?;
+        let stdin = child.stdin.take().expect("stdin was piped");
+        Ok(Mp4Writer {
+            child,
+            stdin,
+            width,
+            height,
+        })
+    }
+
+    /// Add a frame, which must be as big as the video
+    pub fn add_frame(&mut self, img: &PPMImg) -> io::Result<()> {
+        if img.width() != self.width || img.height() != self.height {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Every frame must be as big as the video",
+            ));
+        }
+        let bytes: Vec<u8> = img
+            .rows()
+            .flat_map(|row| row.iter().flat_map(|p| p.to_bytes(img.depth())))
+            .collect();
+        self.stdin.write_all(&bytes)
+    }
+
+    /// End the stream and wait for ffmpeg to finish the file
+    pub fn finish(self) -> io::Result<()> {
+        let Mp4Writer { mut child, stdin, .. } = self;
+        drop(stdin);
+        let status = child.wait()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!("ffmpeg failed with {}", status)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_ffmpeg_is_reported() {
+        let err = Mp4Writer::spawn("no-such-ffmpeg-here", "out.mp4", 4, 4, 30)
+            .err()
+            .expect("The program does not exist");
+        assert_eq!(io::ErrorKind::NotFound, err.kind());
+    }
+}
@@ -0,0 +1,212 @@
+#![allow(dead_code)]
+
+//! An interactive viewer on top of the preview window
+//!
+//! Drag with the left mouse button to pan, scroll to zoom, use the arrow keys to rotate, `r` to
+//! reset and `q` or escape to quit. The scene is only drawn again when the view changes.
+
+use std::io;
+
+use minifb::{Key as WindowKey, KeyRepeat, MouseButton, MouseMode, Window};
+
+use super::matrix::Matrix;
+use super::preview::Preview;
+use super::PPMImg;
+
+/// How much one notch of the scroll wheel zooms
+const ZOOM_STEP: f64 = 1.1;
+/// Degrees turned by each press of an arrow key
+const ROTATE_STEP: f64 = 10.0;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Key {
+    Left,
+    Right,
+    Up,
+    Down,
+    Char(char),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Event {
+    /// The mouse moved this many pixels with the button held
+    Drag(f64, f64),
+    /// Notches of the scroll wheel, positive away from the user
+    Scroll(f64),
+    Key(Key),
+    /// The window was closed, or the user asked to quit
+    Quit,
+}
+
+/// How the scene is looked at: turned about the center of the image, then zoomed and panned
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct View {
+    pub center: (f64, f64),
+    pub pan: (f64, f64),
+    pub zoom: f64,
+    /// Turn about the vertical axis, in degrees
+    pub yaw_deg: f64,
+    /// Turn about the horizontal axis, in degrees
+    pub pitch_deg: f64,
+}
+
+impl View {
+    /// The view that leaves an image of this size as it is
+    pub fn new(width: u32, height: u32) -> Self {
+        View {
+            center: (width as f64 / 2.0, height as f64 / 2.0),
+            pan: (0.0, 0.0),
+            zoom: 1.0,
+            yaw_deg: 0.0,
+            pitch_deg: 0.0,
+        }
+    }
+
+    /// The transformation to apply to the scene before drawing it
+    pub fn matrix(&self) -> Matrix {
+        let (cx, cy) = self.center;
+        Matrix::mv(-cx, -cy, 0.0)
+            .mul(&Matrix::rotatey(self.yaw_deg))
+            .mul(&Matrix::rotatex(self.pitch_deg))
+            .mul(&Matrix::scale(self.zoom, self.zoom, self.zoom))
+            .mul(&Matrix::mv(cx + self.pan.0, cy + self.pan.1, 0.0))
+    }
+
+    /// Change the view for an event, returning whether it changed
+    pub fn handle(&mut self, event: Event) -> bool {
+        match event {
+            Event::Drag(dx, dy) => {
+                self.pan.0 += dx;
+                self.pan.1 += dy;
+            }
+            Event::Scroll(n) => self.zoom *= ZOOM_STEP.powf(n),
+            Event::Key(Key::Left) => self.yaw_deg -= ROTATE_STEP,
+            Event::Key(Key::Right) => self.yaw_deg += ROTATE_STEP,
+            Event::Key(Key::Up) => self.pitch_deg -= ROTATE_STEP,
+            Event::Key(Key::Down) => self.pitch_deg += ROTATE_STEP,
+            Event::Key(Key::Char('+')) => self.zoom *= ZOOM_STEP,
+            Event::Key(Key::Char('-')) => self.zoom /= ZOOM_STEP,
+            Event::Key(Key::Char('r')) => {
+                *self = View {
+                    center: self.center,
+                    ..View::new(0, 0)
+                }
+            }
+            Event::Key(Key::Char(_)) | Event::Quit => return false,
+        }
+        true
+    }
+}
+
+/// Turns the state of a window's mouse and keyboard into events, one frame at a time
+#[derive(Debug, Default)]
+pub struct Input {
+    /// Where the mouse was last frame, if the button was down
+    last_mouse: Option<(f32, f32)>,
+}
+
+impl Input {
+    /// Events since the last frame
+    pub fn poll(&mut self, window: &Window) -> Vec<Event> {
+        if !window.is_open() {
+            return vec![Event::Quit];
+        }
+        let mut events = vec![];
+        let held = window.get_mouse_down(MouseButton::Left);
+        let mouse = window.get_mouse_pos(MouseMode::Pass).filter(|_| held);
+        events.extend(self.mouse(mouse));
+        if let Some((_, dy)) = window.get_scroll_wheel() {
+            if dy != 0.0 {
+                events.push(Event::Scroll(dy.signum() as f64));
+            }
+        }
+        for key in window.get_keys_pressed(KeyRepeat::Yes) {
+            let event = match key {
+                WindowKey::Left => Event::Key(Key::Left),
+                WindowKey::Right => Event::Key(Key::Right),
+                WindowKey::Up => Event::Key(Key::Up),
+                WindowKey::Down => Event::Key(Key::Down),
+                WindowKey::Equal | WindowKey::NumPadPlus => Event::Key(Key::Char('+')),
+                WindowKey::Minus | WindowKey::NumPadMinus => Event::Key(Key::Char('-')),
+                WindowKey::R => Event::Key(Key::Char('r')),
+                WindowKey::Q | WindowKey::Escape => Event::Quit,
+                _ => continue,
+            };
+            events.push(event);
+        }
+        events
+    }
+
+    /// A drag from where the mouse was to `now`, while the button stays down
+    fn mouse(&mut self, now: Option<(f32, f32)>) -> Option<Event> {
+        let (x0, y0) = std::mem::replace(&mut self.last_mouse, now)?;
+        let (x, y) = now?;
+        if (x, y) == (x0, y0) {
+            return None;
+        }
+        Some(Event::Drag((x - x0) as f64, (y - y0) as f64))
+    }
+}
+
+/// Show a scene in a preview window until it is closed or `q` is pressed
+///
+/// `draw` is called with a cleared image and the view transformation whenever the view
+/// changes, and should transform, tessellate and draw the scene. In between, the window is
+/// only handed the last frame again, which is how it takes in input.
+pub fn run(
+    width: u32,
+    height: u32,
+    fps: u32,
+    mut draw: impl FnMut(&mut PPMImg, &Matrix),
+) -> io::Result<()> {
+    let mut preview = Preview::open(width, height, fps)?;
+    let mut view = View::new(width, height);
+    let mut input = Input::default();
+    let mut img = PPMImg::new(height, width, 255);
+    let mut dirty = true;
+    loop {
+        if dirty {
+            img.clear();
+            draw(&mut img, &view.matrix());
+            preview.show(&img)?;
+            dirty = false;
+        } else {
+            preview.refresh()?;
+        }
+        for event in input.poll(preview.window()) {
+            if event == Event::Quit {
+                return Ok(());
+            }
+            dirty |= view.handle(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dragging_and_keys_move_the_view() {
+        let mut input = Input::default();
+        // the button goes down, the mouse moves twice while it is held, then it is let go
+        let drags: Vec<Event> = [Some((10.0, 5.0)), Some((12.0, 4.0)), Some((12.0, 4.0)), None]
+            .iter()
+            .filter_map(|&m| input.mouse(m))
+            .collect();
+        assert_eq!(vec![Event::Drag(2.0, -1.0)], drags);
+        assert_eq!(None, input.mouse(Some((30.0, 30.0))));
+
+        let mut view = View::new(100, 100);
+        assert!((view.matrix().transform_point((3.0, 4.0, 5.0)).0 - 3.0).abs() < 1e-9);
+        for e in [Event::Drag(16.0, -8.0), Event::Scroll(1.0), Event::Key(Key::Right)] {
+            assert!(view.handle(e));
+        }
+        assert!(!view.handle(Event::Key(Key::Char('x'))));
+        assert_eq!((16.0, -8.0), view.pan);
+        assert_eq!(ROTATE_STEP, view.yaw_deg);
+        // the center only moves by the pan
+        let c = view.matrix().transform_point((50.0, 50.0, 0.0));
+        assert!((c.0 - 66.0).abs() < 1e-9 && (c.1 - 42.0).abs() < 1e-9);
+    }
+}
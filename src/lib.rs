@@ -1 +1,2 @@
+pub mod cli;
 pub mod graphics;
\ No newline at end of file